@@ -0,0 +1,27 @@
+//! Compares the Winograd path for 3x3 convolutions on cpu with the direct convolution.
+//!
+//! Run with `cargo bench -p candle-core --features winograd-switch --bench conv2d_winograd`.
+use candle_core::{cpu_backend::set_winograd_conv2d, Device, Tensor};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let device = Device::Cpu;
+    let mut group = c.benchmark_group("conv2d_3x3");
+    // (channels, spatial size) for the 3x3 convolutions of ResNet basic blocks, batch size 1.
+    for (channels, size) in [(64, 56), (128, 28), (256, 14)] {
+        let xs = Tensor::randn(0f32, 1., (1, channels, size, size), &device).unwrap();
+        let ws = Tensor::randn(0f32, 1., (channels, channels, 3, 3), &device).unwrap();
+        let id = format!("{channels}x{size}x{size}");
+        for (name, winograd) in [("winograd", true), ("direct", false)] {
+            group.bench_function(BenchmarkId::new(name, &id), |b| {
+                set_winograd_conv2d(winograd);
+                b.iter(|| black_box(xs.conv2d(&ws, 1, 1, 1, 1).unwrap()))
+            });
+        }
+    }
+    set_winograd_conv2d(true);
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+//! Compares the depthwise conv2d kernel with running one convolution per channel, which is what
+//! a grouped convolution does for a generic number of groups.
+//!
+//! Run with `cargo bench -p candle-core --bench conv_depthwise`.
+use candle_core::{Device, Result, Tensor};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn conv2d_per_channel(xs: &Tensor, ws: &Tensor, padding: usize, stride: usize) -> Result<Tensor> {
+    let ys = xs
+        .chunk(xs.dim(1)?, 1)?
+        .iter()
+        .zip(ws.chunk(ws.dim(0)?, 0)?.iter())
+        .map(|(xs, ws)| xs.conv2d(ws, padding, stride, 1, 1))
+        .collect::<Result<Vec<_>>>()?;
+    Tensor::cat(&ys, 1)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let device = Device::Cpu;
+    let mut group = c.benchmark_group("conv2d_depthwise_3x3");
+    // (channels, spatial size, stride) for typical MobileNet layers with a batch size of 1.
+    for (c_in, size, stride) in [
+        (32, 112, 1),
+        (64, 56, 1),
+        (144, 56, 2),
+        (192, 28, 1),
+        (576, 14, 1),
+    ] {
+        let xs = Tensor::randn(0f32, 1., (1, c_in, size, size), &device).unwrap();
+        let ws = Tensor::randn(0f32, 1., (c_in, 1, 3, 3), &device).unwrap();
+        let id = format!("{c_in}x{size}x{size}/s{stride}");
+        group.bench_function(BenchmarkId::new("depthwise", &id), |b| {
+            b.iter(|| black_box(xs.conv2d(&ws, 1, stride, 1, c_in).unwrap()))
+        });
+        group.bench_function(BenchmarkId::new("per_channel", &id), |b| {
+            b.iter(|| black_box(conv2d_per_channel(&xs, &ws, 1, stride).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
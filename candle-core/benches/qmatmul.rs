@@ -0,0 +1,32 @@
+//! Compares the quantized matmul kernels with a f32 matmul on the same weights, for the shapes
+//! of a 4096 wide projection with a single token (generation) or a small batch of tokens.
+//!
+//! Run with `cargo bench -p candle-core --bench qmatmul`.
+use candle_core::quantized::{GgmlDType, QMatMul, QTensor};
+use candle_core::{Device, Tensor};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let device = Device::Cpu;
+    let (k, n) = (4096, 4096);
+    let w = Tensor::randn(0f32, 1., (n, k), &device).unwrap();
+    let w_t = w.t().unwrap();
+    let mut group = c.benchmark_group("qmatmul");
+    for m in [1, 16] {
+        let xs = Tensor::randn(0f32, 1., (m, k), &device).unwrap();
+        group.throughput(Throughput::Elements((m * k * n) as u64));
+        group.bench_function(BenchmarkId::new("f32", m), |b| {
+            b.iter(|| black_box(xs.matmul(&w_t).unwrap()))
+        });
+        for dtype in [GgmlDType::Q8_0, GgmlDType::Q4_0, GgmlDType::Q4K] {
+            let mm = QMatMul::from_qtensor(QTensor::quantize_as(&w, dtype).unwrap());
+            group.bench_function(BenchmarkId::new(format!("{dtype:?}"), m), |b| {
+                b.iter(|| black_box(mm.forward(&xs).unwrap()))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -47,6 +47,17 @@ pub trait BackendStorage: Sized {
         _params: &crate::conv::ParamsConv2D,
     ) -> Result<Self>;
 
+    /// A 2D convolution where each input channel is convolved with its own set of filters, i.e.
+    /// the number of groups is the number of input channels. `params.c_in` and `params.c_out`
+    /// are the total number of channels.
+    fn conv2d_depthwise(
+        &self,
+        _l: &Layout,
+        _kernel: &Self,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConv2D,
+    ) -> Result<Self>;
+
     fn conv_transpose2d(
         &self,
         _l: &Layout,
@@ -59,6 +70,26 @@ pub trait BackendStorage: Sized {
     fn max_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<Self>;
     fn upsample_nearest2d(&self, _: &Layout, _: usize, _: usize) -> Result<Self>;
 
+    fn grid_sample(
+        &self,
+        _l: &Layout,
+        _grid: &Self,
+        _grid_l: &Layout,
+        _params: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<Self>;
+
+    /// Returns the gradients of the input and of the grid given the gradient of the
+    /// `grid_sample` output.
+    fn grid_sample_backward(
+        &self,
+        _l: &Layout,
+        _grid: &Self,
+        _grid_l: &Layout,
+        _grad: &Self,
+        _grad_l: &Layout,
+        _params: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<(Self, Self)>;
+
     fn gather(&self, _: &Layout, _: &Self, _: &Layout, _: usize) -> Result<Self>;
     fn scatter_add(
         &self,
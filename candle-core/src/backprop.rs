@@ -1,5 +1,5 @@
 use crate::op::{BinaryOp, Op, ReduceOp, UnaryOp};
-use crate::{Error, Result, Tensor, TensorId};
+use crate::{Error, Layout, Result, Tensor, TensorId};
 use std::collections::HashMap;
 
 // arg has been reduced to node via reduce_dims, expand it back to arg.
@@ -38,10 +38,19 @@ impl Tensor {
                 nodes
             } else if let Some(op) = node.op() {
                 match op {
-                    Op::IndexAdd(t1, t2, t3, _)
-                    | Op::ScatterAdd(t1, t2, t3, _)
-                    | Op::CustomOp3(t1, t2, t3, _)
-                    | Op::WhereCond(t1, t2, t3) => {
+                    // The indexes and the where_cond predicate do not get any gradient so they
+                    // are not walked, this way they can be computed from a tracked tensor, e.g.
+                    // via argmax or a comparison.
+                    Op::IndexAdd(t1, _, t3, _)
+                    | Op::ScatterAdd(t1, _, t3, _)
+                    | Op::WhereCond(_, t1, t3) => {
+                        let (tg, nodes) = walk(t1, nodes, already_seen);
+                        track_grad |= tg;
+                        let (tg, nodes) = walk(t3, nodes, already_seen);
+                        track_grad |= tg;
+                        nodes
+                    }
+                    Op::CustomOp3(t1, t2, t3, _) => {
                         let (tg, nodes) = walk(t1, nodes, already_seen);
                         track_grad |= tg;
                         let (tg, nodes) = walk(t2, nodes, already_seen);
@@ -60,15 +69,23 @@ impl Tensor {
                         kernel: rhs,
                         ..
                     }
+                    | Op::Conv2DDepthwise {
+                        arg: lhs,
+                        kernel: rhs,
+                        ..
+                    }
                     | Op::ConvTranspose2D {
                         arg: lhs,
                         kernel: rhs,
                         ..
                     }
+                    | Op::GridSample {
+                        arg: lhs,
+                        grid: rhs,
+                        ..
+                    }
                     | Op::CustomOp2(lhs, rhs, _)
                     | Op::Binary(lhs, rhs, _)
-                    | Op::Gather(lhs, rhs, _)
-                    | Op::IndexSelect(lhs, rhs, _)
                     | Op::Matmul(lhs, rhs) => {
                         let (tg, nodes) = walk(lhs, nodes, already_seen);
                         track_grad |= tg;
@@ -76,6 +93,11 @@ impl Tensor {
                         track_grad |= tg;
                         nodes
                     }
+                    Op::Gather(arg, _, _) | Op::IndexSelect(arg, _, _) => {
+                        let (tg, nodes) = walk(arg, nodes, already_seen);
+                        track_grad |= tg;
+                        nodes
+                    }
                     Op::Cat(args, _) => args.iter().fold(nodes, |nodes, arg| {
                         let (tg, nodes) = walk(arg, nodes, already_seen);
                         track_grad |= tg;
@@ -103,6 +125,7 @@ impl Tensor {
                     | Op::Transpose(node, _, _)
                     | Op::Permute(node, _)
                     | Op::Narrow(node, _, _, _)
+                    | Op::AsStrided { arg: node, .. }
                     | Op::Unary(node, _)
                     | Op::Elu(node, _)
                     | Op::Powf(node, _)
@@ -217,10 +240,57 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&grad_arg)?;
 
+                        // When the stride does not divide the padded input size, the last input
+                        // rows and columns are not used by the forward pass and result in extra
+                        // kernel offsets here.
+                        let k_w = kernel.dim(3)?;
                         let grad_kernel = arg
                             .transpose(0, 1)?
                             .conv2d(&grad.transpose(0, 1)?, *padding, *dilation, *stride, 1)?
-                            .transpose(0, 1)?;
+                            .transpose(0, 1)?
+                            .narrow(2, 0, k_h)?
+                            .narrow(3, 0, k_w)?;
+                        let sum_grad = grads.or_insert(kernel)?;
+                        *sum_grad = sum_grad.add(&grad_kernel)?;
+                    }
+                    Op::Conv2DDepthwise {
+                        arg,
+                        kernel,
+                        padding,
+                        stride,
+                        dilation,
+                    } => {
+                        // Each output channel o only depends on the input channel o / m where m
+                        // is the channel multiplier. Using the unfolded input cols of shape
+                        // (b, c_in, k_h * k_w, l), out[o] = sum_k cols[o / m, k] * kernel[o, k].
+                        let (b_size, c_in, i_h, i_w) = arg.dims4()?;
+                        let (c_out, _, k_h, k_w) = kernel.dims4()?;
+                        let m = c_out / c_in;
+                        let l = grad.dim(2)? * grad.dim(3)?;
+                        let grad = grad.reshape((b_size, c_in, m, 1, l))?;
+                        let kernel_r = kernel.reshape((1, c_in, m, k_h * k_w, 1))?;
+                        let grad_cols = grad.broadcast_mul(&kernel_r)?.sum(2)?.reshape((
+                            b_size,
+                            c_in * k_h * k_w,
+                            l,
+                        ))?;
+                        let grad_arg = grad_cols.fold2d(
+                            (i_h, i_w),
+                            (k_h, k_w),
+                            *stride,
+                            *padding,
+                            *dilation,
+                        )?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+
+                        let cols = arg
+                            .unfold2d((k_h, k_w), *stride, *padding, *dilation)?
+                            .reshape((b_size, c_in, 1, k_h * k_w, l))?;
+                        let grad_kernel = grad
+                            .broadcast_mul(&cols)?
+                            .sum((0, 4))?
+                            .reshape((c_out, 1, k_h, k_w))?;
                         let sum_grad = grads.or_insert(kernel)?;
                         *sum_grad = sum_grad.add(&grad_kernel)?;
                     }
@@ -257,14 +327,48 @@ impl Tensor {
                         // we scale the gradient for this case).
                         let node_upsampled = node.upsample_nearest2d(h, w)?;
                         let mask = arg.eq(&node_upsampled)?.to_dtype(arg.dtype())?;
-                        let avg = mask.avg_pool2d_with_stride(*kernel_size, *stride)?;
-                        let grad_arg = ((grad * avg)?.upsample_nearest2d(h, w)? * mask)?;
+                        let ksize = (kernel_size.0 * kernel_size.1) as f64;
+                        let count = (mask.avg_pool2d_with_stride(*kernel_size, *stride)? * ksize)?;
+                        let grad_arg = ((grad / count)?.upsample_nearest2d(h, w)? * mask)?;
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&grad_arg)?;
                     }
-                    Op::UpsampleNearest2D { .. } => Err(Error::BackwardNotSupported {
-                        op: "upsample-nearest2d",
-                    })?,
+                    Op::UpsampleNearest2D(arg) => {
+                        // Each input element has been duplicated over multiple output positions,
+                        // the gradients of these positions get summed.
+                        let (n, c, h, w) = arg.dims4()?;
+                        let (_n, _c, target_h, target_w) = grad.dims4()?;
+                        let ids = |size, target| {
+                            let ids = crate::tensor::upsample_nearest_indexes(size, target)?;
+                            Tensor::from_vec(ids, target, grad.device())
+                        };
+                        let grad_w =
+                            Tensor::zeros((n, c, target_h, w), grad.dtype(), grad.device())?
+                                .index_add(&ids(w, target_w)?, &grad.contiguous()?, 3)?;
+                        let grad_arg = Tensor::zeros((n, c, h, w), grad.dtype(), grad.device())?
+                            .index_add(&ids(h, target_h)?, &grad_w, 2)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+                    }
+                    Op::GridSample {
+                        arg,
+                        grid,
+                        mode,
+                        padding_mode,
+                        align_corners,
+                    } => {
+                        let (grad_arg, grad_grid) = arg.grid_sample_backward(
+                            grid,
+                            &grad,
+                            *mode,
+                            *padding_mode,
+                            *align_corners,
+                        )?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+                        let sum_grad = grads.or_insert(grid)?;
+                        *sum_grad = sum_grad.add(&grad_grid)?;
+                    }
                     Op::Gather(arg, indexes, dim) => {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.scatter_add(indexes, &grad, *dim)?;
@@ -356,7 +460,7 @@ impl Tensor {
                     }
                     Op::ToDType(arg) => {
                         let sum_grad = grads.or_insert(arg)?;
-                        *sum_grad = sum_grad.add(&grad.to_dtype(node.dtype())?)?
+                        *sum_grad = sum_grad.add(&grad.to_dtype(arg.dtype())?)?
                     }
                     Op::Copy(arg) => {
                         let sum_grad = grads.or_insert(arg)?;
@@ -436,13 +540,31 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&arg_grad)?
                     }
-                    Op::Unary(_, UnaryOp::Gelu) => Err(Error::BackwardNotSupported { op: "gelu" })?,
+                    Op::Unary(arg, UnaryOp::Gelu) => {
+                        // gelu(x) = 0.5 x (1 + tanh(u)) with u = sqrt(2/pi) (x + 0.044715 x^3)
+                        let sum_grad = grads.or_insert(arg)?;
+                        let c = (2. / std::f64::consts::PI).sqrt();
+                        let x2 = arg.sqr()?;
+                        let tanh = (arg * ((&x2 * 0.044715)? + 1.)?)?.affine(c, 0.)?.tanh()?;
+                        let du = ((&x2 * (3. * 0.044715))? + 1.)?.affine(c, 0.)?;
+                        let dtanh = (tanh.sqr()?.neg()? + 1.)?;
+                        let gelu_grad = (((tanh + 1.)? + (arg * (dtanh * du)?)?)? * 0.5)?;
+                        *sum_grad = sum_grad.add(&(&grad * gelu_grad)?)?
+                    }
                     Op::Unary(arg, UnaryOp::Relu) => {
                         let sum_grad = grads.or_insert(arg)?;
                         let relu_grad = arg.ge(&arg.zeros_like()?)?.to_dtype(arg.dtype())?;
                         *sum_grad = sum_grad.add(&(&grad * relu_grad)?)?
                     }
-                    Op::Elu(..) => Err(Error::BackwardNotSupported { op: "elu" })?,
+                    Op::Elu(arg, alpha) => {
+                        // The derivative is 1 for positive inputs and elu(x) + alpha otherwise.
+                        let sum_grad = grads.or_insert(arg)?;
+                        let zeros = arg.zeros_like()?;
+                        let positive = arg.gt(&zeros)?;
+                        let elu_grad =
+                            positive.where_cond(&arg.ones_like()?, &(*node + *alpha)?)?;
+                        *sum_grad = sum_grad.add(&(&grad * elu_grad)?)?
+                    }
                     Op::Powf(arg, e) => {
                         let arg_grad = (&(grad * arg.powf(e - 1.)?)? * *e)?;
                         let sum_grad = grads.or_insert(arg)?;
@@ -491,6 +613,10 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&arg_grad)?
                     }
+                    // These functions are piecewise constant so their gradient is zero.
+                    Op::Unary(_, UnaryOp::Floor)
+                    | Op::Unary(_, UnaryOp::Ceil)
+                    | Op::Unary(_, UnaryOp::Round) => {}
                     Op::ToDevice(arg) => {
                         let sum_grad = grads.or_insert(arg)?;
                         let arg_grad = grad.to_device(sum_grad.device())?;
@@ -510,6 +636,24 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&arg_grad)?
                     }
+                    Op::AsStrided {
+                        arg,
+                        shape,
+                        stride,
+                        offset,
+                    } => {
+                        // Each element of the view reads one element of the flattened input,
+                        // the gradients of elements reading the same input are summed.
+                        let layout = Layout::new(shape.as_slice().into(), stride.clone(), *offset);
+                        let ids: Vec<u32> = layout.strided_index().map(|i| i as u32).collect();
+                        let ids = Tensor::new(ids, grad.device())?;
+                        let arg_grad =
+                            Tensor::zeros(arg.elem_count(), grad.dtype(), grad.device())?
+                                .index_add(&ids, &grad.flatten_all()?, 0)?
+                                .reshape(arg.shape())?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&arg_grad)?
+                    }
                 };
             }
         }
@@ -532,6 +676,16 @@ impl GradStore {
         self.0.get(&tensor.id())
     }
 
+    pub fn get_id_mut(&mut self, id: TensorId) -> Option<&mut Tensor> {
+        self.0.get_mut(&id)
+    }
+
+    /// Mutable access to the gradient of a tensor, e.g. to rescale it in place before an
+    /// optimizer step.
+    pub fn get_mut(&mut self, tensor: &Tensor) -> Option<&mut Tensor> {
+        self.0.get_mut(&tensor.id())
+    }
+
     pub fn remove(&mut self, tensor: &Tensor) -> Option<Tensor> {
         self.0.remove(&tensor.id())
     }
@@ -540,6 +694,25 @@ impl GradStore {
         self.0.insert(tensor.id(), grad)
     }
 
+    /// Adds the gradients from `other` to the gradients stored in `self`. This can be used to
+    /// accumulate the gradients of multiple backward passes, e.g. when splitting a batch in
+    /// multiple micro-batches, before running an optimizer step.
+    pub fn add_in_place(&mut self, other: GradStore) -> Result<()> {
+        use std::collections::hash_map::Entry;
+        for (id, grad) in other.0.into_iter() {
+            match self.0.entry(id) {
+                Entry::Occupied(mut entry) => {
+                    let sum = entry.get().add(&grad)?;
+                    entry.insert(sum);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(grad);
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn or_insert(&mut self, tensor: &Tensor) -> Result<&mut Tensor> {
         use std::collections::hash_map::Entry;
         let grad = match self.0.entry(tensor.id()) {
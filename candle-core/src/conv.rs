@@ -121,6 +121,16 @@ impl Tensor {
             }
             .bt())?
         }
+        if c_out % groups != 0 {
+            Err(Error::Conv1dInvalidArgs {
+                inp_shape: self.shape().clone(),
+                k_shape: kernel.shape().clone(),
+                padding,
+                stride,
+                msg: "the number of out-channels is not divisible by the number of groups",
+            }
+            .bt())?
+        }
 
         let params = ParamsConv1D {
             b_size,
@@ -161,7 +171,28 @@ impl Tensor {
         Ok(crate::tensor::from_storage(storage, out_dims, op, false))
     }
 
+    fn conv2d_depthwise(&self, kernel: &Self, params: &ParamsConv2D) -> Result<Self> {
+        let storage = self.storage().conv2d_depthwise(
+            self.layout(),
+            &kernel.storage(),
+            kernel.layout(),
+            params,
+        )?;
+        let op = BackpropOp::new2(self, kernel, |arg, kernel| Op::Conv2DDepthwise {
+            arg,
+            kernel,
+            padding: params.padding,
+            stride: params.stride,
+            dilation: params.dilation,
+        });
+        let out_dims = params.out_dims();
+        Ok(crate::tensor::from_storage(storage, out_dims, op, false))
+    }
+
     /// Applies a 2D convolution over the input tensor.
+    ///
+    /// When `groups` is equal to the number of input channels, a depthwise convolution, a
+    /// specialized kernel is used rather than running one convolution per group.
     pub fn conv2d(
         &self,
         kernel: &Self,
@@ -177,6 +208,9 @@ impl Tensor {
                 "in_channel mismatch between input ({c_in}, groups {groups}) and kernel ({c_in_k})"
             )
         }
+        if c_out % groups != 0 {
+            crate::bail!("out_channel {c_out} is not divisible by the number of groups {groups}")
+        }
         let params = ParamsConv2D {
             b_size,
             i_h,
@@ -191,6 +225,13 @@ impl Tensor {
         };
         if groups == 1 {
             self.conv2d_single_group(kernel, &params)
+        } else if groups == c_in {
+            let params = ParamsConv2D {
+                c_out,
+                c_in,
+                ..params
+            };
+            self.conv2d_depthwise(kernel, &params)
         } else {
             let blocks = self.chunk(groups, 1)?;
             let kernel = kernel.chunk(groups, 0)?;
@@ -247,4 +288,185 @@ impl Tensor {
         let out_dims = params.out_dims();
         Ok(crate::tensor::from_storage(storage, out_dims, op, false))
     }
+
+    /// Applies a 3D convolution over a `(batch, c_in, d, h, w)` input tensor using a kernel with
+    /// shape `(c_out, c_in / groups, k_d, k_h, k_w)`, e.g. for volumetric data or videos.
+    ///
+    /// This is lowered to one 2D convolution per kernel depth offset: the input depth slices used
+    /// by an offset are gathered into the batch dimension and the results are summed, so the
+    /// backward pass is the one of the 2D convolutions.
+    pub fn conv3d(
+        &self,
+        kernel: &Self,
+        padding: usize,
+        stride: usize,
+        dilation: usize,
+        groups: usize,
+    ) -> Result<Self> {
+        let (b_size, c_in, i_d, i_h, i_w) = self.dims5()?;
+        let (c_out, c_in_k, k_d, _k_h, _k_w) = kernel.dims5()?;
+        if c_in != c_in_k * groups {
+            crate::bail!(
+                "in_channel mismatch between input ({c_in}, groups {groups}) and kernel ({c_in_k})"
+            )
+        }
+        let span = dilation * k_d.saturating_sub(1) + 1;
+        if k_d == 0 || stride == 0 || dilation == 0 || i_d + 2 * padding < span {
+            crate::bail!(
+                "conv3d: invalid kernel depth {k_d}, stride {stride}, padding {padding} or dilation {dilation} for input depth {i_d}"
+            )
+        }
+        let o_d = (i_d + 2 * padding - span) / stride + 1;
+        // index_select requires a contiguous input.
+        let xs = self.pad_with_zeros(2, padding, padding)?.contiguous()?;
+        let conv_offset = |offset: usize| {
+            let ids = (0..o_d)
+                .map(|o| (o * stride + offset * dilation) as u32)
+                .collect::<Vec<_>>();
+            let ids = Tensor::from_vec(ids, o_d, self.device())?;
+            // The output depth is folded into the batch, (b_size * o_d, c_in, i_h, i_w).
+            let xs = xs.index_select(&ids, 2)?.transpose(1, 2)?.reshape((
+                b_size * o_d,
+                c_in,
+                i_h,
+                i_w,
+            ))?;
+            let kernel = kernel.narrow(2, offset, 1)?.squeeze(2)?;
+            xs.conv2d(&kernel, padding, stride, dilation, groups)
+        };
+        let mut ys = conv_offset(0)?;
+        for offset in 1..k_d {
+            ys = (ys + conv_offset(offset)?)?
+        }
+        let (_, _, o_h, o_w) = ys.dims4()?;
+        ys.reshape((b_size, o_d, c_out, o_h, o_w))?.transpose(1, 2)
+    }
+
+    /// Extracts the sliding local blocks of a `(batch, channels, h, w)` tensor, similar to
+    /// PyTorch `nn.Unfold`.
+    ///
+    /// The returned tensor has shape `(batch, channels * k_h * k_w, l)` where `l` is the number of
+    /// blocks, i.e. `h_out * w_out` with `h_out = (h + 2 * padding - dilation * (k_h - 1) - 1) /
+    /// stride + 1` and similarly for `w_out`. Row `c * k_h * k_w + i * k_w + j` contains the values
+    /// of channel `c` at the kernel offset `(i, j)` and the blocks are ordered row-major. The
+    /// input is padded with zeros. This is the im2col matrix used when computing a convolution as
+    /// a matmul, [`Tensor::fold2d`] is the corresponding adjoint operation.
+    pub fn unfold2d(
+        &self,
+        kernel: (usize, usize),
+        stride: usize,
+        padding: usize,
+        dilation: usize,
+    ) -> Result<Self> {
+        let (n, c, h, w) = self.dims4()?;
+        let (ids, l) = unfold2d_indexes((h, w), kernel, stride, padding, dilation, self.device())?;
+        let (k_h, k_w) = kernel;
+        let (h_p, w_p) = (h + 2 * padding, w + 2 * padding);
+        self.pad_with_zeros(2, padding, padding)?
+            .pad_with_zeros(3, padding, padding)?
+            .reshape((n, c, h_p * w_p))?
+            .index_select(&ids, 2)?
+            .reshape((n, c * k_h * k_w, l))
+    }
+
+    /// Combines an array of sliding local blocks into a `(batch, channels, h, w)` tensor, similar
+    /// to PyTorch `nn.Fold`. This is the inverse of [`Tensor::unfold2d`] in terms of layout,
+    /// values from overlapping blocks are summed.
+    ///
+    /// The input should have shape `(batch, channels * k_h * k_w, l)` and `output_size` is
+    /// `(h, w)`.
+    pub fn fold2d(
+        &self,
+        output_size: (usize, usize),
+        kernel: (usize, usize),
+        stride: usize,
+        padding: usize,
+        dilation: usize,
+    ) -> Result<Self> {
+        let (n, ckk, l) = self.dims3()?;
+        let (h, w) = output_size;
+        let (k_h, k_w) = kernel;
+        let (ids, l_expected) =
+            unfold2d_indexes((h, w), kernel, stride, padding, dilation, self.device())?;
+        if ckk % (k_h * k_w) != 0 || l != l_expected {
+            crate::bail!(
+                "fold2d: input shape {:?} is incompatible with output size {output_size:?}, kernel {kernel:?}, stride {stride}, padding {padding} and dilation {dilation}",
+                self.shape()
+            )
+        }
+        let c = ckk / (k_h * k_w);
+        let (h_p, w_p) = (h + 2 * padding, w + 2 * padding);
+        let src = self.reshape((n, c, k_h * k_w * l))?.contiguous()?;
+        Tensor::zeros((n, c, h_p * w_p), self.dtype(), self.device())?
+            .index_add(&ids, &src, 2)?
+            .reshape((n, c, h_p, w_p))?
+            .narrow(2, padding, h)?
+            .narrow(3, padding, w)
+    }
+
+    /// An alias for [`Tensor::unfold2d`], the im2col lowering of convolutions.
+    pub fn im2col(
+        &self,
+        kernel: (usize, usize),
+        stride: usize,
+        padding: usize,
+        dilation: usize,
+    ) -> Result<Self> {
+        self.unfold2d(kernel, stride, padding, dilation)
+    }
+
+    /// An alias for [`Tensor::fold2d`], the adjoint of [`Tensor::im2col`].
+    pub fn col2im(
+        &self,
+        output_size: (usize, usize),
+        kernel: (usize, usize),
+        stride: usize,
+        padding: usize,
+        dilation: usize,
+    ) -> Result<Self> {
+        self.fold2d(output_size, kernel, stride, padding, dilation)
+    }
+}
+
+/// Returns the indexes in the flattened padded plane of the values in each block, ordered by
+/// kernel offset and then block, together with the number of blocks.
+fn unfold2d_indexes(
+    (h, w): (usize, usize),
+    (k_h, k_w): (usize, usize),
+    stride: usize,
+    padding: usize,
+    dilation: usize,
+    device: &crate::Device,
+) -> Result<(Tensor, usize)> {
+    let out_size = |len: usize, k: usize| {
+        let span = dilation * k.saturating_sub(1) + 1;
+        if k == 0 || stride == 0 || dilation == 0 || len + 2 * padding < span {
+            None
+        } else {
+            Some((len + 2 * padding - span) / stride + 1)
+        }
+    };
+    let (h_out, w_out) = match (out_size(h, k_h), out_size(w, k_w)) {
+        (Some(h_out), Some(w_out)) => (h_out, w_out),
+        _ => crate::bail!(
+            "unfold2d: invalid kernel {:?}, stride {stride}, padding {padding} or dilation {dilation} for spatial size {:?}",
+            (k_h, k_w),
+            (h, w)
+        ),
+    };
+    let w_p = w + 2 * padding;
+    let mut ids = Vec::with_capacity(k_h * k_w * h_out * w_out);
+    for i in 0..k_h {
+        for j in 0..k_w {
+            for o_h in 0..h_out {
+                for o_w in 0..w_out {
+                    let x_h = o_h * stride + i * dilation;
+                    let x_w = o_w * stride + j * dilation;
+                    ids.push((x_h * w_p + x_w) as u32)
+                }
+            }
+        }
+    }
+    let ids = Tensor::from_vec(ids, k_h * k_w * h_out * w_out, device)?;
+    Ok((ids, h_out * w_out))
 }
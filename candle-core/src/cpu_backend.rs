@@ -8,13 +8,145 @@ use rayon::prelude::*;
 // intercept the oom errors to avoid panicking and provide a proper error.
 #[derive(Debug, Clone)]
 pub enum CpuStorage {
-    U8(Vec<u8>),
-    U32(Vec<u32>),
-    I64(Vec<i64>),
-    BF16(Vec<bf16>),
-    F16(Vec<f16>),
-    F32(Vec<f32>),
-    F64(Vec<f64>),
+    U8(CpuBuffer<u8>),
+    U32(CpuBuffer<u32>),
+    I64(CpuBuffer<i64>),
+    BF16(CpuBuffer<bf16>),
+    F16(CpuBuffer<f16>),
+    F32(CpuBuffer<f32>),
+    F64(CpuBuffer<f64>),
+}
+
+/// The elements of a cpu storage.
+///
+/// The buffer usually owns its elements as a `Vec`. It can also be a read-only view on memory
+/// owned by another value, e.g. an `Arc<[T]>` or a memory mapped file, so that this memory can
+/// be used by tensors without being copied. Such a view keeps its owner alive and gets copied to
+/// an owned `Vec` the first time it is mutated, e.g. via [`crate::Var::set`].
+pub struct CpuBuffer<T>(CpuBufferData<T>);
+
+enum CpuBufferData<T> {
+    Owned(Vec<T>),
+    Shared {
+        ptr: *const T,
+        len: usize,
+        _owner: std::sync::Arc<dyn std::any::Any + Send + Sync>,
+    },
+}
+
+// SAFETY: a shared buffer is never written through `ptr`, and its owner is `Send + Sync`.
+unsafe impl<T: Send> Send for CpuBuffer<T> {}
+unsafe impl<T: Sync> Sync for CpuBuffer<T> {}
+
+impl<T> CpuBuffer<T> {
+    /// Creates a read-only buffer over the `len` elements at `ptr`, without copying them. The
+    /// `owner` value is kept alive for as long as the buffer or one of its clones is alive.
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` must be non-null, aligned for `T`, and valid for reads of `len` initialized
+    ///   elements for as long as `owner` is alive, e.g. `owner` is the memory mapped file that
+    ///   contains the data.
+    /// - The memory must not be mutated, through `ptr` or any other alias, while `owner` is
+    ///   alive. Mutations of the tensors using this buffer are applied to a copy.
+    pub unsafe fn from_raw_parts(
+        ptr: *const T,
+        len: usize,
+        owner: std::sync::Arc<dyn std::any::Any + Send + Sync>,
+    ) -> Self {
+        Self(CpuBufferData::Shared {
+            ptr,
+            len,
+            _owner: owner,
+        })
+    }
+
+    /// Whether the buffer owns its elements, rather than being a view on memory owned by another
+    /// value.
+    pub fn is_owned(&self) -> bool {
+        matches!(self.0, CpuBufferData::Owned(_))
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Clone> CpuBuffer<T> {
+    /// Returns the elements as a `Vec`, copying them if the buffer does not own them.
+    pub fn into_vec(self) -> Vec<T> {
+        match self.0 {
+            CpuBufferData::Owned(data) => data,
+            CpuBufferData::Shared { .. } => self.to_vec(),
+        }
+    }
+}
+
+impl<T> std::ops::Deref for CpuBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match &self.0 {
+            CpuBufferData::Owned(data) => data,
+            // SAFETY: guaranteed by the caller of `from_raw_parts`.
+            CpuBufferData::Shared { ptr, len, .. } => unsafe {
+                std::slice::from_raw_parts(*ptr, *len)
+            },
+        }
+    }
+}
+
+impl<T: Clone> std::ops::DerefMut for CpuBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        if let CpuBufferData::Shared { .. } = self.0 {
+            self.0 = CpuBufferData::Owned(self.to_vec())
+        }
+        match &mut self.0 {
+            CpuBufferData::Owned(data) => data,
+            CpuBufferData::Shared { .. } => unreachable!(),
+        }
+    }
+}
+
+impl<T: Clone> Clone for CpuBuffer<T> {
+    fn clone(&self) -> Self {
+        match &self.0 {
+            CpuBufferData::Owned(data) => Self(CpuBufferData::Owned(data.clone())),
+            // The shared memory is read-only so clones can use it too.
+            CpuBufferData::Shared { ptr, len, _owner } => Self(CpuBufferData::Shared {
+                ptr: *ptr,
+                len: *len,
+                _owner: _owner.clone(),
+            }),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for CpuBuffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        <[T] as std::fmt::Debug>::fmt(self, f)
+    }
+}
+
+impl<T> From<Vec<T>> for CpuBuffer<T> {
+    fn from(data: Vec<T>) -> Self {
+        Self(CpuBufferData::Owned(data))
+    }
+}
+
+impl<T: Send + Sync + 'static> From<std::sync::Arc<[T]>> for CpuBuffer<T> {
+    fn from(data: std::sync::Arc<[T]>) -> Self {
+        let (ptr, len) = (data.as_ptr(), data.len());
+        // SAFETY: the elements of an `Arc<[T]>` live as long as the arc, and they cannot be
+        // mutated as the buffer holds a reference to the arc.
+        unsafe { Self::from_raw_parts(ptr, len, std::sync::Arc::new(data)) }
+    }
+}
+
+impl<T> FromIterator<T> for CpuBuffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(CpuBufferData::Owned(iter.into_iter().collect()))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,19 +157,19 @@ pub trait Map1 {
 
     fn map(&self, vs: &CpuStorage, layout: &Layout) -> Result<CpuStorage> {
         match vs {
-            CpuStorage::U8(vs) => Ok(CpuStorage::U8(self.f(vs, layout)?)),
-            CpuStorage::U32(vs) => Ok(CpuStorage::U32(self.f(vs, layout)?)),
-            CpuStorage::I64(vs) => Ok(CpuStorage::I64(self.f(vs, layout)?)),
-            CpuStorage::BF16(vs) => Ok(CpuStorage::BF16(self.f(vs, layout)?)),
-            CpuStorage::F16(vs) => Ok(CpuStorage::F16(self.f(vs, layout)?)),
-            CpuStorage::F32(vs) => Ok(CpuStorage::F32(self.f(vs, layout)?)),
-            CpuStorage::F64(vs) => Ok(CpuStorage::F64(self.f(vs, layout)?)),
+            CpuStorage::U8(vs) => Ok(CpuStorage::U8(self.f(vs, layout)?.into())),
+            CpuStorage::U32(vs) => Ok(CpuStorage::U32(self.f(vs, layout)?.into())),
+            CpuStorage::I64(vs) => Ok(CpuStorage::I64(self.f(vs, layout)?.into())),
+            CpuStorage::BF16(vs) => Ok(CpuStorage::BF16(self.f(vs, layout)?.into())),
+            CpuStorage::F16(vs) => Ok(CpuStorage::F16(self.f(vs, layout)?.into())),
+            CpuStorage::F32(vs) => Ok(CpuStorage::F32(self.f(vs, layout)?.into())),
+            CpuStorage::F64(vs) => Ok(CpuStorage::F64(self.f(vs, layout)?.into())),
         }
     }
 }
 
 pub trait Map1Any {
-    fn f<T: WithDType, W: Fn(Vec<T>) -> CpuStorage>(
+    fn f<T: WithDType, W: Fn(CpuBuffer<T>) -> CpuStorage>(
         &self,
         vs: &[T],
         layout: &Layout,
@@ -70,13 +202,13 @@ pub trait Map2 {
         l2: &Layout,
     ) -> Result<CpuStorage> {
         match (v1, v2) {
-            (C::U8(v1), C::U8(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::U32(v1), C::U32(v2)) => Ok(C::U32(self.f(v1, l1, v2, l2)?)),
-            (C::I64(v1), C::I64(v2)) => Ok(C::I64(self.f(v1, l1, v2, l2)?)),
-            (C::BF16(v1), C::BF16(v2)) => Ok(C::BF16(self.f(v1, l1, v2, l2)?)),
-            (C::F16(v1), C::F16(v2)) => Ok(C::F16(self.f(v1, l1, v2, l2)?)),
-            (C::F32(v1), C::F32(v2)) => Ok(C::F32(self.f(v1, l1, v2, l2)?)),
-            (C::F64(v1), C::F64(v2)) => Ok(C::F64(self.f(v1, l1, v2, l2)?)),
+            (C::U8(v1), C::U8(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::U32(v1), C::U32(v2)) => Ok(C::U32(self.f(v1, l1, v2, l2)?.into())),
+            (C::I64(v1), C::I64(v2)) => Ok(C::I64(self.f(v1, l1, v2, l2)?.into())),
+            (C::BF16(v1), C::BF16(v2)) => Ok(C::BF16(self.f(v1, l1, v2, l2)?.into())),
+            (C::F16(v1), C::F16(v2)) => Ok(C::F16(self.f(v1, l1, v2, l2)?.into())),
+            (C::F32(v1), C::F32(v2)) => Ok(C::F32(self.f(v1, l1, v2, l2)?.into())),
+            (C::F64(v1), C::F64(v2)) => Ok(C::F64(self.f(v1, l1, v2, l2)?.into())),
             _ => Err(Error::DTypeMismatchBinaryOp {
                 lhs: v1.dtype(),
                 rhs: v2.dtype(),
@@ -99,13 +231,13 @@ pub trait Map2U8 {
         l2: &Layout,
     ) -> Result<CpuStorage> {
         match (v1, v2) {
-            (C::U8(v1), C::U8(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::U32(v1), C::U32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::I64(v1), C::I64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::BF16(v1), C::BF16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::F16(v1), C::F16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::F32(v1), C::F32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::F64(v1), C::F64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
+            (C::U8(v1), C::U8(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::U32(v1), C::U32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::I64(v1), C::I64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::BF16(v1), C::BF16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::F16(v1), C::F16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::F32(v1), C::F32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
+            (C::F64(v1), C::F64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?.into())),
             _ => Err(Error::DTypeMismatchBinaryOp {
                 lhs: v1.dtype(),
                 rhs: v2.dtype(),
@@ -261,7 +393,7 @@ impl ReduceIndex {
 
 impl Map1Any for ReduceIndex {
     #[inline(always)]
-    fn f<T: WithDType, W: Fn(Vec<T>) -> CpuStorage>(
+    fn f<T: WithDType, W: Fn(CpuBuffer<T>) -> CpuStorage>(
         &self,
         src: &[T],
         src_l: &Layout,
@@ -271,14 +403,16 @@ impl Map1Any for ReduceIndex {
             Err(Error::EmptyTensor { op: "reduce" }.bt())?
         }
         let dst = match (self.return_index, self.use_min) {
-            (false, true) => wrap(self.fold_impl(src, src_l, |x, y| x > y, |v, _i| v)?),
-            (false, false) => wrap(self.fold_impl(src, src_l, |x, y| x < y, |v, _i| v)?),
-            (true, true) => {
-                CpuStorage::U32(self.fold_impl(src, src_l, |x, y| x > y, |_v, i| i as u32)?)
-            }
-            (true, false) => {
-                CpuStorage::U32(self.fold_impl(src, src_l, |x, y| x < y, |_v, i| i as u32)?)
-            }
+            (false, true) => wrap(self.fold_impl(src, src_l, |x, y| x > y, |v, _i| v)?.into()),
+            (false, false) => wrap(self.fold_impl(src, src_l, |x, y| x < y, |v, _i| v)?.into()),
+            (true, true) => CpuStorage::U32(
+                self.fold_impl(src, src_l, |x, y| x > y, |_v, i| i as u32)?
+                    .into(),
+            ),
+            (true, false) => CpuStorage::U32(
+                self.fold_impl(src, src_l, |x, y| x < y, |_v, i| i as u32)?
+                    .into(),
+            ),
         };
         Ok(dst)
     }
@@ -761,6 +895,102 @@ impl Map1 for UpsampleNearest2D {
     }
 }
 
+struct GridSample<'a>(&'a crate::grid_sample::ParamsGridSample);
+
+impl<'a> Map2 for GridSample<'a> {
+    const OP: &'static str = "grid-sample";
+    fn f<T: WithDType>(
+        &self,
+        inp: &[T],
+        inp_l: &Layout,
+        grid: &[T],
+        grid_l: &Layout,
+    ) -> Result<Vec<T>> {
+        let p = self.0;
+        let (inp_s, grid_s) = (inp_l.stride(), grid_l.stride());
+        let mut dst = vec![T::zero(); p.b_size * p.c_in * p.h_out * p.w_out];
+        for b_idx in 0..p.b_size {
+            for dst_h in 0..p.h_out {
+                for dst_w in 0..p.w_out {
+                    let grid_idx = grid_l.start_offset()
+                        + b_idx * grid_s[0]
+                        + dst_h * grid_s[1]
+                        + dst_w * grid_s[2];
+                    let gx = grid[grid_idx].to_f64();
+                    let gy = grid[grid_idx + grid_s[3]].to_f64();
+                    let (taps, _, _) = p.taps(gx, gy);
+                    for c_idx in 0..p.c_in {
+                        let inp_idx = inp_l.start_offset() + b_idx * inp_s[0] + c_idx * inp_s[1];
+                        let mut d = 0f64;
+                        for tap in taps.iter().flatten() {
+                            let (h, w) = (tap.offset / p.w_in, tap.offset % p.w_in);
+                            let v = inp[inp_idx + h * inp_s[2] + w * inp_s[3]].to_f64();
+                            d += tap.weight * v
+                        }
+                        let dst_idx =
+                            ((b_idx * p.c_in + c_idx) * p.h_out + dst_h) * p.w_out + dst_w;
+                        dst[dst_idx] = T::from_f64(d)
+                    }
+                }
+            }
+        }
+        Ok(dst)
+    }
+}
+
+struct GridSampleBackward<'a>(&'a crate::grid_sample::ParamsGridSample);
+
+impl<'a> GridSampleBackward<'a> {
+    fn f<T: WithDType>(
+        &self,
+        (inp, inp_l): (&[T], &Layout),
+        (grid, grid_l): (&[T], &Layout),
+        (grad, grad_l): (&[T], &Layout),
+    ) -> Result<(Vec<T>, Vec<T>)> {
+        let p = self.0;
+        let (inp_s, grid_s, grad_s) = (inp_l.stride(), grid_l.stride(), grad_l.stride());
+        let plane = p.h_in * p.w_in;
+        let mut grad_inp = vec![0f64; p.b_size * p.c_in * plane];
+        let mut grad_grid = vec![T::zero(); p.b_size * p.h_out * p.w_out * 2];
+        for b_idx in 0..p.b_size {
+            for dst_h in 0..p.h_out {
+                for dst_w in 0..p.w_out {
+                    let grid_idx = grid_l.start_offset()
+                        + b_idx * grid_s[0]
+                        + dst_h * grid_s[1]
+                        + dst_w * grid_s[2];
+                    let gx = grid[grid_idx].to_f64();
+                    let gy = grid[grid_idx + grid_s[3]].to_f64();
+                    let (taps, dix, diy) = p.taps(gx, gy);
+                    let (mut gx_grad, mut gy_grad) = (0f64, 0f64);
+                    for c_idx in 0..p.c_in {
+                        let grad_idx = grad_l.start_offset()
+                            + b_idx * grad_s[0]
+                            + c_idx * grad_s[1]
+                            + dst_h * grad_s[2]
+                            + dst_w * grad_s[3];
+                        let g = grad[grad_idx].to_f64();
+                        let inp_idx = inp_l.start_offset() + b_idx * inp_s[0] + c_idx * inp_s[1];
+                        let grad_inp = &mut grad_inp[(b_idx * p.c_in + c_idx) * plane..];
+                        for tap in taps.iter().flatten() {
+                            let (h, w) = (tap.offset / p.w_in, tap.offset % p.w_in);
+                            let v = inp[inp_idx + h * inp_s[2] + w * inp_s[3]].to_f64();
+                            grad_inp[tap.offset] += g * tap.weight;
+                            gx_grad += g * v * tap.dw_dx;
+                            gy_grad += g * v * tap.dw_dy;
+                        }
+                    }
+                    let dst_idx = ((b_idx * p.h_out + dst_h) * p.w_out + dst_w) * 2;
+                    grad_grid[dst_idx] = T::from_f64(gx_grad * dix);
+                    grad_grid[dst_idx + 1] = T::from_f64(gy_grad * diy);
+                }
+            }
+        }
+        let grad_inp = grad_inp.into_iter().map(T::from_f64).collect();
+        Ok((grad_inp, grad_grid))
+    }
+}
+
 struct Gather<'a, I: IntDType> {
     ids: &'a [I],
     ids_l: &'a Layout,
@@ -1177,6 +1407,217 @@ impl<'a> Map2 for Conv2D<'a> {
     }
 }
 
+static WINOGRAD_CONV2D: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+/// Enables or disables the Winograd path for 3x3 convolutions on the cpu, it is enabled by
+/// default. This switch is process-global so it is only available with the `winograd-switch`
+/// feature, which is meant for benchmarking against the direct convolution.
+#[cfg(feature = "winograd-switch")]
+pub fn set_winograd_conv2d(enabled: bool) {
+    WINOGRAD_CONV2D.store(enabled, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Winograd F(2x2, 3x3) convolution, see "Fast Algorithms for Convolutional Neural Networks"
+/// <https://arxiv.org/abs/1509.09308>.
+///
+/// Each 2x2 output tile is computed from a 4x4 input tile using 16 multiplications rather than
+/// 36. The kernels and input tiles are first transformed, the products for the 16 positions of a
+/// tile are then computed as 16 matmuls over the channels and the results are transformed back.
+/// The transforms only involve additions and multiplications by 0.5. In f32, each output stays
+/// within `1e-7 * 9 * c_in * (1 + |direct|)` of the direct convolution.
+struct Conv2DWinograd<'a>(&'a crate::conv::ParamsConv2D);
+
+impl<'a> Conv2DWinograd<'a> {
+    /// Whether the Winograd path applies to these parameters and is expected to be faster than
+    /// the direct convolution.
+    fn is_applicable(p: &crate::conv::ParamsConv2D) -> bool {
+        p.k_h == 3
+            && p.k_w == 3
+            && p.stride == 1
+            && p.dilation == 1
+            && p.c_in >= 16
+            && p.c_out >= 16
+            && WINOGRAD_CONV2D.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+impl<'a> Map2 for Conv2DWinograd<'a> {
+    const OP: &'static str = "conv2d-winograd";
+    fn f<T: WithDType>(&self, inp: &[T], inp_l: &Layout, k: &[T], k_l: &Layout) -> Result<Vec<T>> {
+        let p = self.0;
+        let inp = &inp[inp_l.start_offset()..];
+        let (inp_s0, inp_s1, inp_s2, inp_s3) = crate::shape::dims4(inp_l.stride())?;
+        let k = &k[k_l.start_offset()..];
+        let (k_s0, k_s1, k_s2, k_s3) = crate::shape::dims4(k_l.stride())?;
+        let (out_h, out_w) = (p.out_h(), p.out_w());
+        let (tiles_h, tiles_w) = ((out_h + 1) / 2, (out_w + 1) / 2);
+        let n_tiles = p.b_size * tiles_h * tiles_w;
+        let half = T::from_f64(0.5);
+
+        // Kernel transform G.g.G^T, stored as 16 matrices of shape (c_out, c_in).
+        let mut u = vec![T::zero(); 16 * p.c_out * p.c_in];
+        for c_out_idx in 0..p.c_out {
+            for c_in_idx in 0..p.c_in {
+                let k = &k[c_out_idx * k_s0 + c_in_idx * k_s1..];
+                let mut gg = [[T::zero(); 3]; 4];
+                for j in 0..3 {
+                    let (g0, g1, g2) = (k[j * k_s3], k[k_s2 + j * k_s3], k[2 * k_s2 + j * k_s3]);
+                    gg[0][j] = g0;
+                    gg[1][j] = (g0 + g1 + g2) * half;
+                    gg[2][j] = (g0 - g1 + g2) * half;
+                    gg[3][j] = g2;
+                }
+                for (i, &[g0, g1, g2]) in gg.iter().enumerate() {
+                    let row = [g0, (g0 + g1 + g2) * half, (g0 - g1 + g2) * half, g2];
+                    for (j, v) in row.into_iter().enumerate() {
+                        u[((i * 4 + j) * p.c_out + c_out_idx) * p.c_in + c_in_idx] = v
+                    }
+                }
+            }
+        }
+
+        // Input transform B^T.d.B for each tile, stored as 16 matrices of shape (c_in, n_tiles).
+        let inp_per_channel: Vec<Vec<T>> = (0..p.c_in)
+            .into_par_iter()
+            .map(|c_in_idx| {
+                let mut v = vec![T::zero(); 16 * n_tiles];
+                let mut tile_idx = 0;
+                for b_idx in 0..p.b_size {
+                    let inp = &inp[b_idx * inp_s0 + c_in_idx * inp_s1..];
+                    for tile_h in 0..tiles_h {
+                        for tile_w in 0..tiles_w {
+                            let mut d = [[T::zero(); 4]; 4];
+                            for (i, d) in d.iter_mut().enumerate() {
+                                let src_h = 2 * tile_h + i;
+                                if src_h < p.padding || src_h >= p.i_h + p.padding {
+                                    continue;
+                                }
+                                let inp = &inp[(src_h - p.padding) * inp_s2..];
+                                for (j, d) in d.iter_mut().enumerate() {
+                                    let src_w = 2 * tile_w + j;
+                                    if src_w < p.padding || src_w >= p.i_w + p.padding {
+                                        continue;
+                                    }
+                                    *d = inp[(src_w - p.padding) * inp_s3]
+                                }
+                            }
+                            let mut bd = [[T::zero(); 4]; 4];
+                            for j in 0..4 {
+                                bd[0][j] = d[0][j] - d[2][j];
+                                bd[1][j] = d[1][j] + d[2][j];
+                                bd[2][j] = d[2][j] - d[1][j];
+                                bd[3][j] = d[1][j] - d[3][j];
+                            }
+                            for (i, &[d0, d1, d2, d3]) in bd.iter().enumerate() {
+                                let row = [d0 - d2, d1 + d2, d2 - d1, d1 - d3];
+                                for (j, x) in row.into_iter().enumerate() {
+                                    v[(i * 4 + j) * n_tiles + tile_idx] = x
+                                }
+                            }
+                            tile_idx += 1
+                        }
+                    }
+                }
+                v
+            })
+            .collect();
+        let mut v = Vec::with_capacity(16 * p.c_in * n_tiles);
+        for xy in 0..16 {
+            for inp in inp_per_channel.iter() {
+                v.extend_from_slice(&inp[xy * n_tiles..(xy + 1) * n_tiles])
+            }
+        }
+        drop(inp_per_channel);
+
+        // Element-wise products in the transformed domain, summed over the input channels.
+        let m = MatMul((16, p.c_out, n_tiles, p.c_in)).f(
+            &u,
+            &Layout::contiguous((16, p.c_out, p.c_in)),
+            &v,
+            &Layout::contiguous((16, p.c_in, n_tiles)),
+        )?;
+
+        // Output transform A^T.m.A, each task handles a single output plane.
+        let mut dst = vec![T::zero(); p.b_size * p.c_out * out_h * out_w];
+        dst.par_chunks_mut(out_h * out_w)
+            .enumerate()
+            .for_each(|(plane_idx, dst)| {
+                let (b_idx, c_out_idx) = (plane_idx / p.c_out, plane_idx % p.c_out);
+                let mut tile_idx = b_idx * tiles_h * tiles_w;
+                for tile_h in 0..tiles_h {
+                    for tile_w in 0..tiles_w {
+                        let m = |xy: usize| m[(xy * p.c_out + c_out_idx) * n_tiles + tile_idx];
+                        let am: [[T; 4]; 2] = [
+                            std::array::from_fn(|j| m(j) + m(4 + j) + m(8 + j)),
+                            std::array::from_fn(|j| m(4 + j) - m(8 + j) - m(12 + j)),
+                        ];
+                        for (i, &[m0, m1, m2, m3]) in am.iter().enumerate() {
+                            let dst_h = 2 * tile_h + i;
+                            if dst_h >= out_h {
+                                continue;
+                            }
+                            for (j, y) in [m0 + m1 + m2, m1 - m2 - m3].into_iter().enumerate() {
+                                let dst_w = 2 * tile_w + j;
+                                if dst_w < out_w {
+                                    dst[dst_h * out_w + dst_w] = y
+                                }
+                            }
+                        }
+                        tile_idx += 1
+                    }
+                }
+            });
+        Ok(dst)
+    }
+}
+
+struct Conv2DDepthwise<'a>(&'a crate::conv::ParamsConv2D);
+
+impl<'a> Map2 for Conv2DDepthwise<'a> {
+    const OP: &'static str = "conv2d-depthwise";
+    fn f<T: WithDType>(&self, inp: &[T], inp_l: &Layout, k: &[T], k_l: &Layout) -> Result<Vec<T>> {
+        let p = self.0;
+        let inp = &inp[inp_l.start_offset()..];
+        let (inp_s0, inp_s1, inp_s2, inp_s3) = crate::shape::dims4(inp_l.stride())?;
+        let k = &k[k_l.start_offset()..];
+        let (k_s0, _k_s1, k_s2, k_s3) = crate::shape::dims4(k_l.stride())?;
+        let (out_h, out_w) = (p.out_h(), p.out_w());
+        let multiplier = p.c_out / p.c_in;
+
+        // Output shape: [b_size, c_out, out_h, out_w].
+        let mut dst = vec![T::zero(); p.b_size * p.c_out * out_h * out_w];
+        // Each task handles a single output plane, the kernel offsets are in the outer loops so
+        // that the inner loop runs over a contiguous output row.
+        dst.par_chunks_mut(out_h * out_w)
+            .enumerate()
+            .for_each(|(plane_idx, dst)| {
+                let (b_idx, dst_c_idx) = (plane_idx / p.c_out, plane_idx % p.c_out);
+                let inp = &inp[b_idx * inp_s0 + (dst_c_idx / multiplier) * inp_s1..];
+                for offset_h in 0..p.k_h {
+                    for offset_w in 0..p.k_w {
+                        let k = k[dst_c_idx * k_s0 + offset_h * k_s2 + offset_w * k_s3];
+                        for dst_h in 0..out_h {
+                            let src_h = p.stride * dst_h + offset_h * p.dilation;
+                            if src_h < p.padding || src_h >= p.i_h + p.padding {
+                                continue;
+                            }
+                            let inp = &inp[(src_h - p.padding) * inp_s2..];
+                            let dst = &mut dst[dst_h * out_w..(dst_h + 1) * out_w];
+                            for (dst_w, dst) in dst.iter_mut().enumerate() {
+                                let src_w = p.stride * dst_w + offset_w * p.dilation;
+                                if src_w < p.padding || src_w >= p.i_w + p.padding {
+                                    continue;
+                                }
+                                *dst += inp[(src_w - p.padding) * inp_s3] * k
+                            }
+                        }
+                    }
+                }
+            });
+        Ok(dst)
+    }
+}
+
 struct ConvTranspose2D<'a>(&'a crate::conv::ParamsConvTranspose2D);
 
 impl<'a> Map2 for ConvTranspose2D<'a> {
@@ -1615,6 +2056,67 @@ impl CpuStorage {
         D::cpu_storage_as_slice(self)
     }
 
+    /// The number of elements in the storage.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::U8(data) => data.len(),
+            Self::U32(data) => data.len(),
+            Self::I64(data) => data.len(),
+            Self::BF16(data) => data.len(),
+            Self::F16(data) => data.len(),
+            Self::F32(data) => data.len(),
+            Self::F64(data) => data.len(),
+        }
+    }
+
+    /// Copies the `start..end` elements to a new storage that owns its data.
+    pub(crate) fn copy_range(&self, start: usize, end: usize) -> Self {
+        match self {
+            Self::U8(data) => Self::U8(data[start..end].to_vec().into()),
+            Self::U32(data) => Self::U32(data[start..end].to_vec().into()),
+            Self::I64(data) => Self::I64(data[start..end].to_vec().into()),
+            Self::BF16(data) => Self::BF16(data[start..end].to_vec().into()),
+            Self::F16(data) => Self::F16(data[start..end].to_vec().into()),
+            Self::F32(data) => Self::F32(data[start..end].to_vec().into()),
+            Self::F64(data) => Self::F64(data[start..end].to_vec().into()),
+        }
+    }
+
+    // Adds the contiguous `src` to `self` starting at `dst_offset`.
+    pub(crate) fn add_assign(
+        &mut self,
+        dst_offset: usize,
+        src: &Self,
+        src_l: &Layout,
+    ) -> Result<()> {
+        fn add<T: WithDType>(dst: &mut [T], src: &[T]) {
+            for (d, &s) in dst.iter_mut().zip(src.iter()) {
+                *d += s
+            }
+        }
+        let (o1, o2) = match src_l.contiguous_offsets() {
+            Some(offsets) => offsets,
+            None => Err(Error::RequiresContiguous { op: "add_assign" }.bt())?,
+        };
+        let dst_range = dst_offset..dst_offset + (o2 - o1);
+        match (self, src) {
+            (Self::U8(dst), Self::U8(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (Self::U32(dst), Self::U32(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (Self::I64(dst), Self::I64(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (Self::BF16(dst), Self::BF16(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (Self::F16(dst), Self::F16(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (Self::F32(dst), Self::F32(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (Self::F64(dst), Self::F64(src)) => add(&mut dst[dst_range], &src[o1..o2]),
+            (dst, src) => Err(Error::DTypeMismatchBinaryOp {
+                lhs: dst.dtype(),
+                rhs: src.dtype(),
+                op: "add_assign",
+            }
+            .bt())?,
+        }
+        Ok(())
+    }
+
     pub fn concat(storages: &[CpuStorage]) -> Result<CpuStorage> {
         let storage0 = &storages[0];
         let s = match storage0 {
@@ -1627,7 +2129,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::U8(storages)
+                Self::U8(storages.into())
             }
             Self::U32(_) => {
                 let storages = storages
@@ -1638,7 +2140,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::U32(storages)
+                Self::U32(storages.into())
             }
             Self::I64(_) => {
                 let storages = storages
@@ -1649,7 +2151,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::I64(storages)
+                Self::I64(storages.into())
             }
             Self::BF16(_) => {
                 let storages = storages
@@ -1660,7 +2162,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::BF16(storages)
+                Self::BF16(storages.into())
             }
             Self::F16(_) => {
                 let storages = storages
@@ -1671,7 +2173,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::F16(storages)
+                Self::F16(storages.into())
             }
             Self::F32(_) => {
                 let storages = storages
@@ -1682,7 +2184,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::F32(storages)
+                Self::F32(storages.into())
             }
             Self::F64(_) => {
                 let storages = storages
@@ -1693,7 +2195,7 @@ impl CpuStorage {
                     })
                     .collect::<Result<Vec<_>>>()?
                     .concat();
-                Self::F64(storages)
+                Self::F64(storages.into())
             }
         };
         Ok(s)
@@ -1720,199 +2222,199 @@ impl BackendStorage for CpuStorage {
         match (self, dtype) {
             (Self::U8(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, |v| bf16::from_f32(v as f32));
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::U32(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, |v| bf16::from_f32(v as f32));
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::I64(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, |v| bf16::from_f32(v as f32));
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::BF16(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::F16(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, |v| bf16::from_f32(v.to_f32()));
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::F32(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, bf16::from_f32);
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::F64(storage), DType::BF16) => {
                 let data = unary_map(storage, layout, bf16::from_f64);
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::U8(storage), DType::F16) => {
                 let data = unary_map(storage, layout, |v| f16::from_f32(v as f32));
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::U32(storage), DType::F16) => {
                 let data = unary_map(storage, layout, |v| f16::from_f32(v as f32));
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::I64(storage), DType::F16) => {
                 let data = unary_map(storage, layout, |v| f16::from_f32(v as f32));
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::BF16(storage), DType::F16) => {
                 let data = unary_map(storage, layout, |v| f16::from_f32(v.to_f32()));
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::F16(storage), DType::F16) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::F32(storage), DType::F16) => {
                 let data = unary_map(storage, layout, f16::from_f32);
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::F64(storage), DType::F16) => {
                 let data = unary_map(storage, layout, f16::from_f64);
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::U8(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v as f32);
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::U32(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v as f32);
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::I64(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v as f32);
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::BF16(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v.to_f32());
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::F16(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v.to_f32());
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::F32(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::F64(storage), DType::F32) => {
                 let data = unary_map(storage, layout, |v| v as f32);
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::U8(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::BF16(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v.to_f32() as u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::F16(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v.to_f32() as u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::F32(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v as u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::F64(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v as u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::U32(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v as u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::I64(storage), DType::U8) => {
                 let data = unary_map(storage, layout, |v| v as u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             (Self::U8(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v as u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::U32(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::I64(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v as u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::BF16(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v.to_f32() as u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::F16(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v.to_f32() as u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::F32(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v as u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::F64(storage), DType::U32) => {
                 let data = unary_map(storage, layout, |v| v as u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::U8(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v as i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::U32(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v as i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::I64(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::BF16(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v.to_f32() as i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::F16(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v.to_f32() as i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::F32(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v as i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::F64(storage), DType::I64) => {
                 let data = unary_map(storage, layout, |v| v as i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::U8(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v as f64);
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::U32(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v as f64);
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::I64(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v as f64);
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::BF16(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v.to_f64());
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::F16(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v.to_f64());
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::F32(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v as f64);
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::F64(storage), DType::F64) => {
                 let data = unary_map(storage, layout, |v| v);
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
         }
     }
@@ -2003,25 +2505,58 @@ impl BackendStorage for CpuStorage {
         UpsampleNearest2D(h, w).map(self, layout)
     }
 
+    fn grid_sample(
+        &self,
+        l: &Layout,
+        grid: &Self,
+        grid_l: &Layout,
+        params: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<Self> {
+        GridSample(params).map(self, l, grid, grid_l)
+    }
+
+    fn grid_sample_backward(
+        &self,
+        l: &Layout,
+        grid: &Self,
+        grid_l: &Layout,
+        grad: &Self,
+        grad_l: &Layout,
+        params: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<(Self, Self)> {
+        let bwd = GridSampleBackward(params);
+        match (self, grid, grad) {
+            (Self::F32(inp), Self::F32(grid), Self::F32(grad)) => {
+                let (g1, g2) = bwd.f((inp, l), (grid, grid_l), (grad, grad_l))?;
+                Ok((Self::F32(g1.into()), Self::F32(g2.into())))
+            }
+            (Self::F64(inp), Self::F64(grid), Self::F64(grad)) => {
+                let (g1, g2) = bwd.f((inp, l), (grid, grid_l), (grad, grad_l))?;
+                Ok((Self::F64(g1.into()), Self::F64(g2.into())))
+            }
+            _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "grid-sample-backward").bt()),
+        }
+    }
+
     fn powf(&self, layout: &Layout, e: f64) -> Result<Self> {
         use num_traits::Float;
         // TODO: Have some generic map for functions that apply on num_traits::Float elements.
         match self {
             Self::BF16(storage) => {
                 let data = unary_map(storage, layout, |v| v.powf(bf16::from_f64(e)));
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             Self::F16(storage) => {
                 let data = unary_map(storage, layout, |v| v.powf(f16::from_f64(e)));
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             Self::F32(storage) => {
                 let data = unary_map(storage, layout, |v| v.powf(e as f32));
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             Self::F64(storage) => {
                 let data = unary_map(storage, layout, |v| v.powf(e));
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             Self::U8(_) => Err(Error::UnsupportedDTypeForOp(DType::U8, "elu").bt()),
             Self::U32(_) => Err(Error::UnsupportedDTypeForOp(DType::U32, "elu").bt()),
@@ -2034,19 +2569,19 @@ impl BackendStorage for CpuStorage {
         match self {
             Self::BF16(storage) => {
                 let data = unary_map(storage, layout, |v| elu(v, bf16::from_f64(alpha)));
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             Self::F16(storage) => {
                 let data = unary_map(storage, layout, |v| elu(v, f16::from_f64(alpha)));
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             Self::F32(storage) => {
                 let data = unary_map(storage, layout, |v| elu(v, f32::from_f64(alpha)));
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             Self::F64(storage) => {
                 let data = unary_map(storage, layout, |v| elu(v, alpha));
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             Self::U8(_) => Err(Error::UnsupportedDTypeForOp(DType::U8, "elu").bt()),
             Self::U32(_) => Err(Error::UnsupportedDTypeForOp(DType::U32, "elu").bt()),
@@ -2059,50 +2594,50 @@ impl BackendStorage for CpuStorage {
             Self::BF16(storage) => {
                 if B::BF16_VEC {
                     let data = unary_map_vec(storage, layout, B::bf16, B::bf16_vec);
-                    Ok(Self::BF16(data))
+                    Ok(Self::BF16(data.into()))
                 } else {
                     let data = unary_map(storage, layout, B::bf16);
-                    Ok(Self::BF16(data))
+                    Ok(Self::BF16(data.into()))
                 }
             }
             Self::F16(storage) => {
                 if B::F16_VEC {
                     let data = unary_map_vec(storage, layout, B::f16, B::f16_vec);
-                    Ok(Self::F16(data))
+                    Ok(Self::F16(data.into()))
                 } else {
                     let data = unary_map(storage, layout, B::f16);
-                    Ok(Self::F16(data))
+                    Ok(Self::F16(data.into()))
                 }
             }
             Self::F32(storage) => {
                 if B::F32_VEC {
                     let data = unary_map_vec(storage, layout, B::f32, B::f32_vec);
-                    Ok(Self::F32(data))
+                    Ok(Self::F32(data.into()))
                 } else {
                     let data = unary_map(storage, layout, B::f32);
-                    Ok(Self::F32(data))
+                    Ok(Self::F32(data.into()))
                 }
             }
             Self::F64(storage) => {
                 if B::F64_VEC {
                     let data = unary_map_vec(storage, layout, B::f64, B::f64_vec);
-                    Ok(Self::F64(data))
+                    Ok(Self::F64(data.into()))
                 } else {
                     let data = unary_map(storage, layout, B::f64);
-                    Ok(Self::F64(data))
+                    Ok(Self::F64(data.into()))
                 }
             }
             Self::U8(storage) => {
                 let data = unary_map(storage, layout, B::u8);
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             Self::U32(storage) => {
                 let data = unary_map(storage, layout, B::u32);
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             Self::I64(storage) => {
                 let data = unary_map(storage, layout, B::i64);
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
         }
     }
@@ -2120,7 +2655,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::bf16)
                 };
-                Ok(Self::BF16(data))
+                Ok(Self::BF16(data.into()))
             }
             (Self::F16(lhs), Self::F16(rhs)) => {
                 let data = if B::F16_VEC {
@@ -2128,7 +2663,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::f16)
                 };
-                Ok(Self::F16(data))
+                Ok(Self::F16(data.into()))
             }
             (Self::F32(lhs), Self::F32(rhs)) => {
                 let data = if B::F32_VEC {
@@ -2136,7 +2671,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::f32)
                 };
-                Ok(Self::F32(data))
+                Ok(Self::F32(data.into()))
             }
             (Self::F64(lhs), Self::F64(rhs)) => {
                 let data = if B::F64_VEC {
@@ -2144,7 +2679,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::f64)
                 };
-                Ok(Self::F64(data))
+                Ok(Self::F64(data.into()))
             }
             (Self::U32(lhs), Self::U32(rhs)) => {
                 let data = if B::U32_VEC {
@@ -2152,7 +2687,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::u32)
                 };
-                Ok(Self::U32(data))
+                Ok(Self::U32(data.into()))
             }
             (Self::I64(lhs), Self::I64(rhs)) => {
                 let data = if B::I64_VEC {
@@ -2160,7 +2695,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::i64)
                 };
-                Ok(Self::I64(data))
+                Ok(Self::I64(data.into()))
             }
             (Self::U8(lhs), Self::U8(rhs)) => {
                 let data = if B::U8_VEC {
@@ -2168,7 +2703,7 @@ impl BackendStorage for CpuStorage {
                 } else {
                     binary_map(lhs_l, rhs_l, lhs, rhs, B::u8)
                 };
-                Ok(Self::U8(data))
+                Ok(Self::U8(data.into()))
             }
             _ => {
                 // This should be covered by the dtype check above.
@@ -2237,9 +2772,32 @@ impl BackendStorage for CpuStorage {
         kernel_l: &Layout,
         params: &crate::conv::ParamsConv2D,
     ) -> Result<Self> {
+        if Conv2DWinograd::is_applicable(params) {
+            match (self, kernel) {
+                (Self::F32(inp), Self::F32(k)) => {
+                    let dst = Conv2DWinograd(params).f(inp, l, k, kernel_l)?;
+                    return Ok(Self::F32(dst.into()));
+                }
+                (Self::F64(inp), Self::F64(k)) => {
+                    let dst = Conv2DWinograd(params).f(inp, l, k, kernel_l)?;
+                    return Ok(Self::F64(dst.into()));
+                }
+                _ => {}
+            }
+        }
         Conv2D(params).map(self, l, kernel, kernel_l)
     }
 
+    fn conv2d_depthwise(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConv2D,
+    ) -> Result<Self> {
+        Conv2DDepthwise(params).map(self, l, kernel, kernel_l)
+    }
+
     fn conv_transpose2d(
         &self,
         l: &Layout,
@@ -2327,7 +2885,17 @@ impl BackendStorage for CpuStorage {
         lhs_l: &Layout,
         rhs_l: &Layout,
     ) -> Result<Self> {
-        MatMul(bmnk).map(self, lhs_l, rhs, rhs_l)
+        match (self, rhs) {
+            // There is no bf16 gemm kernel, so the matmul is done in f32 and the result is
+            // rounded back to bf16.
+            (Self::BF16(lhs), Self::BF16(rhs)) => {
+                let lhs: Vec<f32> = lhs.iter().map(|v| v.to_f32()).collect();
+                let rhs: Vec<f32> = rhs.iter().map(|v| v.to_f32()).collect();
+                let dst = MatMul(bmnk).f(&lhs, lhs_l, &rhs, rhs_l)?;
+                Ok(Self::BF16(dst.into_iter().map(bf16::from_f32).collect()))
+            }
+            _ => MatMul(bmnk).map(self, lhs_l, rhs, rhs_l),
+        }
     }
 
     fn device(&self) -> &Self::Device {
@@ -2378,7 +2946,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(rng.sample::<bf16, _>(uniform))
                 }
-                Ok(CpuStorage::BF16(data))
+                Ok(CpuStorage::BF16(data.into()))
             }
             DType::F16 => {
                 let mut data = Vec::with_capacity(elem_count);
@@ -2387,7 +2955,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(rng.sample::<f16, _>(uniform))
                 }
-                Ok(CpuStorage::F16(data))
+                Ok(CpuStorage::F16(data.into()))
             }
             DType::F32 => {
                 let mut data = Vec::with_capacity(elem_count);
@@ -2395,7 +2963,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(rng.sample::<f32, _>(uniform))
                 }
-                Ok(CpuStorage::F32(data))
+                Ok(CpuStorage::F32(data.into()))
             }
             DType::F64 => {
                 let mut data = Vec::with_capacity(elem_count);
@@ -2403,7 +2971,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(rng.sample::<f64, _>(uniform))
                 }
-                Ok(CpuStorage::F64(data))
+                Ok(CpuStorage::F64(data.into()))
             }
         }
     }
@@ -2424,7 +2992,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(normal.sample(&mut rng))
                 }
-                Ok(CpuStorage::BF16(data))
+                Ok(CpuStorage::BF16(data.into()))
             }
             DType::F16 => {
                 let mut data = Vec::with_capacity(elem_count);
@@ -2433,7 +3001,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(normal.sample(&mut rng))
                 }
-                Ok(CpuStorage::F16(data))
+                Ok(CpuStorage::F16(data.into()))
             }
             DType::F32 => {
                 let mut data = Vec::with_capacity(elem_count);
@@ -2442,7 +3010,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(normal.sample(&mut rng))
                 }
-                Ok(CpuStorage::F32(data))
+                Ok(CpuStorage::F32(data.into()))
             }
             DType::F64 => {
                 let mut data = Vec::with_capacity(elem_count);
@@ -2450,7 +3018,7 @@ impl BackendDevice for CpuDevice {
                 for _i in 0..elem_count {
                     data.push(normal.sample(&mut rng))
                 }
-                Ok(CpuStorage::F64(data))
+                Ok(CpuStorage::F64(data.into()))
             }
         }
     }
@@ -2458,13 +3026,13 @@ impl BackendDevice for CpuDevice {
     fn ones_impl(&self, shape: &Shape, dtype: DType) -> Result<CpuStorage> {
         let elem_count = shape.elem_count();
         let storage = match dtype {
-            DType::U8 => CpuStorage::U8(vec![1u8; elem_count]),
-            DType::U32 => CpuStorage::U32(vec![1u32; elem_count]),
-            DType::I64 => CpuStorage::I64(vec![1i64; elem_count]),
-            DType::BF16 => CpuStorage::BF16(vec![bf16::ONE; elem_count]),
-            DType::F16 => CpuStorage::F16(vec![f16::ONE; elem_count]),
-            DType::F32 => CpuStorage::F32(vec![1f32; elem_count]),
-            DType::F64 => CpuStorage::F64(vec![1f64; elem_count]),
+            DType::U8 => CpuStorage::U8(vec![1u8; elem_count].into()),
+            DType::U32 => CpuStorage::U32(vec![1u32; elem_count].into()),
+            DType::I64 => CpuStorage::I64(vec![1i64; elem_count].into()),
+            DType::BF16 => CpuStorage::BF16(vec![bf16::ONE; elem_count].into()),
+            DType::F16 => CpuStorage::F16(vec![f16::ONE; elem_count].into()),
+            DType::F32 => CpuStorage::F32(vec![1f32; elem_count].into()),
+            DType::F64 => CpuStorage::F64(vec![1f64; elem_count].into()),
         };
         Ok(storage)
     }
@@ -2472,13 +3040,13 @@ impl BackendDevice for CpuDevice {
     fn zeros_impl(&self, shape: &Shape, dtype: DType) -> Result<CpuStorage> {
         let elem_count = shape.elem_count();
         let storage = match dtype {
-            DType::U8 => CpuStorage::U8(vec![0u8; elem_count]),
-            DType::U32 => CpuStorage::U32(vec![0u32; elem_count]),
-            DType::I64 => CpuStorage::I64(vec![0i64; elem_count]),
-            DType::BF16 => CpuStorage::BF16(vec![bf16::ZERO; elem_count]),
-            DType::F16 => CpuStorage::F16(vec![f16::ZERO; elem_count]),
-            DType::F32 => CpuStorage::F32(vec![0f32; elem_count]),
-            DType::F64 => CpuStorage::F64(vec![0f64; elem_count]),
+            DType::U8 => CpuStorage::U8(vec![0u8; elem_count].into()),
+            DType::U32 => CpuStorage::U32(vec![0u32; elem_count].into()),
+            DType::I64 => CpuStorage::I64(vec![0i64; elem_count].into()),
+            DType::BF16 => CpuStorage::BF16(vec![bf16::ZERO; elem_count].into()),
+            DType::F16 => CpuStorage::F16(vec![f16::ZERO; elem_count].into()),
+            DType::F32 => CpuStorage::F32(vec![0f32; elem_count].into()),
+            DType::F64 => CpuStorage::F64(vec![0f64; elem_count].into()),
         };
         Ok(storage)
     }
@@ -2488,8 +3056,58 @@ impl BackendDevice for CpuDevice {
 macro_rules! map_dtype {
     ($name:expr, $storage:ident, $fn:expr, ($($dtypes:ident),+)) => {
         match $storage {
-            $(CpuStorage::$dtypes(__e) => CpuStorage::$dtypes($fn(__e)),)*
+            $(CpuStorage::$dtypes(__e) => CpuStorage::$dtypes($fn(__e).into()),)*
             s => Err(Error::UnsupportedDTypeForOp(s.dtype(), $name).bt())?,
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conv2d_winograd() -> Result<()> {
+        use rand::prelude::*;
+        let mut rng = StdRng::seed_from_u64(299792458);
+        let normal = rand_distr::StandardNormal;
+        // (b_size, c_in, c_out, h, w, padding), the last entry is a ResNet basic block.
+        for (b_size, c_in, c_out, i_h, i_w, padding) in [
+            (2, 16, 24, 8, 8, 1),
+            (2, 16, 24, 7, 9, 0),
+            (1, 16, 16, 5, 6, 2),
+            (1, 64, 64, 14, 14, 1),
+            (1, 256, 256, 7, 7, 1),
+        ] {
+            let p = crate::conv::ParamsConv2D {
+                b_size,
+                i_h,
+                i_w,
+                k_h: 3,
+                k_w: 3,
+                c_out,
+                c_in,
+                padding,
+                stride: 1,
+                dilation: 1,
+            };
+            assert!(Conv2DWinograd::is_applicable(&p));
+            let inp: Vec<f32> = (0..b_size * c_in * i_h * i_w)
+                .map(|_| rng.sample(normal))
+                .collect();
+            let k: Vec<f32> = (0..c_out * c_in * 9).map(|_| rng.sample(normal)).collect();
+            let inp_l = Layout::contiguous((b_size, c_in, i_h, i_w));
+            let k_l = Layout::contiguous((c_out, c_in, 3, 3));
+            let winograd = Conv2DWinograd(&p).f(&inp, &inp_l, &k, &k_l)?;
+            let direct = Conv2D(&p).f(&inp, &inp_l, &k, &k_l)?;
+            assert_eq!(winograd.len(), direct.len());
+            // The rounding errors accumulate over the c_in * 9 products of each output.
+            let tol = 1e-7 * (c_in * 9) as f32;
+            for (i, (w, d)) in winograd.iter().zip(direct.iter()).enumerate() {
+                let err = (w - d).abs() / (1. + d.abs());
+                assert!(err <= tol, "c_in {c_in}, index {i}: {w} vs {d}");
+            }
+        }
+        Ok(())
+    }
+}
@@ -4,11 +4,14 @@ use crate::{CpuStorage, DType, Layout, Result, Shape, WithDType};
 pub use candle_kernels as kernels;
 pub use cudarc;
 use cudarc::cublas::{Gemm, GemmConfig, StridedBatchedConfig};
+use cudarc::driver::sys;
 use cudarc::driver::{
-    CudaFunction, CudaSlice, DevicePtr, DeviceRepr, DeviceSlice, LaunchAsync, LaunchConfig,
-    ValidAsZeroBits,
+    CudaFunction, CudaSlice, DevicePtr, DevicePtrMut, DeviceRepr, DeviceSlice, DriverError,
+    LaunchAsync, LaunchConfig, ValidAsZeroBits,
 };
 use half::{bf16, f16};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 /// cudarc related errors
@@ -84,6 +87,8 @@ pub struct CudaDevice {
     device: Arc<cudarc::driver::CudaDevice>,
     blas: Arc<cudarc::cublas::CudaBlas>,
     curand: Arc<Mutex<CudaRng>>,
+    mem_pools_supported: bool,
+    driver_allocations: Arc<AtomicUsize>,
 }
 
 impl std::fmt::Debug for CudaDevice {
@@ -110,6 +115,81 @@ impl<O, E: Into<CudaError>> WrapErr<O> for std::result::Result<O, E> {
     }
 }
 
+// Page-locked host memory, released when the last buffer using it is dropped.
+struct PinnedHostMemory {
+    ptr: *mut c_void,
+    // Keeps the primary context of the device, in which the memory was allocated, alive.
+    device: Arc<cudarc::driver::CudaDevice>,
+}
+
+// SAFETY: the memory is only read through the `CpuBuffer` it backs, and freed on drop.
+unsafe impl Send for PinnedHostMemory {}
+unsafe impl Sync for PinnedHostMemory {}
+
+impl Drop for PinnedHostMemory {
+    fn drop(&mut self) {
+        if self.device.bind_to_thread().is_ok() {
+            let _ = unsafe { sys::cuMemFreeHost(self.ptr) };
+        }
+    }
+}
+
+fn pinned_buffer<T: Copy>(
+    device: &Arc<cudarc::driver::CudaDevice>,
+    src: &[T],
+) -> Result<crate::CpuBuffer<T>> {
+    // The driver does not hand out empty allocations.
+    if src.is_empty() {
+        return Ok(Vec::new().into());
+    }
+    device.bind_to_thread().w()?;
+    let mut ptr = std::ptr::null_mut();
+    unsafe {
+        sys::cuMemHostAlloc(
+            &mut ptr,
+            std::mem::size_of_val(src),
+            sys::CU_MEMHOSTALLOC_PORTABLE,
+        )
+        .result()
+        .w()?
+    };
+    let memory = Arc::new(PinnedHostMemory {
+        ptr,
+        device: device.clone(),
+    });
+    // SAFETY: the allocation is page aligned, large enough for `src`, owned by `memory` and
+    // not used anywhere else.
+    unsafe {
+        std::ptr::copy_nonoverlapping(src.as_ptr(), ptr as *mut T, src.len());
+        Ok(crate::CpuBuffer::from_raw_parts(
+            ptr as *const T,
+            src.len(),
+            memory,
+        ))
+    }
+}
+
+/// Copies the `start..end` elements of `storage` to page-locked host memory allocated through
+/// the gpu `ordinal`. The memory is portable so it can be used with any gpu.
+pub(crate) fn pin_cpu_storage(
+    storage: &CpuStorage,
+    start: usize,
+    end: usize,
+    ordinal: usize,
+) -> Result<CpuStorage> {
+    let device = cudarc::driver::CudaDevice::new(ordinal).w()?;
+    let storage = match storage {
+        CpuStorage::U8(data) => CpuStorage::U8(pinned_buffer(&device, &data[start..end])?),
+        CpuStorage::U32(data) => CpuStorage::U32(pinned_buffer(&device, &data[start..end])?),
+        CpuStorage::I64(data) => CpuStorage::I64(pinned_buffer(&device, &data[start..end])?),
+        CpuStorage::BF16(data) => CpuStorage::BF16(pinned_buffer(&device, &data[start..end])?),
+        CpuStorage::F16(data) => CpuStorage::F16(pinned_buffer(&device, &data[start..end])?),
+        CpuStorage::F32(data) => CpuStorage::F32(pinned_buffer(&device, &data[start..end])?),
+        CpuStorage::F64(data) => CpuStorage::F64(pinned_buffer(&device, &data[start..end])?),
+    };
+    Ok(storage)
+}
+
 impl CudaDevice {
     pub fn cuda_device(&self) -> Arc<cudarc::driver::CudaDevice> {
         self.device.clone()
@@ -119,6 +199,146 @@ impl CudaDevice {
         self.id
     }
 
+    // Allocations go through the stream ordered allocator which uses the default memory pool of
+    // the gpu.
+    fn default_mem_pool(&self) -> Result<sys::CUmemoryPool> {
+        let mut pool = std::mem::MaybeUninit::uninit();
+        unsafe {
+            sys::cuDeviceGetDefaultMemPool(pool.as_mut_ptr(), *self.device.cu_device())
+                .result()
+                .w()?;
+            Ok(pool.assume_init())
+        }
+    }
+
+    fn mem_pool_attribute(&self, attr: sys::CUmemPool_attribute) -> Result<u64> {
+        let pool = self.default_mem_pool()?;
+        let mut value = 0u64;
+        unsafe {
+            sys::cuMemPoolGetAttribute(pool, attr, &mut value as *mut u64 as *mut c_void)
+                .result()
+                .w()?
+        }
+        Ok(value)
+    }
+
+    fn set_mem_pool_attribute(&self, attr: sys::CUmemPool_attribute, value: u64) -> Result<()> {
+        let pool = self.default_mem_pool()?;
+        let mut value = value;
+        unsafe {
+            sys::cuMemPoolSetAttribute(pool, attr, &mut value as *mut u64 as *mut c_void)
+                .result()
+                .w()
+        }
+    }
+
+    pub fn memory_pool_stats(&self) -> Result<crate::MemoryPoolStats> {
+        use sys::CUmemPool_attribute::*;
+        let driver_allocations = self.driver_allocations.load(Ordering::Relaxed);
+        if !self.mem_pools_supported {
+            return Ok(crate::MemoryPoolStats {
+                driver_allocations,
+                ..Default::default()
+            });
+        }
+        let reserved = self.mem_pool_attribute(CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT)?;
+        let used = self.mem_pool_attribute(CU_MEMPOOL_ATTR_USED_MEM_CURRENT)?;
+        let peak = self.mem_pool_attribute(CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH)?;
+        Ok(crate::MemoryPoolStats {
+            used_bytes: used as usize,
+            cached_bytes: reserved.saturating_sub(used) as usize,
+            peak_reserved_bytes: peak as usize,
+            driver_allocations,
+        })
+    }
+
+    pub fn reset_memory_pool(&self) -> Result<()> {
+        use sys::CUmemPool_attribute::*;
+        if !self.mem_pools_supported {
+            return Ok(());
+        }
+        // Buffers are freed asynchronously on the stream, wait for these to be returned to the
+        // pool before trimming it.
+        self.device.synchronize().w()?;
+        let pool = self.default_mem_pool()?;
+        unsafe { sys::cuMemPoolTrimTo(pool, 0).result().w()? };
+        self.set_mem_pool_attribute(CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH, 0)
+    }
+
+    fn mem_pools_supported(device: &cudarc::driver::CudaDevice) -> Result<bool> {
+        let mut value = 0i32;
+        unsafe {
+            sys::cuDeviceGetAttribute(
+                &mut value,
+                sys::CUdevice_attribute::CU_DEVICE_ATTRIBUTE_MEMORY_POOLS_SUPPORTED,
+                *device.cu_device(),
+            )
+            .result()
+            .w()?
+        }
+        Ok(value > 0)
+    }
+
+    // Runs an allocation and counts it if it had to get memory from the driver, i.e. when the
+    // pool reserved more memory or when there is no pool at all.
+    fn track_allocation<T>(
+        &self,
+        alloc: impl FnOnce() -> std::result::Result<T, DriverError>,
+    ) -> std::result::Result<T, DriverError> {
+        use sys::CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT;
+        if !self.mem_pools_supported {
+            let data = alloc()?;
+            self.driver_allocations.fetch_add(1, Ordering::Relaxed);
+            return Ok(data);
+        }
+        let reserved = || {
+            self.mem_pool_attribute(CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT)
+                .ok()
+        };
+        let before = reserved();
+        let data = alloc()?;
+        if reserved() > before {
+            self.driver_allocations.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(data)
+    }
+
+    // The following wrap the cudarc allocation functions so that the allocations are reported
+    // in the memory pool stats.
+
+    /// Allocates a buffer of `len` elements without initializing it.
+    ///
+    /// # Safety
+    ///
+    /// The content of the buffer is uninitialized and must be written before being read.
+    pub unsafe fn alloc<T: DeviceRepr>(
+        &self,
+        len: usize,
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        self.track_allocation(|| self.device.alloc::<T>(len))
+    }
+
+    pub fn alloc_zeros<T: ValidAsZeroBits + DeviceRepr>(
+        &self,
+        len: usize,
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        self.track_allocation(|| self.device.alloc_zeros::<T>(len))
+    }
+
+    pub fn htod_copy<T: Unpin + DeviceRepr>(
+        &self,
+        src: Vec<T>,
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        self.track_allocation(|| self.device.htod_copy(src))
+    }
+
+    pub fn htod_sync_copy<T: DeviceRepr>(
+        &self,
+        src: &[T],
+    ) -> std::result::Result<CudaSlice<T>, DriverError> {
+        self.track_allocation(|| self.device.htod_sync_copy(src))
+    }
+
     fn const_impl(&self, v: f64, shape: &Shape, dtype: DType) -> Result<CudaStorage> {
         let elem_count = shape.elem_count();
         let cfg = LaunchConfig::for_num_elems(elem_count as u32);
@@ -215,12 +435,25 @@ impl BackendDevice for CudaDevice {
         let device = cudarc::driver::CudaDevice::new(ordinal).w()?;
         let blas = cudarc::cublas::CudaBlas::new(device.clone()).w()?;
         let curand = cudarc::curand::CudaRng::new(299792458, device.clone()).w()?;
-        Ok(Self {
+        let mem_pools_supported = Self::mem_pools_supported(&device)?;
+        let device = Self {
             id: DeviceId::new(),
             device,
             blas: Arc::new(blas),
             curand: Arc::new(Mutex::new(CudaRng(curand))),
-        })
+            mem_pools_supported,
+            driver_allocations: Arc::new(AtomicUsize::new(0)),
+        };
+        // By default the pool releases the freed memory back to the driver whenever the stream
+        // is synchronized, keep it cached instead so that it can be reused by later allocations.
+        // This is only an optimization so the device is still usable if it cannot be set.
+        if mem_pools_supported {
+            let _ = device.set_mem_pool_attribute(
+                sys::CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD,
+                u64::MAX,
+            );
+        }
+        Ok(device)
     }
 
     fn location(&self) -> crate::DeviceLocation {
@@ -1033,6 +1266,46 @@ impl<'a> Map2 for Conv2D<'a> {
     }
 }
 
+struct Conv2DDepthwise<'a>(&'a crate::conv::ParamsConv2D);
+impl<'a> Map2 for Conv2DDepthwise<'a> {
+    fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
+        &self,
+        inp: &CudaSlice<T>,
+        inp_l: &Layout,
+        k: &CudaSlice<T>,
+        k_l: &Layout,
+        dev: &CudaDevice,
+    ) -> Result<CudaSlice<T>> {
+        // Kernel shape: (c_out, 1, h_k, w_k)
+        // Input shape: (b_size, c_in, h_in, w_in)
+        let p = &self.0;
+        let (out_w, out_h) = (p.out_w(), p.out_h());
+        let dst_el = p.c_out * out_w * out_h * p.b_size;
+        let inp = &inp.slice(inp_l.start_offset()..);
+        let k = &k.slice(k_l.start_offset()..);
+        let shape = inp_l.shape();
+        let dims = shape.dims();
+        let el = shape.elem_count();
+
+        // SAFETY: Set later by running the kernel.
+        let out = unsafe { dev.alloc::<T>(dst_el) }.w()?;
+        let cfg = LaunchConfig::for_num_elems(dst_el as u32);
+        let func = dev.get_or_load_func(&kernel_name::<T>("conv2d_depthwise"), kernels::CONV)?;
+        let ds = if dims.len() == 4 {
+            [dims, inp_l.stride(), k_l.dims(), k_l.stride()].concat()
+        } else {
+            crate::bail!("unexpected input shape for conv2d-depthwise {dims:?}")
+        };
+        let ds = dev.htod_copy(ds).w()?;
+        let params = (
+            el, out_w, out_h, p.stride, p.padding, p.dilation, &ds, inp, k, &out,
+        );
+        // SAFETY: ffi.
+        unsafe { func.launch(cfg, params) }.w()?;
+        Ok(out)
+    }
+}
+
 struct ConvTranspose2D<'a>(&'a crate::conv::ParamsConvTranspose2D);
 impl<'a> Map2 for ConvTranspose2D<'a> {
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
@@ -1174,6 +1447,108 @@ impl Map1 for UpsampleNearest2D {
     }
 }
 
+// The grid sample parameters packed for the kernels: the dims followed by the mode (0 for
+// bilinear, 1 for nearest), the padding (0 for zeros, 1 for border) and align_corners.
+fn grid_sample_info(p: &crate::grid_sample::ParamsGridSample) -> [usize; 9] {
+    use crate::{GridSampleMode, GridSamplePadding};
+    let mode = match p.mode {
+        GridSampleMode::Bilinear => 0,
+        GridSampleMode::Nearest => 1,
+    };
+    let padding = match p.padding_mode {
+        GridSamplePadding::Zeros => 0,
+        GridSamplePadding::Border => 1,
+    };
+    [
+        p.b_size,
+        p.c_in,
+        p.h_in,
+        p.w_in,
+        p.h_out,
+        p.w_out,
+        mode,
+        padding,
+        p.align_corners as usize,
+    ]
+}
+
+struct GridSample<'a>(&'a crate::grid_sample::ParamsGridSample);
+impl<'a> Map2 for GridSample<'a> {
+    fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
+        &self,
+        inp: &CudaSlice<T>,
+        inp_l: &Layout,
+        grid: &CudaSlice<T>,
+        grid_l: &Layout,
+        dev: &CudaDevice,
+    ) -> Result<CudaSlice<T>> {
+        let p = self.0;
+        let dst_el = p.b_size * p.c_in * p.h_out * p.w_out;
+        let inp = &inp.slice(inp_l.start_offset()..);
+        let grid = &grid.slice(grid_l.start_offset()..);
+        // SAFETY: Set later by running the kernel.
+        let out = unsafe { dev.alloc::<T>(dst_el) }.w()?;
+        let cfg = LaunchConfig::for_num_elems(dst_el as u32);
+        let func = dev.get_or_load_func(&kernel_name::<T>("grid_sample"), kernels::CONV)?;
+        let ds = [&grid_sample_info(p)[..], inp_l.stride(), grid_l.stride()].concat();
+        let ds = dev.htod_copy(ds).w()?;
+        let params = (&ds, inp, grid, &out);
+        // SAFETY: ffi.
+        unsafe { func.launch(cfg, params) }.w()?;
+        Ok(out)
+    }
+}
+
+struct GridSampleBackward<'a>(&'a crate::grid_sample::ParamsGridSample);
+impl<'a> GridSampleBackward<'a> {
+    fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
+        &self,
+        (inp, inp_l): (&CudaSlice<T>, &Layout),
+        (grid, grid_l): (&CudaSlice<T>, &Layout),
+        (grad, grad_l): (&CudaSlice<T>, &Layout),
+        dev: &CudaDevice,
+    ) -> Result<(CudaSlice<T>, CudaSlice<T>)> {
+        let p = self.0;
+        let inp = &inp.slice(inp_l.start_offset()..);
+        let grid = &grid.slice(grid_l.start_offset()..);
+        let grad = &grad.slice(grad_l.start_offset()..);
+        let ds = [
+            &grid_sample_info(p)[..],
+            inp_l.stride(),
+            grid_l.stride(),
+            grad_l.stride(),
+        ]
+        .concat();
+        let ds = dev.htod_copy(ds).w()?;
+
+        // Each thread accumulates the gradient of a whole (h_in, w_in) plane so that no atomic
+        // operations are needed.
+        let planes = p.b_size * p.c_in;
+        let grad_inp = dev.alloc_zeros::<T>(planes * p.h_in * p.w_in).w()?;
+        let cfg = LaunchConfig::for_num_elems(planes as u32);
+        let func = dev.get_or_load_func(
+            &kernel_name::<T>("grid_sample_backward_input"),
+            kernels::CONV,
+        )?;
+        let params = (&ds, grid, grad, &grad_inp);
+        // SAFETY: ffi.
+        unsafe { func.launch(cfg, params) }.w()?;
+
+        let locations = p.b_size * p.h_out * p.w_out;
+        // SAFETY: Set later by running the kernel.
+        let grad_grid = unsafe { dev.alloc::<T>(locations * 2) }.w()?;
+        let cfg = LaunchConfig::for_num_elems(locations as u32);
+        let func = dev.get_or_load_func(
+            &kernel_name::<T>("grid_sample_backward_grid"),
+            kernels::CONV,
+        )?;
+        let params = (&ds, inp, grid, grad, &grad_grid);
+        // SAFETY: ffi.
+        unsafe { func.launch(cfg, params) }.w()?;
+        Ok((grad_inp, grad_grid))
+    }
+}
+
 struct WhereCond<'a>(&'a CudaStorage, &'a Layout);
 impl<'a> Map2 for WhereCond<'a> {
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
@@ -1357,6 +1732,51 @@ impl CudaStorage {
     pub fn as_cuda_slice<T: CudaDType>(&self) -> Result<&CudaSlice<T>> {
         T::as_cuda_slice(self)
     }
+
+    /// Copies the storage to another cuda device without going through a cpu storage. When both
+    /// devices share the same gpu this is a plain device to device copy, otherwise a peer copy is
+    /// used, the driver takes care of staging the data through host memory if peer access is not
+    /// available between the two gpus.
+    pub fn transfer_to_device(&self, dst: &CudaDevice) -> Result<Self> {
+        fn transfer<T: DeviceRepr>(src: &CudaSlice<T>, dst: &CudaDevice) -> Result<CudaSlice<T>> {
+            let src_dev = src.device();
+            let dst_dev = &dst.device;
+            let mut out = unsafe { dst_dev.alloc::<T>(src.len()) }.w()?;
+            if src_dev.ordinal() == dst_dev.ordinal() {
+                dst_dev.dtod_copy(src, &mut out).w()?;
+            } else {
+                // The peer copy is not ordered with respect to the pending work on the source
+                // device so wait for it to complete first.
+                src_dev.synchronize().w()?;
+                let bytes = src.len() * std::mem::size_of::<T>();
+                unsafe {
+                    cudarc::driver::sys::cuMemcpyPeer(
+                        *out.device_ptr_mut(),
+                        *dst_dev.cu_primary_ctx(),
+                        *src.device_ptr(),
+                        *src_dev.cu_primary_ctx(),
+                        bytes,
+                    )
+                    .result()
+                    .w()?
+                }
+            }
+            Ok(out)
+        }
+        let slice = match &self.slice {
+            S::U8(s) => S::U8(transfer(s, dst)?),
+            S::U32(s) => S::U32(transfer(s, dst)?),
+            S::I64(s) => S::I64(transfer(s, dst)?),
+            S::BF16(s) => S::BF16(transfer(s, dst)?),
+            S::F16(s) => S::F16(transfer(s, dst)?),
+            S::F32(s) => S::F32(transfer(s, dst)?),
+            S::F64(s) => S::F64(transfer(s, dst)?),
+        };
+        Ok(Self {
+            slice,
+            device: dst.clone(),
+        })
+    }
 }
 
 fn gemm_config<T>(
@@ -1595,37 +2015,37 @@ impl BackendStorage for CudaStorage {
             CudaStorageSlice::U8(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::U8(cpu_storage))
+                Ok(CpuStorage::U8(cpu_storage.into()))
             }
             CudaStorageSlice::U32(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::U32(cpu_storage))
+                Ok(CpuStorage::U32(cpu_storage.into()))
             }
             CudaStorageSlice::I64(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::I64(cpu_storage))
+                Ok(CpuStorage::I64(cpu_storage.into()))
             }
             CudaStorageSlice::BF16(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::BF16(cpu_storage))
+                Ok(CpuStorage::BF16(cpu_storage.into()))
             }
             CudaStorageSlice::F16(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::F16(cpu_storage))
+                Ok(CpuStorage::F16(cpu_storage.into()))
             }
             CudaStorageSlice::F32(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::F32(cpu_storage))
+                Ok(CpuStorage::F32(cpu_storage.into()))
             }
             CudaStorageSlice::F64(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
-                Ok(CpuStorage::F64(cpu_storage))
+                Ok(CpuStorage::F64(cpu_storage.into()))
             }
         }
     }
@@ -1731,6 +2151,19 @@ impl BackendStorage for CudaStorage {
         Ok(Self { slice, device })
     }
 
+    fn conv2d_depthwise(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConv2D,
+    ) -> Result<Self> {
+        let device = self.device().clone();
+        let slice =
+            Conv2DDepthwise(params).map(&self.slice, l, &kernel.slice, kernel_l, &device)?;
+        Ok(Self { slice, device })
+    }
+
     fn conv_transpose2d(
         &self,
         l: &Layout,
@@ -1776,6 +2209,64 @@ impl BackendStorage for CudaStorage {
         Ok(Self { slice, device })
     }
 
+    fn grid_sample(
+        &self,
+        l: &Layout,
+        grid: &Self,
+        grid_l: &Layout,
+        params: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<Self> {
+        let device = self.device().clone();
+        let slice = match (&self.slice, &grid.slice) {
+            (S::F32(inp), S::F32(grid)) => {
+                S::F32(GridSample(params).f(inp, l, grid, grid_l, &device)?)
+            }
+            (S::F64(inp), S::F64(grid)) => {
+                S::F64(GridSample(params).f(inp, l, grid, grid_l, &device)?)
+            }
+            _ => Err(CudaError::UnexpectedDType {
+                msg: "grid-sample only supports f32 and f64",
+                expected: DType::F32,
+                got: self.dtype(),
+            })?,
+        };
+        Ok(Self { slice, device })
+    }
+
+    fn grid_sample_backward(
+        &self,
+        l: &Layout,
+        grid: &Self,
+        grid_l: &Layout,
+        grad: &Self,
+        grad_l: &Layout,
+        params: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<(Self, Self)> {
+        let device = self.device().clone();
+        let bwd = GridSampleBackward(params);
+        let (g1, g2) = match (&self.slice, &grid.slice, &grad.slice) {
+            (S::F32(inp), S::F32(grid), S::F32(grad)) => {
+                let (g1, g2) = bwd.f((inp, l), (grid, grid_l), (grad, grad_l), &device)?;
+                (S::F32(g1), S::F32(g2))
+            }
+            (S::F64(inp), S::F64(grid), S::F64(grad)) => {
+                let (g1, g2) = bwd.f((inp, l), (grid, grid_l), (grad, grad_l), &device)?;
+                (S::F64(g1), S::F64(g2))
+            }
+            _ => Err(CudaError::UnexpectedDType {
+                msg: "grid-sample-backward only supports f32 and f64",
+                expected: DType::F32,
+                got: self.dtype(),
+            })?,
+        };
+        let g1 = Self {
+            slice: g1,
+            device: device.clone(),
+        };
+        let g2 = Self { slice: g2, device };
+        Ok((g1, g2))
+    }
+
     fn index_select(&self, ids: &Self, l: &Layout, ids_l: &Layout, dim: usize) -> Result<Self> {
         let device = self.device().clone();
         let slice = IndexSelect(ids, ids_l, dim).map(&self.slice, &device, l)?;
@@ -5,15 +5,77 @@ use crate::{CpuStorage, DType, Result, Shape, Storage, WithDType};
 /// A `DeviceLocation` represents a physical device whereas multiple `Device`
 /// can live on the same location (typically for cuda devices).
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DeviceLocation {
     Cpu,
     Cuda { gpu_id: usize },
+    Metal { gpu_id: usize },
+}
+
+/// Memory usage of the caching allocator of a device, see [`Device::memory_pool_stats`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryPoolStats {
+    /// Bytes used by live buffers.
+    pub used_bytes: usize,
+    /// Bytes of freed buffers kept around for later allocations.
+    pub cached_bytes: usize,
+    /// High-water mark of the memory reserved by the pool, i.e. used and cached bytes.
+    pub peak_reserved_bytes: usize,
+    /// Number of allocations that could not reuse cached memory and had to get new memory from
+    /// the driver since the device was created.
+    pub driver_allocations: usize,
 }
 
 #[derive(Debug, Clone)]
 pub enum Device {
     Cpu,
     Cuda(crate::CudaDevice),
+    Metal(crate::MetalDevice),
+}
+
+thread_local! {
+    static DEFAULT_DEVICE: std::cell::RefCell<Device> = const { std::cell::RefCell::new(Device::Cpu) };
+}
+
+/// Returns the default device of the current thread, this is the cpu unless changed via
+/// [`set_default_device`] or [`scoped_default_device`].
+pub fn default_device() -> Device {
+    DEFAULT_DEVICE.with(|d| d.borrow().clone())
+}
+
+/// Sets the default device of the current thread and returns the previous one. This is only
+/// used by the constructors that do not take an explicit device, e.g. [`Tensor::zeros_default`].
+///
+/// [`Tensor::zeros_default`]: crate::Tensor::zeros_default
+pub fn set_default_device(device: Device) -> Device {
+    DEFAULT_DEVICE.with(|d| d.replace(device))
+}
+
+/// Sets the default device of the current thread until the returned guard is dropped.
+///
+/// ```rust
+/// use candle_core::{DType, Device, Tensor};
+/// let _guard = candle_core::scoped_default_device(Device::Cpu);
+/// let t = Tensor::zeros_default((2, 3), DType::F32)?;
+/// assert!(t.device().is_cpu());
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub fn scoped_default_device(device: Device) -> DefaultDeviceGuard {
+    let previous = set_default_device(device);
+    DefaultDeviceGuard { previous }
+}
+
+/// Restores the previous default device when dropped, see [`scoped_default_device`].
+#[must_use = "the default device is restored when the guard is dropped"]
+#[derive(Debug)]
+pub struct DefaultDeviceGuard {
+    previous: Device,
+}
+
+impl Drop for DefaultDeviceGuard {
+    fn drop(&mut self) {
+        set_default_device(self.previous.clone());
+    }
 }
 
 pub trait NdArray {
@@ -128,10 +190,17 @@ impl Device {
         Ok(Self::Cuda(crate::CudaDevice::new(ordinal)?))
     }
 
+    /// Returns the metal device at `ordinal` in the list of the system gpus, this requires the
+    /// `metal` feature.
+    pub fn new_metal(ordinal: usize) -> Result<Self> {
+        Ok(Self::Metal(crate::MetalDevice::new(ordinal)?))
+    }
+
     pub fn same_device(&self, rhs: &Self) -> bool {
         match (self, rhs) {
             (Self::Cpu, Self::Cpu) => true,
             (Self::Cuda(lhs), Self::Cuda(rhs)) => lhs.same_device(rhs),
+            (Self::Metal(lhs), Self::Metal(rhs)) => lhs.same_device(rhs),
             _ => false,
         }
     }
@@ -140,6 +209,7 @@ impl Device {
         match self {
             Self::Cpu => DeviceLocation::Cpu,
             Self::Cuda(device) => device.location(),
+            Self::Metal(device) => device.location(),
         }
     }
 
@@ -147,6 +217,7 @@ impl Device {
         match self {
             Self::Cpu => true,
             Self::Cuda(_) => false,
+            Self::Metal(_) => false,
         }
     }
 
@@ -154,6 +225,40 @@ impl Device {
         match self {
             Self::Cpu => false,
             Self::Cuda(_) => true,
+            Self::Metal(_) => false,
+        }
+    }
+
+    pub fn is_metal(&self) -> bool {
+        match self {
+            Self::Cpu | Self::Cuda(_) => false,
+            Self::Metal(_) => true,
+        }
+    }
+
+    /// Returns the state of the memory pool used for allocating the device buffers.
+    ///
+    /// Cuda devices cache the memory of dropped tensors and reuse it for later allocations of
+    /// similar size, e.g. `zeros` or `from_storage`, rather than returning it to the driver.
+    /// The reserved memory thus only grows up to the high-water mark of the allocations and is
+    /// kept until [`Device::reset_memory_pool`] is called. The cpu and metal devices do not use
+    /// any pool, neither do cuda devices without memory pool support for which only
+    /// `driver_allocations` is reported.
+    pub fn memory_pool_stats(&self) -> Result<MemoryPoolStats> {
+        match self {
+            Self::Cpu => Ok(MemoryPoolStats::default()),
+            Self::Cuda(device) => device.memory_pool_stats(),
+            Self::Metal(_) => Ok(MemoryPoolStats::default()),
+        }
+    }
+
+    /// Releases the cached memory back to the driver and resets the high-water mark. Buffers
+    /// used by live tensors are not affected.
+    pub fn reset_memory_pool(&self) -> Result<()> {
+        match self {
+            Self::Cpu => Ok(()),
+            Self::Cuda(device) => device.reset_memory_pool(),
+            Self::Metal(_) => Ok(()),
         }
     }
 
@@ -165,6 +270,14 @@ impl Device {
         }
     }
 
+    pub fn metal_if_available(ordinal: usize) -> Result<Self> {
+        if crate::utils::metal_is_available() {
+            Self::new_metal(ordinal)
+        } else {
+            Ok(Self::Cpu)
+        }
+    }
+
     pub(crate) fn rand_uniform_f64(
         &self,
         lo: f64,
@@ -181,6 +294,10 @@ impl Device {
                 let storage = device.rand_uniform(shape, dtype, lo, up)?;
                 Ok(Storage::Cuda(storage))
             }
+            Device::Metal(device) => {
+                let storage = device.rand_uniform(shape, dtype, lo, up)?;
+                Ok(Storage::Metal(storage))
+            }
         }
     }
 
@@ -209,6 +326,10 @@ impl Device {
                 let storage = device.rand_normal(shape, dtype, mean, std)?;
                 Ok(Storage::Cuda(storage))
             }
+            Device::Metal(device) => {
+                let storage = device.rand_normal(shape, dtype, mean, std)?;
+                Ok(Storage::Metal(storage))
+            }
         }
     }
 
@@ -231,6 +352,10 @@ impl Device {
                 let storage = device.ones_impl(shape, dtype)?;
                 Ok(Storage::Cuda(storage))
             }
+            Device::Metal(device) => {
+                let storage = device.ones_impl(shape, dtype)?;
+                Ok(Storage::Metal(storage))
+            }
         }
     }
 
@@ -244,6 +369,10 @@ impl Device {
                 let storage = device.zeros_impl(shape, dtype)?;
                 Ok(Storage::Cuda(storage))
             }
+            Device::Metal(device) => {
+                let storage = device.zeros_impl(shape, dtype)?;
+                Ok(Storage::Metal(storage))
+            }
         }
     }
 
@@ -255,6 +384,11 @@ impl Device {
                 let storage = device.storage_from_cpu_storage(&storage)?;
                 Ok(Storage::Cuda(storage))
             }
+            Device::Metal(device) => {
+                let storage = array.to_cpu_storage();
+                let storage = device.storage_from_cpu_storage(&storage)?;
+                Ok(Storage::Metal(storage))
+            }
         }
     }
 
@@ -266,6 +400,11 @@ impl Device {
                 let storage = device.storage_from_cpu_storage(&storage)?;
                 Ok(Storage::Cuda(storage))
             }
+            Device::Metal(device) => {
+                let storage = S::to_cpu_storage_owned(data);
+                let storage = device.storage_from_cpu_storage(&storage)?;
+                Ok(Storage::Metal(storage))
+            }
         }
     }
 }
@@ -14,6 +14,9 @@ impl Tensor {
             crate::DeviceLocation::Cuda { gpu_id } => {
                 format!(", cuda:{}", gpu_id)
             }
+            crate::DeviceLocation::Metal { gpu_id } => {
+                format!(", metal:{}", gpu_id)
+            }
         };
 
         write!(f, "Tensor[")?;
@@ -34,7 +37,14 @@ impl Tensor {
                 }
             }
             dims => {
-                write!(f, "dims ")?;
+                let po = PRINT_OPTS.lock().unwrap().clone();
+                let summarize_items = if self.elem_count() > po.threshold {
+                    Some(po.edge_items)
+                } else {
+                    None
+                };
+                self.fmt_values::<T>(summarize_items, f)?;
+                write!(f, "; dims ")?;
                 for (i, d) in dims.iter().enumerate() {
                     if i > 0 {
                         write!(f, ", ")?;
@@ -45,6 +55,66 @@ impl Tensor {
         }
         write!(f, "; {}{}]", self.dtype().as_str(), device_str)
     }
+
+    // Writes the values on a single line using nested brackets. When summarizing, i.e. when
+    // `summarize_items` is set, dimensions larger than twice this value only have their first
+    // and last `summarize_items` entries written.
+    fn fmt_values<T: WithDType + std::fmt::Display>(
+        &self,
+        summarize_items: Option<usize>,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        let d0 = match self.dims().first() {
+            None => return Ok(()),
+            Some(&d0) => d0,
+        };
+        let edge_items = summarize_items.unwrap_or(d0);
+        let summarize = d0 > 2 * edge_items;
+        let indexes: Vec<Option<usize>> = if summarize {
+            (0..edge_items)
+                .map(Some)
+                .chain(std::iter::once(None))
+                .chain((d0 - edge_items..d0).map(Some))
+                .collect()
+        } else {
+            (0..d0).map(Some).collect()
+        };
+        write!(f, "[")?;
+        if self.rank() == 1 {
+            let vs = if summarize {
+                let head = self.narrow(0, 0, edge_items).and_then(|t| t.to_vec1::<T>());
+                let tail = self
+                    .narrow(0, d0 - edge_items, edge_items)
+                    .and_then(|t| t.to_vec1::<T>());
+                head.and_then(|head| Ok([head, tail?].concat()))
+            } else {
+                self.to_vec1::<T>()
+            };
+            if let Ok(vs) = vs {
+                let mut vs = vs.iter();
+                for (i, index) in indexes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match index.and_then(|_| vs.next()) {
+                        Some(v) => write!(f, "{v}")?,
+                        None => write!(f, "...")?,
+                    }
+                }
+            }
+        } else {
+            for (i, index) in indexes.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                match index.map(|index| self.get(index)) {
+                    Some(Ok(t)) => t.fmt_values::<T>(summarize_items, f)?,
+                    _ => write!(f, "...")?,
+                }
+            }
+        }
+        write!(f, "]")
+    }
 }
 
 impl std::fmt::Debug for Tensor {
@@ -62,12 +132,18 @@ impl std::fmt::Debug for Tensor {
 }
 
 /// Options for Tensor pretty printing
+#[derive(Debug, Clone)]
 pub struct PrinterOptions {
-    precision: usize,
-    threshold: usize,
-    edge_items: usize,
-    line_width: usize,
-    sci_mode: Option<bool>,
+    /// The number of digits after the decimal point for float values.
+    pub precision: usize,
+    /// Tensors with more elements than this are summarized.
+    pub threshold: usize,
+    /// The number of items printed at the beginning and end of each summarized dimension.
+    pub edge_items: usize,
+    /// The number of characters per line before wrapping.
+    pub line_width: usize,
+    /// Forces the scientific notation on or off, by default it is picked from the value range.
+    pub sci_mode: Option<bool>,
 }
 
 static PRINT_OPTS: std::sync::Mutex<PrinterOptions> =
@@ -86,6 +162,28 @@ impl PrinterOptions {
     }
 }
 
+impl Default for PrinterOptions {
+    fn default() -> Self {
+        Self::const_default()
+    }
+}
+
+/// Returns the current global printer options.
+pub fn print_options() -> PrinterOptions {
+    PRINT_OPTS.lock().unwrap().clone()
+}
+
+/// Sets the global printer options, used by both `Display` and `Debug`.
+///
+/// ```rust
+/// use candle_core::display::{set_print_options, PrinterOptions};
+/// set_print_options(PrinterOptions {
+///     precision: 2,
+///     edge_items: 2,
+///     threshold: 100,
+///     ..Default::default()
+/// });
+/// ```
 pub fn set_print_options(options: PrinterOptions) {
     *PRINT_OPTS.lock().unwrap() = options
 }
@@ -410,77 +508,89 @@ fn get_summarized_data(t: &Tensor, edge_items: usize) -> Result<Tensor> {
     }
 }
 
+/// The values are printed using the global printer options, the precision can be overridden
+/// using the formatter precision, e.g. `format!("{t:.2}")`. Tensors that are not on the cpu are
+/// copied to the cpu before being printed.
 impl std::fmt::Display for Tensor {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let po = PRINT_OPTS.lock().unwrap();
-        let summarize = self.elem_count() > po.threshold;
+        let mut po = PRINT_OPTS.lock().unwrap().clone();
+        if let Some(precision) = f.precision() {
+            po.precision = precision
+        }
+        let t = match self.device() {
+            crate::Device::Cpu => self.clone(),
+            _ => match self.to_device(&crate::Device::Cpu) {
+                Ok(t) => t,
+                Err(err) => return write!(f, "{err:?}"),
+            },
+        };
+        let summarize = t.elem_count() > po.threshold;
         let to_display = if summarize {
-            match get_summarized_data(self, po.edge_items) {
+            match get_summarized_data(&t, po.edge_items) {
                 Ok(v) => v,
                 Err(err) => return write!(f, "{err:?}"),
             }
         } else {
-            self.clone()
+            t.clone()
         };
         match self.dtype() {
             DType::U8 => {
                 let tf: IntFormatter<u8> = IntFormatter::new();
                 let max_w = tf.max_width(&to_display);
-                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                 writeln!(f)?;
             }
             DType::U32 => {
                 let tf: IntFormatter<u32> = IntFormatter::new();
                 let max_w = tf.max_width(&to_display);
-                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                 writeln!(f)?;
             }
             DType::I64 => {
                 let tf: IntFormatter<i64> = IntFormatter::new();
                 let max_w = tf.max_width(&to_display);
-                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                 writeln!(f)?;
             }
             DType::BF16 => {
                 if let Ok(tf) = FloatFormatter::<bf16>::new(&to_display, &po) {
                     let max_w = tf.max_width(&to_display);
-                    tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                    tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                     writeln!(f)?;
                 }
             }
             DType::F16 => {
                 if let Ok(tf) = FloatFormatter::<f16>::new(&to_display, &po) {
                     let max_w = tf.max_width(&to_display);
-                    tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                    tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                     writeln!(f)?;
                 }
             }
             DType::F64 => {
                 if let Ok(tf) = FloatFormatter::<f64>::new(&to_display, &po) {
                     let max_w = tf.max_width(&to_display);
-                    tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                    tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                     writeln!(f)?;
                 }
             }
             DType::F32 => {
                 if let Ok(tf) = FloatFormatter::<f32>::new(&to_display, &po) {
                     let max_w = tf.max_width(&to_display);
-                    tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                    tf.fmt_tensor(&t, 1, max_w, summarize, &po, f)?;
                     writeln!(f)?;
                 }
             }
         };
 
         let device_str = match self.device().location() {
-            crate::DeviceLocation::Cpu => "".to_owned(),
-            crate::DeviceLocation::Cuda { gpu_id } => {
-                format!(", cuda:{}", gpu_id)
-            }
+            crate::DeviceLocation::Cpu => "cpu".to_owned(),
+            crate::DeviceLocation::Cuda { gpu_id } => format!("cuda:{gpu_id}"),
+            crate::DeviceLocation::Metal { gpu_id } => format!("metal:{gpu_id}"),
         };
 
         write!(
             f,
-            "Tensor[{:?}, {}{}]",
+            "Tensor[{:?}, {}, {}]",
             self.dims(),
             self.dtype().as_str(),
             device_str
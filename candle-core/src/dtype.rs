@@ -5,6 +5,7 @@ use crate::{CpuStorage, Error, Result};
 
 /// The different types of elements allowed in tensors.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DType {
     // Unsigned 8 bits integer.
     U8,
@@ -55,6 +56,19 @@ impl DType {
         }
     }
 
+    /// Returns true for the integer dtypes, i.e. `U8`, `U32`, and `I64`.
+    pub fn is_int(&self) -> bool {
+        match self {
+            Self::U8 | Self::U32 | Self::I64 => true,
+            Self::BF16 | Self::F16 | Self::F32 | Self::F64 => false,
+        }
+    }
+
+    /// Returns true for the floating point dtypes.
+    pub fn is_float(&self) -> bool {
+        !self.is_int()
+    }
+
     /// The size used by each element in bytes, i.e. 1 for `U8`, 4 for `F32`.
     pub fn size_in_bytes(&self) -> usize {
         match self {
@@ -108,12 +122,12 @@ macro_rules! with_dtype {
             }
 
             fn to_cpu_storage_owned(data: Vec<Self>) -> CpuStorage {
-                CpuStorage::$dtype(data)
+                CpuStorage::$dtype(data.into())
             }
 
             fn cpu_storage_data(s: CpuStorage) -> Result<Vec<Self>> {
                 match s {
-                    CpuStorage::$dtype(data) => Ok(data),
+                    CpuStorage::$dtype(data) => Ok(data.into_vec()),
                     _ => Err(Error::UnexpectedDType {
                         expected: DType::$dtype,
                         got: s.dtype(),
@@ -8,6 +8,26 @@ pub struct CudaDevice;
 #[derive(Debug)]
 pub struct CudaStorage;
 
+impl CudaDevice {
+    pub fn memory_pool_stats(&self) -> Result<crate::MemoryPoolStats> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    pub fn reset_memory_pool(&self) -> Result<()> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+}
+
+impl CudaStorage {
+    pub fn transfer_to_device(&self, _: &CudaDevice) -> Result<Self> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+}
+
+pub(crate) fn pin_cpu_storage(_: &CpuStorage, _: usize, _: usize, _: usize) -> Result<CpuStorage> {
+    Err(Error::NotCompiledWithCudaSupport)
+}
+
 macro_rules! fail {
     () => {
         unimplemented!("cuda support has not been enabled, add `cuda` feature to enable.")
@@ -89,6 +109,16 @@ impl crate::backend::BackendStorage for CudaStorage {
         Err(Error::NotCompiledWithCudaSupport)
     }
 
+    fn conv2d_depthwise(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConv2D,
+    ) -> Result<Self> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
     fn conv_transpose2d(
         &self,
         _l: &Layout,
@@ -155,6 +185,28 @@ impl crate::backend::BackendStorage for CudaStorage {
     fn upsample_nearest2d(&self, _: &Layout, _: usize, _: usize) -> Result<Self> {
         Err(Error::NotCompiledWithCudaSupport)
     }
+
+    fn grid_sample(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<Self> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
+
+    fn grid_sample_backward(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<(Self, Self)> {
+        Err(Error::NotCompiledWithCudaSupport)
+    }
 }
 
 impl crate::backend::BackendDevice for CudaDevice {
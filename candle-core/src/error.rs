@@ -37,6 +37,18 @@ pub enum Error {
         op: &'static str,
     },
 
+    /// A dimension index counted from the end, `resolved` is the corresponding index counted
+    /// from the start.
+    #[error(
+        "{op}: dimension index {dim} (resolved to {resolved}) out of range for shape {shape:?}"
+    )]
+    NegativeDimOutOfRange {
+        shape: Shape,
+        dim: i64,
+        resolved: i64,
+        op: &'static str,
+    },
+
     #[error("{op}: duplicate dim index {dims:?} for shape {shape:?}")]
     DuplicateDimIndex {
         shape: Shape,
@@ -149,6 +161,9 @@ pub enum Error {
     #[error("the candle crate has not been built with cuda support")]
     NotCompiledWithCudaSupport,
 
+    #[error("the candle crate has not been built with metal support")]
+    NotCompiledWithMetalSupport,
+
     #[error("cannot find tensor {path}")]
     CannotFindTensor { path: String },
 
@@ -156,6 +171,9 @@ pub enum Error {
     #[error(transparent)]
     Cuda(Box<dyn std::error::Error + Send + Sync>),
 
+    #[error(transparent)]
+    Metal(Box<dyn std::error::Error + Send + Sync>),
+
     #[error(transparent)]
     TryFromIntError(#[from] core::num::TryFromIntError),
 
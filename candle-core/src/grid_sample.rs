@@ -0,0 +1,221 @@
+//! Sampling of input tensors at arbitrary spatial locations.
+use crate::{op::BackpropOp, op::Op, DType, Result, Tensor};
+
+/// The interpolation used by [`Tensor::grid_sample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridSampleMode {
+    Bilinear,
+    Nearest,
+}
+
+/// How [`Tensor::grid_sample`] handles locations outside of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridSamplePadding {
+    /// Out of bound locations use zero values.
+    Zeros,
+    /// Out of bound locations use the values at the border of the input.
+    Border,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamsGridSample {
+    pub(crate) b_size: usize,
+    pub(crate) c_in: usize,
+    pub(crate) h_in: usize,
+    pub(crate) w_in: usize,
+    pub(crate) h_out: usize,
+    pub(crate) w_out: usize,
+    pub(crate) mode: GridSampleMode,
+    pub(crate) padding_mode: GridSamplePadding,
+    pub(crate) align_corners: bool,
+}
+
+/// An input pixel contributing to a sampled value.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GridSampleTap {
+    /// The offset of the pixel in its `(h_in, w_in)` plane.
+    pub(crate) offset: usize,
+    pub(crate) weight: f64,
+    /// The derivatives of `weight` with respect to the `x` and `y` pixel positions.
+    pub(crate) dw_dx: f64,
+    pub(crate) dw_dy: f64,
+}
+
+impl ParamsGridSample {
+    pub(crate) fn out_dims(&self) -> Vec<usize> {
+        vec![self.b_size, self.c_in, self.h_out, self.w_out]
+    }
+
+    // Maps a normalized coordinate to a pixel position, also returning the derivative of the
+    // position with respect to the coordinate.
+    fn unnormalize(&self, coord: f64, size: usize) -> (f64, f64) {
+        let size = size as f64;
+        let scale = if self.align_corners {
+            (size - 1.) / 2.
+        } else {
+            size / 2.
+        };
+        let pos = coord * scale + (size - 1.) / 2.;
+        match self.padding_mode {
+            GridSamplePadding::Zeros => (pos, scale),
+            GridSamplePadding::Border => {
+                if pos < 0. {
+                    (0., 0.)
+                } else if pos > size - 1. {
+                    (size - 1., 0.)
+                } else {
+                    (pos, scale)
+                }
+            }
+        }
+    }
+
+    fn tap(&self, x: f64, y: f64, weight: f64, dw_dx: f64, dw_dy: f64) -> Option<GridSampleTap> {
+        if x < 0. || y < 0. || x > (self.w_in - 1) as f64 || y > (self.h_in - 1) as f64 {
+            return None;
+        }
+        Some(GridSampleTap {
+            offset: y as usize * self.w_in + x as usize,
+            weight,
+            dw_dx,
+            dw_dy,
+        })
+    }
+
+    /// Returns the input pixels used for the normalized grid location `(gx, gy)`, out of bound
+    /// pixels being `None`, together with the derivatives of the `x` and `y` pixel positions
+    /// with respect to `gx` and `gy`.
+    pub(crate) fn taps(&self, gx: f64, gy: f64) -> ([Option<GridSampleTap>; 4], f64, f64) {
+        let (ix, dix) = self.unnormalize(gx, self.w_in);
+        let (iy, diy) = self.unnormalize(gy, self.h_in);
+        let taps = match self.mode {
+            // Locations exactly halfway between two pixels are rounded away from zero.
+            GridSampleMode::Nearest => [
+                self.tap(ix.round(), iy.round(), 1., 0., 0.),
+                None,
+                None,
+                None,
+            ],
+            GridSampleMode::Bilinear => {
+                let (x0, y0) = (ix.floor(), iy.floor());
+                let (wx1, wy1) = (ix - x0, iy - y0);
+                let (wx0, wy0) = (1. - wx1, 1. - wy1);
+                [
+                    self.tap(x0, y0, wx0 * wy0, -wy0, -wx0),
+                    self.tap(x0 + 1., y0, wx1 * wy0, wy0, -wx1),
+                    self.tap(x0, y0 + 1., wx0 * wy1, -wy1, wx0),
+                    self.tap(x0 + 1., y0 + 1., wx1 * wy1, wy1, wx1),
+                ]
+            }
+        };
+        (taps, dix, diy)
+    }
+}
+
+impl Tensor {
+    /// Samples the input tensor at the locations given by `grid`, similar to PyTorch
+    /// `grid_sample`.
+    ///
+    /// The input tensor should have shape `(batch, channels, h, w)` and `grid` should have shape
+    /// `(batch, h_out, w_out, 2)`. The last dimension of `grid` contains the `x` and `y`
+    /// coordinates, normalized so that -1 and 1 correspond to the left/top and right/bottom
+    /// edges of the input. When `align_corners` is true these refer to the centers of the corner
+    /// pixels, otherwise to the outer edges of the corner pixels. The returned tensor has shape
+    /// `(batch, channels, h_out, w_out)`.
+    ///
+    /// Gradients flow both to the input tensor and to the grid. In nearest mode, locations
+    /// exactly halfway between two pixels are rounded away from zero and the grid does not get
+    /// any gradient.
+    pub fn grid_sample(
+        &self,
+        grid: &Tensor,
+        mode: GridSampleMode,
+        padding_mode: GridSamplePadding,
+        align_corners: bool,
+    ) -> Result<Self> {
+        let (b_size, c_in, h_in, w_in) = self.dims4()?;
+        let (grid_b, h_out, w_out, grid_c) = grid.dims4()?;
+        if grid_b != b_size || grid_c != 2 {
+            crate::bail!(
+                "grid_sample: grid shape {:?} is incompatible with input shape {:?}",
+                grid.shape(),
+                self.shape()
+            )
+        }
+        if h_in == 0 || w_in == 0 {
+            crate::bail!(
+                "grid_sample: empty spatial dims in input {:?}",
+                self.shape()
+            )
+        }
+        let params = ParamsGridSample {
+            b_size,
+            c_in,
+            h_in,
+            w_in,
+            h_out,
+            w_out,
+            mode,
+            padding_mode,
+            align_corners,
+        };
+        // The sampling is done in f32, or f64 for f64 inputs, as half precision grids cannot
+        // address all the pixels above a width of 256 (bf16) or 2048 (f16).
+        let dtype = match self.dtype() {
+            DType::F64 => DType::F64,
+            _ => DType::F32,
+        };
+        let arg = self.to_dtype(dtype)?;
+        let grid = grid.to_dtype(dtype)?;
+        let storage =
+            arg.storage()
+                .grid_sample(arg.layout(), &grid.storage(), grid.layout(), &params)?;
+        let op = BackpropOp::new2(&arg, &grid, |arg, grid| Op::GridSample {
+            arg,
+            grid,
+            mode,
+            padding_mode,
+            align_corners,
+        });
+        let out_dims = params.out_dims();
+        crate::tensor::from_storage(storage, out_dims, op, false).to_dtype(self.dtype())
+    }
+
+    /// Returns the gradients of the input and of the grid for a `grid_sample` with gradient
+    /// `grad` on its output.
+    pub(crate) fn grid_sample_backward(
+        &self,
+        grid: &Tensor,
+        grad: &Tensor,
+        mode: GridSampleMode,
+        padding_mode: GridSamplePadding,
+        align_corners: bool,
+    ) -> Result<(Tensor, Tensor)> {
+        let (b_size, c_in, h_in, w_in) = self.dims4()?;
+        let (_, h_out, w_out, _) = grid.dims4()?;
+        let params = ParamsGridSample {
+            b_size,
+            c_in,
+            h_in,
+            w_in,
+            h_out,
+            w_out,
+            mode,
+            padding_mode,
+            align_corners,
+        };
+        let (grad_arg, grad_grid) = self.storage().grid_sample_backward(
+            self.layout(),
+            &grid.storage(),
+            grid.layout(),
+            &grad.storage(),
+            grad.layout(),
+            &params,
+        )?;
+        let grad_arg =
+            crate::tensor::from_storage(grad_arg, self.shape().clone(), BackpropOp::none(), false);
+        let grad_grid =
+            crate::tensor::from_storage(grad_grid, grid.shape().clone(), BackpropOp::none(), false);
+        Ok((grad_arg, grad_grid))
+    }
+}
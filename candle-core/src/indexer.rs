@@ -27,10 +27,25 @@ impl Tensor {
     fn index(&self, indexers: &[TensorIndexer]) -> Result<Self, Error> {
         let mut x = self.clone();
         let dims = self.shape().dims();
+        if indexers.len() > dims.len() {
+            crate::bail!(
+                "index: {} indexers used on a tensor of shape {:?}",
+                indexers.len(),
+                self.shape()
+            )
+        }
         let mut current_dim = 0;
         for (i, indexer) in indexers.iter().enumerate() {
             x = match indexer {
-                TensorIndexer::Select(n) => x.narrow(current_dim, *n, 1)?.squeeze(current_dim)?,
+                TensorIndexer::Select(n) => {
+                    if *n >= dims[i] {
+                        crate::bail!(
+                            "index: index {n} out of range for dim {i} of size {}",
+                            dims[i]
+                        )
+                    }
+                    x.narrow(current_dim, *n, 1)?.squeeze(current_dim)?
+                }
                 TensorIndexer::Narrow(left_bound, right_bound) => {
                     let start = match left_bound {
                         Bound::Included(n) => *n,
@@ -42,6 +57,12 @@ impl Tensor {
                         Bound::Excluded(n) => *n,
                         Bound::Unbounded => dims[i],
                     };
+                    if start > dims[i] || stop > dims[i] {
+                        crate::bail!(
+                            "index: range {start}..{stop} out of range for dim {i} of size {}",
+                            dims[i]
+                        )
+                    }
                     let out = x.narrow(current_dim, start, stop.saturating_sub(start))?;
                     current_dim += 1;
                     out
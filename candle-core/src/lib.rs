@@ -49,9 +49,14 @@ mod device;
 pub mod display;
 mod dtype;
 mod dummy_cuda_backend;
+mod dummy_metal_backend;
 pub mod error;
+mod grid_sample;
 mod indexer;
 pub mod layout;
+mod linalg;
+#[cfg(feature = "metal")]
+pub mod metal_backend;
 #[cfg(feature = "mkl")]
 mod mkl;
 pub mod npy;
@@ -63,28 +68,46 @@ pub mod shape;
 mod storage;
 mod strided_index;
 mod tensor;
+#[cfg(feature = "ndarray")]
+mod tensor_ndarray;
+#[cfg(feature = "serde")]
+mod tensor_serde;
 pub mod test_utils;
 pub mod utils;
 mod variable;
 
-pub use cpu_backend::CpuStorage;
-pub use device::{Device, DeviceLocation};
+pub use cpu_backend::{CpuBuffer, CpuStorage};
+pub use device::{
+    default_device, scoped_default_device, set_default_device, DefaultDeviceGuard, Device,
+    DeviceLocation, MemoryPoolStats,
+};
 pub use dtype::{DType, FloatDType, IntDType, WithDType};
 pub use error::{Error, Result};
+pub use grid_sample::{GridSampleMode, GridSamplePadding};
 pub use indexer::IndexOp;
 pub use layout::Layout;
 pub use op::{CustomOp1, CustomOp2, CustomOp3};
 pub use shape::{Shape, D};
 pub use storage::Storage;
 pub use strided_index::{StridedBlocks, StridedIndex};
-pub use tensor::{Tensor, TensorId};
+pub use tensor::{NestedVec, PadMode, Tensor, TensorId};
 pub use variable::Var;
 
+#[cfg(feature = "cuda")]
+use cuda_backend::pin_cpu_storage;
 #[cfg(feature = "cuda")]
 pub use cuda_backend::{CudaDevice, CudaStorage};
 
+#[cfg(not(feature = "cuda"))]
+use dummy_cuda_backend::pin_cpu_storage;
+
+#[cfg(feature = "metal")]
+pub use metal_backend::{MetalDevice, MetalStorage};
+
 #[cfg(not(feature = "cuda"))]
 pub use dummy_cuda_backend::{CudaDevice, CudaStorage};
+#[cfg(not(feature = "metal"))]
+pub use dummy_metal_backend::{MetalDevice, MetalStorage};
 
 #[cfg(feature = "mkl")]
 extern crate intel_mkl_src;
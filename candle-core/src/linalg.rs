@@ -0,0 +1,543 @@
+//! Linear algebra on small matrices.
+//!
+//! These operations act on the last two dimensions of their inputs, any leading dimensions are
+//! batch dimensions. They are computed on the cpu in f64 whatever the device and float dtype of
+//! the input, e.g. cuda tensors are copied to the cpu, the result being converted back. These
+//! are forward-only, no gradient is tracked through them.
+use crate::{DType, Device, Result, Shape, Tensor};
+
+// A batch of matrices in row-major order.
+struct Matrices {
+    batch_dims: Vec<usize>,
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrices {
+    fn new_rect(t: &Tensor, op: &'static str) -> Result<Self> {
+        if !t.dtype().is_float() {
+            crate::bail!("{op}: expected a float tensor, got {:?}", t.dtype())
+        }
+        let dims = t.dims();
+        if dims.len() < 2 {
+            crate::bail!("{op}: expected a batch of matrices, got {:?}", t.shape())
+        }
+        let (rows, cols) = (dims[dims.len() - 2], dims[dims.len() - 1]);
+        let batch_dims = dims[..dims.len() - 2].to_vec();
+        let data = t.to_dtype(DType::F64)?.flatten_to_vec::<f64>()?;
+        Ok(Self {
+            batch_dims,
+            rows,
+            cols,
+            data,
+        })
+    }
+
+    fn new(t: &Tensor, op: &'static str) -> Result<Self> {
+        let m = Self::new_rect(t, op)?;
+        if m.rows != m.cols {
+            crate::bail!(
+                "{op}: expected a batch of square matrices, got {:?}",
+                t.shape()
+            )
+        }
+        Ok(m)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &[f64]> {
+        self.data.chunks_exact((self.rows * self.cols).max(1))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut [f64]> {
+        self.data.chunks_exact_mut((self.rows * self.cols).max(1))
+    }
+
+    // The shape of the batch of `(rows, cols)` matrices.
+    fn shape(&self, rows: usize, cols: usize) -> Vec<usize> {
+        let mut dims = self.batch_dims.clone();
+        dims.push(rows);
+        dims.push(cols);
+        dims
+    }
+}
+
+// Builds the result tensor with the dtype and device of `like`.
+fn to_tensor<S: Into<Shape>>(data: Vec<f64>, shape: S, like: &Tensor) -> Result<Tensor> {
+    Tensor::from_vec(data, shape, &Device::Cpu)?
+        .to_dtype(like.dtype())?
+        .to_device(like.device())
+}
+
+// Checks that `b` is a valid right-hand side for the matrices `m` of tensor `a`, either
+// `(..., n, k)` or `(..., n)`, and returns `k`.
+fn rhs_cols(m: &Matrices, a: &Tensor, b: &Tensor, op: &'static str) -> Result<usize> {
+    if !b.dtype().is_float() {
+        crate::bail!(
+            "{op}: expected a float right-hand side, got {:?}",
+            b.dtype()
+        )
+    }
+    let b_dims = b.dims();
+    let r = b_dims.len();
+    let (b_batch, b_n, k) = if r + 1 == a.rank() && r > 0 {
+        (&b_dims[..r - 1], b_dims[r - 1], 1)
+    } else if r == a.rank() {
+        (&b_dims[..r - 2], b_dims[r - 2], b_dims[r - 1])
+    } else {
+        (b_dims, 0, 0)
+    };
+    if b_batch != m.batch_dims.as_slice() || b_n != m.rows {
+        crate::bail!(
+            "{op}: incompatible shapes {:?} and {:?}",
+            a.shape(),
+            b.shape()
+        )
+    }
+    Ok(k)
+}
+
+// In place LU decomposition with partial pivoting, `a` then contains the unit lower triangular
+// factor below its diagonal and the upper triangular factor. Returns the row permutation, its
+// sign, and whether a pivot is negligible compared to the largest input value.
+fn lu(a: &mut [f64], n: usize) -> (Vec<usize>, f64, bool) {
+    let scale = a.iter().fold(0f64, |acc, v| acc.max(v.abs()));
+    let tol = scale * f64::EPSILON * n as f64;
+    let mut perm: Vec<usize> = (0..n).collect();
+    let mut sign = 1.;
+    let mut singular = false;
+    for k in 0..n {
+        let (p, max) = (k..n)
+            .map(|i| (i, a[i * n + k].abs()))
+            .fold((k, -1.), |acc, v| if v.1 > acc.1 { v } else { acc });
+        if max <= tol {
+            singular = true;
+            if max == 0. {
+                continue;
+            }
+        }
+        if p != k {
+            for j in 0..n {
+                a.swap(k * n + j, p * n + j)
+            }
+            perm.swap(k, p);
+            sign = -sign;
+        }
+        let pivot = a[k * n + k];
+        for i in k + 1..n {
+            let f = a[i * n + k] / pivot;
+            a[i * n + k] = f;
+            for j in k + 1..n {
+                a[i * n + j] -= f * a[k * n + j]
+            }
+        }
+    }
+    (perm, sign, singular)
+}
+
+// Solves `a x = b` in place in `b` of shape `(n, k)` using the factorization returned by `lu`.
+fn lu_solve(lu: &[f64], perm: &[usize], b: &mut [f64], n: usize, k: usize) {
+    let mut x: Vec<f64> = perm
+        .iter()
+        .flat_map(|&p| b[p * k..(p + 1) * k].to_vec())
+        .collect();
+    for i in 0..n {
+        for r in 0..i {
+            let f = lu[i * n + r];
+            for c in 0..k {
+                x[i * k + c] -= f * x[r * k + c]
+            }
+        }
+    }
+    for i in (0..n).rev() {
+        for r in i + 1..n {
+            let f = lu[i * n + r];
+            for c in 0..k {
+                x[i * k + c] -= f * x[r * k + c]
+            }
+        }
+        let d = lu[i * n + i];
+        for c in 0..k {
+            x[i * k + c] /= d
+        }
+    }
+    b.copy_from_slice(&x)
+}
+
+// Reduced QR decomposition of the `(m, n)` matrix `a` using Householder reflections. Returns `q`
+// of shape `(m, k)` and `r` of shape `(k, n)` with `k = min(m, n)`, the diagonal of `r` being
+// non-negative.
+fn qr(a: &[f64], m: usize, n: usize) -> (Vec<f64>, Vec<f64>) {
+    let k = m.min(n);
+    let mut r = a.to_vec();
+    let mut q = vec![0f64; m * m];
+    for i in 0..m {
+        q[i * m + i] = 1.
+    }
+    for j in 0..k {
+        let norm = (j..m).map(|i| r[i * n + j].powi(2)).sum::<f64>().sqrt();
+        let alpha = if r[j * n + j] > 0. { -norm } else { norm };
+        let mut v: Vec<f64> = (j..m).map(|i| r[i * n + j]).collect();
+        v[0] -= alpha;
+        let v_norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if v_norm == 0. {
+            continue;
+        }
+        v.iter_mut().for_each(|x| *x /= v_norm);
+        // Applies the reflection `1 - 2 v v^t` on the left of `r` and on the right of `q`.
+        for c in 0..n {
+            let d: f64 = (j..m).map(|i| v[i - j] * r[i * n + c]).sum();
+            for i in j..m {
+                r[i * n + c] -= 2. * d * v[i - j]
+            }
+        }
+        for row in 0..m {
+            let d: f64 = (j..m).map(|i| q[row * m + i] * v[i - j]).sum();
+            for i in j..m {
+                q[row * m + i] -= 2. * d * v[i - j]
+            }
+        }
+    }
+    let mut q_out = vec![0f64; m * k];
+    let mut r_out = vec![0f64; k * n];
+    for i in 0..k {
+        let sign = if r[i * n + i] < 0. { -1. } else { 1. };
+        for c in i..n {
+            r_out[i * n + c] = sign * r[i * n + c]
+        }
+        for row in 0..m {
+            q_out[row * k + i] = sign * q[row * m + i]
+        }
+    }
+    (q_out, r_out)
+}
+
+fn rotate(cols: &mut [Vec<f64>], p: usize, q: usize, c: f64, s: f64) {
+    for i in 0..cols[p].len() {
+        let (x, y) = (cols[p][i], cols[q][i]);
+        cols[p][i] = c * x - s * y;
+        cols[q][i] = s * x + c * y;
+    }
+}
+
+fn dot(x: &[f64], y: &[f64]) -> f64 {
+    x.iter().zip(y.iter()).map(|(x, y)| x * y).sum()
+}
+
+// Thin SVD of the `(m, n)` matrix `a` with `m >= n` using one-sided Jacobi rotations. Returns
+// `u` of shape `(m, n)`, the singular values in decreasing order, and `v` of shape `(n, n)`.
+fn svd_tall(a: &[f64], m: usize, n: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    // The columns of `u` are rotated until they are orthogonal, the same rotations applied to
+    // the identity give `v`.
+    let mut u: Vec<Vec<f64>> = (0..n)
+        .map(|j| (0..m).map(|i| a[i * n + j]).collect())
+        .collect();
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|j| (0..n).map(|i| if i == j { 1. } else { 0. }).collect())
+        .collect();
+    for _sweep in 0..100 {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let alpha = dot(&u[p], &u[p]);
+                let beta = dot(&u[q], &u[q]);
+                let gamma = dot(&u[p], &u[q]);
+                if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2. * gamma);
+                let t = zeta.signum() / (zeta.abs() + (1. + zeta * zeta).sqrt());
+                let c = 1. / (1. + t * t).sqrt();
+                rotate(&mut u, p, q, c, c * t);
+                rotate(&mut v, p, q, c, c * t);
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+    let norms: Vec<f64> = u.iter().map(|c| dot(c, c).sqrt()).collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+    let tol = norms.iter().fold(0f64, |acc, &v| acc.max(v)) * f64::EPSILON * m as f64;
+    let mut u_cols: Vec<Vec<f64>> = Vec::with_capacity(n);
+    for &j in order.iter() {
+        let col = if norms[j] > tol {
+            u[j].iter().map(|x| x / norms[j]).collect()
+        } else {
+            // Completes the basis for null singular values using the first unit vector that is
+            // not in the span of the previous columns.
+            (0..m)
+                .map(|e| {
+                    let mut col: Vec<f64> = (0..m).map(|i| if i == e { 1. } else { 0. }).collect();
+                    for prev in u_cols.iter() {
+                        let d = dot(prev, &col);
+                        col.iter_mut()
+                            .zip(prev.iter())
+                            .for_each(|(x, p)| *x -= d * p)
+                    }
+                    col
+                })
+                .map(|col| {
+                    let norm = dot(&col, &col).sqrt();
+                    (norm, col)
+                })
+                .find(|(norm, _)| *norm > 0.5)
+                .map(|(norm, col)| col.iter().map(|x| x / norm).collect())
+                .unwrap_or_else(|| vec![0.; m])
+        };
+        u_cols.push(col)
+    }
+    let mut u_out = vec![0f64; m * n];
+    let mut v_out = vec![0f64; n * n];
+    for (jj, &j) in order.iter().enumerate() {
+        for i in 0..m {
+            u_out[i * n + jj] = u_cols[jj][i]
+        }
+        for i in 0..n {
+            v_out[i * n + jj] = v[j][i]
+        }
+    }
+    let s = order
+        .iter()
+        .map(|&j| if norms[j] > tol { norms[j] } else { 0. })
+        .collect();
+    (u_out, s, v_out)
+}
+
+fn transpose(a: &[f64], m: usize, n: usize) -> Vec<f64> {
+    (0..n)
+        .flat_map(|j| (0..m).map(move |i| a[i * n + j]))
+        .collect()
+}
+
+impl Tensor {
+    /// The determinant of the matrices in the last two dimensions, the returned tensor has the
+    /// batch dimensions of the input. Singular matrices have a determinant of zero, up to
+    /// rounding errors.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f64, 2.], [3., 4.]], &Device::Cpu)?;
+    /// assert_eq!(a.det()?.to_scalar::<f64>()?, -2.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn det(&self) -> Result<Self> {
+        let mut m = Matrices::new(self, "det")?;
+        let n = m.cols;
+        let dets: Vec<f64> = m
+            .iter_mut()
+            .map(|a| {
+                let (_, sign, _) = lu(a, n);
+                (0..n).fold(sign, |acc, i| acc * a[i * n + i])
+            })
+            .collect();
+        to_tensor(dets, m.batch_dims, self)
+    }
+
+    /// The inverse of the matrices in the last two dimensions. An error is returned if any of
+    /// the matrices is singular.
+    pub fn inverse(&self) -> Result<Self> {
+        let mut m = Matrices::new(self, "inverse")?;
+        let n = m.cols;
+        for a in m.iter_mut() {
+            let (perm, _, singular) = lu(a, n);
+            if singular {
+                crate::bail!("inverse: singular matrix")
+            }
+            let mut inv = vec![0f64; n * n];
+            for i in 0..n {
+                inv[i * n + i] = 1.
+            }
+            lu_solve(a, &perm, &mut inv, n, n);
+            a.copy_from_slice(&inv)
+        }
+        to_tensor(m.data, self.shape(), self)
+    }
+
+    /// Solves `self @ x = b` for `x` where `self` is a batch of square matrices of shape
+    /// `(..., n, n)` and `b` has shape `(..., n, k)`, or `(..., n)` for a single right-hand side.
+    /// The batch dimensions of `b` must be the same as the ones of `self`. An error is returned
+    /// if any of the matrices is singular.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[2f64, 1.], [1., 3.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[3f64, 5.], &Device::Cpu)?;
+    /// assert_eq!(a.solve(&b)?.to_vec1::<f64>()?, [0.8, 1.4]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn solve(&self, b: &Self) -> Result<Self> {
+        let mut m = Matrices::new(self, "solve")?;
+        let n = m.cols;
+        let k = rhs_cols(&m, self, b, "solve")?;
+        let mut b_data = b.to_dtype(DType::F64)?.flatten_to_vec::<f64>()?;
+        for (a, b) in m.iter_mut().zip(b_data.chunks_exact_mut((n * k).max(1))) {
+            let (perm, _, singular) = lu(a, n);
+            if singular {
+                crate::bail!("solve: singular matrix")
+            }
+            lu_solve(a, &perm, b, n, k)
+        }
+        to_tensor(b_data, b.shape(), b)
+    }
+
+    /// The Cholesky factor of the symmetric positive-definite matrices in the last two
+    /// dimensions: the lower triangular `l` such that `self = l @ l.t()`, or when `upper` is
+    /// true the upper triangular `u = l.t()` such that `self = u.t() @ u`. Only the lower
+    /// triangle of the input is read. An error is returned if any of the matrices is not
+    /// positive-definite.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[4f64, 2.], [2., 2.]], &Device::Cpu)?;
+    /// assert_eq!(a.cholesky(false)?.to_vec2::<f64>()?, [[2., 0.], [1., 1.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn cholesky(&self, upper: bool) -> Result<Self> {
+        let mut m = Matrices::new(self, "cholesky")?;
+        let n = m.cols;
+        for a in m.iter_mut() {
+            let mut l = vec![0f64; n * n];
+            for j in 0..n {
+                let d = a[j * n + j] - (0..j).map(|k| l[j * n + k].powi(2)).sum::<f64>();
+                if d <= 0. || !d.is_finite() {
+                    crate::bail!("cholesky: matrix is not positive-definite")
+                }
+                let d = d.sqrt();
+                l[j * n + j] = d;
+                for i in j + 1..n {
+                    let s = (0..j).map(|k| l[i * n + k] * l[j * n + k]).sum::<f64>();
+                    l[i * n + j] = (a[i * n + j] - s) / d
+                }
+            }
+            for i in 0..n {
+                for j in 0..n {
+                    a[i * n + j] = if upper { l[j * n + i] } else { l[i * n + j] }
+                }
+            }
+        }
+        to_tensor(m.data, self.shape(), self)
+    }
+
+    /// Solves `self @ x = b`, or `self.t() @ x = b` when `transpose` is true, for `x` where
+    /// `self` is a batch of triangular matrices of shape `(..., n, n)`, upper triangular if
+    /// `upper` is true and lower triangular otherwise, the other triangle is not read. `b` has
+    /// shape `(..., n, k)`, or `(..., n)` for a single right-hand side, with the same batch
+    /// dimensions as `self`. An error is returned if a diagonal element is zero.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let l = Tensor::new(&[[2f64, 0.], [1., 1.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[4f64, 5.], &Device::Cpu)?;
+    /// assert_eq!(l.triangular_solve(&b, false, false)?.to_vec1::<f64>()?, [2., 3.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn triangular_solve(&self, b: &Self, upper: bool, transpose: bool) -> Result<Self> {
+        let m = Matrices::new(self, "triangular_solve")?;
+        let n = m.cols;
+        let k = rhs_cols(&m, self, b, "triangular_solve")?;
+        let mut b_data = b.to_dtype(DType::F64)?.flatten_to_vec::<f64>()?;
+        // Solving with the transpose of an upper triangular matrix is a forward substitution.
+        let forward = upper == transpose;
+        for (a, x) in m.iter().zip(b_data.chunks_exact_mut((n * k).max(1))) {
+            let get = |i: usize, j: usize| {
+                if transpose {
+                    a[j * n + i]
+                } else {
+                    a[i * n + j]
+                }
+            };
+            for step in 0..n {
+                let i = if forward { step } else { n - 1 - step };
+                let d = get(i, i);
+                if d == 0. {
+                    crate::bail!("triangular_solve: singular matrix")
+                }
+                let others = if forward { 0..i } else { i + 1..n };
+                for r in others {
+                    let f = get(i, r);
+                    for c in 0..k {
+                        x[i * k + c] -= f * x[r * k + c]
+                    }
+                }
+                for c in 0..k {
+                    x[i * k + c] /= d
+                }
+            }
+        }
+        to_tensor(b_data, b.shape(), b)
+    }
+
+    /// The reduced QR decomposition of the matrices in the last two dimensions. For inputs of
+    /// shape `(..., m, n)` and `k = min(m, n)`, this returns `q` of shape `(..., m, k)` with
+    /// orthonormal columns and the upper triangular `r` of shape `(..., k, n)` such that
+    /// `self = q @ r`. The diagonal of `r` is non-negative.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[3f64, 1.], [4., 2.]], &Device::Cpu)?;
+    /// let (q, r) = a.qr()?;
+    /// assert_eq!(q.dims(), &[2, 2]);
+    /// assert_eq!(r.to_vec2::<f64>()?[0][0], 5.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn qr(&self) -> Result<(Self, Self)> {
+        let m = Matrices::new_rect(self, "qr")?;
+        let (rows, cols) = (m.rows, m.cols);
+        let k = rows.min(cols);
+        let mut qs = Vec::with_capacity(m.data.len());
+        let mut rs = Vec::with_capacity(m.data.len());
+        for a in m.iter() {
+            let (q, r) = qr(a, rows, cols);
+            qs.extend(q);
+            rs.extend(r)
+        }
+        let q = to_tensor(qs, m.shape(rows, k), self)?;
+        let r = to_tensor(rs, m.shape(k, cols), self)?;
+        Ok((q, r))
+    }
+
+    /// The reduced singular value decomposition of the matrices in the last two dimensions. For
+    /// inputs of shape `(..., m, n)` and `k = min(m, n)`, this returns `u` of shape `(..., m, k)`,
+    /// the singular values `s` of shape `(..., k)` in decreasing order, and `v` of shape
+    /// `(..., n, k)` such that `self = u @ diag(s) @ v.t()`. The columns of `u` and `v` are
+    /// orthonormal.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[3f64, 0.], [0., -4.]], &Device::Cpu)?;
+    /// let (u, s, v) = a.svd()?;
+    /// assert_eq!(s.to_vec1::<f64>()?, [4., 3.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn svd(&self) -> Result<(Self, Self, Self)> {
+        let m = Matrices::new_rect(self, "svd")?;
+        let (rows, cols) = (m.rows, m.cols);
+        let k = rows.min(cols);
+        let mut us = Vec::with_capacity(rows * k);
+        let mut ss = Vec::with_capacity(k);
+        let mut vs = Vec::with_capacity(cols * k);
+        for a in m.iter() {
+            if rows >= cols {
+                let (u, s, v) = svd_tall(a, rows, cols);
+                us.extend(u);
+                ss.extend(s);
+                vs.extend(v)
+            } else {
+                // The decomposition of the transpose has `u` and `v` swapped.
+                let (v, s, u) = svd_tall(&transpose(a, rows, cols), cols, rows);
+                us.extend(u);
+                ss.extend(s);
+                vs.extend(v)
+            }
+        }
+        let u = to_tensor(us, m.shape(rows, k), self)?;
+        let mut s_dims = m.batch_dims.clone();
+        s_dims.push(k);
+        let s = to_tensor(ss, s_dims, self)?;
+        let v = to_tensor(vs, m.shape(cols, k), self)?;
+        Ok((u, s, v))
+    }
+}
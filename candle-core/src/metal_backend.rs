@@ -0,0 +1,856 @@
+//! The metal backend, running on Apple gpus.
+//!
+//! The kernels in `metal_kernels.metal` are compiled when a device is created. Each op is
+//! encoded in its own command buffer, these run in order on the command queue of the device so
+//! only the reads back to the cpu have to wait for the gpu. The elementwise ops, casts,
+//! reductions, `where_cond`, `index_select` and `matmul` are supported for the `u8`, `u32`,
+//! `i64`, `f16` and `f32` dtypes (the float ops and `matmul` only for `f16` and `f32`). Metal
+//! does not support `f64` and the other ops return an error.
+use crate::backend::{BackendDevice, BackendStorage};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
+use crate::{CpuStorage, DType, Layout, Result, Shape};
+pub use metal;
+use metal::{
+    Buffer, BufferRef, CompileOptions, ComputeCommandEncoderRef, ComputePipelineState, Library,
+    MTLResourceOptions, MTLSize,
+};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::{Arc, Mutex};
+
+const KERNELS: &str = include_str!("metal_kernels.metal");
+
+// The size of the output blocks computed by the threadgroups of the `gemm` kernels.
+const GEMM_TILE: u64 = 16;
+
+/// Metal related errors
+#[derive(thiserror::Error, Debug)]
+pub enum MetalError {
+    #[error("{0}")]
+    Message(String),
+
+    #[error("failed to compile the metal kernels: {0}")]
+    Compile(String),
+
+    #[error("missing kernel '{name}': {msg}")]
+    MissingKernel { name: String, msg: String },
+
+    #[error("{op} is not supported on metal")]
+    UnsupportedOp { op: &'static str },
+}
+
+impl From<MetalError> for crate::Error {
+    fn from(val: MetalError) -> Self {
+        crate::Error::Metal(Box::new(val)).bt()
+    }
+}
+
+/// Unique identifier for metal devices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceId(usize);
+
+impl DeviceId {
+    fn new() -> Self {
+        use std::sync::atomic;
+        static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(1);
+        Self(COUNTER.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+#[derive(Clone)]
+pub struct MetalDevice {
+    id: DeviceId,
+    ordinal: usize,
+    device: metal::Device,
+    command_queue: metal::CommandQueue,
+    library: Library,
+    pipelines: Arc<Mutex<HashMap<String, ComputePipelineState>>>,
+}
+
+impl std::fmt::Debug for MetalDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MetalDevice({:?})", self.id)
+    }
+}
+
+impl std::ops::Deref for MetalDevice {
+    type Target = metal::DeviceRef;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+// The arguments of the kernels, set in order with `set_params!`.
+trait EncoderParam {
+    fn set_param(encoder: &ComputeCommandEncoderRef, position: u64, data: Self);
+}
+
+impl EncoderParam for usize {
+    fn set_param(encoder: &ComputeCommandEncoderRef, position: u64, data: Self) {
+        let size = std::mem::size_of::<usize>() as u64;
+        encoder.set_bytes(position, size, &data as *const usize as *const c_void)
+    }
+}
+
+impl EncoderParam for f32 {
+    fn set_param(encoder: &ComputeCommandEncoderRef, position: u64, data: Self) {
+        let size = std::mem::size_of::<f32>() as u64;
+        encoder.set_bytes(position, size, &data as *const f32 as *const c_void)
+    }
+}
+
+impl EncoderParam for &[usize] {
+    fn set_param(encoder: &ComputeCommandEncoderRef, position: u64, data: Self) {
+        let size = std::mem::size_of_val(data) as u64;
+        encoder.set_bytes(position, size, data.as_ptr() as *const c_void)
+    }
+}
+
+// A buffer together with an offset in elements.
+impl EncoderParam for (&MetalStorage, usize) {
+    fn set_param(encoder: &ComputeCommandEncoderRef, position: u64, data: Self) {
+        let (storage, offset) = data;
+        let offset = (offset * storage.dtype.size_in_bytes()) as u64;
+        encoder.set_buffer(position, Some(&storage.buffer), offset)
+    }
+}
+
+macro_rules! set_params {
+    ($encoder:ident, ($($param:expr),+)) => {
+        let mut _index = 0;
+        $(
+            EncoderParam::set_param($encoder, _index, $param);
+            _index += 1;
+        )*
+    };
+}
+
+// The dims and strides passed to the kernels, scalars use a single dim as empty arrays cannot be
+// passed as kernel arguments.
+fn dims_and_strides(layout: &Layout) -> (Vec<usize>, Vec<usize>) {
+    if layout.dims().is_empty() {
+        (vec![1], vec![0])
+    } else {
+        (layout.dims().to_vec(), layout.stride().to_vec())
+    }
+}
+
+// Returns the batch, row and column strides of a matmul operand, or `None` if the batch dims
+// cannot be addressed with a single stride.
+fn matmul_strides(layout: &Layout) -> Option<[usize; 3]> {
+    let dims = layout.dims();
+    let stride = layout.stride();
+    let rank = dims.len();
+    let mut batch_stride = 0;
+    let mut expected_stride = None;
+    for (&dim, &dim_stride) in dims[..rank - 2].iter().zip(&stride[..rank - 2]).rev() {
+        if dim == 1 {
+            continue;
+        }
+        match expected_stride {
+            None => batch_stride = dim_stride,
+            Some(expected_stride) if expected_stride == dim_stride => {}
+            Some(_) => return None,
+        }
+        expected_stride = Some(dim_stride * dim);
+    }
+    Some([batch_stride, stride[rank - 2], stride[rank - 1]])
+}
+
+fn check_dtype(dtype: DType, op: &'static str) -> Result<()> {
+    match dtype {
+        DType::U8 | DType::U32 | DType::I64 | DType::F16 | DType::F32 => Ok(()),
+        DType::BF16 | DType::F64 => Err(crate::Error::UnsupportedDTypeForOp(dtype, op).bt()),
+    }
+}
+
+fn check_float_dtype(dtype: DType, op: &'static str) -> Result<()> {
+    match dtype {
+        DType::F16 | DType::F32 => Ok(()),
+        _ => Err(crate::Error::UnsupportedDTypeForOp(dtype, op).bt()),
+    }
+}
+
+impl MetalDevice {
+    pub fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    pub fn metal_device(&self) -> &metal::Device {
+        &self.device
+    }
+
+    pub fn command_queue(&self) -> &metal::CommandQueue {
+        &self.command_queue
+    }
+
+    fn pipeline(&self, name: &str) -> Result<ComputePipelineState> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if let Some(pipeline) = pipelines.get(name) {
+            return Ok(pipeline.clone());
+        }
+        let function =
+            self.library
+                .get_function(name, None)
+                .map_err(|msg| MetalError::MissingKernel {
+                    name: name.to_string(),
+                    msg,
+                })?;
+        let pipeline = self
+            .device
+            .new_compute_pipeline_state_with_function(&function)
+            .map_err(|msg| MetalError::MissingKernel {
+                name: name.to_string(),
+                msg,
+            })?;
+        pipelines.insert(name.to_string(), pipeline.clone());
+        Ok(pipeline)
+    }
+
+    // Allocates an uninitialized storage. Empty buffers are not supported by metal so these use
+    // a single byte.
+    fn alloc(&self, count: usize, dtype: DType) -> MetalStorage {
+        let size = (count * dtype.size_in_bytes()).max(1) as u64;
+        let buffer = self
+            .device
+            .new_buffer(size, MTLResourceOptions::StorageModeShared);
+        MetalStorage {
+            buffer,
+            device: self.clone(),
+            count,
+            dtype,
+        }
+    }
+
+    // Encodes the kernel `name` with one thread per element, `set` sets the kernel arguments.
+    fn dispatch(
+        &self,
+        name: &str,
+        numel: usize,
+        set: impl FnOnce(&ComputeCommandEncoderRef),
+    ) -> Result<()> {
+        if numel == 0 {
+            return Ok(());
+        }
+        let pipeline = self.pipeline(name)?;
+        let width = pipeline
+            .max_total_threads_per_threadgroup()
+            .min(numel as u64);
+        let grid = MTLSize::new(numel as u64, 1, 1);
+        let threadgroup = MTLSize::new(width, 1, 1);
+        objc::rc::autoreleasepool(|| {
+            let command_buffer = self.command_queue.new_command_buffer();
+            let encoder = command_buffer.new_compute_command_encoder();
+            encoder.set_compute_pipeline_state(&pipeline);
+            set(encoder);
+            encoder.dispatch_threads(grid, threadgroup);
+            encoder.end_encoding();
+            command_buffer.commit();
+        });
+        Ok(())
+    }
+
+    /// Waits for all the commands submitted to the device to complete.
+    pub fn synchronize(&self) {
+        // The command buffers of a queue run in the order they were committed.
+        objc::rc::autoreleasepool(|| {
+            let command_buffer = self.command_queue.new_command_buffer();
+            command_buffer.commit();
+            command_buffer.wait_until_completed();
+        })
+    }
+
+    fn fill(&self, shape: &Shape, dtype: DType, value: f32) -> Result<MetalStorage> {
+        check_dtype(dtype, "fill")?;
+        let numel = shape.elem_count();
+        let dst = self.alloc(numel, dtype);
+        let name = format!("fill_{}", dtype.as_str());
+        self.dispatch(&name, numel, |encoder| {
+            set_params!(encoder, (numel, value, (&dst, 0)));
+        })?;
+        Ok(dst)
+    }
+}
+
+pub struct MetalStorage {
+    buffer: Buffer,
+    device: MetalDevice,
+    count: usize,
+    dtype: DType,
+}
+
+impl std::fmt::Debug for MetalStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MetalStorage({:?}, {:?}, {})",
+            self.device, self.dtype, self.count
+        )
+    }
+}
+
+impl MetalStorage {
+    pub fn buffer(&self) -> &BufferRef {
+        &self.buffer
+    }
+
+    // Returns a contiguous copy of the elements described by `layout`.
+    fn contiguous(&self, layout: &Layout) -> Result<Self> {
+        let mut dst = self.device.alloc(layout.shape().elem_count(), self.dtype);
+        self.copy_strided_src(&mut dst, 0, layout)?;
+        Ok(dst)
+    }
+
+    // Runs an elementwise kernel taking a strided input and optional float parameters.
+    fn unary_kernel(
+        &self,
+        name: &str,
+        layout: &Layout,
+        params: &[f32],
+        dtype: DType,
+    ) -> Result<Self> {
+        let numel = layout.shape().elem_count();
+        let (dims, strides) = dims_and_strides(layout);
+        let dst = self.device.alloc(numel, dtype);
+        self.device.dispatch(name, numel, |encoder| {
+            set_params!(
+                encoder,
+                (numel, dims.len(), dims.as_slice(), strides.as_slice())
+            );
+            let mut index = 4;
+            for &param in params {
+                EncoderParam::set_param(encoder, index, param);
+                index += 1;
+            }
+            EncoderParam::set_param(encoder, index, (self, layout.start_offset()));
+            EncoderParam::set_param(encoder, index + 1, (&dst, 0));
+        })?;
+        Ok(dst)
+    }
+
+    // Runs a kernel on two strided inputs with the same dims.
+    fn binary_kernel(
+        &self,
+        name: &str,
+        rhs: &Self,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+        dtype: DType,
+    ) -> Result<Self> {
+        let numel = lhs_l.shape().elem_count();
+        let (dims, lhs_strides) = dims_and_strides(lhs_l);
+        let (_, rhs_strides) = dims_and_strides(rhs_l);
+        let dst = self.device.alloc(numel, dtype);
+        self.device.dispatch(name, numel, |encoder| {
+            set_params!(
+                encoder,
+                (
+                    numel,
+                    dims.len(),
+                    dims.as_slice(),
+                    lhs_strides.as_slice(),
+                    rhs_strides.as_slice(),
+                    (self, lhs_l.start_offset()),
+                    (rhs, rhs_l.start_offset()),
+                    (&dst, 0)
+                )
+            );
+        })?;
+        Ok(dst)
+    }
+}
+
+impl BackendStorage for MetalStorage {
+    type Device = MetalDevice;
+
+    fn try_clone(&self, _: &Layout) -> Result<Self> {
+        let dst = self.device.alloc(self.count, self.dtype);
+        let size = (self.count * self.dtype.size_in_bytes()) as u64;
+        if size > 0 {
+            objc::rc::autoreleasepool(|| {
+                let command_buffer = self.device.command_queue.new_command_buffer();
+                let encoder = command_buffer.new_blit_command_encoder();
+                encoder.copy_from_buffer(&self.buffer, 0, &dst.buffer, 0, size);
+                encoder.end_encoding();
+                command_buffer.commit();
+            });
+        }
+        Ok(dst)
+    }
+
+    fn dtype(&self) -> DType {
+        self.dtype
+    }
+
+    fn device(&self) -> &MetalDevice {
+        &self.device
+    }
+
+    fn to_cpu_storage(&self) -> Result<CpuStorage> {
+        fn read<T: Clone>(buffer: &Buffer, count: usize) -> Vec<T> {
+            let ptr = buffer.contents() as *const T;
+            // SAFETY: the buffer uses shared memory and holds `count` elements, the device has
+            // been synchronized so the gpu does not write to it anymore.
+            unsafe { std::slice::from_raw_parts(ptr, count) }.to_vec()
+        }
+        self.device.synchronize();
+        let storage = match self.dtype {
+            DType::U8 => CpuStorage::U8(read(&self.buffer, self.count).into()),
+            DType::U32 => CpuStorage::U32(read(&self.buffer, self.count).into()),
+            DType::I64 => CpuStorage::I64(read(&self.buffer, self.count).into()),
+            DType::F16 => CpuStorage::F16(read(&self.buffer, self.count).into()),
+            DType::F32 => CpuStorage::F32(read(&self.buffer, self.count).into()),
+            dtype => Err(crate::Error::UnsupportedDTypeForOp(dtype, "to-cpu"))?,
+        };
+        Ok(storage)
+    }
+
+    fn affine(&self, layout: &Layout, mul: f64, add: f64) -> Result<Self> {
+        let name = format!("affine_{}", self.dtype.as_str());
+        self.unary_kernel(&name, layout, &[mul as f32, add as f32], self.dtype)
+    }
+
+    fn powf(&self, layout: &Layout, e: f64) -> Result<Self> {
+        check_float_dtype(self.dtype, "powf")?;
+        let name = format!("powf_{}", self.dtype.as_str());
+        self.unary_kernel(&name, layout, &[e as f32], self.dtype)
+    }
+
+    fn elu(&self, layout: &Layout, alpha: f64) -> Result<Self> {
+        check_float_dtype(self.dtype, "elu")?;
+        let name = format!("elu_{}", self.dtype.as_str());
+        self.unary_kernel(&name, layout, &[alpha as f32], self.dtype)
+    }
+
+    fn reduce_op(&self, op: ReduceOp, layout: &Layout, reduce_dims: &[usize]) -> Result<Self> {
+        let (name, dtype) = match op {
+            ReduceOp::Sum => ("reduce_sum", self.dtype),
+            ReduceOp::Min => ("reduce_min", self.dtype),
+            ReduceOp::Max => ("reduce_max", self.dtype),
+            ReduceOp::ArgMin => ("reduce_argmin", DType::U32),
+            ReduceOp::ArgMax => ("reduce_argmax", DType::U32),
+        };
+        let name = format!("{name}_{}", self.dtype.as_str());
+        let (dims, strides) = dims_and_strides(layout);
+        let mut reduced = vec![0usize; dims.len()];
+        let mut numel = 1;
+        for (dim_idx, &dim) in layout.dims().iter().enumerate() {
+            if reduce_dims.contains(&dim_idx) {
+                reduced[dim_idx] = 1
+            } else {
+                numel *= dim
+            }
+        }
+        let dst = self.device.alloc(numel, dtype);
+        self.device.dispatch(&name, numel, |encoder| {
+            set_params!(
+                encoder,
+                (
+                    numel,
+                    dims.len(),
+                    dims.as_slice(),
+                    strides.as_slice(),
+                    reduced.as_slice(),
+                    (self, layout.start_offset()),
+                    (&dst, 0)
+                )
+            );
+        })?;
+        Ok(dst)
+    }
+
+    fn cmp(&self, op: CmpOp, rhs: &Self, lhs_l: &Layout, rhs_l: &Layout) -> Result<Self> {
+        let name = match op {
+            CmpOp::Eq => "cmp_eq",
+            CmpOp::Ne => "cmp_ne",
+            CmpOp::Le => "cmp_le",
+            CmpOp::Ge => "cmp_ge",
+            CmpOp::Lt => "cmp_lt",
+            CmpOp::Gt => "cmp_gt",
+        };
+        let name = format!("{name}_{}", self.dtype.as_str());
+        self.binary_kernel(&name, rhs, lhs_l, rhs_l, DType::U8)
+    }
+
+    fn to_dtype(&self, layout: &Layout, dtype: DType) -> Result<Self> {
+        check_dtype(dtype, "to-dtype")?;
+        let name = if dtype == self.dtype {
+            format!("copy_{}", dtype.as_str())
+        } else {
+            format!("cast_{}_{}", self.dtype.as_str(), dtype.as_str())
+        };
+        self.unary_kernel(&name, layout, &[], dtype)
+    }
+
+    fn unary_impl<B: UnaryOpT>(&self, layout: &Layout) -> Result<Self> {
+        check_float_dtype(self.dtype, B::NAME)?;
+        let name = format!("{}_{}", B::KERNEL, self.dtype.as_str());
+        self.unary_kernel(&name, layout, &[], self.dtype)
+    }
+
+    fn binary_impl<B: BinaryOpT>(
+        &self,
+        rhs: &Self,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<Self> {
+        let name = format!("{}_{}", B::KERNEL, self.dtype.as_str());
+        self.binary_kernel(&name, rhs, lhs_l, rhs_l, self.dtype)
+    }
+
+    fn where_cond(
+        &self,
+        layout: &Layout,
+        t: &Self,
+        t_l: &Layout,
+        f: &Self,
+        f_l: &Layout,
+    ) -> Result<Self> {
+        check_dtype(t.dtype, "where")?;
+        let name = format!("where_{}_{}", self.dtype.as_str(), t.dtype.as_str());
+        let numel = layout.shape().elem_count();
+        let (dims, cond_strides) = dims_and_strides(layout);
+        let (_, t_strides) = dims_and_strides(t_l);
+        let (_, f_strides) = dims_and_strides(f_l);
+        let dst = self.device.alloc(numel, t.dtype);
+        self.device.dispatch(&name, numel, |encoder| {
+            set_params!(
+                encoder,
+                (
+                    numel,
+                    dims.len(),
+                    dims.as_slice(),
+                    cond_strides.as_slice(),
+                    t_strides.as_slice(),
+                    f_strides.as_slice(),
+                    (self, layout.start_offset()),
+                    (t, t_l.start_offset()),
+                    (f, f_l.start_offset()),
+                    (&dst, 0)
+                )
+            );
+        })?;
+        Ok(dst)
+    }
+
+    fn conv1d(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConv1D,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "conv1d" }.into())
+    }
+
+    fn conv2d(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConv2D,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "conv2d" }.into())
+    }
+
+    fn conv2d_depthwise(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConv2D,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp {
+            op: "conv2d-depthwise",
+        }
+        .into())
+    }
+
+    fn conv_transpose2d(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConvTranspose2D,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp {
+            op: "conv-transpose2d",
+        }
+        .into())
+    }
+
+    fn avg_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "avg-pool2d" }.into())
+    }
+
+    fn max_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "max-pool2d" }.into())
+    }
+
+    fn upsample_nearest2d(&self, _: &Layout, _: usize, _: usize) -> Result<Self> {
+        Err(MetalError::UnsupportedOp {
+            op: "upsample-nearest2d",
+        }
+        .into())
+    }
+
+    fn grid_sample(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "grid-sample" }.into())
+    }
+
+    fn grid_sample_backward(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::grid_sample::ParamsGridSample,
+    ) -> Result<(Self, Self)> {
+        Err(MetalError::UnsupportedOp {
+            op: "grid-sample-backward",
+        }
+        .into())
+    }
+
+    fn gather(&self, _: &Layout, _: &Self, _: &Layout, _: usize) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "gather" }.into())
+    }
+
+    fn scatter_add(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: usize,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "scatter-add" }.into())
+    }
+
+    fn index_select(
+        &self,
+        ids: &Self,
+        layout: &Layout,
+        ids_l: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        check_dtype(self.dtype, "index-select")?;
+        let name = format!("is_{}_{}", ids.dtype.as_str(), self.dtype.as_str());
+        let dims = layout.dims();
+        let left_size: usize = dims[..dim].iter().product();
+        let right_size: usize = dims[dim + 1..].iter().product();
+        let src_dim_size = dims[dim];
+        let ids_size = ids_l.shape().elem_count();
+        let numel = left_size * ids_size * right_size;
+        let src = self.contiguous(layout)?;
+        let ids = ids.contiguous(ids_l)?;
+        let dst = self.device.alloc(numel, self.dtype);
+        self.device.dispatch(&name, numel, |encoder| {
+            set_params!(
+                encoder,
+                (
+                    numel,
+                    src_dim_size,
+                    right_size,
+                    ids_size,
+                    (&ids, 0),
+                    (&src, 0),
+                    (&dst, 0)
+                )
+            );
+        })?;
+        Ok(dst)
+    }
+
+    fn index_add(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: usize,
+    ) -> Result<Self> {
+        Err(MetalError::UnsupportedOp { op: "index-add" }.into())
+    }
+
+    fn matmul(
+        &self,
+        rhs: &Self,
+        (b, m, n, k): (usize, usize, usize, usize),
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<Self> {
+        check_float_dtype(self.dtype, "matmul")?;
+        // Operands with batch dims that cannot be addressed with a single stride are copied.
+        let (lhs_copy, lhs_offset, lhs_strides) = match matmul_strides(lhs_l) {
+            Some(strides) => (None, lhs_l.start_offset(), strides),
+            None => (Some(self.contiguous(lhs_l)?), 0, [m * k, k, 1]),
+        };
+        let (rhs_copy, rhs_offset, rhs_strides) = match matmul_strides(rhs_l) {
+            Some(strides) => (None, rhs_l.start_offset(), strides),
+            None => (Some(rhs.contiguous(rhs_l)?), 0, [k * n, n, 1]),
+        };
+        let lhs = lhs_copy.as_ref().unwrap_or(self);
+        let rhs = rhs_copy.as_ref().unwrap_or(rhs);
+        let dst = self.device.alloc(b * m * n, self.dtype);
+        if b * m * n == 0 {
+            return Ok(dst);
+        }
+        let name = format!("gemm_{}", self.dtype.as_str());
+        let pipeline = self.device.pipeline(&name)?;
+        let groups = MTLSize::new(
+            (n as u64).div_ceil(GEMM_TILE),
+            (m as u64).div_ceil(GEMM_TILE),
+            b as u64,
+        );
+        let threadgroup = MTLSize::new(GEMM_TILE, GEMM_TILE, 1);
+        objc::rc::autoreleasepool(|| {
+            let command_buffer = self.device.command_queue.new_command_buffer();
+            let encoder = command_buffer.new_compute_command_encoder();
+            encoder.set_compute_pipeline_state(&pipeline);
+            set_params!(
+                encoder,
+                (
+                    m,
+                    n,
+                    k,
+                    lhs_strides.as_slice(),
+                    rhs_strides.as_slice(),
+                    (lhs, lhs_offset),
+                    (rhs, rhs_offset),
+                    (&dst, 0)
+                )
+            );
+            encoder.dispatch_thread_groups(groups, threadgroup);
+            encoder.end_encoding();
+            command_buffer.commit();
+        });
+        Ok(dst)
+    }
+
+    fn copy_strided_src(&self, dst: &mut Self, dst_offset: usize, src_l: &Layout) -> Result<()> {
+        let name = format!("copy_{}", self.dtype.as_str());
+        let numel = src_l.shape().elem_count();
+        let (dims, strides) = dims_and_strides(src_l);
+        self.device.dispatch(&name, numel, |encoder| {
+            set_params!(
+                encoder,
+                (
+                    numel,
+                    dims.len(),
+                    dims.as_slice(),
+                    strides.as_slice(),
+                    (self, src_l.start_offset()),
+                    (&*dst, dst_offset)
+                )
+            );
+        })
+    }
+}
+
+impl BackendDevice for MetalDevice {
+    type Storage = MetalStorage;
+
+    fn new(ordinal: usize) -> Result<Self> {
+        let device = metal::Device::all()
+            .into_iter()
+            .nth(ordinal)
+            .ok_or_else(|| {
+                MetalError::Message(format!("no metal device with ordinal {ordinal}"))
+            })?;
+        let options = CompileOptions::new();
+        // The fast math variants of some functions, e.g. tanh, return nan for large inputs.
+        options.set_fast_math_enabled(false);
+        let library = device
+            .new_library_with_source(KERNELS, &options)
+            .map_err(MetalError::Compile)?;
+        let command_queue = device.new_command_queue();
+        Ok(Self {
+            id: DeviceId::new(),
+            ordinal,
+            device,
+            command_queue,
+            library,
+            pipelines: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    fn location(&self) -> crate::DeviceLocation {
+        crate::DeviceLocation::Metal {
+            gpu_id: self.ordinal,
+        }
+    }
+
+    fn same_device(&self, rhs: &Self) -> bool {
+        self.id == rhs.id
+    }
+
+    fn zeros_impl(&self, shape: &Shape, dtype: DType) -> Result<MetalStorage> {
+        self.fill(shape, dtype, 0.)
+    }
+
+    fn ones_impl(&self, shape: &Shape, dtype: DType) -> Result<MetalStorage> {
+        self.fill(shape, dtype, 1.)
+    }
+
+    fn storage_from_cpu_storage(&self, storage: &CpuStorage) -> Result<MetalStorage> {
+        fn write<T>(device: &MetalDevice, data: &[T], dtype: DType) -> MetalStorage {
+            // Empty buffers are not supported by metal, see `MetalDevice::alloc`.
+            if data.is_empty() {
+                return device.alloc(0, dtype);
+            }
+            let buffer = device.device.new_buffer_with_data(
+                data.as_ptr() as *const c_void,
+                std::mem::size_of_val(data) as u64,
+                MTLResourceOptions::StorageModeShared,
+            );
+            MetalStorage {
+                buffer,
+                device: device.clone(),
+                count: data.len(),
+                dtype,
+            }
+        }
+        let storage = match storage {
+            CpuStorage::U8(data) => write(self, data, DType::U8),
+            CpuStorage::U32(data) => write(self, data, DType::U32),
+            CpuStorage::I64(data) => write(self, data, DType::I64),
+            CpuStorage::F16(data) => write(self, data, DType::F16),
+            CpuStorage::F32(data) => write(self, data, DType::F32),
+            storage => Err(crate::Error::UnsupportedDTypeForOp(
+                storage.dtype(),
+                "to-metal",
+            ))?,
+        };
+        Ok(storage)
+    }
+
+    // The random values are generated on the cpu.
+    fn rand_uniform(&self, shape: &Shape, dtype: DType, lo: f64, up: f64) -> Result<MetalStorage> {
+        check_dtype(dtype, "rand-uniform")?;
+        let storage = crate::cpu_backend::CpuDevice.rand_uniform(shape, dtype, lo, up)?;
+        self.storage_from_cpu_storage(&storage)
+    }
+
+    fn rand_normal(
+        &self,
+        shape: &Shape,
+        dtype: DType,
+        mean: f64,
+        std: f64,
+    ) -> Result<MetalStorage> {
+        check_dtype(dtype, "rand-normal")?;
+        let storage = crate::cpu_backend::CpuDevice.rand_normal(shape, dtype, mean, std)?;
+        self.storage_from_cpu_storage(&storage)
+    }
+}
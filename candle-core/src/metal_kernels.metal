@@ -0,0 +1,409 @@
+// Compute kernels of the metal backend, compiled when a `MetalDevice` is created.
+//
+// Kernels are named `<op>_<dtype>` using the names of `DType::as_str`. The elementwise kernels
+// read strided inputs and write contiguous outputs, the strided layouts being described by the
+// `num_dims`, `dims` and `strides` arguments. Floating point computations on halfs are done in
+// float.
+#include <metal_stdlib>
+using namespace metal;
+
+// Returns the offset of the `idx`-th element, in row-major order, of a strided tensor.
+inline size_t get_strided_index(
+    size_t idx,
+    constant size_t &num_dims,
+    constant size_t *dims,
+    constant size_t *strides
+) {
+    size_t strided_i = 0;
+    for (size_t d = 0; d < num_dims; d++) {
+        size_t dim_idx = num_dims - 1 - d;
+        strided_i += (idx % dims[dim_idx]) * strides[dim_idx];
+        idx /= dims[dim_idx];
+    }
+    return strided_i;
+}
+
+#define FOR_ALL_TYPES(M) \
+M(uchar, u8) \
+M(uint, u32) \
+M(long, i64) \
+M(half, f16) \
+M(float, f32)
+
+#define FOR_FLOAT_TYPES(M) \
+M(half, f16) \
+M(float, f32)
+
+// Fill and copy.
+
+#define FILL_OP(TYPENAME, SUFFIX) \
+kernel void fill_##SUFFIX( \
+    constant size_t &numel, \
+    constant float &value, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    dst[id] = TYPENAME(value); \
+}
+
+#define COPY_OP(TYPENAME, SUFFIX) \
+kernel void copy_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    device const TYPENAME *src, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    dst[id] = src[get_strided_index(id, num_dims, dims, strides)]; \
+}
+
+FOR_ALL_TYPES(FILL_OP)
+FOR_ALL_TYPES(COPY_OP)
+
+// Casts.
+
+#define CAST_OP(SRC_TYPENAME, SRC_SUFFIX, DST_TYPENAME, DST_SUFFIX) \
+kernel void cast_##SRC_SUFFIX##_##DST_SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    device const SRC_TYPENAME *src, \
+    device DST_TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    dst[id] = DST_TYPENAME(src[get_strided_index(id, num_dims, dims, strides)]); \
+}
+
+#define CAST_FROM(SRC_TYPENAME, SRC_SUFFIX) \
+CAST_OP(SRC_TYPENAME, SRC_SUFFIX, uchar, u8) \
+CAST_OP(SRC_TYPENAME, SRC_SUFFIX, uint, u32) \
+CAST_OP(SRC_TYPENAME, SRC_SUFFIX, long, i64) \
+CAST_OP(SRC_TYPENAME, SRC_SUFFIX, half, f16) \
+CAST_OP(SRC_TYPENAME, SRC_SUFFIX, float, f32)
+
+FOR_ALL_TYPES(CAST_FROM)
+
+// Affine, this applies to all the types like the cpu version, the multiplier and the offset
+// being converted to the tensor type first.
+
+#define AFFINE_OP(TYPENAME, SUFFIX) \
+kernel void affine_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    constant float &mul, \
+    constant float &add, \
+    device const TYPENAME *src, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    TYPENAME x = src[get_strided_index(id, num_dims, dims, strides)]; \
+    dst[id] = x * TYPENAME(mul) + TYPENAME(add); \
+}
+
+FOR_ALL_TYPES(AFFINE_OP)
+
+// Unary ops on floats, `x` is the input value converted to float.
+
+#define UNARY_KERNEL(NAME, FN, TYPENAME, SUFFIX) \
+kernel void NAME##_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    device const TYPENAME *src, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    float x = float(src[get_strided_index(id, num_dims, dims, strides)]); \
+    dst[id] = TYPENAME(FN); \
+}
+
+#define UNARY_OP(NAME, FN) \
+UNARY_KERNEL(NAME, FN, half, f16) \
+UNARY_KERNEL(NAME, FN, float, f32)
+
+UNARY_OP(uexp, exp(x))
+UNARY_OP(ulog, log(x))
+UNARY_OP(usin, sin(x))
+UNARY_OP(ucos, cos(x))
+UNARY_OP(utanh, tanh(x))
+UNARY_OP(uabs, abs(x))
+UNARY_OP(uneg, -x)
+UNARY_OP(urecip, 1.0f / x)
+UNARY_OP(usqr, x * x)
+UNARY_OP(usqrt, sqrt(x))
+UNARY_OP(ufloor, floor(x))
+UNARY_OP(uceil, ceil(x))
+// Halfway values are rounded away from zero, as with `f32::round`.
+UNARY_OP(uround, round(x))
+UNARY_OP(ugelu, 0.5f * x * (1.0f + tanh(0.7978845608028654f * x * (1.0f + 0.044715f * x * x))))
+UNARY_OP(urelu, max(x, 0.0f))
+
+#define POWF_OP(TYPENAME, SUFFIX) \
+kernel void powf_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    constant float &e, \
+    device const TYPENAME *src, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    float x = float(src[get_strided_index(id, num_dims, dims, strides)]); \
+    dst[id] = TYPENAME(pow(x, e)); \
+}
+
+#define ELU_OP(TYPENAME, SUFFIX) \
+kernel void elu_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    constant float &alpha, \
+    device const TYPENAME *src, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    float x = float(src[get_strided_index(id, num_dims, dims, strides)]); \
+    dst[id] = TYPENAME(signbit(x) ? (exp(x) - 1.0f) * alpha : x); \
+}
+
+FOR_FLOAT_TYPES(POWF_OP)
+FOR_FLOAT_TYPES(ELU_OP)
+
+// Binary ops and comparisons, both operands have the same dims but can use different strides.
+
+#define BINARY_KERNEL(NAME, FN, OUT_TYPENAME, TYPENAME, SUFFIX) \
+kernel void NAME##_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *lhs_strides, \
+    constant size_t *rhs_strides, \
+    device const TYPENAME *lhs, \
+    device const TYPENAME *rhs, \
+    device OUT_TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    TYPENAME x = lhs[get_strided_index(id, num_dims, dims, lhs_strides)]; \
+    TYPENAME y = rhs[get_strided_index(id, num_dims, dims, rhs_strides)]; \
+    dst[id] = OUT_TYPENAME(FN); \
+}
+
+#define BINARY_OP(NAME, FN) \
+BINARY_KERNEL(NAME, FN, uchar, uchar, u8) \
+BINARY_KERNEL(NAME, FN, uint, uint, u32) \
+BINARY_KERNEL(NAME, FN, long, long, i64) \
+BINARY_KERNEL(NAME, FN, half, half, f16) \
+BINARY_KERNEL(NAME, FN, float, float, f32)
+
+#define CMP_OP(NAME, FN) \
+BINARY_KERNEL(NAME, FN, uchar, uchar, u8) \
+BINARY_KERNEL(NAME, FN, uchar, uint, u32) \
+BINARY_KERNEL(NAME, FN, uchar, long, i64) \
+BINARY_KERNEL(NAME, FN, uchar, half, f16) \
+BINARY_KERNEL(NAME, FN, uchar, float, f32)
+
+BINARY_OP(badd, x + y)
+BINARY_OP(bsub, x - y)
+BINARY_OP(bmul, x * y)
+BINARY_OP(bdiv, x / y)
+BINARY_OP(bminimum, x > y ? y : x)
+BINARY_OP(bmaximum, x < y ? y : x)
+
+CMP_OP(cmp_eq, x == y)
+CMP_OP(cmp_ne, x != y)
+CMP_OP(cmp_le, x <= y)
+CMP_OP(cmp_ge, x >= y)
+CMP_OP(cmp_lt, x < y)
+CMP_OP(cmp_gt, x > y)
+
+// Where, the condition and the two values have the same dims but can use different strides.
+
+#define WHERE_OP(COND_TYPENAME, COND_SUFFIX, TYPENAME, SUFFIX) \
+kernel void where_##COND_SUFFIX##_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *cond_strides, \
+    constant size_t *t_strides, \
+    constant size_t *f_strides, \
+    device const COND_TYPENAME *cond, \
+    device const TYPENAME *t, \
+    device const TYPENAME *f, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    dst[id] = cond[get_strided_index(id, num_dims, dims, cond_strides)] \
+        ? t[get_strided_index(id, num_dims, dims, t_strides)] \
+        : f[get_strided_index(id, num_dims, dims, f_strides)]; \
+}
+
+#define WHERE_FOR_COND(COND_TYPENAME, COND_SUFFIX) \
+WHERE_OP(COND_TYPENAME, COND_SUFFIX, uchar, u8) \
+WHERE_OP(COND_TYPENAME, COND_SUFFIX, uint, u32) \
+WHERE_OP(COND_TYPENAME, COND_SUFFIX, long, i64) \
+WHERE_OP(COND_TYPENAME, COND_SUFFIX, half, f16) \
+WHERE_OP(COND_TYPENAME, COND_SUFFIX, float, f32)
+
+WHERE_FOR_COND(uchar, u8)
+WHERE_FOR_COND(uint, u32)
+WHERE_FOR_COND(long, i64)
+
+// Reductions, each thread computes one output element. The dims flagged in `reduced` are
+// reduced, the output elements being in row-major order over the other dims.
+
+#define REDUCE_KERNEL(NAME, OUT_TYPENAME, TYPENAME, SUFFIX, ACC_TYPENAME, INIT, UPDATE, RESULT) \
+kernel void NAME##_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &num_dims, \
+    constant size_t *dims, \
+    constant size_t *strides, \
+    constant size_t *reduced, \
+    device const TYPENAME *src, \
+    device OUT_TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    size_t base = 0; \
+    size_t rem = id; \
+    size_t reduce_numel = 1; \
+    for (size_t d = 0; d < num_dims; d++) { \
+        size_t dim_idx = num_dims - 1 - d; \
+        if (reduced[dim_idx]) { \
+            reduce_numel *= dims[dim_idx]; \
+        } else { \
+            base += (rem % dims[dim_idx]) * strides[dim_idx]; \
+            rem /= dims[dim_idx]; \
+        } \
+    } \
+    ACC_TYPENAME acc = INIT; \
+    uint acc_idx = 0; \
+    for (size_t j = 0; j < reduce_numel; j++) { \
+        size_t offset = base; \
+        size_t r = j; \
+        for (size_t d = 0; d < num_dims; d++) { \
+            size_t dim_idx = num_dims - 1 - d; \
+            if (reduced[dim_idx]) { \
+                offset += (r % dims[dim_idx]) * strides[dim_idx]; \
+                r /= dims[dim_idx]; \
+            } \
+        } \
+        TYPENAME v = src[offset]; \
+        UPDATE \
+    } \
+    dst[id] = RESULT; \
+}
+
+#define REDUCE_OPS(TYPENAME, SUFFIX, SUM_TYPENAME) \
+REDUCE_KERNEL(reduce_sum, TYPENAME, TYPENAME, SUFFIX, SUM_TYPENAME, 0, \
+    acc += SUM_TYPENAME(v);, TYPENAME(acc)) \
+REDUCE_KERNEL(reduce_min, TYPENAME, TYPENAME, SUFFIX, TYPENAME, TYPENAME(0), \
+    if (j == 0 || v < acc) { acc = v; }, acc) \
+REDUCE_KERNEL(reduce_max, TYPENAME, TYPENAME, SUFFIX, TYPENAME, TYPENAME(0), \
+    if (j == 0 || v > acc) { acc = v; }, acc) \
+REDUCE_KERNEL(reduce_argmin, uint, TYPENAME, SUFFIX, TYPENAME, TYPENAME(0), \
+    if (j == 0 || v < acc) { acc = v; acc_idx = j; }, acc_idx) \
+REDUCE_KERNEL(reduce_argmax, uint, TYPENAME, SUFFIX, TYPENAME, TYPENAME(0), \
+    if (j == 0 || v > acc) { acc = v; acc_idx = j; }, acc_idx)
+
+REDUCE_OPS(uchar, u8, uchar)
+REDUCE_OPS(uint, u32, uint)
+REDUCE_OPS(long, i64, long)
+REDUCE_OPS(half, f16, float)
+REDUCE_OPS(float, f32, float)
+
+// Index select on contiguous tensors, `ids` indexes the middle dim of `src` which has
+// `src_dim_size` elements.
+
+#define INDEX_SELECT_OP(INDEX_TYPENAME, INDEX_SUFFIX, TYPENAME, SUFFIX) \
+kernel void is_##INDEX_SUFFIX##_##SUFFIX( \
+    constant size_t &numel, \
+    constant size_t &src_dim_size, \
+    constant size_t &right_size, \
+    constant size_t &ids_size, \
+    device const INDEX_TYPENAME *ids, \
+    device const TYPENAME *src, \
+    device TYPENAME *dst, \
+    uint id [[thread_position_in_grid]] \
+) { \
+    if (id >= numel) return; \
+    size_t right_i = id % right_size; \
+    size_t ids_i = (id / right_size) % ids_size; \
+    size_t left_i = id / (right_size * ids_size); \
+    size_t src_i = (left_i * src_dim_size + size_t(ids[ids_i])) * right_size + right_i; \
+    dst[id] = src[src_i]; \
+}
+
+#define INDEX_SELECT_FOR_INDEX(INDEX_TYPENAME, INDEX_SUFFIX) \
+INDEX_SELECT_OP(INDEX_TYPENAME, INDEX_SUFFIX, uchar, u8) \
+INDEX_SELECT_OP(INDEX_TYPENAME, INDEX_SUFFIX, uint, u32) \
+INDEX_SELECT_OP(INDEX_TYPENAME, INDEX_SUFFIX, long, i64) \
+INDEX_SELECT_OP(INDEX_TYPENAME, INDEX_SUFFIX, half, f16) \
+INDEX_SELECT_OP(INDEX_TYPENAME, INDEX_SUFFIX, float, f32)
+
+INDEX_SELECT_FOR_INDEX(uchar, u8)
+INDEX_SELECT_FOR_INDEX(uint, u32)
+INDEX_SELECT_FOR_INDEX(long, i64)
+
+// Batched matrix multiplication, tiled through threadgroup memory. Each threadgroup computes a
+// `GEMM_TILE x GEMM_TILE` block of the output, `lhs_strides` and `rhs_strides` are the batch,
+// row and column strides of the operands. The output is contiguous.
+
+#define GEMM_TILE 16
+
+#define GEMM_OP(TYPENAME, SUFFIX) \
+kernel void gemm_##SUFFIX( \
+    constant size_t &m, \
+    constant size_t &n, \
+    constant size_t &k, \
+    constant size_t *lhs_strides, \
+    constant size_t *rhs_strides, \
+    device const TYPENAME *lhs, \
+    device const TYPENAME *rhs, \
+    device TYPENAME *dst, \
+    uint3 group_id [[threadgroup_position_in_grid]], \
+    uint3 thread_id [[thread_position_in_threadgroup]] \
+) { \
+    threadgroup float lhs_tile[GEMM_TILE][GEMM_TILE]; \
+    threadgroup float rhs_tile[GEMM_TILE][GEMM_TILE]; \
+    size_t b = group_id.z; \
+    size_t row = group_id.y * GEMM_TILE + thread_id.y; \
+    size_t col = group_id.x * GEMM_TILE + thread_id.x; \
+    device const TYPENAME *l = lhs + b * lhs_strides[0]; \
+    device const TYPENAME *r = rhs + b * rhs_strides[0]; \
+    float acc = 0.0f; \
+    for (size_t t = 0; t < k; t += GEMM_TILE) { \
+        size_t lhs_k = t + thread_id.x; \
+        size_t rhs_k = t + thread_id.y; \
+        lhs_tile[thread_id.y][thread_id.x] = (row < m && lhs_k < k) \
+            ? float(l[row * lhs_strides[1] + lhs_k * lhs_strides[2]]) : 0.0f; \
+        rhs_tile[thread_id.y][thread_id.x] = (rhs_k < k && col < n) \
+            ? float(r[rhs_k * rhs_strides[1] + col * rhs_strides[2]]) : 0.0f; \
+        threadgroup_barrier(mem_flags::mem_threadgroup); \
+        for (size_t i = 0; i < GEMM_TILE; i++) { \
+            acc += lhs_tile[thread_id.y][i] * rhs_tile[i][thread_id.x]; \
+        } \
+        threadgroup_barrier(mem_flags::mem_threadgroup); \
+    } \
+    if (row < m && col < n) { \
+        dst[(b * m + row) * n + col] = TYPENAME(acc); \
+    } \
+}
+
+FOR_FLOAT_TYPES(GEMM_OP)
@@ -242,17 +242,24 @@ impl Tensor {
         }
     }
 
+    // Arrays stored in fortran order are read as the C order array with the reversed shape, the
+    // dimensions are then permuted back so that the returned tensor has a strided layout.
+    fn from_npy_reader<R: std::io::Read>(header: &Header, reader: &mut R) -> Result<Self> {
+        if !header.fortran_order || header.shape.len() < 2 {
+            return Self::from_reader(header.shape(), header.descr, reader);
+        }
+        let rev_shape: Vec<usize> = header.shape.iter().rev().copied().collect();
+        let t = Self::from_reader(Shape::from(rev_shape), header.descr, reader)?;
+        let rev_dims: Vec<usize> = (0..header.shape.len()).rev().collect();
+        t.permute(rev_dims)
+    }
+
     /// Reads a npy file and return the stored multi-dimensional array as a tensor.
     pub fn read_npy<T: AsRef<Path>>(path: T) -> Result<Self> {
         let mut reader = File::open(path.as_ref())?;
         let header = read_header(&mut reader)?;
         let header = Header::parse(&header)?;
-        if header.fortran_order {
-            return Err(Error::Npy("fortran order not supported".to_string()));
-        }
-        let mut data: Vec<u8> = vec![];
-        reader.read_to_end(&mut data)?;
-        Self::from_reader(header.shape(), header.descr, &mut reader)
+        Self::from_npy_reader(&header, &mut reader)
     }
 
     /// Reads a npz file and returns the stored multi-dimensional arrays together with their names.
@@ -268,15 +275,17 @@ impl Tensor {
             };
             let header = read_header(&mut reader)?;
             let header = Header::parse(&header)?;
-            if header.fortran_order {
-                return Err(Error::Npy("fortran order not supported".to_string()));
-            }
-            let s = Self::from_reader(header.shape(), header.descr, &mut reader)?;
+            let s = Self::from_npy_reader(&header, &mut reader)?;
             result.push((name, s))
         }
         Ok(result)
     }
 
+    /// Reads a npz file and returns the stored multi-dimensional arrays indexed by their names.
+    pub fn read_npz_map<T: AsRef<Path>>(path: T) -> Result<HashMap<String, Self>> {
+        Ok(Self::read_npz(path)?.into_iter().collect())
+    }
+
     /// Reads a npz file and returns the stored multi-dimensional arrays for some specified names.
     pub fn read_npz_by_name<T: AsRef<Path>>(path: T, names: &[&str]) -> Result<Vec<Self>> {
         let zip_reader = BufReader::new(File::open(path.as_ref())?);
@@ -292,10 +301,7 @@ impl Tensor {
             };
             let header = read_header(&mut reader)?;
             let header = Header::parse(&header)?;
-            if header.fortran_order {
-                return Err(Error::Npy("fortran order not supported".to_string()));
-            }
-            let s = Self::from_reader(header.shape(), header.descr, &mut reader)?;
+            let s = Self::from_npy_reader(&header, &mut reader)?;
             result.push(s)
         }
         Ok(result)
@@ -310,8 +316,10 @@ impl Tensor {
             shape: self.dims().to_vec(),
         };
         let mut header = header.to_string()?;
-        let pad = 16 - (NPY_MAGIC_STRING.len() + 5 + header.len()) % 16;
-        for _ in 0..pad % 16 {
+        // As with recent NumPy versions, the header is padded so that the data starts at an
+        // offset that is a multiple of 64.
+        let pad = 64 - (NPY_MAGIC_STRING.len() + 5 + header.len()) % 64;
+        for _ in 0..pad % 64 {
             header.push(' ')
         }
         header.push('\n');
@@ -401,10 +409,7 @@ impl NpzTensors {
         let mut reader = zip.by_index(index)?;
         let header = read_header(&mut reader)?;
         let header = Header::parse(&header)?;
-        if header.fortran_order {
-            return Err(Error::Npy("fortran order not supported".to_string()));
-        }
-        let tensor = Tensor::from_reader(header.shape(), header.descr, &mut reader)?;
+        let tensor = Tensor::from_npy_reader(&header, &mut reader)?;
         Ok(Some(tensor))
     }
 }
@@ -1,6 +1,8 @@
 // Just enough pickle support to be able to read PyTorch checkpoints.
 // This hardcodes objects that are required for tensor reading, we may want to make this a bit more
 // composable/tensor agnostic at some point.
+// No python code is ever run: globals are only recorded as class names and any op-code outside of
+// the subset used by `torch.save` results in an error.
 use crate::{DType, Error as E, Layout, Result, Tensor};
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::collections::HashMap;
@@ -76,7 +78,7 @@ impl TryFrom<u8> for OpCode {
             b's' => Ok(Self::SetItem),
             b'u' => Ok(Self::SetItems),
             b'}' => Ok(Self::EmptyDict),
-            b'd' => Ok(Self::EmptyDict),
+            b'd' => Ok(Self::Dict),
             b'b' => Ok(Self::Build),
             b'.' => Ok(Self::Stop),
             0x81 => Ok(Self::NewObj),
@@ -476,14 +478,14 @@ impl Stack {
                 }
             }
             OpCode::SetItems => {
-                let mut objs = self.pop_to_marker()?;
+                let objs = self.pop_to_marker()?;
                 let pydict = self.last()?;
                 if let Object::Dict(d) = pydict {
                     if objs.len() % 2 != 0 {
                         crate::bail!("setitems: not an even number of objects")
                     }
-                    while let Some(value) = objs.pop() {
-                        let key = objs.pop().unwrap();
+                    let mut objs = objs.into_iter();
+                    while let (Some(key), Some(value)) = (objs.next(), objs.next()) {
                         d.push((key, value))
                     }
                 } else {
@@ -497,13 +499,13 @@ impl Stack {
             OpCode::Build => self.build()?,
             OpCode::EmptyDict => self.push(Object::Dict(vec![])),
             OpCode::Dict => {
-                let mut objs = self.pop_to_marker()?;
+                let objs = self.pop_to_marker()?;
                 let mut pydict = vec![];
                 if objs.len() % 2 != 0 {
                     crate::bail!("setitems: not an even number of objects")
                 }
-                while let Some(value) = objs.pop() {
-                    let key = objs.pop().unwrap();
+                let mut objs = objs.into_iter();
+                while let (Some(key), Some(value)) = (objs.next(), objs.next()) {
                     pydict.push((key, value))
                 }
                 self.push(Object::Dict(pydict))
@@ -564,7 +566,8 @@ fn rebuild_args(args: Object) -> Result<(Layout, DType, String, usize)> {
         "DoubleStorage" => DType::F64,
         "HalfStorage" => DType::F16,
         "BFloat16Storage" => DType::BF16,
-        "ByteStorage" => DType::U8,
+        "ByteStorage" | "BoolStorage" => DType::U8,
+        "LongStorage" => DType::I64,
         other => {
             crate::bail!("unsupported storage type {other}")
         }
@@ -705,21 +708,62 @@ impl PthTensors {
         // We hope that the file has not changed since first reading it.
         let zip_reader = std::io::BufReader::new(std::fs::File::open(&self.path)?);
         let mut zip = zip::ZipArchive::new(zip_reader)?;
-        let mut reader = zip.by_name(&tensor_info.path)?;
-
-        // Reading the data is a bit tricky as it can be strided, use an offset, etc.
-        // For now only support the basic case.
-        if tensor_info.layout.start_offset() != 0 || !tensor_info.layout.is_contiguous() {
-            crate::bail!(
-                "cannot retrieve non-contiguous tensors {:?}",
-                tensor_info.layout
-            )
-        }
-        let tensor = Tensor::from_reader(
-            tensor_info.layout.shape().clone(),
-            tensor_info.dtype,
-            &mut reader,
-        )?;
+        let tensor = load_tensor(&mut zip, tensor_info)?;
         Ok(Some(tensor))
     }
 }
+
+// The whole storage is read so that tensors that are views on a larger storage, e.g. using an
+// offset or some non-contiguous strides, can be extracted from it.
+fn load_tensor<R: std::io::Read + std::io::Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    tensor_info: &TensorInfo,
+) -> Result<Tensor> {
+    let layout = &tensor_info.layout;
+    let shape = layout.shape();
+    if shape.elem_count() == 0 {
+        return Tensor::zeros(shape, tensor_info.dtype, &crate::Device::Cpu);
+    }
+    let max_offset = layout.start_offset()
+        + shape
+            .dims()
+            .iter()
+            .zip(layout.stride())
+            .map(|(dim, stride)| (dim - 1) * stride)
+            .sum::<usize>();
+    if max_offset >= tensor_info.storage_size {
+        crate::bail!(
+            "{}: layout {layout:?} out of bounds for a storage of size {}",
+            tensor_info.name,
+            tensor_info.storage_size
+        )
+    }
+    let mut reader = zip.by_name(&tensor_info.path)?;
+    let storage = Tensor::from_reader(
+        crate::Shape::from(tensor_info.storage_size),
+        tensor_info.dtype,
+        &mut reader,
+    )?;
+    match layout.contiguous_offsets() {
+        Some((start, end)) => storage.narrow(0, start, end - start)?.reshape(shape),
+        None => {
+            let ids = layout.strided_index().map(|i| i as i64).collect::<Vec<_>>();
+            let ids = Tensor::new(ids, &crate::Device::Cpu)?;
+            storage.index_select(&ids, 0)?.reshape(shape)
+        }
+    }
+}
+
+/// Reads all the tensors from a PyTorch checkpoint, e.g. a `pytorch_model.bin` file, in the
+/// order in which they appear in the pickled state dict.
+pub fn read_all<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<(String, Tensor)>> {
+    let tensor_infos = read_pth_tensor_info(path.as_ref(), false)?;
+    let zip_reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut zip = zip::ZipArchive::new(zip_reader)?;
+    let mut tensors = Vec::with_capacity(tensor_infos.len());
+    for tensor_info in tensor_infos.iter() {
+        let tensor = load_tensor(&mut zip, tensor_info)?;
+        tensors.push((tensor_info.name.to_string(), tensor))
+    }
+    Ok(tensors)
+}
@@ -0,0 +1,110 @@
+//! Support for 4-bit weights packed with group-wise scales and zero points, as used by AWQ and
+//! GPTQ style checkpoints.
+use crate::{DType, Device, Result, Tensor};
+
+fn check_awq_shapes(
+    packed: &Tensor,
+    scales: &Tensor,
+    zeros: &Tensor,
+    group_size: usize,
+) -> Result<(usize, usize, usize)> {
+    if packed.dtype() != DType::U8 {
+        crate::bail!(
+            "awq: packed should be a u8 tensor, got {:?}",
+            packed.dtype()
+        )
+    }
+    let (rows, half_cols) = packed.dims2()?;
+    let cols = half_cols * 2;
+    if group_size == 0 || cols % group_size != 0 {
+        crate::bail!("awq: group size {group_size} does not divide the {cols} columns")
+    }
+    let n_groups = cols / group_size;
+    for (name, t) in [("scales", scales), ("zeros", zeros)] {
+        if t.dims() != [rows, n_groups] {
+            crate::bail!(
+                "awq: {name} shape {:?} does not match the expected ({rows}, {n_groups})",
+                t.shape()
+            )
+        }
+    }
+    Ok((rows, cols, n_groups))
+}
+
+/// Unpacks 4-bit quantized weights and dequantizes them to a f16 matrix.
+///
+/// Arguments
+///
+/// * `packed` - a u8 tensor of shape `(rows, cols / 2)`, each byte contains two values, the low
+///   nibble for the even column and the high nibble for the odd column.
+/// * `scales` - a tensor of shape `(rows, cols / group_size)` containing the scale of each group.
+/// * `zeros` - a tensor of shape `(rows, cols / group_size)` containing the zero point of each
+///   group.
+/// * `group_size` - the number of consecutive columns sharing the same scale and zero point.
+///
+/// The dequantized value for a quantized value `q` is `(q - zero) * scale`, the returned tensor
+/// has shape `(rows, cols)` and is on the same device as `packed`.
+pub fn unpack_awq(
+    packed: &Tensor,
+    scales: &Tensor,
+    zeros: &Tensor,
+    group_size: usize,
+) -> Result<Tensor> {
+    let (rows, cols, n_groups) = check_awq_shapes(packed, scales, zeros, group_size)?;
+    let half_cols = cols / 2;
+    let to_f32_vec = |t: &Tensor| {
+        t.to_device(&Device::Cpu)?
+            .to_dtype(DType::F32)?
+            .flatten_all()?
+            .to_vec1::<f32>()
+    };
+    let packed_data = packed
+        .to_device(&Device::Cpu)?
+        .flatten_all()?
+        .to_vec1::<u8>()?;
+    let scales = to_f32_vec(scales)?;
+    let zeros = to_f32_vec(zeros)?;
+    let mut weights = Vec::with_capacity(rows * cols);
+    for (row, row_data) in packed_data.chunks_exact(half_cols).enumerate() {
+        for (col, &byte) in row_data.iter().enumerate() {
+            for (offset, q) in [byte & 0x0f, byte >> 4].into_iter().enumerate() {
+                let group = row * n_groups + (2 * col + offset) / group_size;
+                let w = (q as f32 - zeros[group]) * scales[group];
+                weights.push(half::f16::from_f32(w))
+            }
+        }
+    }
+    Tensor::from_vec(weights, (rows, cols), packed.device())
+}
+
+/// A 4-bit group-quantized weight matrix, see [`unpack_awq`] for the layout of the different
+/// parts.
+#[derive(Debug, Clone)]
+pub struct AwqTensor {
+    packed: Tensor,
+    scales: Tensor,
+    zeros: Tensor,
+    group_size: usize,
+}
+
+impl AwqTensor {
+    pub fn new(packed: Tensor, scales: Tensor, zeros: Tensor, group_size: usize) -> Result<Self> {
+        check_awq_shapes(&packed, &scales, &zeros, group_size)?;
+        Ok(Self {
+            packed,
+            scales,
+            zeros,
+            group_size,
+        })
+    }
+
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+}
+
+impl super::Dequantize for AwqTensor {
+    fn dequantize(&self, dtype: DType) -> Result<Tensor> {
+        unpack_awq(&self.packed, &self.scales, &self.zeros, self.group_size)?.to_dtype(dtype)
+    }
+}
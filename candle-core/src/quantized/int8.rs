@@ -0,0 +1,131 @@
+//! Int8 tensors using per-tensor affine quantization.
+//!
+//! A quantized value `q` represents the float value `scale * (q - zero_point)`. Quantizing rounds
+//! to the nearest integer and clamps to the int8 range, so values within the representable range
+//! `[scale * (-128 - zero_point), scale * (127 - zero_point)]` are off by at most `scale / 2`.
+//!
+//! [`QInt8Tensor::matmul`] multiplies two quantized matrices accumulating the products of the
+//! centered integers in `i32` before applying both scales, the only error compared to a float
+//! matmul of the dequantized inputs comes from the final conversion to `f32`. Compared to the
+//! original float inputs, each output element of a `(m, k) x (k, n)` product is off by at most
+//! `k * (max|a| * scale_b + max|b| * scale_a + scale_a * scale_b / 2) / 2`, in practice the
+//! rounding errors mostly cancel out and the error grows like `sqrt(k)`.
+//!
+//! Weight-only quantized layers can store a `QInt8Tensor` and dequantize it when needed, full
+//! int8 layers quantize their inputs too and use `matmul`.
+use super::Dequantize;
+use crate::{DType, Device, Result, Shape, Tensor};
+use rayon::prelude::*;
+
+/// An int8 tensor with a single scale and zero point, stored on the cpu.
+#[derive(Debug, Clone)]
+pub struct QInt8Tensor {
+    data: Vec<i8>,
+    shape: Shape,
+    scale: f64,
+    zero_point: i64,
+}
+
+impl QInt8Tensor {
+    /// Quantizes `t` using `q = clamp(round(x / scale) + zero_point, -128, 127)`.
+    pub fn quantize(t: &Tensor, scale: f64, zero_point: i64) -> Result<Self> {
+        if !(scale.is_finite() && scale > 0.) {
+            crate::bail!("quantize_per_tensor: scale has to be positive, got {scale}")
+        }
+        if !(-128..=127).contains(&zero_point) {
+            crate::bail!("quantize_per_tensor: zero point {zero_point} is out of the int8 range")
+        }
+        let values = t
+            .to_device(&Device::Cpu)?
+            .to_dtype(DType::F64)?
+            .flatten_all()?
+            .to_vec1::<f64>()?;
+        let data = values
+            .iter()
+            .map(|v| ((v / scale).round() + zero_point as f64).clamp(-128., 127.) as i8)
+            .collect();
+        Ok(Self {
+            data,
+            shape: t.shape().clone(),
+            scale,
+            zero_point,
+        })
+    }
+
+    pub fn data(&self) -> &[i8] {
+        &self.data
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn zero_point(&self) -> i64 {
+        self.zero_point
+    }
+
+    fn centered(&self) -> Vec<i32> {
+        let zero_point = self.zero_point as i32;
+        self.data.iter().map(|&q| q as i32 - zero_point).collect()
+    }
+
+    /// Multiplies two quantized matrices of shapes `(m, k)` and `(k, n)`, the products are
+    /// accumulated in `i32` and the result is a `f32` tensor of shape `(m, n)` on the cpu.
+    pub fn matmul(&self, rhs: &Self) -> Result<Tensor> {
+        let (m, k) = self.shape.dims2()?;
+        let (k2, n) = rhs.shape.dims2()?;
+        if k != k2 {
+            crate::bail!(
+                "int8 matmul: shape mismatch {:?} {:?}",
+                self.shape,
+                rhs.shape
+            )
+        }
+        // The centered values are in [-255, 255] so this bounds the accumulator.
+        if k > i32::MAX as usize / (255 * 255) {
+            crate::bail!("int8 matmul: the inner dimension {k} is too large for i32 accumulation")
+        }
+        let lhs = self.centered();
+        let rhs_centered = rhs.centered();
+        // Transpose the rhs so that the inner loop reads contiguous memory.
+        let mut rhs_t = vec![0i32; k * n];
+        for (idx_k, row) in rhs_centered.chunks(n.max(1)).enumerate() {
+            for (idx_n, &v) in row.iter().enumerate() {
+                rhs_t[idx_n * k + idx_k] = v
+            }
+        }
+        let scale = (self.scale * rhs.scale) as f32;
+        let mut dst = vec![0f32; m * n];
+        dst.par_chunks_mut(n.max(1))
+            .zip(lhs.par_chunks(k.max(1)))
+            .for_each(|(dst_row, lhs_row)| {
+                for (d, rhs_col) in dst_row.iter_mut().zip(rhs_t.chunks(k.max(1))) {
+                    let acc: i32 = lhs_row.iter().zip(rhs_col).map(|(a, b)| a * b).sum();
+                    *d = acc as f32 * scale
+                }
+            });
+        Tensor::from_vec(dst, (m, n), &Device::Cpu)
+    }
+}
+
+impl Dequantize for QInt8Tensor {
+    fn dequantize(&self, dtype: DType) -> Result<Tensor> {
+        let data: Vec<f32> = self
+            .centered()
+            .into_iter()
+            .map(|q| (q as f64 * self.scale) as f32)
+            .collect();
+        Tensor::from_vec(data, self.shape.clone(), &Device::Cpu)?.to_dtype(dtype)
+    }
+}
+
+impl Tensor {
+    /// Quantizes the tensor to int8 using a single scale and zero point, see [`QInt8Tensor`].
+    pub fn quantize_per_tensor(&self, scale: f64, zero_point: i64) -> Result<QInt8Tensor> {
+        QInt8Tensor::quantize(self, scale, zero_point)
+    }
+}
@@ -1,16 +1,29 @@
-use crate::{Device, Result, Shape, Tensor};
+use crate::{DType, Device, Result, Shape, Tensor};
 
 #[cfg(target_feature = "avx")]
 pub mod avx;
+pub mod awq;
 pub mod ggml_file;
 pub mod gguf_file;
+pub mod int8;
 pub mod k_quants;
 #[cfg(target_feature = "neon")]
 pub mod neon;
 pub mod utils;
 
+pub use awq::{unpack_awq, AwqTensor};
+pub use int8::QInt8Tensor;
 pub use k_quants::GgmlType;
 
+/// Quantized representations that can be converted back to a float tensor.
+///
+/// This lets model loaders handle the different quantization formats in a uniform way, e.g. via
+/// `&dyn Dequantize`.
+pub trait Dequantize {
+    /// Returns the dequantized values as a tensor of type `dtype`.
+    fn dequantize(&self, dtype: DType) -> Result<Tensor>;
+}
+
 pub struct QTensor {
     data: Box<dyn QuantizedType>,
     shape: Shape,
@@ -198,6 +211,28 @@ impl QTensor {
         })
     }
 
+    /// Quantizes `src` using the block format `dtype` selected at runtime, this is the same as
+    /// [`QTensor::quantize`] with the corresponding block type.
+    pub fn quantize_as(src: &Tensor, dtype: GgmlDType) -> Result<Self> {
+        use k_quants::*;
+        match dtype {
+            GgmlDType::F32 => Self::quantize::<f32>(src),
+            GgmlDType::F16 => Self::quantize::<half::f16>(src),
+            GgmlDType::Q4_0 => Self::quantize::<BlockQ4_0>(src),
+            GgmlDType::Q4_1 => Self::quantize::<BlockQ4_1>(src),
+            GgmlDType::Q5_0 => Self::quantize::<BlockQ5_0>(src),
+            GgmlDType::Q5_1 => Self::quantize::<BlockQ5_1>(src),
+            GgmlDType::Q8_0 => Self::quantize::<BlockQ8_0>(src),
+            GgmlDType::Q8_1 => Self::quantize::<BlockQ8_1>(src),
+            GgmlDType::Q2K => Self::quantize::<BlockQ2K>(src),
+            GgmlDType::Q3K => Self::quantize::<BlockQ3K>(src),
+            GgmlDType::Q4K => Self::quantize::<BlockQ4K>(src),
+            GgmlDType::Q5K => Self::quantize::<BlockQ5K>(src),
+            GgmlDType::Q6K => Self::quantize::<BlockQ6K>(src),
+            GgmlDType::Q8K => Self::quantize::<BlockQ8K>(src),
+        }
+    }
+
     pub fn dtype(&self) -> GgmlDType {
         self.data.dtype()
     }
@@ -229,6 +264,13 @@ impl QTensor {
     }
 }
 
+/// The dequantized tensor is stored on the cpu as [`QTensor`] only supports this device.
+impl Dequantize for QTensor {
+    fn dequantize(&self, dtype: DType) -> Result<Tensor> {
+        QTensor::dequantize(self, &Device::Cpu)?.to_dtype(dtype)
+    }
+}
+
 #[derive(Debug)]
 pub struct QMatMul(std::sync::Arc<QTensor>);
 
@@ -281,7 +323,7 @@ impl crate::CustomOp1 for QTensor {
             storage,
             &mut dst_storage,
         )?;
-        Ok((crate::CpuStorage::F32(dst_storage), dst_shape))
+        Ok((crate::CpuStorage::F32(dst_storage.into()), dst_shape))
     }
 }
 
@@ -1,4 +1,4 @@
-use crate::{DType, Device, Error, Result, Tensor, WithDType};
+use crate::{DType, Device, Error, Result, Shape, Tensor, WithDType};
 use safetensors::tensor as st;
 use safetensors::tensor::SafeTensors;
 use std::borrow::Cow;
@@ -88,8 +88,19 @@ impl Tensor {
     }
 }
 
+fn check_buffer_size(len: usize, size_in_bytes: usize, shape: &[usize]) -> Result<()> {
+    let elem_count: usize = shape.iter().product();
+    if len != elem_count * size_in_bytes {
+        crate::bail!(
+            "buffer of {len} bytes does not match shape {shape:?} with {size_in_bytes} bytes per element"
+        )
+    }
+    Ok(())
+}
+
 fn convert_slice<T: WithDType>(data: &[u8], shape: &[usize], device: &Device) -> Result<Tensor> {
     let size_in_bytes = T::DTYPE.size_in_bytes();
+    check_buffer_size(data.len(), size_in_bytes, shape)?;
     let elem_count = data.len() / size_in_bytes;
     if (data.as_ptr() as usize) % size_in_bytes == 0 {
         // SAFETY This is safe because we just checked that this
@@ -120,6 +131,7 @@ fn convert_slice_with_cast<T: Sized + Copy, U: WithDType, F: Fn(T) -> Result<U>>
     conv: F,
 ) -> Result<Tensor> {
     let size_in_bytes = std::mem::size_of::<T>();
+    check_buffer_size(data.len(), size_in_bytes, shape)?;
     let elem_count = data.len() / size_in_bytes;
     if (data.as_ptr() as usize) % size_in_bytes == 0 {
         // SAFETY This is safe because we just checked that this
@@ -198,6 +210,53 @@ impl Tensor {
             DType::F64 => convert_slice::<f64>(data, shape, device),
         }
     }
+
+    /// Returns the raw bytes of the tensor data, the elements are in row-major order and each
+    /// element is encoded in little-endian using `dtype.size_in_bytes()` bytes, e.g. two bytes
+    /// for `f16` and `bf16`. Non-contiguous tensors are made contiguous first.
+    ///
+    /// ```rust
+    /// use candle_core::{DType, Device, Tensor};
+    /// let t = Tensor::new(&[1u32, 256], &Device::Cpu)?;
+    /// let bytes = t.to_bytes()?;
+    /// assert_eq!(bytes, &[1, 0, 0, 0, 0, 1, 0, 0]);
+    /// let t = Tensor::from_bytes(&bytes, 2, DType::U32, &Device::Cpu)?;
+    /// assert_eq!(t.to_vec1::<u32>()?, &[1, 256]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = convert_back(self)?;
+        swap_to_little_endian(&mut bytes, self.dtype());
+        Ok(bytes)
+    }
+
+    /// Creates a tensor from raw bytes in the format returned by [`Tensor::to_bytes`]. An error
+    /// is returned if the number of bytes does not match the shape and dtype.
+    pub fn from_bytes<S: Into<Shape>>(
+        bytes: &[u8],
+        shape: S,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Self> {
+        let shape = shape.into();
+        if cfg!(target_endian = "big") {
+            let mut bytes = bytes.to_vec();
+            swap_to_little_endian(&mut bytes, dtype);
+            Self::from_raw_buffer(&bytes, dtype, shape.dims(), device)
+        } else {
+            Self::from_raw_buffer(bytes, dtype, shape.dims(), device)
+        }
+    }
+}
+
+// Converts between the native byte order and little-endian, this is a no-op on little-endian
+// platforms.
+fn swap_to_little_endian(bytes: &mut [u8], dtype: DType) {
+    if cfg!(target_endian = "big") {
+        for elem in bytes.chunks_exact_mut(dtype.size_in_bytes()) {
+            elem.reverse()
+        }
+    }
 }
 
 fn convert(view: &st::TensorView<'_>, device: &Device) -> Result<Tensor> {
@@ -248,6 +307,24 @@ pub fn load_buffer(data: &[u8], device: &Device) -> Result<HashMap<String, Tenso
         .collect()
 }
 
+/// Loads all the tensors from a safetensors file by memory mapping it rather than reading the
+/// whole file into a buffer first.
+///
+/// # Safety
+///
+/// The unsafe is inherited from [`memmap2::MmapOptions`].
+pub unsafe fn load_mmaped<P: AsRef<Path>>(
+    filename: P,
+    device: &Device,
+) -> Result<HashMap<String, Tensor>> {
+    let file = MmapedFile::new(filename)?;
+    let st = file.deserialize()?;
+    st.tensors()
+        .into_iter()
+        .map(|(name, view)| Ok((name, view.load(device)?)))
+        .collect()
+}
+
 pub fn save<K: AsRef<str> + Ord + std::fmt::Display, P: AsRef<Path>>(
     tensors: &HashMap<K, Tensor>,
     filename: P,
@@ -286,6 +363,72 @@ impl MmapedFile {
     }
 }
 
+/// A collection of memory mapped safetensors files, e.g. the shards of a checkpoint using the
+/// `model-00001-of-00002.safetensors` naming convention. The headers are parsed when creating
+/// this object so as to know which file holds each tensor, the tensor data is only read when
+/// loading a tensor.
+pub struct MmapedSafetensors {
+    files: Vec<MmapedFile>,
+    routing: HashMap<String, usize>,
+}
+
+impl MmapedSafetensors {
+    /// Memory maps a single safetensors file.
+    ///
+    /// # Safety
+    ///
+    /// The unsafe is inherited from [`memmap2::MmapOptions`].
+    pub unsafe fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
+        Self::multi(&[p])
+    }
+
+    /// Memory maps multiple safetensors files. If a tensor name appears in multiple files, the
+    /// last file wins.
+    ///
+    /// # Safety
+    ///
+    /// The unsafe is inherited from [`memmap2::MmapOptions`].
+    pub unsafe fn multi<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let mut files = Vec::with_capacity(paths.len());
+        let mut routing = HashMap::new();
+        for (index, p) in paths.iter().enumerate() {
+            let file = MmapedFile::new(p)?;
+            for name in file.deserialize()?.names() {
+                routing.insert(name.to_string(), index);
+            }
+            files.push(file)
+        }
+        Ok(Self { files, routing })
+    }
+
+    /// Loads the tensor with the given name on the target device, only the data for this tensor
+    /// is read from the mapped files.
+    pub fn load(&self, name: &str, dev: &Device) -> Result<Tensor> {
+        let index = match self.routing.get(name) {
+            None => Err(Error::CannotFindTensor {
+                path: name.to_string(),
+            }
+            .bt())?,
+            Some(index) => *index,
+        };
+        let file = &self.files[index];
+        let st = file.deserialize()?;
+        let view = st
+            .tensor(name)
+            .map_err(|e| Error::from(e).with_path(&file.path))?;
+        view.load(dev)
+    }
+
+    pub fn contains_tensor(&self, name: &str) -> bool {
+        self.routing.contains_key(name)
+    }
+
+    /// The names of all the tensors available in the mapped files.
+    pub fn names(&self) -> Vec<&String> {
+        self.routing.keys().collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,4 +457,53 @@ mod tests {
         assert_eq!(bytes, b"x\0\0\0\0\0\0\0{\"t\":{\"dtype\":\"F32\",\"shape\":[2,2],\"data_offsets\":[0,16]},\"u\":{\"dtype\":\"F32\",\"shape\":[1,2],\"data_offsets\":[16,24]}}      \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0");
         std::fs::remove_file("multi.safetensors").unwrap();
     }
+
+    #[test]
+    fn save_load_round_trip() {
+        let device = &Device::Cpu;
+        let t = Tensor::arange(0f32, 6., device)
+            .unwrap()
+            .reshape((2, 3))
+            .unwrap();
+        let map: HashMap<_, _> = [
+            ("f32", t.clone()),
+            ("f16", t.to_dtype(DType::F16).unwrap()),
+            ("bf16", t.to_dtype(DType::BF16).unwrap()),
+            ("f64", t.to_dtype(DType::F64).unwrap()),
+            ("i64", t.to_dtype(DType::I64).unwrap()),
+            ("u32", t.to_dtype(DType::U32).unwrap()),
+            ("u8", t.to_dtype(DType::U8).unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        save(&map, "round_trip.safetensors").unwrap();
+        let loaded = load("round_trip.safetensors", device).unwrap();
+        let mmaped = unsafe { load_mmaped("round_trip.safetensors", device).unwrap() };
+        std::fs::remove_file("round_trip.safetensors").unwrap();
+        for weights in [loaded, mmaped] {
+            assert_eq!(weights.len(), map.len());
+            for (name, t) in map.iter() {
+                let w = weights.get(*name).unwrap();
+                assert_eq!(w.dtype(), t.dtype());
+                assert_eq!(w.dims(), t.dims());
+                let w = w.to_dtype(DType::F64).unwrap().to_vec2::<f64>().unwrap();
+                let t = t.to_dtype(DType::F64).unwrap().to_vec2::<f64>().unwrap();
+                assert_eq!(w, t, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn load_errors() {
+        // A BOOL tensor, this dtype is not supported.
+        let header = b"{\"t\":{\"dtype\":\"BOOL\",\"shape\":[2],\"data_offsets\":[0,2]}}";
+        let mut data = (header.len() as u64).to_le_bytes().to_vec();
+        data.extend_from_slice(header);
+        data.extend_from_slice(&[0, 1]);
+        let err = load_buffer(&data, &Device::Cpu).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedSafeTensorDtype(_)), "{err}");
+
+        let err = Tensor::from_raw_buffer(&[0u8; 10], DType::F32, &[3], &Device::Cpu).unwrap_err();
+        assert!(err.to_string().contains("does not match shape"), "{err}");
+    }
 }
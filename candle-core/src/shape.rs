@@ -300,44 +300,83 @@ impl Dim for usize {
     }
 }
 
+/// Resolves a dimension index counted from the end, `-1` being the last dimension. When
+/// `plus_one` is set, the index can also refer to the position after the last dimension, e.g. for
+/// `unsqueeze`, so `-1` is resolved to the rank.
+fn resolve_negative_dim(
+    dim: i64,
+    shape: &Shape,
+    op: &'static str,
+    plus_one: bool,
+) -> Result<usize> {
+    let rank = shape.rank() as i64 + i64::from(plus_one);
+    let resolved = rank + dim;
+    if dim >= 0 || resolved < 0 {
+        Err(Error::NegativeDimOutOfRange {
+            shape: shape.clone(),
+            dim,
+            resolved,
+            op,
+        }
+        .bt())?
+    } else {
+        Ok(resolved as usize)
+    }
+}
+
+macro_rules! signed_dim {
+    ($ty:ty) => {
+        /// Non-negative indexes are counted from the start and negative ones from the end, `-1`
+        /// being the last dimension as in PyTorch.
+        impl Dim for $ty {
+            fn to_index(&self, shape: &Shape, op: &'static str) -> Result<usize> {
+                if *self >= 0 {
+                    (*self as usize).to_index(shape, op)
+                } else {
+                    resolve_negative_dim(*self as i64, shape, op, false)
+                }
+            }
+
+            fn to_index_plus_one(&self, shape: &Shape, op: &'static str) -> Result<usize> {
+                if *self >= 0 {
+                    (*self as usize).to_index_plus_one(shape, op)
+                } else {
+                    resolve_negative_dim(*self as i64, shape, op, true)
+                }
+            }
+        }
+    };
+}
+
+signed_dim!(i32);
+signed_dim!(i64);
+
+/// Dimension indexes counted from the end.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum D {
     Minus1,
     Minus2,
+    /// The `n`-th dimension from the end, `D::Minus(1)` being the last dimension.
+    Minus(usize),
 }
 
 impl D {
-    fn out_of_range(&self, shape: &Shape, op: &'static str) -> Error {
-        let dim = match self {
+    fn minus(&self) -> i64 {
+        match self {
             Self::Minus1 => -1,
             Self::Minus2 => -2,
-        };
-        Error::DimOutOfRange {
-            shape: shape.clone(),
-            dim,
-            op,
+            Self::Minus(n) => -(*n as i64),
         }
-        .bt()
     }
 }
 
 impl Dim for D {
     fn to_index(&self, shape: &Shape, op: &'static str) -> Result<usize> {
-        let rank = shape.rank();
-        match self {
-            Self::Minus1 if rank >= 1 => Ok(rank - 1),
-            Self::Minus2 if rank >= 2 => Ok(rank - 2),
-            _ => Err(self.out_of_range(shape, op)),
-        }
+        resolve_negative_dim(self.minus(), shape, op, false)
     }
 
     fn to_index_plus_one(&self, shape: &Shape, op: &'static str) -> Result<usize> {
-        let rank = shape.rank();
-        match self {
-            Self::Minus1 => Ok(rank),
-            Self::Minus2 if rank >= 1 => Ok(rank - 1),
-            _ => Err(self.out_of_range(shape, op)),
-        }
+        resolve_negative_dim(self.minus(), shape, op, true)
     }
 }
 
@@ -49,6 +49,97 @@ impl AsRef<Tensor> for Tensor {
     }
 }
 
+// The source index used for each destination index by the nearest neighbor upsampling ops, this
+// has to match the upsample_nearest2d kernels.
+pub(crate) fn upsample_nearest_indexes(src_size: usize, dst_size: usize) -> Result<Vec<u32>> {
+    if src_size == 0 && dst_size != 0 {
+        crate::bail!("upsample-nearest: cannot upsample an empty dimension to size {dst_size}")
+    }
+    let scale = src_size as f64 / dst_size as f64;
+    let ids = (0..dst_size)
+        .map(|i| usize::min(src_size - 1, (i as f64 * scale) as usize) as u32)
+        .collect();
+    Ok(ids)
+}
+
+// Returns the `(in_size, out_size)` matrix of weights used to linearly (or cubically) interpolate
+// a dimension of size `in_size` to `out_size`, following the PyTorch conventions.
+fn interpolation_weights(
+    in_size: usize,
+    out_size: usize,
+    align_corners: bool,
+    cubic: bool,
+) -> Vec<f64> {
+    // https://github.com/pytorch/pytorch/blob/main/aten/src/ATen/native/UpSample.h
+    const A: f64 = -0.75;
+    let cubic_conv1 = |x: f64| ((A + 2.) * x - (A + 3.)) * x * x + 1.;
+    let cubic_conv2 = |x: f64| ((A * x - 5. * A) * x + 8. * A) * x - 4. * A;
+    let scale = if align_corners {
+        if out_size > 1 {
+            (in_size as f64 - 1.) / (out_size as f64 - 1.)
+        } else {
+            0.
+        }
+    } else {
+        in_size as f64 / out_size as f64
+    };
+    let mut weights = vec![0f64; in_size * out_size];
+    let mut add = |src: i64, dst: usize, weight: f64| {
+        let src = src.clamp(0, in_size as i64 - 1) as usize;
+        weights[src * out_size + dst] += weight
+    };
+    for dst in 0..out_size {
+        let src = if align_corners {
+            scale * dst as f64
+        } else {
+            scale * (dst as f64 + 0.5) - 0.5
+        };
+        if cubic {
+            let src0 = src.floor();
+            let t = src - src0;
+            let src0 = src0 as i64;
+            add(src0 - 1, dst, cubic_conv2(t + 1.));
+            add(src0, dst, cubic_conv1(t));
+            add(src0 + 1, dst, cubic_conv1(1. - t));
+            add(src0 + 2, dst, cubic_conv2(2. - t));
+        } else {
+            let src = src.max(0.);
+            let src0 = src.floor();
+            let lambda = src - src0;
+            let src0 = src0 as i64;
+            add(src0, dst, 1. - lambda);
+            add(src0 + 1, dst, lambda);
+        }
+    }
+    weights
+}
+
+// The windows used by the 2D pooling ops, `values` has shape
+// `(batch, channels, h_out * w_out, k_h * k_w)` and `ids` contains the matching flattened
+// positions in the input plane.
+struct Pool2dWindows {
+    values: Tensor,
+    ids: Tensor,
+    out_dims: (usize, usize, usize, usize),
+    input_counts: Vec<usize>,
+    padded_counts: Vec<usize>,
+}
+
+/// The padding modes supported by [`Tensor::pad`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadMode {
+    /// Pad with zeros.
+    Zeros,
+    /// Pad with the reflection of the values along the edge, excluding the edge value itself,
+    /// e.g. `[1, 2, 3]` padded by 2 on both sides gives `[3, 2, 1, 2, 3, 2, 1]`.
+    Reflect,
+    /// Pad by repeating the edge value, e.g. `[1, 2, 3]` gives `[1, 1, 1, 2, 3, 3, 3]`.
+    Replicate,
+    /// Pad by wrapping around the values from the other side, e.g. `[1, 2, 3]` gives
+    /// `[2, 3, 1, 2, 3, 1, 2]`.
+    Circular,
+}
+
 // Tensors are refcounted so that cloning is cheap when building the op graph.
 // Storages are also refcounted independently so that its possible to avoid
 // copying the storage for operations that only modify the shape or stride.
@@ -177,6 +268,12 @@ impl Tensor {
         Self::ones_impl(shape, dtype, device, false)
     }
 
+    /// Creates a new tensor filled with ones on the default device, see
+    /// [`crate::set_default_device`].
+    pub fn ones_default<S: Into<Shape>>(shape: S, dtype: DType) -> Result<Self> {
+        Self::ones(shape, dtype, &crate::default_device())
+    }
+
     /// Creates a new tensor filled with ones with same shape, dtype, and device as the other tensor.
     ///
     /// ```rust
@@ -222,6 +319,12 @@ impl Tensor {
         Self::zeros_impl(shape, dtype, device, false)
     }
 
+    /// Creates a new tensor filled with zeros on the default device, see
+    /// [`crate::set_default_device`].
+    pub fn zeros_default<S: Into<Shape>>(shape: S, dtype: DType) -> Result<Self> {
+        Self::zeros(shape, dtype, &crate::default_device())
+    }
+
     /// Creates a new tensor filled with ones with same shape, dtype, and device as the other
     /// tensor.
     ///
@@ -348,6 +451,11 @@ impl Tensor {
         Self::new_impl(array, shape, device, false)
     }
 
+    /// Creates a new tensor on the default device using the content and shape of the input.
+    pub fn new_default<A: crate::device::NdArray>(array: A) -> Result<Self> {
+        Self::new(array, &crate::default_device())
+    }
+
     /// Creates a new 1D tensor from an iterator.
     pub fn from_iter<D: crate::WithDType>(
         iter: impl IntoIterator<Item = D>,
@@ -401,6 +509,9 @@ impl Tensor {
     /// Creates a new tensor initialized with values from the input vector. The number of elements
     /// in this vector must be the same as the number of elements defined by the shape.
     /// If the device is cpu, no data copy is made.
+    ///
+    /// Buffers that are shared or not owned, e.g. an `Arc<[T]>` or a memory mapped file, can be
+    /// used without a copy through [`Tensor::from_storage_owned`].
     pub fn from_vec<S: Into<Shape>, D: crate::WithDType>(
         data: Vec<D>,
         shape: S,
@@ -409,6 +520,42 @@ impl Tensor {
         Self::from_vec_impl(data, shape, device, false)
     }
 
+    /// Creates a new tensor on the default device initialized with values from the input
+    /// vector.
+    pub fn from_vec_default<S: Into<Shape>, D: crate::WithDType>(
+        data: Vec<D>,
+        shape: S,
+    ) -> Result<Self> {
+        Self::from_vec(data, shape, &crate::default_device())
+    }
+
+    /// Creates a new cpu tensor that uses an existing cpu storage, no data copy is made. Only the
+    /// number of elements in the storage is checked against the shape.
+    ///
+    /// The storage elements can be a [`crate::CpuBuffer`] on memory owned by another value, so
+    /// that shared buffers or memory mapped files can be used without being copied.
+    ///
+    /// ```rust
+    /// use candle_core::{CpuBuffer, CpuStorage, Tensor};
+    /// let data: std::sync::Arc<[f32]> = vec![1., 2., 3., 4., 5., 6.].into();
+    /// let storage = CpuStorage::F32(CpuBuffer::from(data.clone()));
+    /// let t = Tensor::from_storage_owned(storage, (2, 3))?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[1., 2., 3.], [4., 5., 6.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn from_storage_owned<S: Into<Shape>>(
+        storage: crate::CpuStorage,
+        shape: S,
+    ) -> Result<Self> {
+        let shape = shape.into();
+        let buffer_size = storage.len();
+        if buffer_size != shape.elem_count() {
+            return Err(Error::ShapeMismatch { buffer_size, shape }.bt());
+        }
+        let none = BackpropOp::none();
+        Ok(from_storage(Storage::Cpu(storage), shape, none, false))
+    }
+
     /// Creates a new tensor initialized with values from the input slice. The number of elements
     /// in this vector must be the same as the number of elements defined by the shape.
     pub fn from_slice<S: Into<Shape>, D: crate::WithDType>(
@@ -454,6 +601,10 @@ impl Tensor {
     broadcast_binary_op!(broadcast_div, div);
     broadcast_binary_op!(broadcast_maximum, maximum);
     broadcast_binary_op!(broadcast_minimum, minimum);
+    broadcast_binary_op!(broadcast_pow, pow);
+    broadcast_binary_op!(broadcast_floor_divide, floor_divide);
+    broadcast_binary_op!(broadcast_remainder, remainder);
+    broadcast_binary_op!(broadcast_fmod, fmod);
 
     unary_op!(recip, Recip);
     unary_op!(neg, Neg);
@@ -467,6 +618,9 @@ impl Tensor {
     unary_op!(sqrt, Sqrt);
     unary_op!(gelu, Gelu);
     unary_op!(relu, Relu);
+    unary_op!(floor, Floor);
+    unary_op!(ceil, Ceil);
+    unary_op!(round, Round);
 
     /// Retrieves the single scalar value hold in the tensor. If the tensor contains multiple
     /// dimensions, an error is returned instead.
@@ -486,6 +640,7 @@ impl Tensor {
         match &*self.storage() {
             Storage::Cpu(cpu_storage) => from_cpu_storage(cpu_storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -513,6 +668,32 @@ impl Tensor {
         Ok(inp)
     }
 
+    /// Repeats each slice of the tensor along `dim` `repeats` times consecutively, similar to
+    /// PyTorch `repeat_interleave`, e.g. `[a, b]` becomes `[a, a, b, b]` for two repeats whereas
+    /// [`Tensor::repeat`] would return `[a, b, a, b]`.
+    ///
+    /// This is computed with a single copy of a broadcasted view, the gradients of the copies are
+    /// summed when back-propagating.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1u32, 2], [3, 4]], &Device::Cpu)?;
+    /// let t = t.repeat_interleave(2, 1)?;
+    /// assert_eq!(t.to_vec2::<u32>()?, &[[1, 1, 2, 2], [3, 3, 4, 4]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn repeat_interleave<D: Dim>(&self, repeats: usize, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "repeat_interleave")?;
+        if repeats == 1 {
+            return Ok(self.clone());
+        }
+        let mut dims = self.dims().to_vec();
+        dims.insert(dim + 1, repeats);
+        self.unsqueeze(dim + 1)?
+            .broadcast_as(dims)?
+            .flatten(dim, dim + 1)
+    }
+
     /// This operation multiplies the input tensor by `mul` then adds `add` and return the result.
     /// The input values `mul` and `add` are casted to the appropriate type so some rounding might
     /// be performed.
@@ -530,6 +711,49 @@ impl Tensor {
         Ok(from_storage(storage, self.shape(), op, false))
     }
 
+    // Dividing by `v` for float tensors is a multiplication by `1 / v`, integer tensors use an
+    // integer division instead as `1 / v` would usually round to zero. For these, `v` has to be
+    // a non-zero integer in the range of the dtype rather than being silently truncated.
+    fn scalar_div(&self, v: f64) -> Result<Self> {
+        let dtype = self.dtype();
+        if dtype.is_float() {
+            return self.affine(1. / v, 0.);
+        }
+        // The range is [min, max), the upper bounds are exactly representable as f64.
+        let (min, max) = match dtype {
+            DType::U8 => (0., 256.),
+            DType::U32 => (0., 4294967296.),
+            DType::I64 => (i64::MIN as f64, -(i64::MIN as f64)),
+            DType::BF16 | DType::F16 | DType::F32 | DType::F64 => unreachable!(),
+        };
+        if v == 0. || v.fract() != 0. || v < min || v >= max {
+            crate::bail!(
+                "cannot divide an integer tensor of dtype {dtype:?} by {v}, the divisor has to be a non-zero integer in the range of the dtype"
+            )
+        }
+        let v = Tensor::new(v, self.device())?.to_dtype(dtype)?;
+        self.broadcast_div(&v)
+    }
+
+    // A tensor with the same shape and dtype as `self`, filled with `v`. This only allocates a
+    // single element and broadcasts it, similar to `ones_impl`.
+    fn full_like_broadcast(&self, v: f64) -> Result<Self> {
+        Tensor::new(v, self.device())?
+            .to_dtype(self.dtype())?
+            .broadcast_as(self.shape())
+    }
+
+    /// Element-wise maximum with a scalar, e.g. `maximum_scalar(0.)` is equivalent to `relu`. The
+    /// gradient flows to the input where it is the selected value.
+    pub fn maximum_scalar(&self, v: f64) -> Result<Self> {
+        self.maximum(&self.full_like_broadcast(v)?)
+    }
+
+    /// Element-wise minimum with a scalar.
+    pub fn minimum_scalar(&self, v: f64) -> Result<Self> {
+        self.minimum(&self.full_like_broadcast(v)?)
+    }
+
     /// Applies the Exponential Linear Unit (ELU) function on each element of the input tensor.
     pub fn elu(&self, alpha: f64) -> Result<Self> {
         let storage = self.storage().elu(self.layout(), alpha)?;
@@ -544,6 +768,98 @@ impl Tensor {
         Ok(from_storage(storage, self.shape(), op, false))
     }
 
+    /// Raises the tensor to the power of the `rhs` tensor element-wise, both tensors must have
+    /// the same shape, see `broadcast_pow` for a broadcasting version.
+    ///
+    /// This is computed as `exp(rhs * log(self))` so only positive bases are supported: a
+    /// negative base results in NaN even for integer exponents, and a zero base results in 0 for
+    /// positive exponents and NaN otherwise. The gradients are `rhs * self^(rhs - 1)` for the base
+    /// and `self^rhs * log(self)` for the exponent.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[2f32, 4., 9.], &Device::Cpu)?;
+    /// let e = Tensor::new(&[3f32, 0.5, -1.], &Device::Cpu)?;
+    /// let a = a.pow(&e)?;
+    /// assert_eq!(candle_core::test_utils::to_vec1_round(&a, 4)?, &[8., 2., 0.1111]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn pow(&self, rhs: &Self) -> Result<Self> {
+        self.same_shape_binary_op(rhs, "pow")?;
+        rhs.mul(&self.log()?)?.exp()
+    }
+
+    // Returns the quotient and remainder of the division, the quotient is rounded towards minus
+    // infinity when `floor` is true and towards zero otherwise.
+    fn div_rem(&self, rhs: &Self, floor: bool, op: &'static str) -> Result<(Self, Self)> {
+        self.same_shape_binary_op(rhs, op)?;
+        if self.dtype().is_float() {
+            let q = self.div(rhs)?;
+            let q = if floor {
+                q.floor()?
+            } else {
+                q.ge(&q.zeros_like()?)?
+                    .where_cond(&q.floor()?, &q.ceil()?)?
+            };
+            let r = self.sub(&q.mul(rhs)?)?;
+            return Ok((q, r));
+        }
+        let zero_divisors = rhs.eq_scalar(0.)?.to_dtype(DType::U32)?.sum_all()?;
+        if zero_divisors.to_scalar::<u32>()? > 0 {
+            crate::bail!(
+                "{op}: division by zero for a tensor of dtype {:?}",
+                self.dtype()
+            )
+        }
+        // The integer division truncates towards zero.
+        let q = self.div(rhs)?;
+        let r = self.sub(&q.mul(rhs)?)?;
+        if !floor || self.dtype() != DType::I64 {
+            return Ok((q, r));
+        }
+        // Flooring only differs when the remainder is not zero and the operands have different
+        // signs, in which case the remainder has the sign of the dividend.
+        let adjust = r
+            .ne_scalar(0.)?
+            .mul(&r.lt_scalar(0.)?.ne(&rhs.lt_scalar(0.)?)?)?
+            .to_dtype(DType::I64)?;
+        let q = q.sub(&adjust)?;
+        let r = r.add(&adjust.mul(rhs)?)?;
+        Ok((q, r))
+    }
+
+    /// Element-wise division rounded towards minus infinity, `floor(self / rhs)`.
+    ///
+    /// For integer dtypes, a zero divisor results in an error. For float dtypes it results in
+    /// infinite or NaN values.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[7i64, -7, 7, -7], &Device::Cpu)?;
+    /// let b = Tensor::new(&[2i64, 2, -2, -2], &Device::Cpu)?;
+    /// assert_eq!(a.floor_divide(&b)?.to_vec1::<i64>()?, &[3, -4, -4, 3]);
+    /// assert_eq!(a.remainder(&b)?.to_vec1::<i64>()?, &[1, 1, -1, -1]);
+    /// assert_eq!(a.fmod(&b)?.to_vec1::<i64>()?, &[1, -1, 1, -1]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn floor_divide(&self, rhs: &Self) -> Result<Self> {
+        Ok(self.div_rem(rhs, true, "floor_divide")?.0)
+    }
+
+    /// Element-wise remainder of the division with the Python sign convention, the result has
+    /// the same sign as `rhs`. This is `self - rhs * floor(self / rhs)`, see `floor_divide` for
+    /// the handling of zero divisors.
+    pub fn remainder(&self, rhs: &Self) -> Result<Self> {
+        Ok(self.div_rem(rhs, true, "remainder")?.1)
+    }
+
+    /// Element-wise remainder of the division with the C sign convention, the result has the
+    /// same sign as `self`. This is `self - rhs * trunc(self / rhs)`, see `floor_divide` for the
+    /// handling of zero divisors.
+    pub fn fmod(&self, rhs: &Self) -> Result<Self> {
+        Ok(self.div_rem(rhs, false, "fmod")?.1)
+    }
+
     fn check_dim(&self, dim: usize, op: &'static str) -> Result<()> {
         if dim >= self.dims().len() {
             Err(Error::DimOutOfRange {
@@ -557,6 +873,53 @@ impl Tensor {
         }
     }
 
+    /// Partitions a contiguous 1D tensor into consecutive non-overlapping views of the given
+    /// sizes, the sizes have to sum to the number of elements of the tensor.
+    ///
+    /// The returned tensors alias the storage of `self`, no data is copied. As tensors are
+    /// immutable this is only observable when the storage is modified, e.g. when splitting a
+    /// variable with [`crate::Var::split_storage`] and setting one of the resulting variables.
+    /// As the views are disjoint, modifying one of them does not change the others.
+    pub fn split_storage(&self, sizes: &[usize]) -> Result<Vec<Self>> {
+        self.split_storage_impl(sizes, false)
+    }
+
+    pub(crate) fn split_storage_impl(
+        &self,
+        sizes: &[usize],
+        is_variable: bool,
+    ) -> Result<Vec<Self>> {
+        let len = self.dims1()?;
+        if !self.is_contiguous() {
+            crate::bail!("split_storage: the tensor has to be contiguous")
+        }
+        let total: usize = sizes.iter().sum();
+        if total != len {
+            crate::bail!("split_storage: sizes {sizes:?} do not sum to the tensor length {len}")
+        }
+        let mut start = 0;
+        let mut views = Vec::with_capacity(sizes.len());
+        for &size in sizes.iter() {
+            let op = if is_variable {
+                BackpropOp::none()
+            } else {
+                BackpropOp::new1(self, |t| Op::Narrow(t, 0, start, size))
+            };
+            let tensor_ = Tensor_ {
+                id: TensorId::new(),
+                storage: self.storage.clone(),
+                layout: self.layout().narrow(0, start, size)?,
+                op,
+                is_variable,
+                dtype: self.dtype,
+                device: self.device.clone(),
+            };
+            views.push(Tensor(Arc::new(tensor_)));
+            start += size
+        }
+        Ok(views)
+    }
+
     /// Split a tensor into the specified number of chunks, this may return less chunks than
     /// specificed.
     pub fn chunk<D: Dim>(&self, chunks: usize, dim: D) -> Result<Vec<Self>> {
@@ -583,6 +946,105 @@ impl Tensor {
         }
     }
 
+    /// Splits a tensor along `dim` into pieces of the given sizes, the sizes have to sum to the
+    /// size of `dim`. The pieces are narrowed views, no data is copied.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let qkv = Tensor::arange(0u32, 6, &Device::Cpu)?;
+    /// let pieces = qkv.split(&[3, 2, 1], 0)?;
+    /// assert_eq!(pieces[1].to_vec1::<u32>()?, &[3, 4]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn split<D: Dim>(&self, sizes: &[usize], dim: D) -> Result<Vec<Self>> {
+        let dim = dim.to_index(self.shape(), "split")?;
+        let size = self.dims()[dim];
+        let total: usize = sizes.iter().sum();
+        if total != size {
+            crate::bail!(
+                "split: sizes {sizes:?} do not sum to the size {size} of dim {dim}, tensor {:?}",
+                self.dims()
+            )
+        }
+        let mut start = 0;
+        let mut pieces = Vec::with_capacity(sizes.len());
+        for &len in sizes.iter() {
+            pieces.push(self.narrow(dim, start, len)?);
+            start += len
+        }
+        Ok(pieces)
+    }
+
+    /// Returns the slices of the tensor along `dim`, with this dimension removed. This is the
+    /// inverse of [`Tensor::stack`]. The slices are views on the storage of `self`, no data is
+    /// copied even when they are not contiguous.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[0u32, 1, 2], [3, 4, 5]], &Device::Cpu)?;
+    /// let cols = t.unbind(1)?;
+    /// assert_eq!(cols.len(), 3);
+    /// assert_eq!(cols[2].to_vec1::<u32>()?, &[2, 5]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn unbind<D: Dim>(&self, dim: D) -> Result<Vec<Self>> {
+        let dim = dim.to_index(self.shape(), "unbind")?;
+        let mut dims = self.dims().to_vec();
+        let mut stride = self.stride().to_vec();
+        let size = dims.remove(dim);
+        stride.remove(dim);
+        (0..size)
+            .map(|i| {
+                // Removing the narrowed dimension of size one is done on the layout directly as
+                // reshape would copy non-contiguous tensors.
+                let slice = self.narrow(dim, i, 1)?;
+                let op = BackpropOp::new1(&slice, Op::Reshape);
+                let layout = Layout::new(
+                    dims.clone().into(),
+                    stride.clone(),
+                    slice.layout.start_offset(),
+                );
+                let tensor_ = Tensor_ {
+                    id: TensorId::new(),
+                    storage: slice.storage.clone(),
+                    layout,
+                    op,
+                    is_variable: false,
+                    dtype: slice.dtype,
+                    device: slice.device.clone(),
+                };
+                Ok(Tensor(Arc::new(tensor_)))
+            })
+            .collect()
+    }
+
+    /// Iterates over slabs of `chunk_size` elements along `dim`, the last slab can be smaller.
+    ///
+    /// The slabs are narrowed views on the original device, e.g. they can be moved to another
+    /// device one at a time when the whole tensor does not fit there. An invalid `dim` or a zero
+    /// `chunk_size` results in a single error item.
+    pub fn chunks_along<D: Dim>(
+        &self,
+        dim: D,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<Self>> + '_ {
+        let dim = dim.to_index(self.shape(), "chunks_along").and_then(|dim| {
+            if chunk_size == 0 {
+                crate::bail!("chunks_along: chunk_size has to be positive")
+            }
+            Ok(dim)
+        });
+        let (dim, size, err) = match dim {
+            Ok(dim) => (dim, self.dims()[dim], None),
+            Err(err) => (0, 0, Some(err)),
+        };
+        err.map(Err).into_iter().chain(
+            (0..size)
+                .step_by(chunk_size.max(1))
+                .map(move |start| self.narrow(dim, start, chunk_size.min(size - start))),
+        )
+    }
+
     /// Returns a new tensor that is a narrowed version of the input, the dimension `dim`
     /// ranges from `start` to `start + len`.
     pub fn narrow<D: Dim>(&self, dim: D, start: usize, len: usize) -> Result<Self> {
@@ -616,6 +1078,33 @@ impl Tensor {
         }
     }
 
+    /// Computes the `n`-th order discrete difference along dimension `dim`, similar to
+    /// `numpy.diff`. The first order difference is given by `out[i] = self[i + 1] - self[i]`,
+    /// higher orders are obtained by applying this recursively.
+    ///
+    /// The returned tensor has `n` less elements than `self` on dimension `dim`, or no element at
+    /// all if `n` is larger than the dimension size.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, 2., 4., 7., 0.], &Device::Cpu)?;
+    /// let d = a.diff(1, 0)?;
+    /// assert_eq!(d.to_vec1::<f32>()?, &[1., 2., 3., -7.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn diff<D: Dim>(&self, n: usize, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "diff")?;
+        let mut xs = self.clone();
+        for _ in 0..n {
+            let len = xs.dim(dim)?;
+            if len <= 1 {
+                return xs.narrow(dim, 0, 0);
+            }
+            xs = (xs.narrow(dim, 1, len - 1)? - xs.narrow(dim, 0, len - 1)?)?;
+        }
+        Ok(xs)
+    }
+
     fn squeeze_dims(self, dims: &[usize]) -> Result<Self> {
         match dims {
             [] => Ok(self),
@@ -732,6 +1221,83 @@ impl Tensor {
         self.sum_impl(mean_dims, false)? * scale
     }
 
+    /// Returns the `p`-norm of the input tensor over the dimensions `dims`, i.e.
+    /// `sum(abs(x)^p)^(1/p)`. When `p` is `f64::INFINITY` (resp. `f64::NEG_INFINITY`) this
+    /// returns the maximum (resp. minimum) of the absolute values. If `keepdim` is true the
+    /// reduced dimensions are kept with a single element, otherwise they are squeezed.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[3f32, -4.], [1., 0.]], &Device::Cpu)?;
+    /// let n = a.norm(2., 1, false)?;
+    /// assert_eq!(n.to_vec1::<f32>()?, &[5., 1.]);
+    /// let n = a.norm(1., (0, 1), false)?;
+    /// assert_eq!(n.to_vec0::<f32>()?, 8.);
+    /// let n = a.norm(f64::INFINITY, 1, true)?;
+    /// assert_eq!(n.to_vec2::<f32>()?, &[[4.], [1.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn norm<D: Dims>(&self, p: f64, dims: D, keepdim: bool) -> Result<Self> {
+        let dims = dims.to_indexes(self.shape(), "norm")?;
+        if p.is_nan() || p == 0. {
+            crate::bail!("norm: unsupported p {p}")
+        }
+        if p.is_infinite() {
+            let mut norm = self.abs()?;
+            for &dim in dims.iter() {
+                norm = if p > 0. {
+                    norm.max_keepdim(dim)?
+                } else {
+                    norm.min_keepdim(dim)?
+                };
+            }
+            return if keepdim {
+                Ok(norm)
+            } else {
+                norm.squeeze_dims(&dims)
+            };
+        }
+        if p == 1. {
+            return self.abs()?.sum_impl(dims, keepdim);
+        }
+        let sum = if p == 2. {
+            self.sqr()?
+        } else {
+            self.abs()?.powf(p)?
+        };
+        let sum = sum.sum_impl(dims, keepdim)?;
+        // The root has an infinite derivative at 0 which would result in a NaN gradient for
+        // zero inputs. The zero sums are replaced by ones before taking the root and the root is
+        // not used for them, so that their gradient is 0 instead.
+        let is_zero = sum.eq_scalar(0.)?;
+        let root = is_zero.where_cond(&sum.ones_like()?, &sum)?;
+        let root = if p == 2. {
+            root.sqrt()?
+        } else {
+            root.powf(1. / p)?
+        };
+        is_zero.where_cond(&sum, &root)
+    }
+
+    /// Divides the input tensor by its `p`-norm over dimension `dim`, the norm being floored to
+    /// `eps` to avoid divisions by zero.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[3f32, -4.], [0., 0.]], &Device::Cpu)?;
+    /// let n = a.normalize(2., 1, 1e-12)?;
+    /// assert_eq!(n.to_vec2::<f32>()?, &[[0.6, -0.8], [0., 0.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn normalize<D: Dim>(&self, p: f64, dim: D, eps: f64) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "normalize")?;
+        let norm = self.norm(p, dim, true)?;
+        let eps = Tensor::new(eps, self.device())?
+            .to_dtype(norm.dtype())?
+            .broadcast_as(norm.shape())?;
+        self.broadcast_div(&norm.maximum(&eps)?)
+    }
+
     /// Gathers the maximum value across the selected dimension. The resulting shape has the same
     /// number of dimensions as the original tensor and the select dimension has a single element.
     pub fn max_keepdim<D: Dim>(&self, dim: D) -> Result<Self> {
@@ -776,6 +1342,9 @@ impl Tensor {
     /// comparison operation is specified by the `op` argument.
     ///
     /// The returned tensor has the same shape as the original tensors and uses `u8` elements.
+    /// Binary operations require both operands to have the same dtype, so the mask has to be
+    /// converted with `to_dtype` before being used in arithmetic with float tensors, or
+    /// `where_scalar` can be used to directly build a float tensor from the mask.
     pub fn cmp(&self, rhs: &Self, op: CmpOp) -> Result<Self> {
         let shape = self.same_shape_binary_op(rhs, "cmp")?;
         let storage = self
@@ -819,13 +1388,216 @@ impl Tensor {
         self.cmp(rhs, CmpOp::Le)
     }
 
+    /// Element-wise comparison with a scalar, the scalar is broadcasted to the shape of the
+    /// tensor. The returned tensor uses `u8` elements.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[0f32, 1.], [2., 3.]], &Device::Cpu)?;
+    /// let mask = a.gt_scalar(1.5)?;
+    /// assert_eq!(mask.to_vec2::<u8>()?, &[[0, 0], [1, 1]]);
+    /// let a = (a * mask.to_dtype(candle_core::DType::F32)?)?;
+    /// assert_eq!(a.to_vec2::<f32>()?, &[[0., 0.], [2., 3.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn cmp_scalar(&self, rhs: f64, op: CmpOp) -> Result<Self> {
+        let dtype = self.dtype();
+        // Integer tensors are compared in f64 when the scalar has no exact integer
+        // representation, e.g. `x >= 2.5` should not be turned into `x >= 2`.
+        let exact = dtype.is_float()
+            || Tensor::new(rhs, &Device::Cpu)?
+                .to_dtype(dtype)?
+                .to_dtype(DType::F64)?
+                .to_scalar::<f64>()?
+                == rhs;
+        let lhs = if exact {
+            self.clone()
+        } else {
+            self.to_dtype(DType::F64)?
+        };
+        let rhs = lhs.full_like_broadcast(rhs)?;
+        lhs.cmp(&rhs, op)
+    }
+
+    /// Element-wise equality with a scalar.
+    pub fn eq_scalar(&self, rhs: f64) -> Result<Self> {
+        self.cmp_scalar(rhs, CmpOp::Eq)
+    }
+
+    /// Element-wise non-equality with a scalar.
+    pub fn ne_scalar(&self, rhs: f64) -> Result<Self> {
+        self.cmp_scalar(rhs, CmpOp::Ne)
+    }
+
+    /// Element-wise comparison with lower-than a scalar.
+    pub fn lt_scalar(&self, rhs: f64) -> Result<Self> {
+        self.cmp_scalar(rhs, CmpOp::Lt)
+    }
+
+    /// Element-wise comparison with greater-than a scalar.
+    pub fn gt_scalar(&self, rhs: f64) -> Result<Self> {
+        self.cmp_scalar(rhs, CmpOp::Gt)
+    }
+
+    /// Element-wise comparison with greater-equal a scalar.
+    pub fn ge_scalar(&self, rhs: f64) -> Result<Self> {
+        self.cmp_scalar(rhs, CmpOp::Ge)
+    }
+
+    /// Element-wise comparison with lower-equal a scalar.
+    pub fn le_scalar(&self, rhs: f64) -> Result<Self> {
+        self.cmp_scalar(rhs, CmpOp::Le)
+    }
+
+    // Turns a `u8` mask into a mask that only contains 0 and 1 values.
+    fn to_bool_mask(&self, op: &'static str) -> Result<Self> {
+        if self.dtype() != DType::U8 {
+            Err(Error::UnexpectedDType {
+                msg: op,
+                expected: DType::U8,
+                got: self.dtype(),
+            }
+            .bt())?
+        }
+        self.ne_scalar(0.)
+    }
+
+    fn logical_binary_op<F>(&self, rhs: &Self, op: &'static str, f: F) -> Result<Self>
+    where
+        F: Fn(&Self, &Self) -> Result<Self>,
+    {
+        let shape = self.shape().broadcast_shape_binary_op(rhs.shape(), op)?;
+        let lhs = self.to_bool_mask(op)?.broadcast_as(&shape)?;
+        let rhs = rhs.to_bool_mask(op)?.broadcast_as(&shape)?;
+        f(&lhs, &rhs)
+    }
+
+    /// Element-wise logical and between two `u8` masks, nonzero values are considered as true.
+    /// The operands are broadcasted and the returned tensor only contains 0 and 1 values.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let padding = Tensor::new(&[[1u8, 1, 0]], &Device::Cpu)?;
+    /// let causal = Tensor::new(&[[1u8, 0, 0], [1, 1, 0], [1, 1, 1]], &Device::Cpu)?;
+    /// let mask = causal.logical_and(&padding)?;
+    /// assert_eq!(mask.to_vec2::<u8>()?, &[[1, 0, 0], [1, 1, 0], [1, 1, 0]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn logical_and(&self, rhs: &Self) -> Result<Self> {
+        self.logical_binary_op(rhs, "logical_and", |l, r| l.minimum(r))
+    }
+
+    /// Element-wise logical or between two `u8` masks, see [`Tensor::logical_and`].
+    pub fn logical_or(&self, rhs: &Self) -> Result<Self> {
+        self.logical_binary_op(rhs, "logical_or", |l, r| l.maximum(r))
+    }
+
+    /// Element-wise logical xor between two `u8` masks, see [`Tensor::logical_and`].
+    pub fn logical_xor(&self, rhs: &Self) -> Result<Self> {
+        self.logical_binary_op(rhs, "logical_xor", |l, r| l.ne(r))
+    }
+
+    /// Element-wise logical not of a `u8` mask, the returned tensor contains 1 where the input
+    /// is zero and 0 elsewhere.
+    pub fn logical_not(&self) -> Result<Self> {
+        self.to_bool_mask("logical_not")?.eq_scalar(0.)
+    }
+
+    /// Element-wise check that two tensors are close, i.e. that `|self - rhs| <= atol + rtol *
+    /// |rhs|`. The operands are broadcasted and compared as `f64` values, the returned tensor
+    /// uses `u8` elements.
+    ///
+    /// As in NumPy, infinite values are only close to themselves and NaN values are not close
+    /// to anything unless `equal_nan` is set, in which case two NaN values are close.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, 2., f32::NAN], &Device::Cpu)?;
+    /// let b = Tensor::new(&[1f32, 2.1, f32::NAN], &Device::Cpu)?;
+    /// assert_eq!(a.isclose(&b, 1e-5, 1e-8, false)?.to_vec1::<u8>()?, &[1, 0, 0]);
+    /// assert_eq!(a.isclose(&b, 0.1, 0., true)?.to_vec1::<u8>()?, &[1, 1, 1]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn isclose(&self, rhs: &Self, rtol: f64, atol: f64, equal_nan: bool) -> Result<Self> {
+        let shape = self
+            .shape()
+            .broadcast_shape_binary_op(rhs.shape(), "isclose")?;
+        let lhs = self.to_dtype(DType::F64)?.broadcast_as(&shape)?;
+        let rhs = rhs.to_dtype(DType::F64)?.broadcast_as(&shape)?;
+        let tol = rhs.abs()?.affine(rtol, atol)?;
+        let close = (&lhs - &rhs)?.abs()?.le(&tol)?;
+        // The tolerance check only applies to finite values, infinite values have to be equal.
+        let finite = lhs
+            .abs()?
+            .lt_scalar(f64::INFINITY)?
+            .logical_and(&rhs.abs()?.lt_scalar(f64::INFINITY)?)?;
+        let close = close.logical_and(&finite)?.logical_or(&lhs.eq(&rhs)?)?;
+        if equal_nan {
+            let both_nan = lhs.ne(&lhs)?.logical_and(&rhs.ne(&rhs)?)?;
+            close.logical_or(&both_nan)
+        } else {
+            Ok(close)
+        }
+    }
+
+    /// Returns true if all the elements of the two tensors are close, see [`Tensor::isclose`].
+    /// NaN values are never considered close.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[1f32, 2.0001], &Device::Cpu)?;
+    /// assert!(!a.allclose(&b, 1e-5, 1e-8)?);
+    /// assert!(a.narrow(0, 0, 1)?.allclose(&b, 1e-3, 0.)?);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn allclose(&self, rhs: &Self, rtol: f64, atol: f64) -> Result<bool> {
+        let not_close = self.isclose(rhs, rtol, atol, false)?.logical_not()?;
+        let not_close = not_close.to_dtype(DType::U32)?.sum_all()?;
+        Ok(not_close.to_scalar::<u32>()? == 0)
+    }
+
+    /// The largest absolute difference between the elements of the two tensors, the operands
+    /// are broadcasted and compared as `f64` values. Equal values, including infinite ones, have
+    /// no difference. As in [`Tensor::allclose`] NaN values are not equal to anything, so NaN is
+    /// returned if any of the two tensors contains a NaN.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[1f32, 2.5], &Device::Cpu)?;
+    /// assert_eq!(a.max_abs_diff(&b)?, 2.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn max_abs_diff(&self, rhs: &Self) -> Result<f64> {
+        let equal = self.isclose(rhs, 0., 0., false)?;
+        let lhs = self.to_dtype(DType::F64)?.broadcast_as(equal.shape())?;
+        let rhs = rhs.to_dtype(DType::F64)?.broadcast_as(equal.shape())?;
+        let diff = (lhs - rhs)?.abs()?;
+        let diff = equal.where_cond(&diff.zeros_like()?, &diff)?;
+        let mut max = 0f64;
+        for diff in diff.flatten_to_vec::<f64>()? {
+            if diff.is_nan() {
+                return Ok(f64::NAN);
+            }
+            max = max.max(diff)
+        }
+        Ok(max)
+    }
+
     /// Upsample the input tensor to the `(target_h, target_w)` size, taking the value of the
     /// nearest element.
     ///
     /// The input tensor should have four dimensions, `(batch, channels, h, w)`, the returned
     /// tensor also has four dimensions, `(batch, channels, target_h, target_w)`.
     pub fn upsample_nearest2d(&self, target_h: usize, target_w: usize) -> Result<Self> {
-        let (n, c, _h, _w) = self.dims4()?;
+        let (n, c, h, w) = self.dims4()?;
+        if (h == 0 && target_h != 0) || (w == 0 && target_w != 0) {
+            crate::bail!(
+                "upsample-nearest2d: cannot upsample {:?} to ({target_h}, {target_w})",
+                self.shape()
+            )
+        }
         let op = BackpropOp::new1(self, Op::UpsampleNearest2D);
         let storage = self
             .storage()
@@ -833,6 +1605,213 @@ impl Tensor {
         Ok(from_storage(storage, (n, c, target_h, target_w), op, false))
     }
 
+    /// Upsample the input tensor to the `target_l` length, taking the value of the nearest
+    /// element.
+    ///
+    /// The input tensor should have three dimensions, `(batch, channels, l)`, the returned
+    /// tensor also has three dimensions, `(batch, channels, target_l)`.
+    pub fn upsample_nearest1d(&self, target_l: usize) -> Result<Self> {
+        let (n, c, _l) = self.dims3()?;
+        self.unsqueeze(2)?
+            .upsample_nearest2d(1, target_l)?
+            .reshape((n, c, target_l))
+    }
+
+    /// Upsample the input tensor to the `(target_d, target_h, target_w)` size, taking the value
+    /// of the nearest element.
+    ///
+    /// The input tensor should have five dimensions, `(batch, channels, d, h, w)`, the returned
+    /// tensor also has five dimensions, `(batch, channels, target_d, target_h, target_w)`.
+    pub fn upsample_nearest3d(
+        &self,
+        target_d: usize,
+        target_h: usize,
+        target_w: usize,
+    ) -> Result<Self> {
+        let (n, c, d, h, w) = self.dims5()?;
+        // The two last dimensions are upsampled with the 2d kernel, the depth dimension is then
+        // expanded by selecting the nearest slices.
+        let ids = upsample_nearest_indexes(d, target_d)?;
+        let ids = Tensor::from_vec(ids, target_d, self.device())?;
+        self.reshape((n, c * d, h, w))?
+            .upsample_nearest2d(target_h, target_w)?
+            .reshape((n, c, d, target_h * target_w))?
+            .index_select(&ids, 2)?
+            .reshape((n, c, target_d, target_h, target_w))
+    }
+
+    /// Upsample the input tensor to the `(target_h, target_w)` size using bilinear interpolation.
+    ///
+    /// The input tensor should have four dimensions, `(batch, channels, h, w)`, the returned
+    /// tensor also has four dimensions, `(batch, channels, target_h, target_w)`. When
+    /// `align_corners` is true the corner pixels of the input and output are aligned, otherwise
+    /// the pixel centers are used as in PyTorch `align_corners=False`.
+    pub fn upsample_bilinear2d(
+        &self,
+        target_h: usize,
+        target_w: usize,
+        align_corners: bool,
+    ) -> Result<Self> {
+        self.upsample_interpolate2d(target_h, target_w, align_corners, false)
+    }
+
+    /// Upsample the input tensor to the `(target_h, target_w)` size using bicubic interpolation,
+    /// see `upsample_bilinear2d` for the meaning of `align_corners`.
+    pub fn upsample_bicubic2d(
+        &self,
+        target_h: usize,
+        target_w: usize,
+        align_corners: bool,
+    ) -> Result<Self> {
+        self.upsample_interpolate2d(target_h, target_w, align_corners, true)
+    }
+
+    // The interpolation is separable so it is applied as two matmuls with the interpolation
+    // weights, one per spatial dimension. The backward pass then distributes the gradients
+    // according to the same weights.
+    fn upsample_interpolate2d(
+        &self,
+        target_h: usize,
+        target_w: usize,
+        align_corners: bool,
+        cubic: bool,
+    ) -> Result<Self> {
+        let (n, c, h, w) = self.dims4()?;
+        let weights = |in_size, out_size| {
+            let weights = interpolation_weights(in_size, out_size, align_corners, cubic);
+            Tensor::from_vec(weights, (in_size, out_size), self.device())?.to_dtype(self.dtype())
+        };
+        let xs = self
+            .reshape((n * c * h, w))?
+            .matmul(&weights(w, target_w)?)?
+            .reshape((n * c, h, target_w))?
+            .transpose(1, 2)?
+            .contiguous()?
+            .reshape((n * c * target_w, h))?
+            .matmul(&weights(h, target_h)?)?;
+        xs.reshape((n, c, target_w, target_h))?.transpose(2, 3)
+    }
+
+    /// 1D average pooling over an input tensor with multiple channels.
+    ///
+    /// The input tensor should have three dimensions, `(batch, channels, l)`, the returned
+    /// tensor also has three dimensions, `(batch, channels, l')`. The pooling is performed on
+    /// the last dimension using a kernel of size `sz`. The returned element is the average
+    /// value over the kernel window.
+    pub fn avg_pool1d(&self, sz: usize) -> Result<Self> {
+        self.avg_pool1d_with_stride(sz, sz)
+    }
+
+    /// Same as `avg_pool1d` but with a `stride` that can be set to a value different from the
+    /// kernel size.
+    ///
+    /// When the stride differs from the kernel size, the windows are gathered explicitly so that
+    /// the backward pass is supported.
+    pub fn avg_pool1d_with_stride(&self, kernel_size: usize, stride: usize) -> Result<Self> {
+        let (n, c, l_out) = self.pool1d_out_dims(kernel_size, stride, "avg_pool1d")?;
+        let xs = self.unsqueeze(2)?;
+        let xs = if kernel_size == stride {
+            xs.avg_pool2d_with_stride((1, kernel_size), (1, stride))?
+        } else {
+            xs.avg_pool2d_with_padding((1, kernel_size), (1, stride), (0, 0), false, false)?
+        };
+        xs.reshape((n, c, l_out))
+    }
+
+    /// 1D max pooling over an input tensor with multiple channels.
+    ///
+    /// The input tensor should have three dimensions, `(batch, channels, l)`, the returned
+    /// tensor also has three dimensions, `(batch, channels, l')`. The pooling is performed on
+    /// the last dimension using a kernel of size `sz`, the returned element is the maximum
+    /// value over the kernel window.
+    pub fn max_pool1d(&self, sz: usize) -> Result<Self> {
+        self.max_pool1d_with_stride(sz, sz)
+    }
+
+    /// Same as `max_pool1d` but with a `stride` that can be set to a value different from the
+    /// kernel size.
+    ///
+    /// When the stride differs from the kernel size, the windows are gathered explicitly so that
+    /// the gradient flows back to the position of the maximum of each window, the first one in
+    /// case of ties.
+    pub fn max_pool1d_with_stride(&self, kernel_size: usize, stride: usize) -> Result<Self> {
+        let (n, c, l_out) = self.pool1d_out_dims(kernel_size, stride, "max_pool1d")?;
+        let xs = self.unsqueeze(2)?;
+        let xs = if kernel_size == stride {
+            xs.max_pool2d_with_stride((1, kernel_size), (1, stride))?
+        } else {
+            xs.max_pool2d_with_padding((1, kernel_size), (1, stride), (0, 0), false)?
+        };
+        xs.reshape((n, c, l_out))
+    }
+
+    /// 1D average pooling with support for implicit zero padding on both sides of the last
+    /// dimension, similar to PyTorch `AvgPool1d`. The input tensor should have three dimensions,
+    /// `(batch, channels, l)`.
+    ///
+    /// `ceil_mode` and `count_include_pad` have the same meaning as for
+    /// [`Tensor::avg_pool2d_with_padding`], the padding can be at most half the kernel size.
+    pub fn avg_pool1d_with_padding(
+        &self,
+        kernel_size: usize,
+        stride: usize,
+        padding: usize,
+        ceil_mode: bool,
+        count_include_pad: bool,
+    ) -> Result<Self> {
+        let (n, c, _l) = self.dims3()?;
+        let xs = self.unsqueeze(2)?.avg_pool2d_with_padding(
+            (1, kernel_size),
+            (1, stride),
+            (0, padding),
+            ceil_mode,
+            count_include_pad,
+        )?;
+        let l_out = xs.dim(3)?;
+        xs.reshape((n, c, l_out))
+    }
+
+    /// 1D max pooling with support for implicit padding on both sides of the last dimension,
+    /// similar to PyTorch `MaxPool1d`. The input tensor should have three dimensions,
+    /// `(batch, channels, l)`, the padded positions are never selected.
+    ///
+    /// `ceil_mode` has the same meaning as for [`Tensor::max_pool2d_with_padding`], the padding
+    /// can be at most half the kernel size.
+    pub fn max_pool1d_with_padding(
+        &self,
+        kernel_size: usize,
+        stride: usize,
+        padding: usize,
+        ceil_mode: bool,
+    ) -> Result<Self> {
+        let (n, c, _l) = self.dims3()?;
+        let xs = self.unsqueeze(2)?.max_pool2d_with_padding(
+            (1, kernel_size),
+            (1, stride),
+            (0, padding),
+            ceil_mode,
+        )?;
+        let l_out = xs.dim(3)?;
+        xs.reshape((n, c, l_out))
+    }
+
+    fn pool1d_out_dims(
+        &self,
+        kernel_size: usize,
+        stride: usize,
+        op: &'static str,
+    ) -> Result<(usize, usize, usize)> {
+        let (n, c, l) = self.dims3()?;
+        if kernel_size == 0 || stride == 0 {
+            crate::bail!("{op}: kernel size ({kernel_size}) and stride ({stride}) must be positive")
+        }
+        if kernel_size > l {
+            crate::bail!("{op}: kernel size ({kernel_size}) is larger than the input length ({l})")
+        }
+        // https://pytorch.org/docs/stable/generated/torch.nn.AvgPool1d.html
+        Ok((n, c, (l - kernel_size) / stride + 1))
+    }
+
     /// 2D average pooling over an input tensor with multiple channels.
     ///
     /// The input tensor should have four dimensions, `(batch, channels, h, w)`, the returned
@@ -889,18 +1868,236 @@ impl Tensor {
         let kernel_size = kernel_size.to_usize2();
         let stride = stride.to_usize2();
         let (n, c, h, w) = self.dims4()?;
-        // https://pytorch.org/docs/stable/generated/torch.nn.MaxPool2d.html#torch.nn.MaxPool2d
-        let h_out = (h - kernel_size.0) / stride.0 + 1;
-        let w_out = (w - kernel_size.1) / stride.1 + 1;
-        let op = BackpropOp::new1(self, |arg| Op::MaxPool2D {
-            arg,
-            kernel_size,
-            stride,
-        });
-        let storage = self
-            .storage()
-            .max_pool2d(self.layout(), kernel_size, stride)?;
-        Ok(from_storage(storage, (n, c, h_out, w_out), op, false))
+        // https://pytorch.org/docs/stable/generated/torch.nn.MaxPool2d.html#torch.nn.MaxPool2d
+        let h_out = (h - kernel_size.0) / stride.0 + 1;
+        let w_out = (w - kernel_size.1) / stride.1 + 1;
+        let op = BackpropOp::new1(self, |arg| Op::MaxPool2D {
+            arg,
+            kernel_size,
+            stride,
+        });
+        let storage = self
+            .storage()
+            .max_pool2d(self.layout(), kernel_size, stride)?;
+        Ok(from_storage(storage, (n, c, h_out, w_out), op, false))
+    }
+
+    /// Same as `max_pool2d_with_stride` but also returns the position of the maximum value for
+    /// each window.
+    ///
+    /// The returned indices are a u32 tensor with the same shape as the pooled values, each
+    /// index is the flattened position `h * w_in + w` of the maximum in its `(h_in, w_in)` input
+    /// plane. When several elements of a window share the maximum value, the first one is used.
+    ///
+    /// The pooling is performed by gathering the windows so the gradient only flows to the
+    /// selected positions.
+    pub fn max_pool2d_with_indices<T: crate::ToUsize2>(
+        &self,
+        kernel_size: T,
+        stride: T,
+    ) -> Result<(Self, Self)> {
+        let windows = self.pool2d_windows(
+            kernel_size.to_usize2(),
+            stride.to_usize2(),
+            (0, 0),
+            false,
+            f64::NEG_INFINITY,
+            "max_pool2d_with_indices",
+        )?;
+        let (n, c, h_out, w_out) = windows.out_dims;
+        let argmax = windows.values.argmax_keepdim(3)?;
+        let values = windows.values.gather(&argmax, 3)?;
+        let indices = windows
+            .ids
+            .broadcast_as(windows.values.shape())?
+            .contiguous()?
+            .gather(&argmax, 3)?;
+        Ok((
+            values.reshape((n, c, h_out, w_out))?,
+            indices.reshape((n, c, h_out, w_out))?,
+        ))
+    }
+
+    /// 2D average pooling with support for implicit zero padding on both sides of the two last
+    /// dimensions, similar to PyTorch `AvgPool2d`.
+    ///
+    /// When `ceil_mode` is true, the output size is computed using a ceil rather than a floor so
+    /// that partial windows at the end of the input are kept, windows that would start in the
+    /// right padding are dropped. When `count_include_pad` is true, the padded positions are
+    /// included in the divisor used to compute the average.
+    pub fn avg_pool2d_with_padding<T: crate::ToUsize2>(
+        &self,
+        kernel_size: T,
+        stride: T,
+        padding: T,
+        ceil_mode: bool,
+        count_include_pad: bool,
+    ) -> Result<Self> {
+        let windows = self.pool2d_windows(
+            kernel_size.to_usize2(),
+            stride.to_usize2(),
+            padding.to_usize2(),
+            ceil_mode,
+            0.,
+            "avg_pool2d_with_padding",
+        )?;
+        let (n, c, h_out, w_out) = windows.out_dims;
+        let divisor = if count_include_pad {
+            windows.padded_counts
+        } else {
+            windows.input_counts
+        };
+        let divisor = divisor.into_iter().map(|v| v as f64).collect::<Vec<_>>();
+        let divisor =
+            Tensor::from_vec(divisor, (h_out * w_out, 1), self.device())?.to_dtype(self.dtype())?;
+        windows
+            .values
+            .sum_keepdim(3)?
+            .broadcast_div(&divisor)?
+            .reshape((n, c, h_out, w_out))
+    }
+
+    /// 2D max pooling with support for implicit padding on both sides of the two last
+    /// dimensions, similar to PyTorch `MaxPool2d`. The padded positions are never selected.
+    ///
+    /// When `ceil_mode` is true, the output size is computed using a ceil rather than a floor so
+    /// that partial windows at the end of the input are kept, windows that would start in the
+    /// right padding are dropped.
+    pub fn max_pool2d_with_padding<T: crate::ToUsize2>(
+        &self,
+        kernel_size: T,
+        stride: T,
+        padding: T,
+        ceil_mode: bool,
+    ) -> Result<Self> {
+        let windows = self.pool2d_windows(
+            kernel_size.to_usize2(),
+            stride.to_usize2(),
+            padding.to_usize2(),
+            ceil_mode,
+            f64::NEG_INFINITY,
+            "max_pool2d_with_padding",
+        )?;
+        let (n, c, h_out, w_out) = windows.out_dims;
+        let argmax = windows.values.argmax_keepdim(3)?;
+        windows
+            .values
+            .gather(&argmax, 3)?
+            .reshape((n, c, h_out, w_out))
+    }
+
+    // Gathers the pooling windows of a `(batch, channels, h, w)` tensor. Padded positions get
+    // the value `pad_value`.
+    fn pool2d_windows(
+        &self,
+        (k_h, k_w): (usize, usize),
+        (s_h, s_w): (usize, usize),
+        (p_h, p_w): (usize, usize),
+        ceil_mode: bool,
+        pad_value: f64,
+        op: &'static str,
+    ) -> Result<Pool2dWindows> {
+        // https://pytorch.org/docs/stable/generated/torch.nn.MaxPool2d.html
+        fn out_size(len: usize, k: usize, s: usize, p: usize, ceil_mode: bool) -> Option<usize> {
+            if k == 0 || s == 0 || p > k / 2 || len + 2 * p < k {
+                return None;
+            }
+            let num = len + 2 * p - k;
+            let out = if ceil_mode {
+                let out = (num + s - 1) / s + 1;
+                // The last window must start within the input or the left padding.
+                if (out - 1) * s >= len + p {
+                    out - 1
+                } else {
+                    out
+                }
+            } else {
+                num / s + 1
+            };
+            Some(out)
+        }
+        let (n, c, h, w) = self.dims4()?;
+        let (h_out, w_out) = match (
+            out_size(h, k_h, s_h, p_h, ceil_mode),
+            out_size(w, k_w, s_w, p_w, ceil_mode),
+        ) {
+            (Some(h_out), Some(w_out)) => (h_out, w_out),
+            _ => crate::bail!(
+                "{op}: invalid kernel size {:?}, stride {:?} or padding {:?} for input {:?}",
+                (k_h, k_w),
+                (s_h, s_w),
+                (p_h, p_w),
+                self.shape()
+            ),
+        };
+        // Padded positions point at an extra element appended to each flattened plane.
+        let pad_id = (h * w) as u32;
+        let mut ids = Vec::with_capacity(h_out * w_out * k_h * k_w);
+        let mut input_counts = Vec::with_capacity(h_out * w_out);
+        let mut padded_counts = Vec::with_capacity(h_out * w_out);
+        for i_h in 0..h_out {
+            for i_w in 0..w_out {
+                let mut input_count = 0;
+                for j_h in 0..k_h {
+                    for j_w in 0..k_w {
+                        let (x_h, x_w) = (i_h * s_h + j_h, i_w * s_w + j_w);
+                        if x_h < p_h || x_h >= h + p_h || x_w < p_w || x_w >= w + p_w {
+                            ids.push(pad_id)
+                        } else {
+                            input_count += 1;
+                            ids.push(((x_h - p_h) * w + x_w - p_w) as u32)
+                        }
+                    }
+                }
+                // Only the positions in the input or in the padding are counted, not the ones
+                // that go past the right padding in ceil mode.
+                let padded_h = usize::min(i_h * s_h + k_h, h + 2 * p_h) - i_h * s_h;
+                let padded_w = usize::min(i_w * s_w + k_w, w + 2 * p_w) - i_w * s_w;
+                input_counts.push(input_count);
+                padded_counts.push(padded_h * padded_w);
+            }
+        }
+        let ids = Tensor::from_vec(ids, (h_out * w_out, k_h * k_w), self.device())?;
+        let pad = (Tensor::ones((n, c, 1), self.dtype(), self.device())? * pad_value)?;
+        let values = Tensor::cat(&[&self.reshape((n, c, h * w))?, &pad], 2)?
+            .contiguous()?
+            .index_select(&ids.flatten_all()?, 2)?
+            .reshape((n, c, h_out * w_out, k_h * k_w))?;
+        Ok(Pool2dWindows {
+            values,
+            ids,
+            out_dims: (n, c, h_out, w_out),
+            input_counts,
+            padded_counts,
+        })
+    }
+
+    /// Computes a partial inverse of `max_pool2d_with_indices`, the values of `self` are placed
+    /// at the positions given by `indices` in a zero initialized tensor of shape
+    /// `(batch, channels, target_h, target_w)`.
+    ///
+    /// `self` and `indices` should both have shape `(batch, channels, h, w)`. If multiple values
+    /// point at the same position, which can happen when the pooling windows overlap, they get
+    /// summed.
+    pub fn max_unpool2d(&self, indices: &Self, target_hw: (usize, usize)) -> Result<Self> {
+        let (n, c, h, w) = self.dims4()?;
+        if indices.shape() != self.shape() {
+            Err(Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: indices.shape().clone(),
+                op: "max_unpool2d",
+            }
+            .bt())?
+        }
+        let (target_h, target_w) = target_hw;
+        let zeros = Tensor::zeros((n, c, target_h * target_w), self.dtype(), self.device())?;
+        zeros
+            .scatter_add(
+                &indices.reshape((n, c, h * w))?,
+                &self.reshape((n, c, h * w))?,
+                2,
+            )?
+            .reshape((n, c, target_h, target_w))
     }
 
     /// Returns the matrix-multiplication of the input tensor with the other provided tensor.
@@ -975,6 +2172,51 @@ impl Tensor {
         }
     }
 
+    /// Returns the outer product of two vectors, if `self` has `m` elements and `rhs` has `n`
+    /// elements the result has shape `(m, n)`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+    /// let b = Tensor::new(&[3f32, 4., 5.], &Device::Cpu)?;
+    /// let c = a.outer(&b)?;
+    /// assert_eq!(c.to_vec2::<f32>()?, &[[3., 4., 5.], [6., 8., 10.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn outer(&self, rhs: &Self) -> Result<Self> {
+        for t in [self, rhs] {
+            if t.rank() != 1 {
+                Err(Error::UnexpectedNumberOfDims {
+                    expected: 1,
+                    got: t.rank(),
+                    shape: t.shape().clone(),
+                }
+                .bt())?
+            }
+        }
+        self.unsqueeze(1)?.broadcast_mul(&rhs.unsqueeze(0)?)
+    }
+
+    /// Returns the Kronecker product of two matrixes. If `self` has shape `(a, b)` and `rhs` has
+    /// shape `(c, d)`, the result has shape `(a * c, b * d)` and is made of `a * b` blocks, the
+    /// block at position `(i, j)` being `self[i, j] * rhs`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[[1f32, 0.], [0., 1.]], &Device::Cpu)?;
+    /// let c = a.kron(&b)?;
+    /// assert_eq!(c.to_vec2::<f32>()?, &[[1., 0., 2., 0.], [0., 1., 0., 2.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn kron(&self, rhs: &Self) -> Result<Self> {
+        let (a, b) = self.dims2()?;
+        let (c, d) = rhs.dims2()?;
+        self.reshape((a, 1, b, 1))?
+            .broadcast_mul(&rhs.reshape((1, c, 1, d))?)?
+            .reshape((a * c, b * d))
+    }
+
     /// Returns a tensor with the same shape as the input tensor, the values are taken from
     /// `on_true` if the input tensor value is not zero, and `on_false` at the positions where the
     /// input tensor is equal to zero.
@@ -992,6 +2234,27 @@ impl Tensor {
         Ok(from_storage(storage, shape, op, false))
     }
 
+    /// Similar to `where_cond` with scalar values, the returned tensor uses `dtype` elements and
+    /// contains `on_true` where the input tensor is not zero and `on_false` elsewhere. This
+    /// can be used to turn a comparison mask into a tensor with the dtype of the values it
+    /// applies to.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[0f32, 1., 2., 3.], &Device::Cpu)?;
+    /// let a = a.lt_scalar(2.)?.where_scalar(f64::NEG_INFINITY, 0., a.dtype())?;
+    /// assert_eq!(a.to_vec1::<f32>()?, &[f32::NEG_INFINITY, f32::NEG_INFINITY, 0., 0.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn where_scalar(&self, on_true: f64, on_false: f64, dtype: DType) -> Result<Self> {
+        let scalar = |v: f64| {
+            Tensor::zeros((), dtype, self.device())?
+                .affine(1., v)?
+                .broadcast_as(self.shape())
+        };
+        self.where_cond(&scalar(on_true)?, &scalar(on_false)?)
+    }
+
     /// Returns a tensor with the values from the `self` tensor at the index corresponding to the
     /// values hold in the `ids` tensor.
     ///
@@ -1222,6 +2485,7 @@ impl Tensor {
         match &*self.storage() {
             Storage::Cpu(storage) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -1252,6 +2516,7 @@ impl Tensor {
         match &*self.storage() {
             Storage::Cpu(storage) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
@@ -1292,7 +2557,111 @@ impl Tensor {
         match &*self.storage() {
             Storage::Cpu(storage) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+        }
+    }
+
+    /// Returns the data contained in a 4D tensor.
+    pub fn to_vec4<S: crate::WithDType>(&self) -> Result<Vec<Vec<Vec<Vec<S>>>>> {
+        let (dim1, dim2, dim3, dim4) = self.dims4()?;
+        let from_cpu_storage = |cpu_storage: &crate::CpuStorage| {
+            let data = S::cpu_storage_as_slice(cpu_storage)?;
+            let mut all = vec![];
+            match self.layout.contiguous_offsets() {
+                Some((o1, o2)) => {
+                    let data = &data[o1..o2];
+                    let dim34 = dim3 * dim4;
+                    let dim234 = dim2 * dim34;
+                    for idx1 in 0..dim1 {
+                        let data = &data[idx1 * dim234..(idx1 + 1) * dim234];
+                        let mut top_rows = vec![];
+                        for idx2 in 0..dim2 {
+                            let data = &data[idx2 * dim34..(idx2 + 1) * dim34];
+                            let mut rows = vec![];
+                            for idx3 in 0..dim3 {
+                                rows.push(data[idx3 * dim4..(idx3 + 1) * dim4].to_vec())
+                            }
+                            top_rows.push(rows);
+                        }
+                        all.push(top_rows);
+                    }
+                }
+                None => {
+                    let mut src_index = self.strided_index();
+                    for _idx in 0..dim1 {
+                        let mut top_rows = vec![];
+                        for _jdx in 0..dim2 {
+                            let mut rows = vec![];
+                            for _kdx in 0..dim3 {
+                                let row =
+                                    (0..dim4).map(|_| data[src_index.next().unwrap()]).collect();
+                                rows.push(row)
+                            }
+                            top_rows.push(rows);
+                        }
+                        all.push(top_rows);
+                    }
+                    assert!(src_index.next().is_none());
+                }
+            }
+            Ok(all)
+        };
+        match &*self.storage() {
+            Storage::Cpu(storage) => from_cpu_storage(storage),
+            Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+        }
+    }
+
+    /// Returns the data contained in a tensor of any rank as a flat vector, the elements are in
+    /// row-major order whatever the strides of the tensor.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::arange(0u32, 6, &Device::Cpu)?.reshape((2, 3))?;
+    /// assert_eq!(a.flatten_to_vec::<u32>()?, &[0, 1, 2, 3, 4, 5]);
+    /// assert_eq!(a.t()?.flatten_to_vec::<u32>()?, &[0, 3, 1, 4, 2, 5]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn flatten_to_vec<S: crate::WithDType>(&self) -> Result<Vec<S>> {
+        let from_cpu_storage = |cpu_storage: &crate::CpuStorage| {
+            let data = S::cpu_storage_as_slice(cpu_storage)?;
+            let data = match self.layout.contiguous_offsets() {
+                Some((o1, o2)) => data[o1..o2].to_vec(),
+                None => self.strided_index().map(|i| data[i]).collect(),
+            };
+            Ok::<Vec<_>, Error>(data)
+        };
+        match &*self.storage() {
+            Storage::Cpu(storage) => from_cpu_storage(storage),
+            Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+            Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
+        }
+    }
+
+    /// Returns the data contained in a tensor as nested vectors, the nesting depth of the output
+    /// type has to match the rank of the tensor, e.g. `Vec<Vec<Vec<Vec<Vec<f32>>>>>` for a 5D
+    /// tensor.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::arange(0u32, 6, &Device::Cpu)?.reshape((1, 2, 3))?;
+    /// let v: Vec<Vec<Vec<u32>>> = a.to_vec_nd()?;
+    /// assert_eq!(v, [[[0, 1, 2], [3, 4, 5]]]);
+    /// assert!(a.to_vec_nd::<Vec<Vec<u32>>>().is_err());
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn to_vec_nd<V: NestedVec>(&self) -> Result<V> {
+        if self.rank() != V::RANK {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: V::RANK,
+                got: self.rank(),
+                shape: self.shape().clone(),
+            }
+            .bt())?
         }
+        let data = self.flatten_to_vec::<V::Elem>()?;
+        Ok(V::from_flat(&mut data.into_iter(), self.dims()))
     }
 
     /// The dtype for the elements stored in the input tensor.
@@ -1478,6 +2847,48 @@ impl Tensor {
         self.transpose(rank - 2, rank - 1)
     }
 
+    /// Swaps the two last dimensions of the input, an alias for [`Tensor::t`] for inputs of any
+    /// rank greater than one.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, DType, Device};
+    /// let tensor = Tensor::zeros((2, 3, 4), DType::F32, &Device::Cpu)?;
+    /// assert_eq!(tensor.mT()?.dims(), &[2, 4, 3]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    #[allow(non_snake_case)]
+    pub fn mT(&self) -> Result<Tensor> {
+        self.t()
+    }
+
+    /// The sum of the main diagonal of the matrices in the two last dimensions, the returned
+    /// tensor has the leading dimensions of the input. Non-square matrices use the diagonal
+    /// starting at the top left element.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let tensor = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// assert_eq!(tensor.trace()?.to_scalar::<f32>()?, 5.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn trace(&self) -> Result<Tensor> {
+        let rank = self.rank();
+        if rank < 2 {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 2,
+                got: rank,
+                shape: self.shape().clone(),
+            }
+            .bt())?
+        }
+        let (n, m) = (self.dims()[rank - 2], self.dims()[rank - 1]);
+        let diag: Vec<u32> = (0..n.min(m)).map(|i| (i * (m + 1)) as u32).collect();
+        let diag = Tensor::new(diag, self.device())?;
+        self.flatten_from(rank - 2)?
+            .index_select(&diag, rank - 2)?
+            .sum(rank - 2)
+    }
+
     /// Returns a tensor that is a transposed version of the input, the given dimensions are
     /// swapped.
     pub fn transpose<D1: Dim, D2: Dim>(&self, dim1: D1, dim2: D2) -> Result<Tensor> {
@@ -1532,6 +2943,154 @@ impl Tensor {
         Ok(Tensor(Arc::new(tensor_)))
     }
 
+    /// Moves the dimension `src` to position `dst`, the other dimensions keep their relative
+    /// order.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, DType, Device, D};
+    /// let tensor = Tensor::zeros((2, 3, 4, 5), DType::F32, &Device::Cpu)?;
+    /// assert_eq!(tensor.movedim(1, D::Minus1)?.dims(), &[2, 4, 5, 3]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn movedim<D1: Dim, D2: Dim>(&self, src: D1, dst: D2) -> Result<Tensor> {
+        let src = src.to_index(self.shape(), "movedim")?;
+        let dst = dst.to_index(self.shape(), "movedim")?;
+        self.movedim_multi(vec![src], vec![dst])
+    }
+
+    /// Moves the dimensions `src` to the positions `dst`, the other dimensions keep their
+    /// relative order.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, DType, Device};
+    /// let tensor = Tensor::zeros((2, 3, 4, 5), DType::F32, &Device::Cpu)?;
+    /// assert_eq!(tensor.movedim_multi((0, 1), (3, 2))?.dims(), &[4, 5, 3, 2]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn movedim_multi<D1: Dims, D2: Dims>(&self, src: D1, dst: D2) -> Result<Tensor> {
+        let src = src.to_indexes(self.shape(), "movedim")?;
+        let dst = dst.to_indexes(self.shape(), "movedim")?;
+        if src.len() != dst.len() {
+            crate::bail!(
+                "movedim: src {src:?} and dst {dst:?} have different lengths, tensor {:?}",
+                self.dims()
+            )
+        }
+        let mut perm: Vec<usize> = (0..self.rank()).filter(|d| !src.contains(d)).collect();
+        let mut moves: Vec<(usize, usize)> = dst.into_iter().zip(src).collect();
+        moves.sort();
+        for (dst, src) in moves {
+            perm.insert(dst, src)
+        }
+        self.permute(perm)
+    }
+
+    /// Returns a view of the input using the given shape and strides, the element at index
+    /// `(i_0, ..., i_n)` of the view being the element at position `offset + i_0 * stride[0] +
+    /// ... + i_n * stride[n]` of the input flattened in row-major order. An error is returned if
+    /// a position is out of the input bounds. Contiguous inputs are not copied, the view uses the
+    /// same storage.
+    ///
+    /// This is an expert tool: with small strides, several elements of the view read the same
+    /// input element, e.g. for sliding windows. Their gradients are summed when back-propagating.
+    /// Writing to the underlying storage in place, e.g. with [`crate::Var::set`], is visible
+    /// through all the overlapping elements of the view.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 5, &Device::Cpu)?;
+    /// // Sliding windows of size 3.
+    /// let windows = t.as_strided(&[3, 3], &[1, 1], 0)?;
+    /// assert_eq!(windows.to_vec2::<u32>()?, &[[0, 1, 2], [1, 2, 3], [2, 3, 4]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn as_strided(&self, shape: &[usize], stride: &[usize], offset: usize) -> Result<Self> {
+        if shape.len() != stride.len() {
+            crate::bail!("as_strided: shape {shape:?} and stride {stride:?} have different lengths")
+        }
+        let elem_count = self.elem_count();
+        let in_bounds = if shape.contains(&0) {
+            offset <= elem_count
+        } else {
+            let max_offset = shape.iter().zip(stride).try_fold(offset, |acc, (d, s)| {
+                (d - 1).checked_mul(*s)?.checked_add(acc)
+            });
+            match max_offset {
+                None => crate::bail!(
+                    "as_strided: shape {shape:?}, stride {stride:?} and offset {offset} overflow"
+                ),
+                Some(max_offset) => max_offset < elem_count,
+            }
+        };
+        if !in_bounds {
+            crate::bail!(
+                "as_strided: shape {shape:?}, stride {stride:?} and offset {offset} are out of bounds for {elem_count} elements"
+            )
+        }
+        let t = self.contiguous()?;
+        let op = BackpropOp::new1(&t, |arg| Op::AsStrided {
+            arg,
+            shape: shape.to_vec(),
+            stride: stride.to_vec(),
+            offset,
+        });
+        let layout = Layout::new(
+            shape.into(),
+            stride.to_vec(),
+            t.layout.start_offset() + offset,
+        );
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: t.storage.clone(),
+            layout,
+            op,
+            is_variable: false,
+            dtype: t.dtype,
+            device: t.device.clone(),
+        };
+        Ok(Tensor(Arc::new(tensor_)))
+    }
+
+    /// Returns a view with all the windows of length `size` taken every `step` elements along
+    /// dimension `dim`. The dimension `dim` is replaced by the number of windows and a trailing
+    /// dimension of length `size` is added, as in PyTorch's `Tensor.unfold`.
+    ///
+    /// This uses [`Tensor::as_strided`] so the windows share the storage of the (contiguous) input,
+    /// gradients are summed over overlapping windows when back-propagating.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 5, &Device::Cpu)?;
+    /// let windows = t.unfold(0, 2, 2)?;
+    /// assert_eq!(windows.to_vec2::<u32>()?, &[[0, 1], [2, 3]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn unfold<D: Dim>(&self, dim: D, size: usize, step: usize) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "unfold")?;
+        let dim_len = self.dims()[dim];
+        if step == 0 {
+            crate::bail!("unfold: step has to be positive")
+        }
+        if size > dim_len {
+            crate::bail!(
+                "unfold: window size {size} is larger than dim {dim} of size {dim_len}, tensor {:?}",
+                self.dims()
+            )
+        }
+        let mut dims = self.dims().to_vec();
+        let mut stride = self.shape().stride_contiguous();
+        dims[dim] = (dim_len - size) / step + 1;
+        dims.push(size);
+        stride.push(stride[dim]);
+        stride[dim] *= step;
+        self.as_strided(&dims, &stride, 0)
+    }
+
+    /// An alias for `transpose`.
+    pub fn swapaxes<D1: Dim, D2: Dim>(&self, dim1: D1, dim2: D2) -> Result<Tensor> {
+        self.transpose(dim1, dim2)
+    }
+
     /// Returns true if the data is stored in a C contiguous (aka row major) way.
     pub fn is_contiguous(&self) -> bool {
         self.layout.is_contiguous()
@@ -1584,10 +3143,21 @@ impl Tensor {
                 }
                 (Storage::Cuda(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
                 (Storage::Cuda(storage), Device::Cuda(cuda)) => {
-                    // TODO: Avoid passing through the cpu storage here, especially if the gpu ids
-                    // are the same.
-                    let cpu_storage = storage.to_cpu_storage()?;
-                    Storage::Cuda(cuda.storage_from_cpu_storage(&cpu_storage)?)
+                    Storage::Cuda(storage.transfer_to_device(cuda)?)
+                }
+                (Storage::Cpu(storage), Device::Metal(metal)) => {
+                    Storage::Metal(metal.storage_from_cpu_storage(storage)?)
+                }
+                (Storage::Metal(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
+                // The other copies between gpus go through the cpu.
+                (Storage::Metal(storage), Device::Metal(metal)) => {
+                    Storage::Metal(metal.storage_from_cpu_storage(&storage.to_cpu_storage()?)?)
+                }
+                (Storage::Metal(storage), Device::Cuda(cuda)) => {
+                    Storage::Cuda(cuda.storage_from_cpu_storage(&storage.to_cpu_storage()?)?)
+                }
+                (Storage::Cuda(storage), Device::Metal(metal)) => {
+                    Storage::Metal(metal.storage_from_cpu_storage(&storage.to_cpu_storage()?)?)
                 }
                 (Storage::Cpu(storage), Device::Cpu) => Storage::Cpu(storage.clone()),
             };
@@ -1605,6 +3175,54 @@ impl Tensor {
         }
     }
 
+    /// Returns a cpu tensor with the same data stored in page-locked host memory. The data is
+    /// always copied, also when `self` is already pinned. This requires the `cuda` feature.
+    ///
+    /// Copies from pinned memory to a cuda device skip the staging buffer of the driver, which
+    /// makes them noticeably faster than copies from pageable memory. A data loader can pin the
+    /// next batch on a background thread while the current one is being processed. Page-locked
+    /// memory cannot be swapped out though, so only the batches in flight should be pinned.
+    pub fn pin(&self) -> Result<Self> {
+        let ordinal = match self.device().location() {
+            crate::DeviceLocation::Cuda { gpu_id } => gpu_id,
+            crate::DeviceLocation::Cpu | crate::DeviceLocation::Metal { .. } => 0,
+        };
+        let tensor = self.to_device(&Device::Cpu)?.contiguous()?;
+        let (storage, layout) = tensor.storage_and_layout();
+        let storage = match (&*storage, layout.contiguous_offsets()) {
+            (Storage::Cpu(storage), Some((start, end))) => {
+                crate::pin_cpu_storage(storage, start, end, ordinal)?
+            }
+            _ => crate::bail!("pin: expected a contiguous cpu tensor"),
+        };
+        let op = BackpropOp::new1(self, Op::ToDevice);
+        Ok(from_storage(
+            Storage::Cpu(storage),
+            self.shape().clone(),
+            op,
+            false,
+        ))
+    }
+
+    /// Returns a cpu tensor with the same data stored in regular pageable memory. The data is
+    /// always copied, this can be used to release the page-locked memory of a pinned tensor
+    /// while keeping its values.
+    pub fn unpin(&self) -> Result<Self> {
+        let tensor = self.to_device(&Device::Cpu)?.contiguous()?;
+        let (storage, layout) = tensor.storage_and_layout();
+        let storage = match (&*storage, layout.contiguous_offsets()) {
+            (Storage::Cpu(storage), Some((start, end))) => storage.copy_range(start, end),
+            _ => crate::bail!("unpin: expected a contiguous cpu tensor"),
+        };
+        let op = BackpropOp::new1(self, Op::ToDevice);
+        Ok(from_storage(
+            Storage::Cpu(storage),
+            self.shape().clone(),
+            op,
+            false,
+        ))
+    }
+
     /// Returns a new tensor duplicating data from the original tensor. New dimensions are inserted
     /// on the left.
     pub fn broadcast_left<S: Into<Shape>>(&self, left_shape: S) -> Result<Self> {
@@ -1780,6 +3398,62 @@ impl Tensor {
         self.reshape(dims)
     }
 
+    /// Creates coordinate grids from one dimensional tensors, similar to `numpy.meshgrid`.
+    ///
+    /// Given `N` one dimensional tensors of lengths `l0, l1, ..., lN`, this returns `N` tensors
+    /// all with the same shape. With `"ij"` indexing the shape is `(l0, l1, ..., lN)` and the
+    /// values of the `k`-th input are broadcasted along dimension `k`. With `"xy"` indexing the
+    /// first two dimensions of the output are swapped, i.e. the shape is `(l1, l0, ..., lN)`.
+    ///
+    /// The returned tensors are broadcasted views of the inputs so no data gets copied.
+    ///
+    /// ```rust
+    /// # use candle_core::{Tensor, Device};
+    /// let x = Tensor::new(&[1u32, 2, 3], &Device::Cpu)?;
+    /// let y = Tensor::new(&[4u32, 5], &Device::Cpu)?;
+    /// let grids = Tensor::meshgrid(&[&x, &y], "xy")?;
+    /// assert_eq!(grids[0].to_vec2::<u32>()?, &[[1, 2, 3], [1, 2, 3]]);
+    /// assert_eq!(grids[1].to_vec2::<u32>()?, &[[4, 4, 4], [5, 5, 5]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn meshgrid<A: AsRef<Tensor>>(args: &[A], indexing: &str) -> Result<Vec<Self>> {
+        if args.is_empty() {
+            Err(Error::OpRequiresAtLeastOneTensor { op: "meshgrid" }.bt())?
+        }
+        let xy_indexing = match indexing {
+            "ij" => false,
+            "xy" => true,
+            _ => crate::bail!("meshgrid: unsupported indexing {indexing:?}, use \"ij\" or \"xy\""),
+        };
+        let mut dims = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            let arg = arg.as_ref();
+            if arg.rank() != 1 {
+                Err(Error::UnexpectedNumberOfDims {
+                    expected: 1,
+                    got: arg.rank(),
+                    shape: arg.shape().clone(),
+                }
+                .bt())?
+            }
+            dims.push(arg.elem_count())
+        }
+        // The position of each input in the output shape.
+        let mut axes: Vec<usize> = (0..args.len()).collect();
+        if xy_indexing && args.len() > 1 {
+            dims.swap(0, 1);
+            axes.swap(0, 1);
+        }
+        args.iter()
+            .zip(axes.iter())
+            .map(|(arg, &axis)| {
+                let mut shape = vec![1; dims.len()];
+                shape[axis] = dims[axis];
+                arg.as_ref().reshape(shape)?.broadcast_as(dims.as_slice())
+            })
+            .collect()
+    }
+
     /// Stacks two or more tensors along a particular dimension.
     ///
     /// All tensors must have the same rank, and the output has one additional rank
@@ -1954,6 +3628,74 @@ impl Tensor {
         }
     }
 
+    /// Pad the input tensor along dimension `dim` by replicating the edge values. This adds
+    /// `left` copies of the first element before the input tensor values and `right` copies of
+    /// the last element after.
+    pub fn pad_with_same<D: Dim>(&self, dim: D, left: usize, right: usize) -> Result<Self> {
+        self.pad(dim, left, right, PadMode::Replicate)
+    }
+
+    /// Pad the input tensor along dimension `dim`, adding `left` elements before the input tensor
+    /// values and `right` elements after. The values used for padding depend on `mode`, see
+    /// [`PadMode`].
+    ///
+    /// Apart from `PadMode::Zeros`, the padding is performed by selecting indexes from the
+    /// original tensor so gradients get accumulated into the source positions.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, PadMode};
+    /// let a = Tensor::new(&[1f32, 2., 3., 4.], &Device::Cpu)?;
+    /// let p = a.pad(0, 2, 1, PadMode::Reflect)?;
+    /// assert_eq!(p.to_vec1::<f32>()?, &[3., 2., 1., 2., 3., 4., 3.]);
+    /// let p = a.pad(0, 2, 1, PadMode::Circular)?;
+    /// assert_eq!(p.to_vec1::<f32>()?, &[3., 4., 1., 2., 3., 4., 1.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn pad<D: Dim>(&self, dim: D, left: usize, right: usize, mode: PadMode) -> Result<Self> {
+        if left == 0 && right == 0 {
+            return Ok(self.clone());
+        }
+        let dim = dim.to_index(self.shape(), "pad")?;
+        let size = self.dims()[dim];
+        if mode == PadMode::Zeros {
+            return self.pad_with_zeros(dim, left, right);
+        }
+        if size == 0 {
+            crate::bail!(
+                "cannot use {mode:?} padding on dim {dim} of size 0 ({:?})",
+                self.shape()
+            )
+        }
+        if mode == PadMode::Reflect && (left >= size || right >= size) {
+            crate::bail!(
+                "reflect padding ({left}, {right}) should be smaller than the size of dim {dim} ({:?})",
+                self.shape()
+            )
+        }
+        let size = size as i64;
+        let ids = (-(left as i64)..size + right as i64)
+            .map(|i| {
+                let i = match mode {
+                    PadMode::Reflect => {
+                        if i < 0 {
+                            -i
+                        } else if i >= size {
+                            2 * (size - 1) - i
+                        } else {
+                            i
+                        }
+                    }
+                    PadMode::Replicate => i.clamp(0, size - 1),
+                    PadMode::Circular => i.rem_euclid(size),
+                    PadMode::Zeros => unreachable!(),
+                };
+                i as u32
+            })
+            .collect::<Vec<_>>();
+        let ids = Tensor::new(ids.as_slice(), self.device())?;
+        self.index_select(&ids, dim)
+    }
+
     /// Run the `forward` method of `m` on `self`.
     pub fn apply<M: crate::Module>(&self, m: &M) -> Result<Self> {
         m.forward(self)
@@ -2076,7 +3818,7 @@ impl Tensor {
 }
 
 macro_rules! bin_trait {
-    ($trait:ident, $fn1:ident, $mul:expr, $add:expr) => {
+    ($trait:ident, $fn1:ident, $scalar:expr) => {
         impl<B: std::borrow::Borrow<Tensor>> std::ops::$trait<B> for Tensor {
             type Output = Result<Tensor>;
 
@@ -2129,7 +3871,7 @@ macro_rules! bin_trait {
             type Output = Result<Tensor>;
 
             fn $fn1(self, rhs: f64) -> Self::Output {
-                self.affine($mul(rhs), $add(rhs))
+                $scalar(&self, rhs)
             }
         }
 
@@ -2137,16 +3879,16 @@ macro_rules! bin_trait {
             type Output = Result<Tensor>;
 
             fn $fn1(self, rhs: f64) -> Self::Output {
-                self.affine($mul(rhs), $add(rhs))
+                $scalar(self, rhs)
             }
         }
     };
 }
 
-bin_trait!(Add, add, |_| 1., |v| v);
-bin_trait!(Sub, sub, |_| 1., |v: f64| -v);
-bin_trait!(Mul, mul, |v| v, |_| 0.);
-bin_trait!(Div, div, |v| 1. / v, |_| 0.);
+bin_trait!(Add, add, |t: &Tensor, v| t.affine(1., v));
+bin_trait!(Sub, sub, |t: &Tensor, v: f64| t.affine(1., -v));
+bin_trait!(Mul, mul, |t: &Tensor, v| t.affine(v, 0.));
+bin_trait!(Div, div, |t: &Tensor, v| t.scalar_div(v));
 
 impl std::ops::Add<Tensor> for f64 {
     type Output = Result<Tensor>;
@@ -2213,3 +3955,44 @@ impl std::ops::Div<&Tensor> for f64 {
         rhs.recip()? * self
     }
 }
+
+/// Nested vectors that a tensor can be converted to using [`Tensor::to_vec_nd`], from `Vec<S>`
+/// for 1D tensors up to six levels of nesting for 6D tensors.
+pub trait NestedVec: Sized {
+    type Elem: crate::WithDType;
+    /// The rank of the tensors that convert to this type.
+    const RANK: usize;
+
+    /// Builds the nested vectors from elements in row-major order.
+    fn from_flat(data: &mut std::vec::IntoIter<Self::Elem>, dims: &[usize]) -> Self;
+}
+
+impl<S: crate::WithDType> NestedVec for Vec<S> {
+    type Elem = S;
+    const RANK: usize = 1;
+
+    fn from_flat(data: &mut std::vec::IntoIter<S>, dims: &[usize]) -> Self {
+        data.take(dims[0]).collect()
+    }
+}
+
+macro_rules! nested_vec {
+    ($rank:expr, $inner:ty) => {
+        impl<S: crate::WithDType> NestedVec for Vec<$inner> {
+            type Elem = S;
+            const RANK: usize = $rank;
+
+            fn from_flat(data: &mut std::vec::IntoIter<S>, dims: &[usize]) -> Self {
+                (0..dims[0])
+                    .map(|_| <$inner>::from_flat(data, &dims[1..]))
+                    .collect()
+            }
+        }
+    };
+}
+
+nested_vec!(2, Vec<S>);
+nested_vec!(3, Vec<Vec<S>>);
+nested_vec!(4, Vec<Vec<Vec<S>>>);
+nested_vec!(5, Vec<Vec<Vec<Vec<S>>>>);
+nested_vec!(6, Vec<Vec<Vec<Vec<Vec<S>>>>>);
@@ -0,0 +1,54 @@
+//! Conversions between tensors and `ndarray` arrays, this requires the `ndarray` feature.
+//!
+//! Arrays of any dimension and memory layout can be used where an `NdArray` is expected, e.g.
+//! with [`Tensor::new`], their elements are copied in logical order. Converting back copies the
+//! tensor elements in row-major order to an owned array, whatever the tensor layout or device.
+use crate::device::NdArray;
+use crate::{CpuStorage, Device, Error, Result, Shape, Tensor, WithDType};
+use ndarray::{ArrayBase, ArrayD, Data, Dimension, IxDyn};
+
+impl<S: WithDType, A: Data<Elem = S>, D: Dimension> NdArray for &ArrayBase<A, D> {
+    fn shape(&self) -> Result<Shape> {
+        Ok(Shape::from(ArrayBase::shape(*self)))
+    }
+
+    fn to_cpu_storage(&self) -> CpuStorage {
+        match self.as_slice() {
+            Some(data) => S::to_cpu_storage(data),
+            None => S::to_cpu_storage_owned(self.iter().copied().collect()),
+        }
+    }
+}
+
+impl Tensor {
+    /// Creates a tensor with the shape and elements of an `ndarray` array.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let array = ndarray::array![[1f32, 2.], [3., 4.]];
+    /// let t = Tensor::from_ndarray(&array.t(), &Device::Cpu)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, [[1., 3.], [2., 4.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn from_ndarray<S: WithDType, A: Data<Elem = S>, D: Dimension>(
+        array: &ArrayBase<A, D>,
+        device: &Device,
+    ) -> Result<Self> {
+        Self::new(array, device)
+    }
+
+    /// Returns the elements of the tensor as an owned `ndarray` array with the same shape, the
+    /// requested element type has to match the tensor dtype.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 6, &Device::Cpu)?.reshape((2, 3))?;
+    /// let array = t.to_ndarray::<u32>()?;
+    /// assert_eq!(array, ndarray::array![[0u32, 1, 2], [3, 4, 5]].into_dyn());
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn to_ndarray<S: WithDType>(&self) -> Result<ArrayD<S>> {
+        let data = self.flatten_to_vec::<S>()?;
+        ArrayD::from_shape_vec(IxDyn(self.dims()), data).map_err(Error::wrap)
+    }
+}
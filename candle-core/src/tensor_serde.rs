@@ -0,0 +1,38 @@
+//! Serde support for tensors, this requires the `serde` feature.
+//!
+//! A tensor is serialized as its shape, dtype, device location, and its contiguous data encoded
+//! in little endian. Tensors on other devices are copied to the cpu first. Deserialization always
+//! returns a cpu tensor, moving it back to the original device is left to the caller.
+use crate::{DType, Device, DeviceLocation, Tensor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+struct TensorData {
+    shape: Vec<usize>,
+    dtype: DType,
+    device: DeviceLocation,
+    data: Vec<u8>,
+}
+
+impl Serialize for Tensor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut data = Vec::with_capacity(self.elem_count() * self.dtype().size_in_bytes());
+        self.write_bytes(&mut data)
+            .map_err(serde::ser::Error::custom)?;
+        let tensor_data = TensorData {
+            shape: self.dims().to_vec(),
+            dtype: self.dtype(),
+            device: self.device().location(),
+            data,
+        };
+        tensor_data.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tensor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let t = TensorData::deserialize(deserializer)?;
+        Tensor::from_raw_buffer(&t.data, t.dtype, &t.shape, &Device::Cpu)
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Result, Tensor};
+use crate::{DType, Result, Tensor};
 
 #[macro_export]
 macro_rules! test_device {
@@ -54,3 +54,97 @@ pub fn to_vec3_round(t: &Tensor, digits: i32) -> Result<Vec<Vec<Vec<f32>>>> {
         .collect();
     Ok(t)
 }
+
+/// Asserts that two tensors are close, i.e. that `|lhs - rhs| <= atol + rtol * |rhs|` holds for
+/// all the elements after broadcasting, see [`Tensor::isclose`]. The tolerances default to
+/// `rtol = 1e-5` and `atol = 1e-8`. NaN values are not close to anything unless `equal_nan` is
+/// passed as a fifth argument. On failure the message reports the number of mismatches and the
+/// index and values of the worst one.
+///
+/// ```rust
+/// use candle_core::{test_utils::assert_close, Device, Tensor};
+/// let a = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+/// assert_close!(a, (&a + 1e-7)?);
+/// assert_close!(a, (&a + 0.1)?, 0., 0.2);
+/// let nan = Tensor::new(&[f32::NAN], &Device::Cpu)?;
+/// assert_close!(nan, nan, 1e-5, 1e-8, true);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+#[macro_export]
+macro_rules! assert_close {
+    ($lhs: expr, $rhs: expr) => {
+        $crate::assert_close!($lhs, $rhs, 1e-5, 1e-8)
+    };
+    ($lhs: expr, $rhs: expr, $rtol: expr, $atol: expr) => {
+        $crate::assert_close!($lhs, $rhs, $rtol, $atol, false)
+    };
+    ($lhs: expr, $rhs: expr, $rtol: expr, $atol: expr, $equal_nan: expr) => {
+        match $crate::test_utils::check_close(&$lhs, &$rhs, $rtol, $atol, $equal_nan) {
+            Ok(None) => {}
+            Ok(Some(msg)) => panic!(
+                "assertion failed: `{}` is not close to `{}`\n{msg}",
+                stringify!($lhs),
+                stringify!($rhs),
+            ),
+            Err(err) => panic!(
+                "assertion failed: cannot compare `{}` and `{}`: {err}",
+                stringify!($lhs),
+                stringify!($rhs),
+            ),
+        }
+    };
+}
+
+pub use crate::assert_close;
+
+/// Checks that two tensors are close as in [`assert_close`], returning a description of the
+/// mismatches if some elements are not close.
+pub fn check_close(
+    lhs: &Tensor,
+    rhs: &Tensor,
+    rtol: f64,
+    atol: f64,
+    equal_nan: bool,
+) -> Result<Option<String>> {
+    let close = lhs.isclose(rhs, rtol, atol, equal_nan)?;
+    let shape = close.shape().clone();
+    let close = close.flatten_to_vec::<u8>()?;
+    let l = lhs.to_dtype(DType::F64)?.broadcast_as(&shape)?;
+    let r = rhs.to_dtype(DType::F64)?.broadcast_as(&shape)?;
+    let l = l.flatten_to_vec::<f64>()?;
+    let r = r.flatten_to_vec::<f64>()?;
+    let mut mismatches = 0;
+    // The index of the worst mismatch and by how much it exceeds the tolerance.
+    let mut worst: Option<(usize, f64)> = None;
+    for (i, (&l, &r)) in l.iter().zip(r.iter()).enumerate() {
+        if close[i] != 0 {
+            continue;
+        }
+        mismatches += 1;
+        let excess = (l - r).abs() - (atol + rtol * r.abs());
+        let excess = if excess.is_nan() {
+            f64::INFINITY
+        } else {
+            excess
+        };
+        if worst.map_or(true, |(_, w)| excess > w) {
+            worst = Some((i, excess))
+        }
+    }
+    let msg = worst.map(|(i, _)| {
+        let mut index = vec![0; shape.rank()];
+        let mut rem = i;
+        for (d, &size) in shape.dims().iter().enumerate().rev() {
+            index[d] = rem % size;
+            rem /= size;
+        }
+        format!(
+            "{mismatches} of {} elements are not close (rtol: {rtol}, atol: {atol}), worst at index {index:?}: {} vs {}, difference {}",
+            l.len(),
+            l[i],
+            r[i],
+            (l[i] - r[i]).abs()
+        )
+    });
+    Ok(msg)
+}
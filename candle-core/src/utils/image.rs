@@ -0,0 +1,106 @@
+//! Loading and saving images as tensors using the `image` crate, this requires the `image`
+//! feature.
+use crate::{DType, Device, Error, Result, Tensor};
+use std::path::Path;
+
+/// The per-channel mean of the ImageNet training set, used to normalize images for most
+/// pretrained vision models.
+pub const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+/// The per-channel standard deviation of the ImageNet training set.
+pub const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// The dimension order of the tensors returned by [`load_image_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageLayout {
+    /// Channels first, i.e. `(3, height, width)`.
+    #[default]
+    Chw,
+    /// Channels last, i.e. `(height, width, 3)`.
+    Hwc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LoadImageOptions {
+    pub layout: ImageLayout,
+    /// Adds a leading batch dimension of size 1, resulting in NCHW or NHWC tensors.
+    pub batch_dim: bool,
+    /// The per-channel `(mean, std)` used to normalize the values, e.g.
+    /// `(IMAGENET_MEAN, IMAGENET_STD)`. The normalization is applied after scaling the values to
+    /// `[0, 1]`.
+    pub normalize: Option<([f32; 3], [f32; 3])>,
+}
+
+/// Loads an RGB image from disk and resizes it to `(target_h, target_w)`, this returns a f32
+/// tensor with shape `(3, target_h, target_w)` and values in `[0, 1]` on the cpu.
+pub fn load_image<P: AsRef<Path>>(p: P, target_h: usize, target_w: usize) -> Result<Tensor> {
+    load_image_with_options(p, target_h, target_w, &LoadImageOptions::default())
+}
+
+/// Loads an RGB image from disk and resizes it to `(target_h, target_w)` using a bilinear
+/// filter, the layout and normalization of the resulting f32 tensor are given by `options`.
+pub fn load_image_with_options<P: AsRef<Path>>(
+    p: P,
+    target_h: usize,
+    target_w: usize,
+    options: &LoadImageOptions,
+) -> Result<Tensor> {
+    let img = image::io::Reader::open(p)?.decode().map_err(Error::wrap)?;
+    let (h, w) = (target_h as u32, target_w as u32);
+    let img = if img.height() == h && img.width() == w {
+        img
+    } else {
+        img.resize_exact(w, h, image::imageops::FilterType::Triangle)
+    };
+    let data = img.to_rgb8().into_raw();
+    let img = Tensor::from_vec(data, (target_h, target_w, 3), &Device::Cpu)?;
+    let img = (img.to_dtype(DType::F32)? / 255.)?;
+    let img = match options.normalize {
+        None => img,
+        Some((mean, std)) => {
+            let mean = Tensor::new(&mean, &Device::Cpu)?;
+            let std = Tensor::new(&std, &Device::Cpu)?;
+            img.broadcast_sub(&mean)?.broadcast_div(&std)?
+        }
+    };
+    let img = match options.layout {
+        ImageLayout::Chw => img.permute((2, 0, 1))?,
+        ImageLayout::Hwc => img,
+    };
+    if options.batch_dim {
+        img.unsqueeze(0)
+    } else {
+        Ok(img)
+    }
+}
+
+/// Saves a tensor with shape `(3, height, width)` as an RGB image, the format is deduced from
+/// the file extension. The tensor can either use u8 values or float values in `[0, 1]`, the
+/// latter being clamped to this range.
+pub fn save_image<P: AsRef<Path>>(img: &Tensor, p: P) -> Result<()> {
+    let p = p.as_ref();
+    let (channel, height, width) = img.dims3()?;
+    if channel != 3 {
+        crate::bail!(
+            "save_image expects an input of shape (3, height, width), got {channel} channels"
+        )
+    }
+    let img = match img.dtype() {
+        DType::U8 => img.clone(),
+        DType::F16 | DType::BF16 | DType::F32 | DType::F64 => (img
+            .to_dtype(DType::F32)?
+            .maximum_scalar(0.)?
+            .minimum_scalar(1.)?
+            * 255.)?
+            .round()?
+            .to_dtype(DType::U8)?,
+        dtype => crate::bail!("save_image expects a u8 or float tensor, got {dtype:?}"),
+    };
+    let pixels = img.permute((1, 2, 0))?.flatten_all()?.to_vec1::<u8>()?;
+    let image: image::ImageBuffer<image::Rgb<u8>, Vec<u8>> =
+        match image::ImageBuffer::from_raw(width as u32, height as u32, pixels) {
+            Some(image) => image,
+            None => crate::bail!("error saving image {p:?}"),
+        };
+    image.save(p).map_err(Error::wrap)?;
+    Ok(())
+}
@@ -2,7 +2,7 @@
 // weights and being modified by gradient descent.
 // We do not expose a public way to create variables as this would break the invariant that the
 // tensor within a variable is actually with `is_variable` set to `true`.
-use crate::{DType, Device, Error, Result, Shape, Tensor};
+use crate::{DType, Device, Error, Result, Shape, Storage, Tensor};
 
 /// A variable is a wrapper around a tensor, however variables can have their content modified
 /// whereas tensors are immutable.
@@ -107,6 +107,17 @@ impl Var {
         Ok(Self(inner))
     }
 
+    /// Returns a `Var` sharing the storage of `t` when `t` is a variable, e.g. a tensor obtained
+    /// via [`Var::as_tensor`], so that setting the returned `Var` also modifies the original
+    /// variable. Returns `None` when `t` is not a variable.
+    pub fn from_variable(t: &Tensor) -> Option<Self> {
+        if t.is_variable() {
+            Some(Self(t.clone()))
+        } else {
+            None
+        }
+    }
+
     pub fn as_tensor(&self) -> &Tensor {
         &self.0
     }
@@ -116,6 +127,17 @@ impl Var {
         self.0
     }
 
+    /// Partitions a contiguous 1D variable into consecutive non-overlapping variables of the given
+    /// sizes, e.g. to split a preallocated workspace.
+    ///
+    /// The returned variables alias the storage of `self`: setting one of them modifies the
+    /// corresponding region of `self` but leaves the other ones untouched. See
+    /// [`Tensor::split_storage`].
+    pub fn split_storage(&self, sizes: &[usize]) -> Result<Vec<Self>> {
+        let views = self.0.split_storage_impl(sizes, true)?;
+        Ok(views.into_iter().map(Self).collect())
+    }
+
     /// Sets the content of the inner tensor, this does not require a mutable reference as inner
     /// mutability is used.
     pub fn set(&self, src: &Tensor) -> Result<()> {
@@ -140,4 +162,66 @@ impl Var {
         src.copy_strided_src(&mut dst, layout.start_offset(), src_l)?;
         Ok(())
     }
+
+    /// Copies the content of `src` into the storage of this variable, this is the same as
+    /// [`Var::set`] but `src` can be derived from the variable value, in which case it is copied
+    /// to a new buffer first.
+    ///
+    /// As for the other in-place operations, the variable keeps its identity and the copy is not
+    /// recorded in the computation graph: gradients computed from tensors that were obtained
+    /// before the copy are not aware of it. These operations are intended for parameter and
+    /// buffer updates, e.g. in optimizers.
+    pub fn copy_(&self, src: &Tensor) -> Result<()> {
+        if self.same_storage(src) {
+            self.set(&src.copy()?)
+        } else {
+            self.set(src)
+        }
+    }
+
+    /// Adds `delta` to the variable in place, see [`Var::copy_`] regarding autograd. On the cpu
+    /// no new buffer is allocated when `delta` does not share the variable storage, other
+    /// devices compute the sum in a temporary buffer and copy it back.
+    ///
+    /// Since the storage is modified in place, the tensors that share this storage, e.g. the
+    /// views returned by `narrow` on the variable, observe the change.
+    pub fn add_(&self, delta: &Tensor) -> Result<()> {
+        if self.shape() != delta.shape() {
+            Err(Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: delta.shape().clone(),
+                op: "add_",
+            }
+            .bt())?
+        }
+        if self.dtype() != delta.dtype() {
+            Err(Error::DTypeMismatchBinaryOp {
+                lhs: self.dtype(),
+                rhs: delta.dtype(),
+                op: "add_",
+            }
+            .bt())?
+        }
+        if self.same_storage(delta) {
+            return self.add_(&delta.copy()?);
+        }
+        let delta = delta.contiguous()?;
+        let (mut dst, layout) = self.storage_mut_and_layout();
+        if !layout.is_contiguous() {
+            let msg = "cannot modify a non-contiguous variable in place";
+            Err(Error::CannotSetVar { msg }.bt())?
+        }
+        let (src, src_l) = delta.storage_and_layout();
+        match (&mut *dst, &*src) {
+            (Storage::Cpu(dst), Storage::Cpu(src)) => {
+                dst.add_assign(layout.start_offset(), src, src_l)
+            }
+            _ => {
+                drop(src);
+                drop(dst);
+                let sum = self.0.detach()?.add(&delta)?;
+                self.set(&sum)
+            }
+        }
+    }
 }
@@ -48,6 +48,50 @@ fn conv1d(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+fn conv1d_groups(dev: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 56., dev)?
+        .affine(0.3, 0.)?
+        .sin()?
+        .reshape((2, 4, 7))?;
+    let w = Tensor::arange(0f32, 36., dev)?
+        .affine(0.7, 0.1)?
+        .cos()?
+        .reshape((6, 2, 3))?;
+    for (padding, stride, dilation) in [(0, 1, 1), (1, 2, 1), (2, 1, 2)] {
+        let res = t.conv1d(&w, padding, stride, dilation, 2)?;
+        // Each group of input channels is only convolved with the corresponding group of
+        // kernels, the outputs are concatenated in order along the channel axis.
+        let expected = Tensor::cat(
+            &[
+                t.narrow(1, 0, 2)?
+                    .conv1d(&w.narrow(0, 0, 3)?, padding, stride, dilation, 1)?,
+                t.narrow(1, 2, 2)?
+                    .conv1d(&w.narrow(0, 3, 3)?, padding, stride, dilation, 1)?,
+            ],
+            1,
+        )?;
+        assert_eq!(res.dims(), expected.dims());
+        assert_eq!(res.to_vec3::<f32>()?, expected.to_vec3::<f32>()?);
+    }
+    // Depthwise, one group per input channel.
+    let w = w.narrow(1, 0, 1)?.narrow(0, 0, 4)?;
+    let res = t.conv1d(&w, 1, 1, 1, 4)?;
+    let expected = (0..4)
+        .map(|c| t.narrow(1, c, 1)?.conv1d(&w.narrow(0, c, 1)?, 1, 1, 1, 1))
+        .collect::<candle_core::Result<Vec<_>>>()?;
+    assert_eq!(
+        res.to_vec3::<f32>()?,
+        Tensor::cat(&expected, 1)?.to_vec3::<f32>()?
+    );
+
+    // The number of output channels has to be divisible by the number of groups.
+    let w = Tensor::zeros((5, 2, 3), candle_core::DType::F32, dev)?;
+    assert!(t.conv1d(&w, 0, 1, 1, 2).is_err());
+    let w = Tensor::zeros((6, 3, 3), candle_core::DType::F32, dev)?;
+    assert!(t.conv1d(&w, 0, 1, 1, 2).is_err());
+    Ok(())
+}
+
 fn conv1d_small(dev: &Device) -> Result<()> {
     let t = Tensor::new(&[0.4056f32, -0.8689, -0.0773, -1.5630], dev)?.reshape((1, 1, 4))?;
     let w = Tensor::new(&[1f32, 0., 0.], dev)?.reshape((1, 1, 3))?;
@@ -482,7 +526,275 @@ fn conv2d_grad(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+fn unfold_fold2d(dev: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 18., dev)?.reshape((1, 2, 3, 3))?;
+    let cols = t.unfold2d((2, 2), 1, 0, 1)?;
+    assert_eq!(cols.dims(), [1, 8, 4]);
+    // Rows are ordered by channel then kernel offset, columns by block.
+    assert_eq!(
+        cols.i(0)?.to_vec2::<f32>()?,
+        [
+            [0., 1., 3., 4.],
+            [1., 2., 4., 5.],
+            [3., 4., 6., 7.],
+            [4., 5., 7., 8.],
+            [9., 10., 12., 13.],
+            [10., 11., 13., 14.],
+            [12., 13., 15., 16.],
+            [13., 14., 16., 17.]
+        ]
+    );
+    // Convolution as a matmul.
+    let w = Tensor::arange(0f32, 24., dev)?.reshape((3, 2, 2, 2))?;
+    let conv = w
+        .reshape((3, 8))?
+        .matmul(&cols.i(0)?)?
+        .reshape((1, 3, 2, 2))?;
+    assert_eq!(
+        conv.flatten_all()?.to_vec1::<f32>()?,
+        t.conv2d(&w, 0, 1, 1, 1)?.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    // Zero padding and dilation.
+    let cols = t.unfold2d((2, 2), 1, 1, 2)?;
+    assert_eq!(
+        cols.i((0, ..4))?.to_vec2::<f32>()?,
+        [
+            [0., 0., 0., 0., 0., 1., 0., 3., 4.],
+            [0., 0., 0., 1., 2., 0., 4., 5., 0.],
+            [0., 3., 4., 0., 6., 7., 0., 0., 0.],
+            [4., 5., 0., 7., 8., 0., 0., 0., 0.]
+        ]
+    );
+
+    // With non-overlapping blocks fold is the inverse of unfold, otherwise values covered by
+    // several blocks get multiplied by the number of blocks covering them.
+    let t = Tensor::arange(0f32, 32., dev)?.reshape((2, 1, 4, 4))?;
+    let cols = t.unfold2d((2, 2), 2, 0, 1)?;
+    let folded = cols.fold2d((4, 4), (2, 2), 2, 0, 1)?;
+    assert_eq!(
+        folded.flatten_all()?.to_vec1::<f32>()?,
+        t.flatten_all()?.to_vec1::<f32>()?
+    );
+    let cols = t.unfold2d((3, 3), 1, 1, 1)?;
+    let folded = cols.fold2d((4, 4), (3, 3), 1, 1, 1)?;
+    let ones = t.ones_like()?;
+    let divisor = ones
+        .unfold2d((3, 3), 1, 1, 1)?
+        .fold2d((4, 4), (3, 3), 1, 1, 1)?;
+    assert_eq!(
+        divisor.i((0, 0))?.to_vec2::<f32>()?,
+        [
+            [4., 6., 6., 4.],
+            [6., 9., 9., 6.],
+            [6., 9., 9., 6.],
+            [4., 6., 6., 4.]
+        ]
+    );
+    assert_eq!(
+        (folded / divisor)?.flatten_all()?.to_vec1::<f32>()?,
+        t.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    assert!(t.unfold2d((5, 5), 1, 0, 1).is_err());
+    assert!(cols.fold2d((4, 5), (3, 3), 1, 1, 1).is_err());
+
+    // Batched im2col convolution with stride, padding and dilation.
+    let t = Tensor::arange(0f32, 150., dev)?.reshape((2, 3, 5, 5))?;
+    let w = Tensor::arange(0f32, 108., dev)?.reshape((4, 3, 3, 3))?;
+    let cols = t.im2col((3, 3), 2, 2, 2)?;
+    let conv = w.reshape((4, 27))?.broadcast_matmul(&cols)?;
+    let expected = t.conv2d(&w, 2, 2, 2, 1)?;
+    assert_eq!(conv.dims(), [2, 4, 9]);
+    assert_eq!(
+        conv.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+    assert_eq!(
+        cols.col2im((5, 5), (3, 3), 2, 2, 2)?
+            .flatten_all()?
+            .to_vec1::<f32>()?,
+        cols.fold2d((5, 5), (3, 3), 2, 2, 2)?
+            .flatten_all()?
+            .to_vec1::<f32>()?
+    );
+    Ok(())
+}
+
+fn conv2d_depthwise(dev: &Device) -> Result<()> {
+    use candle_core::Var;
+    // Reference implementation using one convolution per input channel.
+    fn per_channel(
+        t: &Tensor,
+        w: &Tensor,
+        p: usize,
+        s: usize,
+        d: usize,
+    ) -> candle_core::Result<Tensor> {
+        let c_in = t.dim(1)?;
+        let m = w.dim(0)? / c_in;
+        let ys = (0..c_in)
+            .map(|c| {
+                t.narrow(1, c, 1)?
+                    .conv2d(&w.narrow(0, c * m, m)?, p, s, d, 1)
+            })
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        Tensor::cat(&ys, 1)
+    }
+    let t = Tensor::arange(0f32, 150., dev)?
+        .affine(0.1, -7.)?
+        .sin()?
+        .reshape((2, 3, 5, 5))?;
+    for (multiplier, (p, s, d)) in [(1, (0, 1, 1)), (1, (1, 2, 1)), (2, (2, 1, 2))] {
+        let w = Tensor::arange(0f32, (3 * multiplier * 9) as f32, dev)?
+            .affine(0.3, 1.)?
+            .cos()?
+            .reshape((3 * multiplier, 1, 3, 3))?;
+        let expected = per_channel(&t, &w, p, s, d)?;
+        let res = t.conv2d(&w, p, s, d, 3)?;
+        assert_eq!(res.dims(), expected.dims());
+        // On cpu the depthwise kernels accumulate in the same order as the generic ones so the
+        // results are bit-identical. The cuda kernels are only compared up to rounding.
+        let check = |res: &Tensor, expected: &Tensor| -> Result<()> {
+            if dev.is_cuda() {
+                assert_eq!(
+                    test_utils::to_vec1_round(&res.flatten_all()?, 4)?,
+                    test_utils::to_vec1_round(&expected.flatten_all()?, 4)?
+                );
+            } else {
+                assert_eq!(
+                    res.flatten_to_vec::<f32>()?,
+                    expected.flatten_to_vec::<f32>()?
+                );
+            }
+            Ok(())
+        };
+        check(&res, &expected)?;
+        // Non-contiguous inputs.
+        let t_nc = t.transpose(2, 3)?;
+        check(
+            &t_nc.conv2d(&w, p, s, d, 3)?,
+            &per_channel(&t_nc, &w, p, s, d)?,
+        )?;
+
+        // Gradients.
+        let (t_var, w_var) = (Var::from_tensor(&t)?, Var::from_tensor(&w)?);
+        let res = t_var.conv2d(&w_var, p, s, d, 3)?;
+        let weights = Tensor::arange(0f32, res.elem_count() as f32, dev)?
+            .affine(0.01, 0.)?
+            .reshape(res.shape())?;
+        let grads = (res * &weights)?.sum_all()?.backward()?;
+        let expected = per_channel(&t_var, &w_var, p, s, d)?;
+        let expected_grads = (expected * &weights)?.sum_all()?.backward()?;
+        for var in [&t_var, &w_var] {
+            assert_eq!(
+                test_utils::to_vec1_round(&grads.get(var).unwrap().flatten_all()?, 3)?,
+                test_utils::to_vec1_round(&expected_grads.get(var).unwrap().flatten_all()?, 3)?
+            );
+        }
+    }
+    Ok(())
+}
+
+fn conv3d(dev: &Device) -> Result<()> {
+    // Direct implementation of the 3D convolution on flattened vectors.
+    #[allow(clippy::too_many_arguments)]
+    fn reference(
+        t: &[f32],
+        (b, c_in, d, h, w): (usize, usize, usize, usize, usize),
+        k: &[f32],
+        (c_out, k_c, k_d, k_h, k_w): (usize, usize, usize, usize, usize),
+        padding: usize,
+        stride: usize,
+        dilation: usize,
+    ) -> (Vec<usize>, Vec<f32>) {
+        let out = |len: usize, k: usize| (len + 2 * padding - dilation * (k - 1) - 1) / stride + 1;
+        let (o_d, o_h, o_w) = (out(d, k_d), out(h, k_h), out(w, k_w));
+        let groups = c_in / k_c;
+        let mut res = vec![];
+        for b_i in 0..b {
+            for o_c in 0..c_out {
+                let g = o_c / (c_out / groups);
+                for z in 0..o_d {
+                    for y in 0..o_h {
+                        for x in 0..o_w {
+                            let mut acc = 0f32;
+                            for k_ci in 0..k_c {
+                                let c = g * k_c + k_ci;
+                                for (dz, dy, dx) in (0..k_d).flat_map(|dz| {
+                                    (0..k_h)
+                                        .flat_map(move |dy| (0..k_w).map(move |dx| (dz, dy, dx)))
+                                }) {
+                                    let iz =
+                                        (z * stride + dz * dilation) as isize - padding as isize;
+                                    let iy =
+                                        (y * stride + dy * dilation) as isize - padding as isize;
+                                    let ix =
+                                        (x * stride + dx * dilation) as isize - padding as isize;
+                                    if iz < 0 || iy < 0 || ix < 0 {
+                                        continue;
+                                    }
+                                    let (iz, iy, ix) = (iz as usize, iy as usize, ix as usize);
+                                    if iz >= d || iy >= h || ix >= w {
+                                        continue;
+                                    }
+                                    let t_idx = (((b_i * c_in + c) * d + iz) * h + iy) * w + ix;
+                                    let k_idx =
+                                        (((o_c * k_c + k_ci) * k_d + dz) * k_h + dy) * k_w + dx;
+                                    acc += t[t_idx] * k[k_idx]
+                                }
+                            }
+                            res.push(acc)
+                        }
+                    }
+                }
+            }
+        }
+        (vec![b, c_out, o_d, o_h, o_w], res)
+    }
+    let t_dims = (2, 4, 5, 6, 4);
+    let t = Tensor::arange(0f32, 960., dev)?
+        .affine(0.37, 0.)?
+        .sin()?
+        .reshape(t_dims)?;
+    for (groups, k_dims, (padding, stride, dilation)) in [
+        (1, (3, 4, 2, 3, 2), (0, 1, 1)),
+        (1, (2, 4, 3, 3, 3), (1, 2, 1)),
+        (2, (4, 2, 2, 2, 2), (1, 1, 2)),
+        (4, (4, 1, 3, 1, 3), (2, 2, 1)),
+    ] {
+        let (c_out, k_c, k_d, k_h, k_w) = k_dims;
+        let k = Tensor::arange(0f32, (c_out * k_c * k_d * k_h * k_w) as f32, dev)?
+            .affine(0.61, 0.5)?
+            .cos()?
+            .reshape(k_dims)?;
+        let res = t.conv3d(&k, padding, stride, dilation, groups)?;
+        let (dims, expected) = reference(
+            &t.flatten_to_vec::<f32>()?,
+            t_dims,
+            &k.flatten_to_vec::<f32>()?,
+            k_dims,
+            padding,
+            stride,
+            dilation,
+        );
+        assert_eq!(res.dims(), dims);
+        let res = res.flatten_to_vec::<f32>()?;
+        for (r, e) in res.iter().zip(expected.iter()) {
+            assert!((r - e).abs() < 1e-4, "{r} {e}")
+        }
+    }
+    // Spatial dims for a typical video layer.
+    let t = Tensor::zeros((1, 3, 8, 16, 16), candle_core::DType::F32, dev)?;
+    let k = Tensor::zeros((8, 3, 3, 3, 3), candle_core::DType::F32, dev)?;
+    assert_eq!(t.conv3d(&k, 1, 2, 1, 1)?.dims(), [1, 8, 4, 8, 8]);
+    assert!(t.conv3d(&k, 0, 1, 5, 1).is_err());
+    assert!(t.conv3d(&k, 1, 1, 1, 3).is_err());
+    Ok(())
+}
+
 test_device!(conv1d, conv1d_cpu, conv1d_gpu);
+test_device!(conv1d_groups, conv1d_groups_cpu, conv1d_groups_gpu);
 test_device!(conv1d_small, conv1d_small_cpu, conv1d_small_gpu);
 test_device!(conv2d, conv2d_cpu, conv2d_gpu);
 test_device!(
@@ -493,3 +805,6 @@ test_device!(
 test_device!(conv2d_small, conv2d_small_cpu, conv2d_small_gpu);
 test_device!(conv2d_smaller, conv2d_smaller_cpu, conv2d_smaller_gpu);
 test_device!(conv2d_grad, conv2d_grad_cpu, conv2d_grad_gpu);
+test_device!(unfold_fold2d, unfold_fold2d_cpu, unfold_fold2d_gpu);
+test_device!(conv2d_depthwise, conv2d_depthwise_cpu, conv2d_depthwise_gpu);
+test_device!(conv3d, conv3d_cpu, conv3d_gpu);
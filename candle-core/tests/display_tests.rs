@@ -1,33 +1,42 @@
 use anyhow::Result;
-use candle_core::{DType, Device::Cpu, Tensor};
+use candle_core::{display, DType, Device::Cpu, Tensor};
+
+// The printer options are global so the tests that rely on them cannot run concurrently.
+static PRINT_OPTS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn lock() -> std::sync::MutexGuard<'static, ()> {
+    PRINT_OPTS_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
 
 #[test]
 fn display_scalar() -> Result<()> {
+    let _guard = lock();
     let t = Tensor::new(1234u32, &Cpu)?;
     let s = format!("{t}");
-    assert_eq!(&s, "[1234]\nTensor[[], u32]");
+    assert_eq!(&s, "[1234]\nTensor[[], u32, cpu]");
     let t = t.to_dtype(DType::F32)?.neg()?;
     let s = format!("{}", (&t / 10.0)?);
-    assert_eq!(&s, "[-123.4000]\nTensor[[], f32]");
+    assert_eq!(&s, "[-123.4000]\nTensor[[], f32, cpu]");
     let s = format!("{}", (&t / 1e8)?);
-    assert_eq!(&s, "[-1.2340e-5]\nTensor[[], f32]");
+    assert_eq!(&s, "[-1.2340e-5]\nTensor[[], f32, cpu]");
     let s = format!("{}", (&t * 1e8)?);
-    assert_eq!(&s, "[-1.2340e11]\nTensor[[], f32]");
+    assert_eq!(&s, "[-1.2340e11]\nTensor[[], f32, cpu]");
     let s = format!("{}", (&t * 0.)?);
-    assert_eq!(&s, "[0.]\nTensor[[], f32]");
+    assert_eq!(&s, "[0.]\nTensor[[], f32, cpu]");
     Ok(())
 }
 
 #[test]
 fn display_vector() -> Result<()> {
+    let _guard = lock();
     let t = Tensor::new::<&[u32; 0]>(&[], &Cpu)?;
     let s = format!("{t}");
-    assert_eq!(&s, "[]\nTensor[[0], u32]");
+    assert_eq!(&s, "[]\nTensor[[0], u32, cpu]");
     let t = Tensor::new(&[0.1234567, 1.0, -1.2, 4.1, f64::NAN], &Cpu)?;
     let s = format!("{t}");
     assert_eq!(
         &s,
-        "[ 0.1235,  1.0000, -1.2000,  4.1000,     NaN]\nTensor[[5], f64]"
+        "[ 0.1235,  1.0000, -1.2000,  4.1000,     NaN]\nTensor[[5], f64, cpu]"
     );
     let t = (Tensor::ones(50, DType::F32, &Cpu)? * 42.)?;
     let s = format!("\n{t}");
@@ -36,19 +45,20 @@ fn display_vector() -> Result<()> {
  42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42.,
  42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42., 42.,
  42., 42.]
-Tensor[[50], f32]"#;
+Tensor[[50], f32, cpu]"#;
     assert_eq!(&s, expected);
     let t = (Tensor::ones(11000, DType::F32, &Cpu)? * 42.)?;
     let s = format!("{t}");
     assert_eq!(
         &s,
-        "[42., 42., 42., ..., 42., 42., 42.]\nTensor[[11000], f32]"
+        "[42., 42., 42., ..., 42., 42., 42.]\nTensor[[11000], f32, cpu]"
     );
     Ok(())
 }
 
 #[test]
 fn display_multi_dim() -> Result<()> {
+    let _guard = lock();
     let t = (Tensor::ones((200, 100), DType::F32, &Cpu)? * 42.)?;
     let s = format!("\n{t}");
     let expected = r#"
@@ -59,7 +69,7 @@ fn display_multi_dim() -> Result<()> {
  [42., 42., 42., ..., 42., 42., 42.],
  [42., 42., 42., ..., 42., 42., 42.],
  [42., 42., 42., ..., 42., 42., 42.]]
-Tensor[[200, 100], f32]"#;
+Tensor[[200, 100], f32, cpu]"#;
     assert_eq!(&s, expected);
     let t = t.reshape(&[2, 1, 1, 100, 100])?;
     let t = format!("\n{t}");
@@ -78,7 +88,81 @@ Tensor[[200, 100], f32]"#;
     [42., 42., 42., ..., 42., 42., 42.],
     [42., 42., 42., ..., 42., 42., 42.],
     [42., 42., 42., ..., 42., 42., 42.]]]]]
-Tensor[[2, 1, 1, 100, 100], f32]"#;
+Tensor[[2, 1, 1, 100, 100], f32, cpu]"#;
     assert_eq!(&t, expected);
     Ok(())
 }
+
+#[test]
+fn display_precision() -> Result<()> {
+    let _guard = lock();
+    let t = Tensor::new(&[[1.23456f32, -2.5], [0.1, 10.]], &Cpu)?;
+    let s = format!("\n{t}");
+    let expected = r#"
+[[ 1.2346, -2.5000],
+ [ 0.1000, 10.0000]]
+Tensor[[2, 2], f32, cpu]"#;
+    assert_eq!(&s, expected);
+    let s = format!("\n{t:.2}");
+    let expected = r#"
+[[ 1.23, -2.50],
+ [ 0.10, 10.00]]
+Tensor[[2, 2], f32, cpu]"#;
+    assert_eq!(&s, expected);
+
+    let t = Tensor::arange(1000u32, 3000, &Cpu)?.to_dtype(DType::F64)?;
+    let t = (t / 3.)?;
+    let s = format!("{t:.1}");
+    assert_eq!(
+        &s,
+        "[333.3, 333.7, 334.0, ..., 999.0, 999.3, 999.7]\nTensor[[2000], f64, cpu]"
+    );
+    Ok(())
+}
+
+#[test]
+fn display_print_options() -> Result<()> {
+    let _guard = lock();
+    let t = (Tensor::arange(0u32, 30, &Cpu)?.to_dtype(DType::F32)? / 4.)?;
+    display::set_print_options(display::PrinterOptions {
+        precision: 1,
+        edge_items: 2,
+        threshold: 20,
+        ..Default::default()
+    });
+    let s = format!("{t}");
+    display::set_print_options_default();
+    assert_eq!(&s, "[0.0, 0.2, ..., 7.0, 7.2]\nTensor[[30], f32, cpu]");
+    assert_eq!(display::print_options().precision, 4);
+    Ok(())
+}
+
+#[test]
+fn debug() -> Result<()> {
+    let _guard = lock();
+    let t = Tensor::new(&[1u32, 2, 3], &Cpu)?;
+    assert_eq!(format!("{t:?}"), "Tensor[1, 2, 3; u32]");
+    let t = Tensor::arange(0i64, 6, &Cpu)?.reshape((2, 3))?;
+    assert_eq!(
+        format!("{t:?}"),
+        "Tensor[[[0, 1, 2], [3, 4, 5]]; dims 2, 3; i64]"
+    );
+    let t = Tensor::arange(0u32, 80, &Cpu)?.reshape((8, 10))?;
+    let s = format!("{t:?}");
+    assert!(s.starts_with("Tensor[[[0, 1, 2, 3, 4, 5, 6, 7, 8, 9], [10, 11,"));
+    assert!(s.ends_with("77, 78, 79]]; dims 8, 10; u32]"), "{s}");
+    assert!(!s.contains("..."), "{s}");
+    display::set_print_options(display::PrinterOptions {
+        threshold: 50,
+        ..Default::default()
+    });
+    let s = format!("{t:?}");
+    display::set_print_options_default();
+    assert_eq!(
+        s,
+        "Tensor[[[0, 1, 2, ..., 7, 8, 9], [10, 11, 12, ..., 17, 18, 19], \
+         [20, 21, 22, ..., 27, 28, 29], ..., [50, 51, 52, ..., 57, 58, 59], \
+         [60, 61, 62, ..., 67, 68, 69], [70, 71, 72, ..., 77, 78, 79]]; dims 8, 10; u32]"
+    );
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use candle_core::{test_device, test_utils, Device, Shape, Tensor, Var};
+use candle_core::{test_device, test_utils, Device, IndexOp, PadMode, Shape, Tensor, Var};
 
 fn simple_grad(device: &Device) -> Result<()> {
     let x = Var::new(&[3f32, 1., 4.], device)?;
@@ -192,6 +192,75 @@ fn unary_grad(device: &Device) -> Result<()> {
         test_utils::to_vec1_round(grad_x, 2)?,
         [0.01, 0.42, 0.0, 0.98],
     );
+
+    let x = Var::new(&[-2f32, -0.5, 0., 1.5], device)?;
+    let y = x.gelu()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec1_round(&y, 4)?,
+        [-0.0454, -0.1543, 0.0, 1.3996]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(grad_x, 4)?,
+        [-0.0861, 0.1326, 0.5, 1.1277]
+    );
+
+    // maximum_scalar(0.) behaves as relu, ties split the gradient.
+    let y = x.maximum_scalar(0.)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(y.to_vec1::<f32>()?, [0., 0., 0., 1.5]);
+    assert_eq!(grad_x.to_vec1::<f32>()?, [0., 0., 0.5, 1.]);
+    let y = x.minimum_scalar(-1.)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(y.to_vec1::<f32>()?, [-2., -1., -1., -1.]);
+    assert_eq!(grad_x.to_vec1::<f32>()?, [1., 0., 0., 0.]);
+
+    /* Equivalent python code:
+    import torch
+    b = torch.tensor([[0.5, 1., 2.], [3., 0.25, 1.5]], requires_grad=True)
+    e = torch.tensor([2., -0.5, 1.3], requires_grad=True)
+    y = torch.pow(b, e)
+    y.sum().backward()
+    print(y, b.grad, e.grad)
+    */
+    let b = Var::new(&[[0.5f32, 1., 2.], [3., 0.25, 1.5]], device)?;
+    let e = Var::new(&[2f32, -0.5, 1.3], device)?;
+    let y = b.broadcast_pow(&e)?;
+    let grads = y.backward()?;
+    let grad_b = grads.get(&b).context("no grad for b")?;
+    let grad_e = grads.get(&e).context("no grad for e")?;
+    assert_eq!(
+        test_utils::to_vec2_round(&y, 4)?,
+        [[0.25, 1.0, 2.4623], [9.0, 2.0, 1.694]]
+    );
+    assert_eq!(
+        test_utils::to_vec2_round(grad_b, 4)?,
+        [[1.0, -0.5, 1.6005], [6.0, -4.0, 1.4682]]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(grad_e, 4)?,
+        [9.7142, -2.7726, 2.3936]
+    );
+    assert!(b.pow(&e).is_err());
+    let y = Tensor::new(&[-2f32, 0.], device)?.pow(&Tensor::new(&[2f32, 2.], device)?)?;
+    let y = y.to_vec1::<f32>()?;
+    assert!(y[0].is_nan());
+    assert_eq!(y[1], 0.);
+
+    let y = x.elu(0.5)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec1_round(&y, 4)?,
+        [-0.4323, -0.1967, 0.0, 1.5]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(grad_x, 4)?,
+        [0.0677, 0.3033, 0.5, 1.0]
+    );
     Ok(())
 }
 
@@ -221,9 +290,431 @@ fn binary_grad(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn pad_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[1f32, 2., 3., 4.], device)?;
+    // The padded output is [3, 2, 1, 2, 3, 4, 3], the gradients for 2 and 3 get accumulated.
+    let y = x.pad(0, 2, 1, PadMode::Reflect)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [1., 2., 3., 1.]);
+
+    let y = x.pad(0, 2, 3, PadMode::Replicate)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [3., 1., 1., 4.]);
+
+    let y = x.pad(0, 1, 5, PadMode::Circular)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [3., 2., 2., 3.]);
+    Ok(())
+}
+
+fn pool1d_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[[1f32, 2., 4., 3., 0., 5.]]], device)?;
+    let y = x.avg_pool1d(2)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.to_vec3::<f32>()?,
+        [[[0.5f32, 0.5, 0.5, 0.5, 0.5, 0.5]]]
+    );
+
+    let y = x.max_pool1d(3)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec3::<f32>()?, [[[0f32, 0., 1., 0., 0., 1.]]]);
+
+    // Overlapping windows [1, 2, 4] and [4, 3, 0]: the 4 is the max of both windows.
+    let y = x.max_pool1d_with_stride(3, 2)?;
+    assert_eq!(y.to_vec3::<f32>()?, [[[4f32, 4.]]]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec3::<f32>()?, [[[0f32, 0., 2., 0., 0., 0.]]]);
+
+    let y = x.avg_pool1d_with_stride(2, 3)?;
+    assert_eq!(y.to_vec3::<f32>()?, [[[1.5f32, 1.5]]]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.to_vec3::<f32>()?,
+        [[[0.5f32, 0.5, 0., 0.5, 0.5, 0.]]]
+    );
+
+    // With padding, the padded positions get no gradient.
+    let y = x.max_pool1d_with_padding(2, 2, 1, false)?;
+    assert_eq!(y.to_vec3::<f32>()?, [[[1f32, 4., 3., 5.]]]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec3::<f32>()?, [[[1f32, 0., 1., 1., 0., 1.]]]);
+    let y = x.avg_pool1d_with_padding(2, 2, 1, false, true)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec3::<f32>()?, [[[0.5f32; 6]]]);
+    Ok(())
+}
+
+fn max_pool2d_with_indices_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[[[1f32, 4., 4.], [2., 0., 3.], [1., 5., 2.]]]], device)?;
+    // Overlapping windows: the 4 at position 1 is the max of both top windows.
+    let (y, _indices) = x.max_pool2d_with_indices(2, 1)?;
+    assert_eq!(y.i((0, 0))?.to_vec2::<f32>()?, [[4f32, 4.], [5., 5.]]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.i((0, 0))?.to_vec2::<f32>()?,
+        [[0f32, 2., 0.], [0., 0., 0.], [0., 2., 0.]]
+    );
+    Ok(())
+}
+
+fn norm_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[3f32, -4.], [1., 0.]], device)?;
+    let y = x.norm(2., 1, false)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[0.6, -0.8], [1., 0.]]);
+
+    let y = x.norm(f64::INFINITY, 1, false)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[0., -1.], [1., 0.]]);
+
+    // The gradient of the norm is 0 rather than NaN for zero inputs.
+    let x = Var::new(&[[3f32, -4.], [0., 0.]], device)?;
+    for p in [2., 3.] {
+        let y = x.norm(p, 1, false)?.sum_all()?;
+        let grads = y.backward()?;
+        let grad_x = grads.get(&x).context("no grad for x")?;
+        assert_eq!(grad_x.i(1)?.to_vec1::<f32>()?, [0., 0.]);
+    }
+    let y = x.normalize(2., 1, 1e-2)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec2_round(grad_x, 4)?,
+        [[0.224, 0.168], [100., 100.]]
+    );
+    Ok(())
+}
+
+fn pool2d_with_padding_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[[[1f32, 2., 3., 4., 5.]]]], device)?;
+    let y = x.avg_pool2d_with_padding((1, 3), (1, 2), (0, 1), true, false)?;
+    // Windows: [pad, 1, 2], [2, 3, 4], [4, 5, pad].
+    assert_eq!(y.i((0, 0))?.to_vec2::<f32>()?, [[1.5, 3., 4.5]]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec3_round(&grad_x.i(0)?, 4)?,
+        [[[0.5, 0.8333, 0.3333, 0.8333, 0.5]]]
+    );
+
+    let y = x.max_pool2d_with_padding((1, 3), (1, 2), (0, 1), true)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.i((0, 0))?.to_vec2::<f32>()?, [[0., 1., 0., 1., 1.]]);
+    Ok(())
+}
+
+fn upsample_bilinear2d_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[[[1f32, 2., 3.]]]], device)?;
+    // The two outputs are at positions 0.25 and 1.75 of the input.
+    let y = x.upsample_bilinear2d(1, 2, false)?;
+    assert_eq!(y.i((0, 0))?.to_vec2::<f32>()?, [[1.25, 2.75]]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.i((0, 0))?.to_vec2::<f32>()?, [[0.75, 0.5, 0.75]]);
+    Ok(())
+}
+
+fn upsample_nearest_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[[1f32, 2., 3.]]], device)?;
+    let y = x.upsample_nearest1d(5)?;
+    let grads = (y * Tensor::new(&[[[1f32, 2., 3., 4., 5.]]], device)?)?
+        .sum_all()?
+        .backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec3::<f32>()?, [[[3f32, 7., 5.]]]);
+
+    let x = Var::new(&[[[[1f32, 2.], [3., 4.]]]], device)?;
+    let y = x.upsample_nearest2d(4, 3)?;
+    let grads = y.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.i((0, 0))?.to_vec2::<f32>()?, [[4f32, 2.], [4., 2.]]);
+
+    let x = Var::from_tensor(&Tensor::new(&[1f32, 2., 3., 4.], device)?.reshape((1, 1, 2, 1, 2))?)?;
+    let y = x.upsample_nearest3d(3, 2, 2)?;
+    let grads = y.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.i((0, 0))?.to_vec3::<f32>()?,
+        [[[4f32, 4.]], [[2., 2.]]]
+    );
+    Ok(())
+}
+
+fn grid_sample_grad(device: &Device) -> Result<()> {
+    use candle_core::{GridSampleMode, GridSamplePadding};
+    let x = Var::new(&[[[[1f32, 3.]]]], device)?;
+    let grid = Var::new(&[[[[0f32, 0.5]]]], device)?;
+    let y = x.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridSamplePadding::Zeros,
+        true,
+    )?;
+    assert_eq!(y.flatten_all()?.to_vec1::<f32>()?, [2.]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.flatten_all()?.to_vec1::<f32>()?, [0.5, 0.5]);
+    // dy/dgx = (3 - 1) * (w - 1) / 2, the input has a single row so y has no effect.
+    let grad_grid = grads.get(&grid).context("no grad for grid")?;
+    assert_eq!(grad_grid.flatten_all()?.to_vec1::<f32>()?, [1., 0.]);
+
+    // With border padding, the x coordinate of the second location gets clamped to the last
+    // column so it does not get any gradient.
+    let x = Var::new(&[[[[1f32, 2.], [3., 4.]]]], device)?;
+    let grid = Var::new(&[[[[0f32, 0.], [1.5, -1.]]]], device)?;
+    let y = x.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridSamplePadding::Border,
+        true,
+    )?;
+    assert_eq!(y.flatten_all()?.to_vec1::<f32>()?, [2.5, 2.]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.flatten_all()?.to_vec1::<f32>()?,
+        [0.25, 1.25, 0.25, 0.25]
+    );
+    let grad_grid = grads.get(&grid).context("no grad for grid")?;
+    assert_eq!(
+        grad_grid.flatten_all()?.to_vec1::<f32>()?,
+        [0.5, 1., 0., 1.]
+    );
+
+    let y = x.grid_sample(
+        &grid,
+        GridSampleMode::Nearest,
+        GridSamplePadding::Border,
+        true,
+    )?;
+    assert_eq!(y.flatten_all()?.to_vec1::<f32>()?, [4., 2.]);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.flatten_all()?.to_vec1::<f32>()?, [0., 1., 0., 1.]);
+    let grad_grid = grads.get(&grid).context("no grad for grid")?;
+    assert_eq!(grad_grid.flatten_all()?.to_vec1::<f32>()?, [0., 0., 0., 0.]);
+    Ok(())
+}
+
+fn unfold2d_grad(device: &Device) -> Result<()> {
+    let x = Var::from_tensor(&Tensor::arange(0f32, 9., device)?.reshape((1, 1, 3, 3))?)?;
+    let w = Tensor::arange(0f32, 16., device)?.reshape((1, 4, 4))?;
+    let y = (x.unfold2d((2, 2), 1, 0, 1)? * &w)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    // The gradient of unfold is fold.
+    assert_eq!(
+        grad_x.flatten_all()?.to_vec1::<f32>()?,
+        w.fold2d((3, 3), (2, 2), 1, 0, 1)?
+            .flatten_all()?
+            .to_vec1::<f32>()?
+    );
+    assert_eq!(
+        grad_x.i((0, 0))?.to_vec2::<f32>()?,
+        [[0., 5., 5.], [10., 30., 20.], [10., 25., 15.]]
+    );
+    Ok(())
+}
+
+fn trace_mt_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let w = Tensor::new(&[[1f32, 0.], [2., 1.]], device)?;
+    // trace(x^t w) is the sum of the elementwise product so its gradient is w.
+    let y = x.mT()?.matmul(&w)?.trace()?;
+    assert_eq!(y.to_scalar::<f32>()?, 11.);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[1., 0.], [2., 1.]]);
+    Ok(())
+}
+
+fn as_strided_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[1f32, 2., 6., 3., 9., 0.], device)?;
+    let windows = x.as_strided(&[4, 3], &[1, 1], 0)?;
+    let y = (windows.sum(1)? * Tensor::new(&[1f32, 1., 1., 2.], device)?)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    // Each element gets the weights of the windows it belongs to.
+    assert_eq!(grad_x.to_vec1::<f32>()?, [1., 2., 3., 4., 3., 2.]);
+    Ok(())
+}
+
+fn unfold_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let windows = x.unfold(1, 2, 1)?;
+    let y = windows.sqr()?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    // The middle column belongs to two windows.
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[2., 8., 6.], [8., 20., 12.]]);
+    Ok(())
+}
+
+fn split_unbind_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let pieces = x.split(&[2, 1], 1)?;
+    let rows = x.unbind(0)?;
+    let y = ((pieces[0].sum_all()? * 2.)? + pieces[1].sqr()?.sum_all()?)?;
+    let y = (y + (&rows[1] * Tensor::new(&[1f32, 10., 100.], device)?)?.sum_all()?)?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[2., 2., 6.], [3., 12., 112.]]);
+    Ok(())
+}
+
+fn conv3d_grad(device: &Device) -> Result<()> {
+    let x = Var::from_tensor(
+        &Tensor::arange(0f32, 54., device)?
+            .affine(0.3, 0.)?
+            .sin()?
+            .reshape((1, 2, 3, 3, 3))?,
+    )?;
+    let w = Var::from_tensor(
+        &Tensor::arange(0f32, 32., device)?
+            .affine(0.7, 0.)?
+            .cos()?
+            .reshape((2, 2, 2, 2, 2))?,
+    )?;
+    let g = Tensor::arange(0f32, 16., device)?
+        .affine(0.2, -1.)?
+        .reshape((1, 2, 2, 2, 2))?;
+    let loss = |x: &Tensor, w: &Tensor| (x.conv3d(w, 1, 2, 1, 1)? * &g)?.sum_all();
+    let grads = loss(&x, &w)?.backward()?;
+    // The loss is linear in each argument so each gradient entry is the loss obtained when
+    // replacing this argument with the corresponding basis tensor.
+    let basis = |i: usize, t: &Tensor| {
+        let mut v = vec![0f32; t.elem_count()];
+        v[i] = 1.;
+        Tensor::from_vec(v, t.shape(), device)
+    };
+    for var in [&x, &w] {
+        let grad = grads.get(var).context("no grad")?.flatten_to_vec::<f32>()?;
+        for (i, grad) in grad.iter().enumerate() {
+            let b = basis(i, var)?;
+            let expected = if std::ptr::eq(var, &x) {
+                loss(&b, &w)?
+            } else {
+                loss(&x, &b)?
+            };
+            let expected = expected.to_scalar::<f32>()?;
+            assert!((grad - expected).abs() < 1e-4, "{i} {grad} {expected}")
+        }
+    }
+    Ok(())
+}
+
+fn repeat_interleave_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let y = x.repeat_interleave(3, 1)?;
+    let w = Tensor::arange(0f32, 12., device)?.reshape((2, 6))?;
+    let grads = (y * w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    // Each element gets the sum of the weights of its three copies.
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[3., 12.], [21., 30.]]);
+    Ok(())
+}
+
+fn indexes_from_tracked_grad(device: &Device) -> Result<()> {
+    // The indexes and predicates computed from a tracked tensor do not get a gradient.
+    let x = Var::new(&[[1f32, 3., 2.], [5., 4., 0.]], device)?;
+    let argmax = x.argmax_keepdim(1)?;
+    let y = x.gather(&argmax, 1)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[0f32, 1., 0.], [1., 0., 0.]]);
+    assert!(grads.get(&argmax).is_none());
+
+    let x = x.as_tensor();
+    let mask = x.ge(&Tensor::new(2f32, device)?.broadcast_as(x.shape())?)?;
+    let y = mask.where_cond(x, &(x * 3.)?)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[3f32, 1., 1.], [1., 1., 3.]]);
+    assert!(grads.get(&mask).is_none());
+    Ok(())
+}
+
+fn max_pool2d_grad_ties(device: &Device) -> Result<()> {
+    // The gradient of each window is split evenly between the elements tied for the maximum.
+    let x = Var::new(&[[[[1f32, 1., 0., 2.], [0., 1., 2., 2.]]]], device)?;
+    let y = x.max_pool2d(2)?;
+    assert_eq!(y.flatten_all()?.to_vec1::<f32>()?, [1., 2.]);
+    let w = Tensor::new(&[[[[2f32, 3.]]]], device)?;
+    let grads = y.mul(&w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec1_round(&grad_x.flatten_all()?, 4)?,
+        [0.6667, 0.6667, 0.0, 1.0, 0.0, 0.6667, 1.0, 1.0]
+    );
+    Ok(())
+}
+
 test_device!(simple_grad, simple_grad_cpu, simple_grad_gpu);
 test_device!(sum_grad, sum_grad_cpu, sum_grad_gpu);
 test_device!(matmul_grad, matmul_grad_cpu, matmul_grad_gpu);
 test_device!(grad_descent, grad_descent_cpu, grad_descent_gpu);
 test_device!(unary_grad, unary_grad_cpu, unary_grad_gpu);
 test_device!(binary_grad, binary_grad_cpu, binary_grad_gpu);
+test_device!(pad_grad, pad_grad_cpu, pad_grad_gpu);
+test_device!(
+    max_pool2d_grad_ties,
+    max_pool2d_grad_ties_cpu,
+    max_pool2d_grad_ties_gpu
+);
+test_device!(pool1d_grad, pool1d_grad_cpu, pool1d_grad_gpu);
+test_device!(
+    max_pool2d_with_indices_grad,
+    max_pool2d_with_indices_grad_cpu,
+    max_pool2d_with_indices_grad_gpu
+);
+test_device!(norm_grad, norm_grad_cpu, norm_grad_gpu);
+test_device!(
+    pool2d_with_padding_grad,
+    pool2d_with_padding_grad_cpu,
+    pool2d_with_padding_grad_gpu
+);
+test_device!(
+    upsample_bilinear2d_grad,
+    upsample_bilinear2d_grad_cpu,
+    upsample_bilinear2d_grad_gpu
+);
+test_device!(
+    upsample_nearest_grad,
+    upsample_nearest_grad_cpu,
+    upsample_nearest_grad_gpu
+);
+test_device!(grid_sample_grad, grid_sample_grad_cpu, grid_sample_grad_gpu);
+test_device!(unfold2d_grad, unfold2d_grad_cpu, unfold2d_grad_gpu);
+test_device!(trace_mt_grad, trace_mt_grad_cpu, trace_mt_grad_gpu);
+test_device!(as_strided_grad, as_strided_grad_cpu, as_strided_grad_gpu);
+test_device!(unfold_grad, unfold_grad_cpu, unfold_grad_gpu);
+test_device!(
+    split_unbind_grad,
+    split_unbind_grad_cpu,
+    split_unbind_grad_gpu
+);
+test_device!(conv3d_grad, conv3d_grad_cpu, conv3d_grad_gpu);
+test_device!(
+    repeat_interleave_grad,
+    repeat_interleave_grad_cpu,
+    repeat_interleave_grad_gpu
+);
+test_device!(
+    indexes_from_tracked_grad,
+    indexes_from_tracked_grad_cpu,
+    indexes_from_tracked_grad_gpu
+);
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use candle_core::utils::image::{
+    load_image, load_image_with_options, save_image, ImageLayout, LoadImageOptions,
+};
+use candle_core::{DType, Device, Tensor};
+
+fn tmp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("candle-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn image_round_trip() -> Result<()> {
+    let dev = &Device::Cpu;
+    let pixels = Tensor::arange(0u8, 24, dev)?.affine(10., 5.)?;
+    let img = pixels.reshape((3, 2, 4))?;
+    let path = tmp_path("round_trip.png");
+    save_image(&img, &path)?;
+    let loaded = load_image(&path, 2, 4);
+    std::fs::remove_file(&path)?;
+    let loaded = loaded?;
+    assert_eq!(loaded.dims(), [3, 2, 4]);
+    assert_eq!(loaded.dtype(), DType::F32);
+    let back = (loaded * 255.)?.round()?.to_dtype(DType::U8)?;
+    assert_eq!(back.to_vec3::<u8>()?, img.to_vec3::<u8>()?);
+
+    // Float images are scaled from [0, 1] and clamped.
+    let img = Tensor::new(&[[[0f32, 0.5]], [[1., 2.]], [[-1., 0.2]]], dev)?;
+    let path = tmp_path("float.png");
+    save_image(&img, &path)?;
+    let loaded = load_image(&path, 1, 2);
+    std::fs::remove_file(&path)?;
+    let back = (loaded? * 255.)?.round()?.to_dtype(DType::U8)?;
+    assert_eq!(back.to_vec3::<u8>()?, [[[0, 128]], [[255, 255]], [[0, 51]]]);
+    Ok(())
+}
+
+#[test]
+fn image_options() -> Result<()> {
+    let dev = &Device::Cpu;
+    let img = Tensor::ones((3, 4, 6), DType::U8, dev)?.affine(1., 50.)?;
+    let path = tmp_path("options.png");
+    save_image(&img, &path)?;
+    let options = LoadImageOptions {
+        layout: ImageLayout::Hwc,
+        batch_dim: true,
+        normalize: Some(([0.1, 0.2, 0.3], [0.5, 0.5, 2.])),
+    };
+    let nhwc = load_image_with_options(&path, 4, 6, &options);
+    // Resizing a uniform image keeps its values.
+    let resized = load_image(&path, 2, 3);
+    std::fs::remove_file(&path)?;
+    let nhwc = nhwc?;
+    assert_eq!(nhwc.dims(), [1, 4, 6, 3]);
+    let v = nhwc.flatten_to(2)?.to_vec2::<f32>()?;
+    // The pixel values are 51 / 255 = 0.2.
+    let expected = [0.2, 0., -0.05];
+    for pixel in v {
+        for (p, e) in pixel.iter().zip(expected) {
+            assert!((p - e).abs() < 1e-5, "{p} {e}")
+        }
+    }
+    let resized = resized?;
+    assert_eq!(resized.dims(), [3, 2, 3]);
+    let v = resized.flatten_all()?.to_vec1::<f32>()?;
+    assert!(v.iter().all(|v| (v - 51. / 255.).abs() < 1e-6));
+
+    assert!(save_image(&img.narrow(0, 0, 2)?, tmp_path("bad.png")).is_err());
+    assert!(save_image(&img.to_dtype(DType::U32)?, tmp_path("bad.png")).is_err());
+    assert!(load_image(tmp_path("missing.png"), 2, 2).is_err());
+    Ok(())
+}
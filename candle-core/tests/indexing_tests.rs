@@ -1,5 +1,5 @@
 use anyhow::Result;
-use candle_core::{Device, IndexOp, Tensor};
+use candle_core::{Device, IndexOp, Tensor, Var};
 
 #[test]
 fn integer_index() -> Result<()> {
@@ -91,3 +91,36 @@ fn index_3d() -> Result<()> {
     assert_eq!(tensor.i((1, .., 3))?.to_vec1::<u32>()?, &[15, 19, 23]);
     Ok(())
 }
+
+#[test]
+fn index_matmul_backward() -> Result<()> {
+    let dev = Device::Cpu;
+    let x = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], &dev)?;
+    let w = Tensor::new(&[[1f32, 0.], [0., 1.], [1., 1.]], &dev)?;
+    let ys = x.i((.., 1..))?.matmul(&w.i(1..)?)?;
+    assert_eq!(ys.to_vec2::<f32>()?, &[[3., 5.], [6., 11.]]);
+    let grads = ys.i(1)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).unwrap();
+    assert_eq!(grad_x.to_vec2::<f32>()?, &[[0., 0., 0.], [0., 1., 2.]]);
+    Ok(())
+}
+
+#[test]
+fn index_errors() -> Result<()> {
+    let tensor = Tensor::arange(0u32, 2 * 3, &Device::Cpu)?.reshape((2, 3))?;
+    let err = tensor.i((.., 3)).unwrap_err().to_string();
+    assert!(
+        err.contains("index 3 out of range for dim 1 of size 3"),
+        "{err}"
+    );
+    let err = tensor.i((1, 1..4)).unwrap_err().to_string();
+    assert!(
+        err.contains("range 1..4 out of range for dim 1 of size 3"),
+        "{err}"
+    );
+    let err = tensor.i(3..).unwrap_err().to_string();
+    assert!(err.contains("range 3..2"), "{err}");
+    let err = tensor.i((0, 0, 0)).unwrap_err().to_string();
+    assert!(err.contains("3 indexers"), "{err}");
+    Ok(())
+}
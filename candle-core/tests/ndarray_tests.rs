@@ -0,0 +1,47 @@
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+use ndarray::{array, Array3, ArrayD, IxDyn};
+
+#[test]
+fn ndarray_round_trip() -> Result<()> {
+    let dev = &Device::Cpu;
+    let array = Array3::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f32);
+    let t = Tensor::from_ndarray(&array, dev)?;
+    assert_eq!(t.dims(), [2, 3, 4]);
+    assert_eq!(t.dtype(), DType::F32);
+    assert_eq!(t.to_vec3::<f32>()?[1][2], [20., 21., 22., 23.]);
+    assert_eq!(t.to_ndarray::<f32>()?, array.into_dyn());
+
+    let array = array![[1u32, 2], [3, 4]];
+    let t = Tensor::new(&array, dev)?;
+    assert_eq!(t.dtype(), DType::U32);
+    assert_eq!(t.to_ndarray::<u32>()?, array.into_dyn());
+
+    let array = ArrayD::from_shape_vec(IxDyn(&[]), vec![-3i64])?;
+    let t = Tensor::from_ndarray(&array, dev)?;
+    assert_eq!(t.rank(), 0);
+    assert_eq!(t.to_scalar::<i64>()?, -3);
+    assert_eq!(t.to_ndarray::<i64>()?, array);
+    Ok(())
+}
+
+#[test]
+fn ndarray_layouts() -> Result<()> {
+    let dev = &Device::Cpu;
+    // Non-contiguous arrays are copied in their logical order.
+    let array = array![[0f64, 1., 2.], [3., 4., 5.]];
+    let t = Tensor::from_ndarray(&array.t(), dev)?;
+    assert_eq!(t.to_vec2::<f64>()?, [[0., 3.], [1., 4.], [2., 5.]]);
+    let t = Tensor::from_ndarray(&array.slice(ndarray::s![.., ..;2]), dev)?;
+    assert_eq!(t.to_vec2::<f64>()?, [[0., 2.], [3., 5.]]);
+
+    // Non-contiguous tensors are converted to standard layout arrays.
+    let t = Tensor::arange(0f64, 6., dev)?.reshape((2, 3))?.t()?;
+    let back = t.to_ndarray::<f64>()?;
+    assert!(back.is_standard_layout());
+    assert_eq!(back, array.t().into_dyn());
+
+    // The requested element type has to match the tensor dtype.
+    assert!(t.to_ndarray::<f32>().is_err());
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+
+fn tmp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("candle-npy-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn npy_round_trip() -> Result<()> {
+    let t = Tensor::arange(0f32, 24., &Device::Cpu)?.reshape((2, 3, 4))?;
+    for dtype in [DType::F32, DType::F64, DType::I64, DType::U32, DType::U8] {
+        let t = t.to_dtype(dtype)?;
+        let path = tmp_path("round_trip.npy");
+        t.write_npy(&path)?;
+        let read = Tensor::read_npy(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(read.dtype(), dtype);
+        assert_eq!(read.dims(), &[2, 3, 4]);
+        assert_eq!(
+            read.to_dtype(DType::F32)?.to_vec3::<f32>()?,
+            t.to_dtype(DType::F32)?.to_vec3::<f32>()?
+        );
+    }
+
+    // Non-contiguous tensors are written in C order.
+    let t = t.transpose(0, 2)?;
+    let path = tmp_path("transposed.npy");
+    t.write_npy(&path)?;
+    let read = Tensor::read_npy(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(read.to_vec3::<f32>()?, t.to_vec3::<f32>()?);
+    Ok(())
+}
+
+#[test]
+fn npz_round_trip() -> Result<()> {
+    let a = Tensor::arange(0f64, 6., &Device::Cpu)?.reshape((2, 3))?;
+    let b = Tensor::new(&[1u8, 2, 3], &Device::Cpu)?;
+    let path = tmp_path("round_trip.npz");
+    Tensor::write_npz(&[("a", &a), ("b", &b)], &path)?;
+    let mut read = Tensor::read_npz(&path)?;
+    read.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
+    let [b_read] = &Tensor::read_npz_by_name(&path, &["b"])?[..] else {
+        panic!("expected a single tensor")
+    };
+    let npz = candle_core::npy::NpzTensors::new(&path)?;
+    let a_lazy = npz.get("a")?.expect("no tensor for a");
+    std::fs::remove_file(&path)?;
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[0].0, "a");
+    assert_eq!(read[0].1.to_vec2::<f64>()?, a.to_vec2::<f64>()?);
+    assert_eq!(read[1].0, "b");
+    assert_eq!(read[1].1.to_vec1::<u8>()?, [1, 2, 3]);
+    assert_eq!(b_read.to_vec1::<u8>()?, [1, 2, 3]);
+    assert_eq!(a_lazy.to_vec2::<f64>()?, a.to_vec2::<f64>()?);
+    Ok(())
+}
+
+#[test]
+fn npy_fortran_order() -> Result<()> {
+    // The bytes written by NumPy for:
+    // np.save(f, np.asfortranarray(np.arange(6, dtype=np.float32).reshape(2, 3)))
+    let mut bytes = b"\x93NUMPY\x01\x00v\x00".to_vec();
+    bytes.extend_from_slice(b"{'descr': '<f4', 'fortran_order': True, 'shape': (2, 3), }");
+    bytes.extend_from_slice(&[b' '; 59]);
+    bytes.push(b'\n');
+    for v in [0f32, 3., 1., 4., 2., 5.] {
+        bytes.extend_from_slice(&v.to_le_bytes())
+    }
+    let path = tmp_path("fortran.npy");
+    std::fs::write(&path, bytes)?;
+    let t = Tensor::read_npy(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(t.dims(), &[2, 3]);
+    assert!(!t.is_contiguous());
+    assert_eq!(t.to_vec2::<f32>()?, [[0., 1., 2.], [3., 4., 5.]]);
+    Ok(())
+}
+
+#[test]
+fn npy_header_versions() -> Result<()> {
+    let header = b"{'descr': '<i8', 'fortran_order': False, 'shape': (3,), }";
+    let data: Vec<u8> = [1i64, -2, 3].iter().flat_map(|v| v.to_le_bytes()).collect();
+    // Version 2 headers use a 4 bytes header length.
+    let mut bytes = b"\x93NUMPY\x02\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u32 + 1).to_le_bytes());
+    bytes.extend_from_slice(header);
+    bytes.push(b'\n');
+    bytes.extend_from_slice(&data);
+    let path = tmp_path("v2.npy");
+    std::fs::write(&path, bytes)?;
+    let t = Tensor::read_npy(&path)?;
+    assert_eq!(t.to_vec1::<i64>()?, [1, -2, 3]);
+
+    // The writer emits version 1 headers with the data aligned on 64 bytes.
+    let t = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?.to_dtype(DType::F16)?;
+    t.write_npy(&path)?;
+    let bytes = std::fs::read(&path)?;
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    assert_eq!(bytes.len(), 10 + header_len + 8);
+    let header = std::str::from_utf8(&bytes[10..10 + header_len])?;
+    assert!(header.starts_with("{'descr': '<f2', 'fortran_order': False, 'shape': (2,2,), }"));
+    assert!(header.ends_with(" \n"));
+    let read = Tensor::read_npy(&path)?;
+    assert_eq!(
+        read.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+        [[1., 2.], [3., 4.]]
+    );
+
+    let mut bytes = bytes;
+    bytes[6] = 4;
+    std::fs::write(&path, bytes)?;
+    assert!(Tensor::read_npy(&path).is_err());
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn npz_map() -> Result<()> {
+    let a = Tensor::arange(0u32, 4, &Device::Cpu)?.reshape((2, 2))?;
+    let b = Tensor::new(&[0.5f64, -1.5], &Device::Cpu)?;
+    let path = tmp_path("map.npz");
+    Tensor::write_npz(&[("layer.a", &a), ("layer.b", &b)], &path)?;
+    let map = Tensor::read_npz_map(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["layer.a"].to_vec2::<u32>()?, [[0, 1], [2, 3]]);
+    assert_eq!(map["layer.b"].to_vec1::<f64>()?, [0.5, -1.5]);
+    Ok(())
+}
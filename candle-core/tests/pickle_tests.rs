@@ -0,0 +1,171 @@
+use candle_core::{pickle, DType, Result};
+use std::io::Write;
+
+// Minimal pickle writer producing the same op-codes as `torch.save` with protocol 2.
+struct Pickle(Vec<u8>);
+
+impl Pickle {
+    fn new() -> Self {
+        Self(vec![0x80, 2])
+    }
+
+    fn op(&mut self, op: u8) -> &mut Self {
+        self.0.push(op);
+        self
+    }
+
+    fn global(&mut self, module_name: &str, class_name: &str) -> &mut Self {
+        self.0.push(b'c');
+        self.0
+            .extend_from_slice(format!("{module_name}\n{class_name}\n").as_bytes());
+        self
+    }
+
+    fn unicode(&mut self, s: &str) -> &mut Self {
+        self.0.push(b'X');
+        self.0.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        self.0.extend_from_slice(s.as_bytes());
+        self
+    }
+
+    fn int(&mut self, v: usize) -> &mut Self {
+        self.0.push(b'J');
+        self.0.extend_from_slice(&(v as i32).to_le_bytes());
+        self
+    }
+
+    fn ints(&mut self, vs: &[usize]) -> &mut Self {
+        self.op(b'(');
+        for &v in vs.iter() {
+            self.int(v);
+        }
+        self.op(b't')
+    }
+
+    // A `torch._utils._rebuild_tensor_v2` call on a persistent storage.
+    fn tensor(
+        &mut self,
+        name: &str,
+        storage: (&str, &str, usize),
+        offset: usize,
+        size: &[usize],
+        stride: &[usize],
+    ) -> &mut Self {
+        let (storage_type, key, numel) = storage;
+        self.unicode(name)
+            .global("torch._utils", "_rebuild_tensor_v2")
+            .op(b'(')
+            .op(b'(')
+            .unicode("storage")
+            .global("torch", storage_type)
+            .unicode(key)
+            .unicode("cpu")
+            .int(numel)
+            .op(b't')
+            .op(b'Q')
+            .int(offset)
+            .ints(size)
+            .ints(stride)
+            .op(0x89)
+            .global("collections", "OrderedDict")
+            .op(b')')
+            .op(b'R')
+            .op(b't')
+            .op(b'R')
+    }
+}
+
+fn write_archive(path: &std::path::Path, pkl: &[u8], storages: &[(&str, Vec<u8>)]) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("archive/data.pkl", options)?;
+    zip.write_all(pkl)?;
+    for (key, data) in storages.iter() {
+        zip.start_file(format!("archive/data/{key}"), options)?;
+        zip.write_all(data)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn le_bytes<T: Copy, const N: usize>(vs: &[T], f: impl Fn(T) -> [u8; N]) -> Vec<u8> {
+    vs.iter().flat_map(|&v| f(v)).collect()
+}
+
+#[test]
+fn read_pth() -> Result<()> {
+    let f32_data: Vec<f32> = (0..6).map(|v| v as f32).collect();
+    let f16_data = [1.5f32, -2., 0.25].map(half::f16::from_f32);
+    let mut pkl = Pickle::new();
+    pkl.op(b'}').op(b'(');
+    pkl.tensor("weight", ("FloatStorage", "0", 6), 0, &[2, 3], &[3, 1]);
+    // A transposed view and an offset view sharing the storage of `weight`.
+    pkl.tensor("weight_t", ("FloatStorage", "0", 6), 0, &[3, 2], &[1, 3]);
+    pkl.tensor("tail", ("FloatStorage", "0", 6), 4, &[2], &[1]);
+    pkl.tensor("half", ("HalfStorage", "1", 3), 0, &[3], &[1]);
+    pkl.tensor("steps", ("LongStorage", "2", 1), 0, &[], &[]);
+    pkl.op(b'u').op(b'.');
+
+    let tmp_dir = std::env::temp_dir();
+    let path = tmp_dir.join("candle_pickle_test.pt");
+    write_archive(
+        &path,
+        &pkl.0,
+        &[
+            ("0", le_bytes(&f32_data, f32::to_le_bytes)),
+            ("1", le_bytes(&f16_data, half::f16::to_le_bytes)),
+            ("2", 42i64.to_le_bytes().to_vec()),
+        ],
+    )?;
+
+    let tensors = pickle::read_all(&path)?;
+    let names: Vec<_> = tensors.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, ["weight", "weight_t", "tail", "half", "steps"]);
+    let t = &tensors[0].1;
+    assert_eq!(t.to_vec2::<f32>()?, [[0., 1., 2.], [3., 4., 5.]]);
+    let t = &tensors[1].1;
+    assert_eq!(t.to_vec2::<f32>()?, [[0., 3.], [1., 4.], [2., 5.]]);
+    assert_eq!(tensors[2].1.to_vec1::<f32>()?, [4., 5.]);
+    let t = &tensors[3].1;
+    assert_eq!(t.dtype(), DType::F16);
+    assert_eq!(t.to_dtype(DType::F32)?.to_vec1::<f32>()?, [1.5, -2., 0.25]);
+    assert_eq!(tensors[4].1.to_scalar::<i64>()?, 42);
+
+    let pth = pickle::PthTensors::new(&path)?;
+    assert_eq!(pth.tensor_infos().len(), 5);
+    let t = pth.get("weight_t")?.unwrap();
+    assert_eq!(t.to_vec2::<f32>()?, [[0., 3.], [1., 4.], [2., 5.]]);
+    assert!(pth.get("bias")?.is_none());
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn read_pth_errors() -> Result<()> {
+    let tmp_dir = std::env::temp_dir();
+
+    // A view reaching past the end of its storage.
+    let mut pkl = Pickle::new();
+    pkl.op(b'}').op(b'(');
+    pkl.tensor("weight", ("FloatStorage", "0", 4), 1, &[2, 2], &[2, 1]);
+    pkl.op(b'u').op(b'.');
+    let path = tmp_dir.join("candle_pickle_test_oob.pt");
+    write_archive(&path, &pkl.0, &[("0", vec![0u8; 16])])?;
+    let err = pickle::read_all(&path).unwrap_err();
+    assert!(err.to_string().contains("out of bounds"), "{err}");
+    std::fs::remove_file(&path)?;
+
+    // Op-codes outside of the supported subset, here INST which would instantiate an arbitrary
+    // class, are rejected.
+    let mut pkl = Pickle::new();
+    pkl.op(b'(').op(b'i');
+    pkl.0.extend_from_slice(b"os\nsystem\n");
+    pkl.op(b'.');
+    let path = tmp_dir.join("candle_pickle_test_inst.pt");
+    write_archive(&path, &pkl.0, &[])?;
+    let err = pickle::read_all(&path).unwrap_err();
+    assert!(err.to_string().contains("unknown op-code"), "{err}");
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
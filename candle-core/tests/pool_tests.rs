@@ -1,4 +1,4 @@
-use candle_core::{test_device, test_utils, Device, IndexOp, Result, Tensor};
+use candle_core::{test_device, test_utils, DType, Device, IndexOp, Result, Tensor};
 
 // https://github.com/huggingface/candle/issues/364
 fn avg_pool2d(dev: &Device) -> Result<()> {
@@ -98,6 +98,237 @@ fn upsample_nearest2d(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+fn pool1d(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[[1f32, 2., 1., 3., 0., 0., 5., 1.]]], dev)?;
+    let pool = t.avg_pool1d(2)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[1.5f32, 2., 0., 3.]]]);
+    let pool = t.max_pool1d(2)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[2f32, 3., 0., 5.]]]);
+    let pool = t.avg_pool1d_with_stride(3, 2)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&pool, 4)?,
+        [[[1.3333, 1.3333, 1.6667]]]
+    );
+    let pool = t.max_pool1d_with_stride(3, 2)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[2f32, 3., 5.]]]);
+
+    // Padding, the padded positions count as zeros only with count_include_pad.
+    let pool = t.avg_pool1d_with_padding(2, 2, 1, false, true)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[0.5f32, 1.5, 1.5, 2.5, 0.5]]]);
+    let pool = t.avg_pool1d_with_padding(3, 2, 1, false, false)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[1.5f32, 2., 1., 2.]]]);
+    let pool = t.max_pool1d_with_padding(3, 3, 1, false)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[2f32, 3., 5.]]]);
+    let pool = t.max_pool1d_with_padding(3, 3, 1, true)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[2f32, 3., 5.]]]);
+    let pool = t.max_pool1d_with_padding(2, 3, 1, true)?;
+    assert_eq!(pool.to_vec3::<f32>()?, [[[1f32, 3., 5.]]]);
+
+    // The input must be 3D and the kernel must fit in the input.
+    assert!(t.squeeze(0)?.max_pool1d(2).is_err());
+    assert!(t.avg_pool1d(9).is_err());
+    assert!(t.max_pool1d_with_stride(2, 0).is_err());
+    assert!(t.max_pool1d_with_padding(2, 2, 2, false).is_err());
+    assert!(t
+        .squeeze(0)?
+        .avg_pool1d_with_padding(2, 2, 1, false, false)
+        .is_err());
+    Ok(())
+}
+
+fn max_pool2d_with_indices(dev: &Device) -> Result<()> {
+    let data: Vec<f32> = vec![
+        1., 2., 1., 3., 0., 0., 1., 1., 1., 1., 1., 1., 5., 1., 1., 1.,
+    ];
+    let t = Tensor::from_vec(data, (1, 1, 4, 4), dev)?;
+    let (pool, indices) = t.max_pool2d_with_indices(2, 2)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[2f32, 3.], [5., 1.]]);
+    // The bottom right window only contains ones, the first one is selected.
+    assert_eq!(indices.i((0, 0))?.to_vec2::<u32>()?, [[1, 3], [12, 10]]);
+
+    let unpool = pool.max_unpool2d(&indices, (4, 4))?;
+    assert_eq!(
+        unpool.i((0, 0))?.to_vec2::<f32>()?,
+        [
+            [0f32, 2., 0., 3.],
+            [0., 0., 0., 0.],
+            [0., 0., 1., 0.],
+            [5., 0., 0., 0.]
+        ]
+    );
+
+    let (pool, indices) = t.max_pool2d_with_indices(3, 1)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[2f32, 3.], [5., 1.]]);
+    assert_eq!(indices.i((0, 0))?.to_vec2::<u32>()?, [[1, 3], [12, 6]]);
+    Ok(())
+}
+
+fn pool2d_with_padding(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[1f32, 2., 3., 4., 5.], dev)?.reshape((1, 1, 1, 5))?;
+    // With ceil mode, the last partial window is kept.
+    let pool = t.avg_pool2d_with_padding((1, 2), (1, 2), (0, 0), false, true)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[1.5, 3.5]]);
+    let pool = t.avg_pool2d_with_padding((1, 2), (1, 2), (0, 0), true, true)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[1.5, 3.5, 5.]]);
+    let pool = t.max_pool2d_with_padding((1, 2), (1, 2), (0, 0), true)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[2., 4., 5.]]);
+
+    // The ceil mode would add a third window starting at position 5 of the padded input, which
+    // is entirely in the padding so it gets dropped.
+    let t = Tensor::new(&[1f32, 2., 3., 4.], dev)?.reshape((1, 1, 1, 4))?;
+    let pool = t.avg_pool2d_with_padding((1, 2), (1, 3), (0, 1), true, true)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[0.5, 3.5]]);
+    let pool = t.avg_pool2d_with_padding((1, 2), (1, 3), (0, 1), true, false)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[1., 3.5]]);
+    let pool = t.max_pool2d_with_padding((1, 2), (1, 3), (0, 1), true)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[1., 4.]]);
+
+    let t = Tensor::new(&[[-1f32, -2., -3.], [-4., -5., -6.], [-7., -8., -9.]], dev)?;
+    let t = t.reshape((1, 1, 3, 3))?;
+    let pool = t.max_pool2d_with_padding(2, 2, 1, false)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[-1., -2.], [-4., -5.]]);
+    let pool = t.avg_pool2d_with_padding(3, 2, 1, false, true)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&pool.i((0, 0))?, 4)?,
+        [[-1.3333, -1.7778], [-2.6667, -3.1111]]
+    );
+    let pool = t.avg_pool2d_with_padding(3, 2, 1, false, false)?;
+    assert_eq!(pool.i((0, 0))?.to_vec2::<f32>()?, [[-3., -4.], [-6., -7.]]);
+
+    // The padding cannot be larger than half the kernel size.
+    assert!(t.max_pool2d_with_padding(2, 2, 2, false).is_err());
+    Ok(())
+}
+
+fn upsample_interpolate2d(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], dev)?;
+    let up = t.upsample_bilinear2d(4, 4, false)?;
+    assert_eq!(
+        up.i((0, 0))?.to_vec2::<f32>()?,
+        [
+            [1.0, 1.25, 1.75, 2.0],
+            [1.5, 1.75, 2.25, 2.5],
+            [2.5, 2.75, 3.25, 3.5],
+            [3.0, 3.25, 3.75, 4.0]
+        ]
+    );
+    let up = t.upsample_bilinear2d(3, 2, true)?;
+    assert_eq!(
+        up.i((0, 0))?.to_vec2::<f32>()?,
+        [[1.0, 2.0], [2.0, 3.0], [3.0, 4.0]]
+    );
+    // Downsampling with the half-pixel convention.
+    let t = Tensor::arange(0f32, 16., dev)?.reshape((1, 1, 4, 4))?;
+    let down = t.upsample_bilinear2d(2, 2, false)?;
+    assert_eq!(
+        down.i((0, 0))?.to_vec2::<f32>()?,
+        [[2.5, 4.5], [10.5, 12.5]]
+    );
+
+    let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], dev)?;
+    let up = t.upsample_bicubic2d(4, 4, false)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&up.i((0, 0))?, 4)?,
+        [
+            [0.6836, 1.0156, 1.5625, 1.8945],
+            [1.3477, 1.6797, 2.2266, 2.5586],
+            [2.4414, 2.7734, 3.3203, 3.6523],
+            [3.1055, 3.4375, 3.9844, 4.3164]
+        ]
+    );
+    let up = t.upsample_bicubic2d(3, 3, true)?;
+    assert_eq!(
+        up.i((0, 0))?.to_vec2::<f32>()?,
+        [[1.0, 1.5, 2.0], [2.0, 2.5, 3.0], [3.0, 3.5, 4.0]]
+    );
+    Ok(())
+}
+
+fn upsample_nearest1d_3d(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[[1f32, 2., 3.]]], dev)?;
+    let up = t.upsample_nearest1d(6)?;
+    assert_eq!(up.to_vec3::<f32>()?, [[[1f32, 1., 2., 2., 3., 3.]]]);
+    // Non integer scale factors.
+    let up = t.upsample_nearest1d(5)?;
+    assert_eq!(up.to_vec3::<f32>()?, [[[1f32, 1., 2., 2., 3.]]]);
+    let up = t.upsample_nearest1d(2)?;
+    assert_eq!(up.to_vec3::<f32>()?, [[[1f32, 2.]]]);
+
+    let t = Tensor::arange(0f32, 8., dev)?.reshape((1, 1, 2, 2, 2))?;
+    let up = t.upsample_nearest3d(4, 2, 3)?;
+    assert_eq!(up.dims(), &[1, 1, 4, 2, 3]);
+    assert_eq!(
+        up.i((0, 0))?.to_vec3::<f32>()?,
+        [
+            [[0f32, 0., 1.], [2., 2., 3.]],
+            [[0., 0., 1.], [2., 2., 3.]],
+            [[4., 4., 5.], [6., 6., 7.]],
+            [[4., 4., 5.], [6., 6., 7.]]
+        ]
+    );
+
+    // Empty dimensions can only be upsampled to empty dimensions.
+    let t = Tensor::zeros((1, 2, 0), DType::F32, dev)?;
+    assert_eq!(t.upsample_nearest1d(0)?.dims(), &[1, 2, 0]);
+    assert!(t.upsample_nearest1d(3).is_err());
+    let t = Tensor::zeros((1, 1, 0, 2, 2), DType::F32, dev)?;
+    assert!(t.upsample_nearest3d(2, 2, 2).is_err());
+    let t = Tensor::zeros((1, 1, 2, 0, 2), DType::F32, dev)?;
+    assert!(t.upsample_nearest3d(2, 2, 2).is_err());
+    Ok(())
+}
+
+fn grid_sample(dev: &Device) -> Result<()> {
+    use candle_core::{GridSampleMode::*, GridSamplePadding::*};
+    let t = Tensor::new(&[[[[1f32, 2., 3.], [4., 5., 6.]]]], dev)?;
+    // The identity grid with aligned corners gives back the input.
+    let grid = Tensor::new(
+        &[[
+            [[-1f32, -1.], [0., -1.], [1., -1.]],
+            [[-1., 1.], [0., 1.], [1., 1.]],
+        ]],
+        dev,
+    )?;
+    for mode in [Bilinear, Nearest] {
+        let ys = t.grid_sample(&grid, mode, Zeros, true)?;
+        assert_eq!(
+            ys.i((0, 0))?.to_vec2::<f32>()?,
+            [[1., 2., 3.], [4., 5., 6.]]
+        );
+    }
+
+    // Half-pixel centers: x = -1 is the left edge of the first pixel, so half of the value
+    // comes from the zero padding.
+    let grid = Tensor::new(&[[[[-1f32, -0.5], [0.5, 0.], [1.5, 0.5]]]], dev)?;
+    let ys = t.grid_sample(&grid, Bilinear, Zeros, false)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[0.5, 4.25, 0.]]);
+    let ys = t.grid_sample(&grid, Bilinear, Border, false)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[1., 4.25, 6.]]);
+    let grid = Tensor::new(&[[[[-0.9f32, -0.8], [0.5, 0.6], [1.5, 0.6]]]], dev)?;
+    let ys = t.grid_sample(&grid, Nearest, Zeros, false)?;
+    assert_eq!(ys.i((0, 0))?.to_vec2::<f32>()?, [[1., 6., 0.]]);
+
+    assert!(t
+        .grid_sample(&grid.i((.., .., .., ..1))?, Bilinear, Zeros, false)
+        .is_err());
+
+    // The pixel positions above 256 cannot be represented in bf16, they are computed in f32.
+    let t = (0..1200).map(|i| (i % 8) as f32).collect::<Vec<_>>();
+    let t = Tensor::from_vec(t, (1, 1, 2, 600), dev)?.to_dtype(DType::BF16)?;
+    let grid = [301f32, 513., 599.]
+        .iter()
+        .flat_map(|x| [2. * x / 599. - 1., 1.])
+        .collect::<Vec<_>>();
+    let grid = Tensor::from_vec(grid, (1, 1, 3, 2), dev)?;
+    for mode in [Bilinear, Nearest] {
+        let ys = t.grid_sample(&grid, mode, Zeros, true)?;
+        assert_eq!(ys.dtype(), DType::BF16);
+        let ys = ys.to_dtype(DType::F32)?.flatten_all()?;
+        assert_eq!(test_utils::to_vec1_round(&ys, 1)?, [5., 1., 7.]);
+    }
+    Ok(())
+}
+
 test_device!(avg_pool2d, avg_pool2d_cpu, avg_pool2d_gpu);
 test_device!(
     avg_pool2d_pytorch,
@@ -110,3 +341,25 @@ test_device!(
     upsample_nearest2d_cpu,
     upsample_nearest2d_gpu
 );
+test_device!(pool1d, pool1d_cpu, pool1d_gpu);
+test_device!(
+    max_pool2d_with_indices,
+    max_pool2d_with_indices_cpu,
+    max_pool2d_with_indices_gpu
+);
+test_device!(
+    pool2d_with_padding,
+    pool2d_with_padding_cpu,
+    pool2d_with_padding_gpu
+);
+test_device!(
+    upsample_interpolate2d,
+    upsample_interpolate2d_cpu,
+    upsample_interpolate2d_gpu
+);
+test_device!(
+    upsample_nearest1d_3d,
+    upsample_nearest1d_3d_cpu,
+    upsample_nearest1d_3d_gpu
+);
+test_device!(grid_sample, grid_sample_cpu, grid_sample_gpu);
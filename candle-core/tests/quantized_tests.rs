@@ -1,7 +1,7 @@
 use candle_core::{
     quantized::{self, GgmlDType},
     test_utils::to_vec2_round,
-    Device, Result, Tensor,
+    DType, Device, Result, Tensor,
 };
 use quantized::{k_quants, GgmlType};
 use rand::prelude::*;
@@ -687,3 +687,274 @@ fn quantized_matmul_q6k() -> Result<()> {
     ggml_matmul_error_test::<BlockQ6K>()?;
     Ok(())
 }
+
+#[test]
+fn unpack_awq() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let (rows, cols, group_size) = (3, 16, 8);
+    let weights: Vec<f32> = (0..rows * cols)
+        .map(|i| ((i * 7) % 11) as f32 * 0.37 - 1.5)
+        .collect();
+
+    // Quantize each group with a per-group scale and zero point and pack two values per byte.
+    let mut scales = vec![];
+    let mut zeros = vec![];
+    let mut quantized = vec![];
+    for group in weights.chunks(group_size) {
+        let min = group.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = group.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let scale = (max - min) / 15.;
+        let zero = (-min / scale).round();
+        scales.push(scale);
+        zeros.push(zero as u8);
+        for &w in group {
+            quantized.push((w / scale + zero).round().clamp(0., 15.) as u8)
+        }
+    }
+    let packed: Vec<u8> = quantized.chunks(2).map(|q| q[0] | (q[1] << 4)).collect();
+    let packed = Tensor::from_vec(packed, (rows, cols / 2), cpu)?;
+    let scales_t = Tensor::from_vec(scales.clone(), (rows, cols / group_size), cpu)?;
+    let zeros = Tensor::from_vec(zeros, (rows, cols / group_size), cpu)?;
+
+    let unpacked = quantized::unpack_awq(&packed, &scales_t, &zeros, group_size)?;
+    assert_eq!(unpacked.dims(), &[rows, cols]);
+    assert_eq!(unpacked.dtype(), candle_core::DType::F16);
+    let unpacked = unpacked
+        .to_dtype(candle_core::DType::F32)?
+        .flatten_all()?
+        .to_vec1::<f32>()?;
+    for (i, (w, u)) in weights.iter().zip(unpacked.iter()).enumerate() {
+        // Quantization error plus some slack for the f16 conversion.
+        let tolerance = scales[i / group_size] / 2. + 1e-2;
+        assert!((w - u).abs() <= tolerance, "{i} {w} {u}");
+    }
+
+    assert!(quantized::unpack_awq(&packed, &scales_t, &scales_t, 5).is_err());
+    Ok(())
+}
+
+#[test]
+fn dequantize_trait() -> Result<()> {
+    use candle_core::{quantized::Dequantize, DType};
+    let cpu = &Device::Cpu;
+    // With a maximum absolute value of 127, q8_0 uses a scale of 1 so integers are exact.
+    let values: Vec<f32> = (0..32).map(|i| (i * 9 % 255) as f32 - 127.).collect();
+    let q8 = quantized::QTensor::quantize::<k_quants::BlockQ8_0>(&Tensor::from_vec(
+        values.clone(),
+        (1, 32),
+        cpu,
+    )?)?;
+    // Two columns per byte, 0x21 unpacks to (1, 2) and 0x43 to (3, 4).
+    let awq = quantized::AwqTensor::new(
+        Tensor::new(&[[0x21u8, 0x43]], cpu)?,
+        Tensor::new(&[[0.5f32, 2.]], cpu)?,
+        Tensor::new(&[[1f32, 4.]], cpu)?,
+        2,
+    )?;
+    let all: Vec<Box<dyn Dequantize>> = vec![Box::new(q8), Box::new(awq)];
+    let ys = all
+        .iter()
+        .map(|q| q.dequantize(DType::F64))
+        .collect::<Result<Vec<_>>>()?;
+    assert!(ys.iter().all(|y| y.dtype() == DType::F64));
+    assert_eq!(
+        ys[0].to_vec2::<f64>()?[0],
+        values.iter().map(|&v| v as f64).collect::<Vec<_>>()
+    );
+    assert_eq!(ys[1].to_vec2::<f64>()?, [[0., 0.5, -2., 0.]]);
+    Ok(())
+}
+
+/// A tiny GGUF v2 file with three metadata entries, a Q4_0 tensor of shape (2, 32) and a Q8_0
+/// tensor of shape (32,).
+fn tiny_gguf() -> Vec<u8> {
+    fn string(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+    let f16 = |v: f32| half::f16::from_f32(v).to_le_bytes();
+    let mut buf = b"GGUF".to_vec();
+    buf.extend_from_slice(&2u32.to_le_bytes()); // version
+    buf.extend_from_slice(&2u64.to_le_bytes()); // tensor count
+    buf.extend_from_slice(&3u64.to_le_bytes()); // metadata kv count
+    string(&mut buf, "general.name");
+    buf.extend_from_slice(&8u32.to_le_bytes()); // string
+    string(&mut buf, "tiny");
+    string(&mut buf, "tiny.context_length");
+    buf.extend_from_slice(&4u32.to_le_bytes()); // u32
+    buf.extend_from_slice(&128u32.to_le_bytes());
+    string(&mut buf, "tiny.scales");
+    buf.extend_from_slice(&9u32.to_le_bytes()); // array
+    buf.extend_from_slice(&6u32.to_le_bytes()); // of f32
+    buf.extend_from_slice(&2u64.to_le_bytes());
+    buf.extend_from_slice(&0.5f32.to_le_bytes());
+    buf.extend_from_slice(&(-1f32).to_le_bytes());
+    // Tensor infos, the dimensions are stored in reverse order.
+    string(&mut buf, "q4");
+    buf.extend_from_slice(&2u32.to_le_bytes());
+    buf.extend_from_slice(&32u64.to_le_bytes());
+    buf.extend_from_slice(&2u64.to_le_bytes());
+    buf.extend_from_slice(&2u32.to_le_bytes()); // Q4_0
+    buf.extend_from_slice(&0u64.to_le_bytes());
+    string(&mut buf, "q8");
+    buf.extend_from_slice(&1u32.to_le_bytes());
+    buf.extend_from_slice(&32u64.to_le_bytes());
+    buf.extend_from_slice(&8u32.to_le_bytes()); // Q8_0
+    buf.extend_from_slice(&64u64.to_le_bytes());
+    while buf.len() % 32 != 0 {
+        buf.push(0)
+    }
+    // Q4_0 blocks: a f16 scale then 16 bytes, the low nibbles hold the first 16 values and the
+    // high nibbles the last 16, all offset by 8.
+    for d in [1f32, 0.5] {
+        buf.extend_from_slice(&f16(d));
+        buf.extend((0..16u8).map(|i| i | ((15 - i) << 4)));
+    }
+    buf.extend_from_slice(&[0; 28]); // padding so that the next tensor starts at offset 64
+                                     // Q8_0 block: a f16 scale then 32 signed bytes.
+    buf.extend_from_slice(&f16(0.25));
+    buf.extend((0..32i8).map(|i| (i - 16) as u8));
+    buf
+}
+
+#[test]
+fn gguf_read() -> Result<()> {
+    use quantized::gguf_file;
+
+    let data = tiny_gguf();
+    let mut reader = std::io::Cursor::new(&data);
+    let content = gguf_file::Content::read(&mut reader)?;
+    assert_eq!(content.magic, gguf_file::VersionedMagic::GgufV2);
+    assert_eq!(content.metadata.len(), 3);
+    assert_eq!(content.metadata["general.name"].to_string()?, "tiny");
+    assert_eq!(content.metadata["tiny.context_length"].to_u32()?, 128);
+    let scales = content.metadata["tiny.scales"].to_vec()?;
+    let scales = scales
+        .iter()
+        .map(|v| v.to_f32())
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(scales, [0.5, -1.]);
+    assert_eq!(content.tensor_data_offset % 32, 0);
+
+    let q4_info = &content.tensor_infos["q4"];
+    assert_eq!(q4_info.ggml_dtype, GgmlDType::Q4_0);
+    assert_eq!(q4_info.shape.dims(), &[2, 32]);
+    assert_eq!(q4_info.offset, 0);
+    let q8_info = &content.tensor_infos["q8"];
+    assert_eq!(q8_info.ggml_dtype, GgmlDType::Q8_0);
+    assert_eq!(q8_info.shape.dims(), &[32]);
+    assert_eq!(q8_info.offset, 64);
+
+    let q8 = content
+        .tensor(&mut reader, "q8")?
+        .dequantize(&Device::Cpu)?;
+    let expected: Vec<f32> = (0..32).map(|i| (i - 16) as f32 * 0.25).collect();
+    assert_eq!(q8.to_vec1::<f32>()?, expected);
+
+    let q4 = content
+        .tensor(&mut reader, "q4")?
+        .dequantize(&Device::Cpu)?;
+    let row = |d: f32| -> Vec<f32> {
+        let low = (0..16).map(|i| (i - 8) as f32 * d);
+        let high = (0..16).map(|i| (7 - i) as f32 * d);
+        low.chain(high).collect()
+    };
+    assert_eq!(q4.to_vec2::<f32>()?, [row(1.), row(0.5)]);
+
+    // Invalid magic bytes and unsupported versions are rejected.
+    let mut bad_magic = data.clone();
+    bad_magic[..4].copy_from_slice(b"GGML");
+    assert!(gguf_file::Content::read(&mut std::io::Cursor::new(&bad_magic)).is_err());
+    let mut bad_version = data;
+    bad_version[4..8].copy_from_slice(&7u32.to_le_bytes());
+    assert!(gguf_file::Content::read(&mut std::io::Cursor::new(&bad_version)).is_err());
+    Ok(())
+}
+
+#[test]
+fn gguf_write_read() -> Result<()> {
+    use quantized::gguf_file::{self, Value};
+
+    let cpu = &Device::Cpu;
+    let weight = Tensor::arange(0f32, 96., cpu)?.reshape((3, 32))?;
+    let bias = Tensor::new(&[0.5f32, -1.5, 2.], cpu)?;
+    let q_weight = quantized::QTensor::quantize::<k_quants::BlockQ8_0>(&weight)?;
+    let bias = quantized::QTensor::quantize::<f32>(&bias)?;
+    let vocab = Value::Array(vec![Value::String("a".into()), Value::String("b".into())]);
+    let mut buf = std::io::Cursor::new(vec![]);
+    gguf_file::write(
+        &mut buf,
+        &[
+            ("general.architecture", &Value::String("tiny".into())),
+            ("tiny.rope.freq_base", &Value::F32(10000.)),
+            ("tokenizer.ggml.tokens", &vocab),
+        ],
+        &[("weight", &q_weight), ("bias", &bias)],
+    )?;
+    let mut data = buf.into_inner();
+    // The v3 layout is the same as v2 for little-endian files.
+    data[4..8].copy_from_slice(&3u32.to_le_bytes());
+
+    let mut reader = std::io::Cursor::new(&data);
+    let content = gguf_file::Content::read(&mut reader)?;
+    assert_eq!(content.magic, gguf_file::VersionedMagic::GgufV3);
+    assert_eq!(content.get("general.architecture")?.to_string()?, "tiny");
+    assert_eq!(content.get("tiny.rope.freq_base")?.to_f32()?, 10000.);
+    assert_eq!(content.get("tokenizer.ggml.tokens")?.to_vec()?.len(), 2);
+    assert!(content.get("tiny.context_length").is_err());
+
+    let bias = content.tensor(&mut reader, "bias")?;
+    assert_eq!(bias.dtype(), GgmlDType::F32);
+    assert_eq!(bias.dequantize(cpu)?.to_vec1::<f32>()?, [0.5, -1.5, 2.]);
+    let w = content.tensor(&mut reader, "weight")?;
+    assert_eq!(w.dtype(), GgmlDType::Q8_0);
+    assert_eq!(w.shape().dims(), &[3, 32]);
+    let diff = (w.dequantize(cpu)? - &weight)?
+        .abs()?
+        .flatten_all()?
+        .max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 0.5);
+    Ok(())
+}
+
+#[test]
+fn int8_matmul() -> Result<()> {
+    use quantized::Dequantize;
+
+    let cpu = &Device::Cpu;
+    let (m, k, n) = (4, 64, 5);
+    let mut rng = StdRng::seed_from_u64(42);
+    let lhs: Vec<f32> = (0..m * k).map(|_| rng.gen_range(-1f32..1.)).collect();
+    let rhs: Vec<f32> = (0..k * n).map(|_| rng.gen_range(0f32..2.)).collect();
+    let lhs = Tensor::from_vec(lhs, (m, k), cpu)?;
+    let rhs = Tensor::from_vec(rhs, (k, n), cpu)?;
+
+    // Symmetric quantization for the lhs, asymmetric for the non-negative rhs.
+    let (scale_a, scale_b) = (1. / 127., 2. / 255.);
+    let q_lhs = lhs.quantize_per_tensor(scale_a, 0)?;
+    let q_rhs = rhs.quantize_per_tensor(scale_b, -128)?;
+    assert_eq!(q_rhs.data().iter().min(), Some(&-128));
+    for (q, t, scale) in [(&q_lhs, &lhs, scale_a), (&q_rhs, &rhs, scale_b)] {
+        let diff = (q.dequantize(DType::F32)? - t)?.abs()?;
+        let max_diff = diff.flatten_all()?.max(0)?.to_scalar::<f32>()?;
+        assert!(max_diff as f64 <= scale / 2. + 1e-6, "{max_diff}");
+    }
+
+    // The integer accumulation matches the float product of the dequantized inputs.
+    let res = q_lhs.matmul(&q_rhs)?;
+    let dequantized = q_lhs
+        .dequantize(DType::F32)?
+        .matmul(&q_rhs.dequantize(DType::F32)?)?;
+    let diff = (&res - dequantized)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+
+    // And stays close to the original float product.
+    let expected = lhs.matmul(&rhs)?;
+    let diff = (&res - expected)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 0.1, "{diff}");
+
+    assert!(lhs.quantize_per_tensor(0., 0).is_err());
+    assert!(lhs.quantize_per_tensor(0.1, 200).is_err());
+    assert!(q_lhs.matmul(&q_lhs).is_err());
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use candle_core::{DType, Device, Tensor};
+
+#[test]
+fn bincode_round_trip() -> Result<()> {
+    let t = Tensor::arange(0f32, 6., &Device::Cpu)?
+        .reshape((2, 3))?
+        .affine(0.5, -1.)?;
+    let bytes = bincode::serialize(&t)?;
+    let t2: Tensor = bincode::deserialize(&bytes)?;
+    assert_eq!(t2.dtype(), DType::F32);
+    assert_eq!(t2.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+
+    // Non-contiguous tensors are serialized in their logical order.
+    let t = Tensor::arange(0i64, 6, &Device::Cpu)?
+        .reshape((2, 3))?
+        .t()?;
+    let bytes = bincode::serialize(&t)?;
+    let t2: Tensor = bincode::deserialize(&bytes)?;
+    assert_eq!(t2.dtype(), DType::I64);
+    assert!(t2.is_contiguous());
+    assert_eq!(t2.to_vec2::<i64>()?, [[0, 3], [1, 4], [2, 5]]);
+
+    let t = Tensor::new(&[1u8, 2, 255], &Device::Cpu)?;
+    let t2: Tensor = bincode::deserialize(&bincode::serialize(&t)?)?;
+    assert_eq!(t2.to_vec1::<u8>()?, [1, 2, 255]);
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use candle_core::{test_device, DType, Device, IndexOp, Result, Tensor};
+use candle_core::{
+    test_device, test_utils, DType, Device, IndexOp, PadMode, Result, Tensor, Var, D,
+};
 
 fn zeros(device: &Device) -> Result<()> {
     let tensor = Tensor::zeros((5, 2), DType::F32, device)?;
@@ -877,6 +879,1054 @@ fn broadcasting(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn diff(device: &Device) -> Result<()> {
+    let tensor = Tensor::new(&[1f32, 2., 4., 7., 0.], device)?;
+    assert_eq!(tensor.diff(1, 0)?.to_vec1::<f32>()?, &[1., 2., 3., -7.]);
+    assert_eq!(tensor.diff(2, 0)?.to_vec1::<f32>()?, &[1., 1., -10.]);
+    assert_eq!(tensor.diff(0, 0)?.to_vec1::<f32>()?, &[1., 2., 4., 7., 0.]);
+    let empty = tensor.diff(5, 0)?;
+    assert_eq!(empty.dims(), &[0]);
+    let empty = tensor.diff(7, 0)?;
+    assert_eq!(empty.dims(), &[0]);
+
+    let tensor = Tensor::new(&[[1f32, 2., 4.], [3., 3., 5.]], device)?;
+    assert_eq!(tensor.diff(1, 1)?.to_vec2::<f32>()?, &[[1., 2.], [0., 2.]]);
+    assert_eq!(tensor.diff(1, 0)?.to_vec2::<f32>()?, &[[2., 1., 1.]]);
+    Ok(())
+}
+
+fn pad(device: &Device) -> Result<()> {
+    let tensor = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let p = tensor.pad(1, 1, 2, PadMode::Zeros)?;
+    assert_eq!(
+        p.to_vec2::<f32>()?,
+        &[[0., 1., 2., 3., 0., 0.], [0., 4., 5., 6., 0., 0.]]
+    );
+    let p = tensor.pad(1, 2, 2, PadMode::Reflect)?;
+    assert_eq!(
+        p.to_vec2::<f32>()?,
+        &[[3., 2., 1., 2., 3., 2., 1.], [6., 5., 4., 5., 6., 5., 4.]]
+    );
+    let p = tensor.pad(1, 2, 1, PadMode::Replicate)?;
+    assert_eq!(
+        p.to_vec2::<f32>()?,
+        &[[1., 1., 1., 2., 3., 3.], [4., 4., 4., 5., 6., 6.]]
+    );
+    let p = tensor.pad_with_same(0, 1, 0)?;
+    assert_eq!(
+        p.to_vec2::<f32>()?,
+        &[[1., 2., 3.], [1., 2., 3.], [4., 5., 6.]]
+    );
+    let p = tensor.pad(1, 2, 4, PadMode::Circular)?;
+    assert_eq!(
+        p.to_vec2::<f32>()?,
+        &[
+            [2., 3., 1., 2., 3., 1., 2., 3., 1.],
+            [5., 6., 4., 5., 6., 4., 5., 6., 4.]
+        ]
+    );
+    // Reflection requires the padding to be smaller than the dimension size.
+    assert!(tensor.pad(1, 3, 0, PadMode::Reflect).is_err());
+    assert!(tensor.pad(0, 0, 2, PadMode::Reflect).is_err());
+    Ok(())
+}
+
+fn meshgrid(device: &Device) -> Result<()> {
+    let x = Tensor::new(&[1f32, 2., 3.], device)?;
+    let y = Tensor::new(&[4f32, 5.], device)?;
+
+    let grids = Tensor::meshgrid(&[&x, &y], "ij")?;
+    assert_eq!(grids.len(), 2);
+    assert_eq!(grids[0].dims(), &[3, 2]);
+    assert_eq!(grids[0].to_vec2::<f32>()?, &[[1., 1.], [2., 2.], [3., 3.]]);
+    assert_eq!(grids[1].to_vec2::<f32>()?, &[[4., 5.], [4., 5.], [4., 5.]]);
+
+    let grids = Tensor::meshgrid(&[&x, &y], "xy")?;
+    assert_eq!(grids[0].dims(), &[2, 3]);
+    assert_eq!(grids[0].to_vec2::<f32>()?, &[[1., 2., 3.], [1., 2., 3.]]);
+    assert_eq!(grids[1].to_vec2::<f32>()?, &[[4., 4., 4.], [5., 5., 5.]]);
+
+    let z = Tensor::new(&[6f32, 7., 8., 9.], device)?;
+    let grids = Tensor::meshgrid(&[&x, &y, &z], "xy")?;
+    assert_eq!(grids[2].dims(), &[2, 3, 4]);
+    assert_eq!(grids[2].i((1, 2))?.to_vec1::<f32>()?, &[6., 7., 8., 9.]);
+
+    assert!(Tensor::meshgrid(&[&x, &y], "yx").is_err());
+    assert!(Tensor::meshgrid(&[&x.unsqueeze(0)?], "ij").is_err());
+    Ok(())
+}
+
+fn outer_kron(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[1f32, -2., 3.], device)?;
+    let b = Tensor::new(&[2f32, 5.], device)?;
+    let c = a.outer(&b)?;
+    assert_eq!(c.to_vec2::<f32>()?, &[[2., 5.], [-4., -10.], [6., 15.]]);
+    assert!(a.outer(&b.unsqueeze(0)?).is_err());
+
+    let a = Tensor::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let b = Tensor::new(&[[0f32, 5., 1.], [6., 7., 1.]], device)?;
+    let c = a.kron(&b)?;
+    assert_eq!(c.dims(), &[4, 6]);
+    assert_eq!(
+        c.to_vec2::<f32>()?,
+        &[
+            [0., 5., 1., 0., 10., 2.],
+            [6., 7., 1., 12., 14., 2.],
+            [0., 15., 3., 0., 20., 4.],
+            [18., 21., 3., 24., 28., 4.]
+        ]
+    );
+    Ok(())
+}
+
+fn norm(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[3f32, -4., 0.], [-1., 2., -2.], [0., 0., 0.]], device)?;
+    let l1 = t.norm(1., 1, false)?;
+    assert_eq!(l1.to_vec1::<f32>()?, &[7., 5., 0.]);
+    let l2 = t.norm(2., 1, false)?;
+    assert_eq!(l2.to_vec1::<f32>()?, &[5., 3., 0.]);
+    let l_inf = t.norm(f64::INFINITY, 1, true)?;
+    assert_eq!(l_inf.to_vec2::<f32>()?, &[[4.], [2.], [0.]]);
+    let l_neg_inf = t.norm(f64::NEG_INFINITY, 1, false)?;
+    assert_eq!(l_neg_inf.to_vec1::<f32>()?, &[0., 1., 0.]);
+    let l3 = t.norm(3., 1, false)?;
+    assert_eq!(test_utils::to_vec1_round(&l3, 4)?, &[4.4979, 2.5713, 0.]);
+    let l2 = t.norm(2., (0, 1), false)?;
+    assert_eq!(test_utils::to_vec0_round(&l2, 4)?, 5.831);
+    assert!(t.norm(0., 1, false).is_err());
+
+    let n = t.normalize(1., 1, 1e-12)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&n, 4)?,
+        &[[0.4286, -0.5714, 0.], [-0.2, 0.4, -0.4], [0., 0., 0.]]
+    );
+    Ok(())
+}
+
+fn floor_ceil_round(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[-1.5f32, -0.2, 0., 0.5, 1.7], device)?;
+    assert_eq!(t.floor()?.to_vec1::<f32>()?, [-2., -1., 0., 0., 1.]);
+    assert_eq!(t.ceil()?.to_vec1::<f32>()?, [-1., -0., 0., 1., 2.]);
+    assert_eq!(t.round()?.to_vec1::<f32>()?, [-2., -0., 0., 1., 2.]);
+    Ok(())
+}
+
+fn split_storage(device: &Device) -> Result<()> {
+    let buffer = Var::zeros(10, DType::F32, device)?;
+    let views = buffer.split_storage(&[3, 4, 3])?;
+    assert_eq!(
+        views
+            .iter()
+            .map(|v| v.dims1())
+            .collect::<candle_core::Result<Vec<_>>>()?,
+        [3, 4, 3]
+    );
+    views[1].set(&Tensor::new(&[1f32, 2., 3., 4.], device)?)?;
+    // The write is visible through the buffer but does not leak into the other views.
+    assert_eq!(
+        buffer.to_vec1::<f32>()?,
+        [0., 0., 0., 1., 2., 3., 4., 0., 0., 0.]
+    );
+    assert_eq!(views[0].to_vec1::<f32>()?, [0., 0., 0.]);
+    assert_eq!(views[2].to_vec1::<f32>()?, [0., 0., 0.]);
+    views[2].set(&Tensor::new(&[5f32, 6., 7.], device)?)?;
+    assert_eq!(views[1].to_vec1::<f32>()?, [1., 2., 3., 4.]);
+    // Views of a tensor alias the same storage.
+    let tensor_views = buffer.as_tensor().split_storage(&[5, 5])?;
+    assert_eq!(tensor_views[1].to_vec1::<f32>()?, [3., 4., 5., 6., 7.]);
+    // A view cannot be set from a tensor aliasing its storage.
+    assert!(views[0].set(&tensor_views[1].narrow(0, 2, 3)?).is_err());
+
+    assert!(buffer.split_storage(&[3, 4]).is_err());
+    assert!(Tensor::zeros((2, 5), DType::F32, device)?
+        .split_storage(&[5, 5])
+        .is_err());
+    Ok(())
+}
+
+fn chunks_along(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 20., device)?.reshape((2, 10))?;
+    let chunks = t
+        .chunks_along(1, 3)
+        .collect::<candle_core::Result<Vec<_>>>()?;
+    let sizes: Vec<usize> = chunks
+        .iter()
+        .map(|c| c.dim(1))
+        .collect::<candle_core::Result<_>>()?;
+    assert_eq!(sizes, [3, 3, 3, 1]);
+    assert_eq!(chunks[3].to_vec2::<f32>()?, [[9.], [19.]]);
+    // Each slab can be processed on its own, e.g. on another device, before merging.
+    let processed = chunks
+        .iter()
+        .map(|c| c.to_device(&Device::Cpu)?.affine(2., 1.))
+        .collect::<candle_core::Result<Vec<_>>>()?;
+    let merged = Tensor::cat(&processed, 1)?;
+    assert_eq!(
+        merged.to_vec2::<f32>()?,
+        t.affine(2., 1.)?.to_vec2::<f32>()?
+    );
+
+    assert_eq!(t.chunks_along(0, 5).count(), 1);
+    assert!(t.chunks_along(1, 0).next().unwrap().is_err());
+    assert!(t.chunks_along(2, 3).next().unwrap().is_err());
+    Ok(())
+}
+
+fn bf16_ops(device: &Device) -> Result<()> {
+    use half::bf16;
+    let values = [1f32, -2.5, 3.25, 0.5, 8., -0.125];
+    let bf: Vec<bf16> = values.iter().map(|&v| bf16::from_f32(v)).collect();
+    let t = Tensor::from_slice(&bf, (2, 3), device)?;
+    assert_eq!(t.dtype(), DType::BF16);
+    assert_eq!(t.flatten_all()?.to_vec1::<bf16>()?, bf);
+
+    // Conversions to and from f32 are exact for these values.
+    let t32 = Tensor::new(&values, device)?.reshape((2, 3))?;
+    assert_eq!(
+        t.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+        t32.to_vec2::<f32>()?
+    );
+    assert_eq!(
+        t32.to_dtype(DType::BF16)?
+            .flatten_all()?
+            .to_vec1::<bf16>()?,
+        bf
+    );
+
+    // Elementwise binary and unary ops.
+    let sum = (&t + &t)?.to_dtype(DType::F32)?;
+    assert_eq!(sum.to_vec2::<f32>()?, [[2., -5., 6.5], [1., 16., -0.25]]);
+    let prod = (&t * &t)?.to_dtype(DType::F32)?;
+    assert_eq!(
+        prod.to_vec2::<f32>()?,
+        [[1., 6.25, 10.5625], [0.25, 64., 0.015625]]
+    );
+    let abs = t.abs()?.neg()?.to_dtype(DType::F32)?;
+    assert_eq!(
+        abs.to_vec2::<f32>()?,
+        [[-1., -2.5, -3.25], [-0.5, -8., -0.125]]
+    );
+    let exp = t.exp()?.to_dtype(DType::F32)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&exp, 1)?,
+        [[2.7, 0.1, 25.8], [1.6, 2976.0, 0.9]]
+    );
+
+    // The matmul upcasts to f32 internally and rounds the result to bf16.
+    let rhs = Tensor::arange(0f32, 6., device)?.reshape((3, 2))?;
+    let mm = t.matmul(&rhs.to_dtype(DType::BF16)?)?;
+    assert_eq!(mm.dtype(), DType::BF16);
+    assert_eq!(
+        mm.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+        t32.matmul(&rhs)?.to_vec2::<f32>()?
+    );
+    // Batched matmul with a transposed rhs.
+    let lhs = t.unsqueeze(0)?.repeat((2, 1, 1))?;
+    let mm = lhs.matmul(&lhs.transpose(1, 2)?)?.to_dtype(DType::F32)?;
+    let t32 = t32.unsqueeze(0)?.repeat((2, 1, 1))?;
+    let expected = t32
+        .matmul(&t32.transpose(1, 2)?)?
+        .to_dtype(DType::BF16)?
+        .to_dtype(DType::F32)?;
+    assert_eq!(mm.to_vec3::<f32>()?, expected.to_vec3::<f32>()?);
+    Ok(())
+}
+
+fn i64_ops(device: &Device) -> Result<()> {
+    // Values outside of the u32 range are preserved.
+    let t = Tensor::from_slice(&[7i64, -3, 1 << 40, 0], (2, 2), device)?;
+    assert_eq!(t.dtype(), DType::I64);
+    assert_eq!(t.to_vec2::<i64>()?, [[7, -3], [1 << 40, 0]]);
+    let t = Tensor::from_vec(vec![7i64, -3, 12, 0], (2, 2), device)?;
+
+    // Conversions to and from f32, the float to int conversion truncates.
+    let f = t.to_dtype(DType::F32)?;
+    assert_eq!(f.to_vec2::<f32>()?, [[7., -3.], [12., 0.]]);
+    let f = Tensor::new(&[1.7f32, -2.5, 3.], device)?;
+    assert_eq!(f.to_dtype(DType::I64)?.to_vec1::<i64>()?, [1, -2, 3]);
+
+    // Arithmetic uses integer semantics.
+    let two = Tensor::new(&[[2i64, 2], [5, 5]], device)?;
+    assert_eq!((&t / &two)?.to_vec2::<i64>()?, [[3, -1], [2, 0]]);
+    assert_eq!((&t / 2.)?.to_vec2::<i64>()?, [[3, -1], [6, 0]]);
+    assert_eq!((&t * 3.)?.to_vec2::<i64>()?, [[21, -9], [36, 0]]);
+    assert_eq!((&t - &two)?.to_vec2::<i64>()?, [[5, -5], [7, -5]]);
+    assert!((&t / 0.).is_err());
+    // The divisor is not truncated to an integer.
+    assert!((&t / 2.5).is_err());
+    assert!((&t / 0.5).is_err());
+    assert!((&t / f64::NAN).is_err());
+    assert!((&t / 1e19).is_err());
+    assert_eq!((&t / -2.)?.to_vec2::<i64>()?, [[-3, 1], [-6, 0]]);
+    let u = Tensor::new(&[7u32, 12], device)?;
+    assert!((&u / -2.).is_err());
+    assert!((&u / 4294967296.).is_err());
+    assert_eq!((&u / 4294967295.)?.to_vec1::<u32>()?, [0, 0]);
+    let u = Tensor::new(&[200u8], device)?;
+    assert!((&u / 256.).is_err());
+    assert_eq!((&u / 3.)?.to_vec1::<u8>()?, [66]);
+    assert_eq!(t.sum_all()?.to_vec0::<i64>()?, 16);
+
+    // Comparisons.
+    assert_eq!(t.ge(&two)?.to_vec2::<u8>()?, [[1, 0], [1, 0]]);
+
+    // Indexing with i64 ids.
+    let src = Tensor::arange(0f32, 12., device)?.reshape((3, 4))?;
+    let ids = Tensor::new(&[[3i64, 0], [1, 1], [2, 3]], device)?;
+    let g = src.gather(&ids, 1)?;
+    assert_eq!(g.to_vec2::<f32>()?, [[3., 0.], [5., 5.], [10., 11.]]);
+    let ids = Tensor::new(&[2i64, 0], device)?;
+    let s = src.index_select(&ids, 0)?;
+    assert_eq!(s.to_vec2::<f32>()?, [[8., 9., 10., 11.], [0., 1., 2., 3.]]);
+    let emb = src.embedding(&ids)?;
+    assert_eq!(emb.to_vec2::<f32>()?, s.to_vec2::<f32>()?);
+    // Negative ids are rejected rather than wrapping around.
+    let ids = Tensor::new(&[-1i64], device)?;
+    assert!(src.index_select(&ids, 0).is_err());
+    Ok(())
+}
+
+fn cmp_scalar(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[-1f32, 0.5], [2., 3.]], device)?;
+    assert_eq!(t.gt_scalar(0.5)?.to_vec2::<u8>()?, [[0, 0], [1, 1]]);
+    assert_eq!(t.ge_scalar(0.5)?.to_vec2::<u8>()?, [[0, 1], [1, 1]]);
+    assert_eq!(t.lt_scalar(2.)?.to_vec2::<u8>()?, [[1, 1], [0, 0]]);
+    assert_eq!(t.le_scalar(2.)?.to_vec2::<u8>()?, [[1, 1], [1, 0]]);
+    assert_eq!(t.eq_scalar(3.)?.to_vec2::<u8>()?, [[0, 0], [0, 1]]);
+    assert_eq!(t.ne_scalar(3.)?.to_vec2::<u8>()?, [[1, 1], [1, 0]]);
+
+    // Integer tensors compared to a non integer scalar.
+    let i = Tensor::new(&[1u32, 2, 3], device)?;
+    assert_eq!(i.ge_scalar(2.5)?.to_vec1::<u8>()?, [0, 0, 1]);
+    assert_eq!(i.lt_scalar(2.5)?.to_vec1::<u8>()?, [1, 1, 0]);
+    assert_eq!(i.ge_scalar(2.)?.to_vec1::<u8>()?, [0, 1, 1]);
+    assert_eq!(i.gt_scalar(-1.)?.to_vec1::<u8>()?, [1, 1, 1]);
+
+    // Using the masks in arithmetic: relu as a mask multiplication and a clamp from below.
+    let mask = t.gt_scalar(0.)?;
+    let relu = (&t * mask.to_dtype(DType::F32)?)?;
+    assert_eq!(relu.to_vec2::<f32>()?, [[0., 0.5], [2., 3.]]);
+    let weights = mask.where_scalar(1., 0.25, t.dtype())?;
+    assert_eq!(weights.dtype(), DType::F32);
+    assert_eq!((&t * weights)?.to_vec2::<f32>()?, [[-0.25, 0.5], [2., 3.]]);
+    let t64 = t.to_dtype(DType::F64)?;
+    let weights = mask.where_scalar(1., 0.25, t64.dtype())?;
+    assert_eq!(weights.dtype(), DType::F64);
+    assert_eq!(
+        (&t64 * weights)?.to_vec2::<f64>()?,
+        [[-0.25, 0.5], [2., 3.]]
+    );
+    let ids = mask.where_scalar(7., 2., DType::U32)?;
+    assert_eq!(ids.to_vec2::<u32>()?, [[2, 7], [7, 7]]);
+    let count = mask.to_dtype(DType::U32)?.sum_all()?.to_vec0::<u32>()?;
+    assert_eq!(count, 3);
+    // The mask dtype has to be converted explicitly.
+    assert!((&t * &mask).is_err());
+    Ok(())
+}
+
+fn min_max_scalar(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[-1.5f32, 0.], [2., -0.25]], device)?;
+    let relu = t.maximum_scalar(0.)?;
+    assert_eq!(relu.to_vec2::<f32>()?, t.relu()?.to_vec2::<f32>()?);
+    let clamped = t.maximum_scalar(-1.)?.minimum_scalar(1.)?;
+    assert_eq!(clamped.to_vec2::<f32>()?, [[-1., 0.], [1., -0.25]]);
+    let t = Tensor::new(&[3u32, 7, 1], device)?;
+    assert_eq!(t.minimum_scalar(4.)?.to_vec1::<u32>()?, [3, 4, 1]);
+    Ok(())
+}
+
+fn floor_divide_remainder(device: &Device) -> Result<()> {
+    // Python: [7 // 2, -7 // 2, 7 // -2, -7 // -2] and the same with %, math.fmod for fmod.
+    let a = Tensor::new(&[7i64, -7, 7, -7, 6, -6, 0], device)?;
+    let b = Tensor::new(&[2i64, 2, -2, -2, 3, 3, -5], device)?;
+    assert_eq!(
+        a.floor_divide(&b)?.to_vec1::<i64>()?,
+        [3, -4, -4, 3, 2, -2, 0]
+    );
+    assert_eq!(a.remainder(&b)?.to_vec1::<i64>()?, [1, 1, -1, -1, 0, 0, 0]);
+    assert_eq!(a.fmod(&b)?.to_vec1::<i64>()?, [1, -1, 1, -1, 0, 0, 0]);
+
+    let a = a.to_dtype(DType::F32)?;
+    let b = b.to_dtype(DType::F32)?;
+    assert_eq!(
+        a.floor_divide(&b)?.to_vec1::<f32>()?,
+        [3., -4., -4., 3., 2., -2., 0.]
+    );
+    assert_eq!(
+        a.remainder(&b)?.to_vec1::<f32>()?,
+        [1., 1., -1., -1., 0., 0., 0.]
+    );
+    assert_eq!(
+        a.fmod(&b)?.to_vec1::<f32>()?,
+        [1., -1., 1., -1., 0., 0., 0.]
+    );
+    let a = Tensor::new(&[5.5f32, -5.5], device)?;
+    let b = Tensor::new(&[2f32, 2.], device)?;
+    assert_eq!(a.remainder(&b)?.to_vec1::<f32>()?, [1.5, 0.5]);
+    assert_eq!(a.fmod(&b)?.to_vec1::<f32>()?, [1.5, -1.5]);
+
+    // Broadcasting, e.g. positional bucketing.
+    let pos = Tensor::arange(0u32, 6, device)?.reshape((2, 3))?;
+    let div = Tensor::new(&[2u32, 4, 5], device)?;
+    assert_eq!(
+        pos.broadcast_floor_divide(&div)?.to_vec2::<u32>()?,
+        [[0, 0, 0], [1, 1, 1]]
+    );
+    assert_eq!(
+        pos.broadcast_remainder(&div)?.to_vec2::<u32>()?,
+        [[0, 1, 2], [1, 0, 0]]
+    );
+    assert_eq!(
+        pos.broadcast_fmod(&div)?.to_vec2::<u32>()?,
+        [[0, 1, 2], [1, 0, 0]]
+    );
+
+    // Division by zero errors for integers and gives inf/NaN for floats.
+    let zero = Tensor::new(&[1i64, 0], device)?;
+    let a = Tensor::new(&[3i64, 3], device)?;
+    assert!(a.floor_divide(&zero).is_err());
+    assert!(a.remainder(&zero).is_err());
+    let a = a.to_dtype(DType::F32)?;
+    let zero = zero.to_dtype(DType::F32)?;
+    assert_eq!(
+        a.floor_divide(&zero)?.to_vec1::<f32>()?,
+        [3., f32::INFINITY]
+    );
+    assert!(a.remainder(&zero)?.to_vec1::<f32>()?[1].is_nan());
+    assert!(a.fmod(&zero)?.to_vec1::<f32>()?[1].is_nan());
+    Ok(())
+}
+
+fn logical_ops(device: &Device) -> Result<()> {
+    // Truth tables, using nonzero values other than 1 for true.
+    let a = Tensor::new(&[0u8, 0, 7, 255], device)?;
+    let b = Tensor::new(&[0u8, 3, 0, 1], device)?;
+    assert_eq!(a.logical_and(&b)?.to_vec1::<u8>()?, [0, 0, 0, 1]);
+    assert_eq!(a.logical_or(&b)?.to_vec1::<u8>()?, [0, 1, 1, 1]);
+    assert_eq!(a.logical_xor(&b)?.to_vec1::<u8>()?, [0, 1, 1, 0]);
+    assert_eq!(a.logical_not()?.to_vec1::<u8>()?, [1, 1, 0, 0]);
+
+    // Combining a (batch, 1, seq) padding mask with a (seq, seq) causal mask.
+    let padding = Tensor::new(&[[[1u8, 1, 0]], [[2, 2, 2]]], device)?;
+    let causal = Tensor::new(&[[1u8, 0, 0], [1, 1, 0], [1, 1, 1]], device)?;
+    let mask = padding.logical_and(&causal)?;
+    assert_eq!(
+        mask.to_vec3::<u8>()?,
+        [
+            [[1, 0, 0], [1, 1, 0], [1, 1, 0]],
+            [[1, 0, 0], [1, 1, 0], [1, 1, 1]]
+        ]
+    );
+    let mask = padding.logical_not()?.logical_or(&causal.logical_not()?)?;
+    assert_eq!(
+        mask.to_vec3::<u8>()?,
+        [
+            [[0, 1, 1], [0, 0, 1], [0, 0, 1]],
+            [[0, 1, 1], [0, 0, 1], [0, 0, 0]]
+        ]
+    );
+    let mask = padding.logical_xor(&causal)?;
+    assert_eq!(
+        mask.to_vec3::<u8>()?,
+        [
+            [[0, 1, 0], [0, 0, 0], [0, 0, 1]],
+            [[0, 1, 1], [0, 0, 1], [0, 0, 0]]
+        ]
+    );
+
+    // Only u8 masks are supported.
+    let f = Tensor::new(&[0f32, 1.], device)?;
+    assert!(f.logical_not().is_err());
+    assert!(f.logical_and(&f).is_err());
+    assert!(a.logical_or(&Tensor::new(&[1u8, 0], device)?).is_err());
+    Ok(())
+}
+
+fn allclose(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[1f32, -2., 100.], [0., 1e-9, 3.]], device)?;
+
+    // Exactly equal tensors, with no tolerance at all.
+    assert!(a.allclose(&a, 0., 0.)?);
+    assert!(a.allclose(&a.copy()?, 1e-5, 1e-8)?);
+
+    // Within tolerance: relative for large values, absolute for values close to 0.
+    let b = Tensor::new(
+        &[[1.000001f32, -2.000001, 100.0005], [1e-9, 0., 3.]],
+        device,
+    )?;
+    assert!(a.allclose(&b, 1e-5, 1e-8)?);
+    assert_eq!(
+        a.isclose(&b, 1e-5, 1e-8, false)?.to_vec2::<u8>()?,
+        [[1, 1, 1], [1, 1, 1]]
+    );
+
+    // Out of tolerance.
+    assert!(!a.allclose(&b, 1e-7, 1e-8)?);
+    assert_eq!(
+        a.isclose(&b, 1e-7, 0., false)?.to_vec2::<u8>()?,
+        [[0, 0, 0], [0, 0, 1]]
+    );
+    let c = Tensor::new(&[[1.1f32, -2., 100.], [0., 1e-9, 3.]], device)?;
+    assert!(!a.allclose(&c, 1e-5, 1e-8)?);
+    assert!(a.allclose(&c, 0.1, 0.)?);
+
+    // The tolerance is relative to the second argument.
+    let x = Tensor::new(&[10f32], device)?;
+    let y = Tensor::new(&[11f32], device)?;
+    assert!(x.allclose(&y, 0.095, 0.)?);
+    assert!(!y.allclose(&x, 0.095, 0.)?);
+
+    // Broadcasting and mixed dtypes.
+    let row = Tensor::new(&[1f32, 2., 3.], device)?;
+    let rows = Tensor::new(&[[1u32, 2, 3], [1, 2, 3]], device)?;
+    assert!(rows.allclose(&row, 0., 0.)?);
+    assert!(row.allclose(&rows, 0., 0.)?);
+    assert!(a
+        .allclose(&Tensor::new(&[1f32, 2.], device)?, 0., 0.)
+        .is_err());
+
+    // NaN and infinite values.
+    let a = Tensor::new(&[f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 1.], device)?;
+    let b = Tensor::new(&[f32::NAN, f32::INFINITY, f32::INFINITY, f32::NAN], device)?;
+    assert_eq!(
+        a.isclose(&b, 1e-5, 1e-8, false)?.to_vec1::<u8>()?,
+        [0, 1, 0, 0]
+    );
+    assert_eq!(
+        a.isclose(&b, 1e-5, 1e-8, true)?.to_vec1::<u8>()?,
+        [1, 1, 0, 0]
+    );
+    assert!(!a.allclose(&a, 1e-5, 1e-8)?);
+
+    // Maximum absolute differences.
+    assert!(a.max_abs_diff(&a)?.is_nan());
+    assert!(a.max_abs_diff(&b)?.is_nan());
+    let inf = a.narrow(0, 1, 2)?;
+    assert_eq!(inf.max_abs_diff(&inf)?, 0.);
+    let nan = Tensor::new(&[f32::NAN, f32::INFINITY, 1.], device)?;
+    assert_eq!(rows.max_abs_diff(&(&row * 1.5)?)?, 1.5);
+    assert_eq!(
+        Tensor::new(&[f32::INFINITY], device)?.max_abs_diff(&Tensor::new(&[0u8], device)?)?,
+        f64::INFINITY
+    );
+
+    // The assert_close macro.
+    test_utils::assert_close!(rows, row);
+    test_utils::assert_close!(x, y, 0., 1.);
+    test_utils::assert_close!(nan, nan, 1e-5, 1e-8, true);
+    let msg = test_utils::check_close(&nan, &nan, 1e-5, 1e-8, false)?.unwrap();
+    assert!(msg.contains("1 of 3 elements are not close"), "{msg}");
+    let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        test_utils::assert_close!(x, y, 0.05, 0.)
+    }))
+    .unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("`x` is not close to `y`"), "{msg}");
+    let rows_2 = Tensor::new(&[[1f32, 2., 3.], [1., 2.5, 3.1]], device)?;
+    let msg = test_utils::check_close(&rows, &rows_2, 1e-5, 1e-8, false)?.unwrap();
+    assert!(msg.contains("2 of 6 elements are not close"), "{msg}");
+    assert!(msg.contains("worst at index [1, 1]: 2 vs 2.5"), "{msg}");
+    Ok(())
+}
+
+fn to_vec4(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0u32, 24, device)?.reshape((1, 2, 3, 4))?;
+    assert_eq!(
+        t.to_vec4::<u32>()?,
+        [[
+            [[0, 1, 2, 3], [4, 5, 6, 7], [8, 9, 10, 11]],
+            [[12, 13, 14, 15], [16, 17, 18, 19], [20, 21, 22, 23]]
+        ]]
+    );
+    assert_eq!(t.flatten_to_vec::<u32>()?, (0..24).collect::<Vec<_>>());
+    assert!(t.flatten_to_vec::<f32>().is_err());
+    assert!(t.squeeze(0)?.to_vec4::<u32>().is_err());
+
+    // Strided tensors.
+    let t = t.permute((3, 1, 0, 2))?;
+    assert!(!t.is_contiguous());
+    assert_eq!(
+        t.to_vec4::<u32>()?,
+        [
+            [[[0, 4, 8]], [[12, 16, 20]]],
+            [[[1, 5, 9]], [[13, 17, 21]]],
+            [[[2, 6, 10]], [[14, 18, 22]]],
+            [[[3, 7, 11]], [[15, 19, 23]]]
+        ]
+    );
+    let flat = t.flatten_to_vec::<u32>()?;
+    assert_eq!(flat, t.flatten_all()?.to_vec1::<u32>()?);
+    assert_eq!(flat[..6], [0, 4, 8, 12, 16, 20]);
+    let t = t.narrow(0, 1, 2)?.narrow(3, 1, 2)?;
+    assert_eq!(
+        t.to_vec4::<u32>()?,
+        [[[[5, 9]], [[17, 21]]], [[[6, 10]], [[18, 22]]]]
+    );
+    assert_eq!(t.flatten_to_vec::<u32>()?, [5, 9, 17, 21, 6, 10, 18, 22]);
+    assert_eq!(Tensor::new(3f32, device)?.flatten_to_vec::<f32>()?, [3.]);
+
+    // Generic nested vectors, using a strided 5D tensor.
+    let v4: Vec<Vec<Vec<Vec<u32>>>> = t.to_vec_nd()?;
+    assert_eq!(v4, t.to_vec4::<u32>()?);
+    let t = Tensor::arange(0u32, 24, device)?
+        .reshape((2, 1, 3, 2, 2))?
+        .transpose(0, 4)?;
+    let v5: Vec<Vec<Vec<Vec<Vec<u32>>>>> = t.to_vec_nd()?;
+    assert_eq!(v5[1][0][2][1], [11, 23]);
+    assert_eq!(
+        t.flatten_to_vec::<u32>()?,
+        t.flatten_all()?.to_vec1::<u32>()?
+    );
+    assert!(t.to_vec_nd::<Vec<Vec<Vec<Vec<u32>>>>>().is_err());
+    assert!(t.to_vec_nd::<Vec<Vec<Vec<Vec<Vec<f32>>>>>>().is_err());
+    Ok(())
+}
+
+fn to_from_bytes(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[1f32, -2.5, 3.25], [0., 1e-3, 42.]], device)?;
+    let bytes = t.to_bytes()?;
+    assert_eq!(bytes.len(), 24);
+    assert_eq!(bytes[4..8], (-2.5f32).to_le_bytes());
+    let t2 = Tensor::from_bytes(&bytes, (2, 3), DType::F32, device)?;
+    assert_eq!(t2.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+
+    // Non-contiguous tensors are serialized in row-major order.
+    let t = Tensor::arange(0u32, 6, device)?.reshape((2, 3))?.t()?;
+    let bytes = t.to_bytes()?;
+    assert_eq!(bytes[4..8], 3u32.to_le_bytes());
+    let t2 = Tensor::from_bytes(&bytes, (3, 2), DType::U32, device)?;
+    assert_eq!(t2.to_vec2::<u32>()?, [[0, 3], [1, 4], [2, 5]]);
+
+    let t = Tensor::new(&[1f32, 0.5], device)?;
+    assert_eq!(t.to_dtype(DType::F16)?.to_bytes()?.len(), 4);
+    assert_eq!(
+        t.to_dtype(DType::BF16)?.to_bytes()?,
+        [0x80, 0x3f, 0x00, 0x3f]
+    );
+
+    // Byte-length mismatch.
+    let err = Tensor::from_bytes(&bytes[..23], (3, 2), DType::U32, device).unwrap_err();
+    assert!(err.to_string().contains("does not match shape"), "{err}");
+    assert!(Tensor::from_bytes(&bytes, (2, 2), DType::U32, device).is_err());
+    assert!(Tensor::from_bytes(&bytes, (3, 2), DType::F64, device).is_err());
+    Ok(())
+}
+
+fn var_inplace(device: &Device) -> Result<()> {
+    let var = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let view = var.narrow(1, 1, 2)?;
+    let before = var.as_tensor().copy()?;
+
+    var.add_(&Tensor::new(&[[10f32, 20., 30.], [40., 50., 60.]], device)?)?;
+    assert_eq!(var.to_vec2::<f32>()?, [[11., 22., 33.], [44., 55., 66.]]);
+    // Views sharing the storage observe the change, copies don't.
+    assert_eq!(view.to_vec2::<f32>()?, [[22., 33.], [55., 66.]]);
+    assert_eq!(before.to_vec2::<f32>()?, [[1., 2., 3.], [4., 5., 6.]]);
+
+    // Non-contiguous deltas and deltas derived from the variable itself.
+    let delta = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?.t()?;
+    var.add_(&delta)?;
+    assert_eq!(var.to_vec2::<f32>()?, [[12., 25., 38.], [46., 59., 72.]]);
+    var.add_(&var.neg()?)?;
+    var.add_(var.as_tensor())?;
+    assert_eq!(var.to_vec2::<f32>()?, [[0., 0., 0.], [0., 0., 0.]]);
+
+    // copy_ accepts values derived from the variable.
+    var.copy_(&before)?;
+    var.copy_(&(var.as_tensor() * 2.)?)?;
+    assert_eq!(view.to_vec2::<f32>()?, [[4., 6.], [10., 12.]]);
+
+    // In-place updates are not tracked in the graph and keep the variable identity.
+    let id = var.id();
+    var.add_(&Tensor::ones((2, 3), DType::F32, device)?)?;
+    assert_eq!(var.id(), id);
+    let grads = var.sum_all()?.backward()?;
+    assert_eq!(
+        grads.get(&var).unwrap().to_vec2::<f32>()?,
+        [[1., 1., 1.], [1., 1., 1.]]
+    );
+
+    assert!(var
+        .add_(&Tensor::ones((3, 2), DType::F32, device)?)
+        .is_err());
+    assert!(var
+        .add_(&Tensor::ones((2, 3), DType::F64, device)?)
+        .is_err());
+
+    let var = Var::new(&[1u32, 2, 3], device)?;
+    var.add_(&Tensor::new(&[5u32, 5, 5], device)?)?;
+    assert_eq!(var.to_vec1::<u32>()?, [6, 7, 8]);
+    Ok(())
+}
+
+fn det_inverse_solve(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[4f32, 7.], [2., 6.]], device)?;
+    assert_eq!(test_utils::to_vec0_round(&a.det()?, 4)?, 10.);
+    assert_eq!(
+        test_utils::to_vec2_round(&a.inverse()?, 4)?,
+        [[0.6, -0.7], [-0.2, 0.4]]
+    );
+
+    let a = Tensor::new(&[[2f32, -1., 0.], [-1., 2., -1.], [0., -1., 2.]], device)?;
+    assert_eq!(test_utils::to_vec0_round(&a.det()?, 6)?, 4.);
+    let inv = a.inverse()?;
+    assert_eq!(
+        test_utils::to_vec2_round(&inv, 6)?,
+        [[0.75, 0.5, 0.25], [0.5, 1., 0.5], [0.25, 0.5, 0.75]]
+    );
+    let eye = test_utils::to_vec2_round(&a.matmul(&inv)?, 6)?;
+    assert_eq!(eye, [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+
+    // A permutation is needed as the first pivot is zero.
+    let a = Tensor::new(&[[0f32, 2., 1.], [1., 1., 0.], [3., 0., 1.]], device)?;
+    assert_eq!(test_utils::to_vec0_round(&a.det()?, 6)?, -5.);
+    let b = Tensor::new(&[6f32, 3., 5.], device)?;
+    let x = a.solve(&b)?;
+    assert_eq!(test_utils::to_vec1_round(&x, 6)?, [1., 2., 2.]);
+    let bs = Tensor::new(&[[5f32, 1.], [3., 0.], [5., 3.]], device)?;
+    let xs = a.solve(&bs)?;
+    assert_eq!(xs.dims(), &[3, 2]);
+    assert_eq!(
+        test_utils::to_vec2_round(&a.matmul(&xs)?, 6)?,
+        [[5., 1.], [3., 0.], [5., 3.]]
+    );
+
+    // Batched inputs and singular matrices.
+    let batch = Tensor::stack(&[&a, &a.t()?, &(&a * 2.)?], 0)?;
+    assert_eq!(
+        test_utils::to_vec1_round(&batch.det()?, 4)?,
+        [-5., -5., -40.]
+    );
+    let singular = Tensor::new(&[[1f32, 2.], [2., 4.]], device)?;
+    assert_eq!(singular.det()?.to_scalar::<f32>()?, 0.);
+    assert!(singular.inverse().is_err());
+    assert!(singular.solve(&Tensor::new(&[1f32, 1.], device)?).is_err());
+    assert!(Tensor::zeros((2, 3), DType::F32, device)?.det().is_err());
+    assert!(a.solve(&Tensor::new(&[1f32, 1.], device)?).is_err());
+    Ok(())
+}
+
+fn cholesky_triangular_solve(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[4f32, 2., 2.], [2., 5., 3.], [2., 3., 6.]], device)?;
+    let l = a.cholesky(false)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&l, 6)?,
+        [[2., 0., 0.], [1., 2., 0.], [1., 1., 2.]]
+    );
+    assert_eq!(
+        test_utils::to_vec2_round(&l.matmul(&l.t()?)?, 5)?,
+        test_utils::to_vec2_round(&a, 5)?
+    );
+    let u = a.cholesky(true)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&u.t()?.matmul(&u)?, 5)?,
+        test_utils::to_vec2_round(&a, 5)?
+    );
+
+    let b = Tensor::new(&[2f32, 5., 7.], device)?;
+    let x = l.triangular_solve(&b, false, false)?;
+    assert_eq!(test_utils::to_vec1_round(&x, 6)?, [1., 2., 2.]);
+    let x = l.triangular_solve(&b, false, true)?;
+    assert_eq!(test_utils::to_vec1_round(&x, 6)?, [-1.125, 0.75, 3.5]);
+    let x = u.triangular_solve(&b, true, false)?;
+    assert_eq!(test_utils::to_vec1_round(&x, 6)?, [-1.125, 0.75, 3.5]);
+    let bs = Tensor::stack(&[&b, &(&b * 2.)?], 1)?;
+    let xs = u.triangular_solve(&bs, true, true)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&l.matmul(&xs)?, 5)?,
+        [[2., 4.], [5., 10.], [7., 14.]]
+    );
+
+    // Batched inputs and matrices that are not positive-definite.
+    let batch = Tensor::stack(&[&a, &(&a * 4.)?], 0)?;
+    let ls = batch.cholesky(false)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&ls.get(1)?, 6)?,
+        [[4., 0., 0.], [2., 4., 0.], [2., 2., 4.]]
+    );
+    let not_pd = Tensor::new(&[[1f32, 2.], [2., 1.]], device)?;
+    assert!(not_pd.cholesky(false).is_err());
+    let singular = Tensor::new(&[[1f32, 0.], [1., 0.]], device)?;
+    assert!(singular
+        .triangular_solve(&b.narrow(0, 0, 2)?, false, false)
+        .is_err());
+    Ok(())
+}
+
+fn qr_svd(device: &Device) -> Result<()> {
+    let eye = |n: usize| -> Vec<Vec<f32>> {
+        (0..n)
+            .map(|i| (0..n).map(|j| if i == j { 1. } else { 0. }).collect())
+            .collect()
+    };
+    let a = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?;
+    let (q, r) = a.qr()?;
+    assert_eq!(q.dims(), &[3, 2]);
+    assert_eq!(r.dims(), &[2, 2]);
+    assert_eq!(
+        test_utils::to_vec2_round(&q.matmul(&r)?, 4)?,
+        test_utils::to_vec2_round(&a, 4)?
+    );
+    assert_eq!(test_utils::to_vec2_round(&q.t()?.matmul(&q)?, 4)?, eye(2));
+    let r = r.to_vec2::<f32>()?;
+    assert_eq!(r[1][0], 0.);
+    assert!(r[0][0] > 0. && r[1][1] > 0.);
+
+    // Wide and batched inputs.
+    let (q, r) = a.t()?.qr()?;
+    assert_eq!((q.dims(), r.dims()), (&[2, 2][..], &[2, 3][..]));
+    assert_eq!(
+        test_utils::to_vec2_round(&q.matmul(&r)?, 4)?,
+        test_utils::to_vec2_round(&a.t()?, 4)?
+    );
+    let batch = Tensor::stack(&[&a, &(&a * -2.)?], 0)?;
+    let (q, r) = batch.qr()?;
+    assert_eq!(
+        test_utils::to_vec3_round(&q.matmul(&r)?, 4)?,
+        test_utils::to_vec3_round(&batch, 4)?
+    );
+
+    let a = Tensor::new(&[[3f32, 0.], [0., -4.]], device)?;
+    let (_, s, _) = a.svd()?;
+    assert_eq!(test_utils::to_vec1_round(&s, 4)?, [4., 3.]);
+    for a in [
+        Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?,
+        Tensor::new(&[[2f32, 0., 1.], [-1., 3., 0.5]], device)?,
+        // A rank deficient matrix.
+        Tensor::new(&[[1f32, 2.], [2., 4.]], device)?,
+    ] {
+        let (u, s, v) = a.svd()?;
+        let k = s.dim(0)?;
+        let ys = u.broadcast_mul(&s.unsqueeze(0)?)?.matmul(&v.t()?)?;
+        assert_eq!(
+            test_utils::to_vec2_round(&ys, 4)?,
+            test_utils::to_vec2_round(&a, 4)?
+        );
+        assert_eq!(test_utils::to_vec2_round(&u.t()?.matmul(&u)?, 4)?, eye(k));
+        assert_eq!(test_utils::to_vec2_round(&v.t()?.matmul(&v)?, 4)?, eye(k));
+    }
+    let (_, s, _) = Tensor::new(&[[1f32, 2.], [2., 4.]], device)?.svd()?;
+    assert_eq!(test_utils::to_vec1_round(&s, 4)?, [5., 0.]);
+    let (u, s, v) = batch.svd()?;
+    assert_eq!(
+        (u.dims(), s.dims(), v.dims()),
+        (&[2, 3, 2][..], &[2, 2][..], &[2, 2, 2][..])
+    );
+    Ok(())
+}
+
+fn trace_mt(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 9., device)?.reshape((3, 3))?;
+    assert_eq!(t.trace()?.to_scalar::<f32>()?, 12.);
+    let t = Tensor::arange(0f32, 24., device)?.reshape((2, 3, 4))?;
+    assert_eq!(t.trace()?.to_vec1::<f32>()?, [15., 51.]);
+    let mt = t.mT()?;
+    assert_eq!(mt.dims(), &[2, 4, 3]);
+    assert_eq!(mt.i((1, 3))?.to_vec1::<f32>()?, [15., 19., 23.]);
+    assert!(Tensor::new(&[1f32, 2.], device)?.trace().is_err());
+    Ok(())
+}
+
+fn movedim(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0u32, 120, device)?.reshape((2, 3, 4, 5))?;
+    let nhwc = t.movedim(1, 3)?;
+    assert_eq!(nhwc.dims(), &[2, 4, 5, 3]);
+    assert_eq!(
+        nhwc.i((1, 2, 3))?.to_vec1::<u32>()?,
+        t.i((1, .., 2, 3))?.to_vec1::<u32>()?
+    );
+    let nchw = nhwc.movedim(D::Minus1, 1)?;
+    assert_eq!(nchw.dims(), &[2, 3, 4, 5]);
+    assert_eq!(nchw.flatten_to_vec::<u32>()?, (0..120).collect::<Vec<_>>());
+    assert_eq!(t.movedim(2, 2)?.dims(), t.dims());
+
+    let moved = t.movedim_multi((0, 1), (3, 2))?;
+    assert_eq!(moved.dims(), &[4, 5, 3, 2]);
+    assert_eq!(
+        moved.flatten_to_vec::<u32>()?,
+        t.permute((2, 3, 1, 0))?.flatten_to_vec::<u32>()?
+    );
+    assert_eq!(t.movedim_multi((3, 0), (0, 3))?.dims(), &[5, 3, 4, 2]);
+    assert!(t.movedim_multi((0, 1), 2).is_err());
+    assert!(t.movedim_multi((0, 1), (2, 2)).is_err());
+
+    assert_eq!(t.swapaxes(0, 2)?.dims(), &[4, 3, 2, 5]);
+    Ok(())
+}
+
+fn as_strided(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 6., device)?.reshape((2, 3))?;
+    let tt = t.as_strided(&[3, 2], &[1, 3], 0)?;
+    assert_eq!(tt.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    // Offsets are relative to the input, including for narrowed or non-contiguous inputs.
+    let row = t.as_strided(&[2], &[1], 4)?;
+    assert_eq!(row.to_vec1::<f32>()?, [4., 5.]);
+    let col = t.narrow(1, 1, 2)?.as_strided(&[2], &[2], 1)?;
+    assert_eq!(col.to_vec1::<f32>()?, [2., 5.]);
+    assert_eq!(
+        tt.as_strided(&[3], &[2], 0)?.to_vec1::<f32>()?,
+        [0., 1., 2.]
+    );
+
+    // Overlapping windows for a moving average.
+    let xs = Tensor::new(&[1f32, 2., 6., 3., 9., 0.], device)?;
+    let windows = xs.as_strided(&[4, 3], &[1, 1], 0)?;
+    assert_eq!(windows.mean(1)?.to_vec1::<f32>()?, [3., 11. / 3., 6., 4.]);
+
+    assert_eq!(xs.as_strided(&[3, 3], &[1, 1], 1)?.dims(), &[3, 3]);
+    assert!(xs.as_strided(&[4, 3], &[1, 1], 1).is_err());
+    assert!(xs.as_strided(&[2, 3], &[1], 0).is_err());
+    assert_eq!(xs.as_strided(&[0, 3], &[1, 1], 6)?.dims(), &[0, 3]);
+    // Offsets that overflow are rejected rather than wrapping around.
+    let err = xs
+        .as_strided(&[3, 2], &[usize::MAX / 2 + 1, 1], 0)
+        .unwrap_err();
+    assert!(err.to_string().contains("overflow"), "{err}");
+    assert!(xs.as_strided(&[2], &[1], usize::MAX).is_err());
+    Ok(())
+}
+
+fn unfold(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 5., device)?;
+    let windows = t.unfold(0, 2, 1)?;
+    assert_eq!(
+        windows.to_vec2::<f32>()?,
+        [[0., 1.], [1., 2.], [2., 3.], [3., 4.]]
+    );
+    assert_eq!(t.unfold(0, 2, 3)?.to_vec2::<f32>()?, [[0., 1.], [3., 4.]]);
+    assert_eq!(t.unfold(0, 5, 1)?.dims(), [1, 5]);
+
+    // Unfolding an inner dimension adds the window dimension at the end.
+    let t = Tensor::arange(0f32, 8., device)?.reshape((2, 4))?;
+    let windows = t.unfold(1, 3, 1)?;
+    assert_eq!(windows.dims(), [2, 2, 3]);
+    assert_eq!(
+        windows.to_vec3::<f32>()?,
+        [[[0., 1., 2.], [1., 2., 3.]], [[4., 5., 6.], [5., 6., 7.]]]
+    );
+    let windows = t.t()?.unfold(0, 2, 2)?;
+    assert_eq!(windows.dims(), [2, 2, 2]);
+    assert_eq!(
+        windows.to_vec3::<f32>()?,
+        [[[0., 1.], [4., 5.]], [[2., 3.], [6., 7.]]]
+    );
+
+    assert!(t.unfold(1, 5, 1).is_err());
+    assert!(t.unfold(1, 2, 0).is_err());
+    Ok(())
+}
+
+fn negative_dims(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 24., device)?.reshape((2, 3, 4))?;
+    assert_eq!(
+        t.narrow(-1, 1, 2)?.to_vec3::<f32>()?,
+        t.narrow(2, 1, 2)?.to_vec3::<f32>()?
+    );
+    assert_eq!(
+        t.sum(-3)?.to_vec2::<f32>()?,
+        t.sum(D::Minus(3))?.to_vec2::<f32>()?
+    );
+    assert_eq!(t.sum_keepdim((-1, 0))?.dims(), [1, 3, 1]);
+    assert_eq!(t.transpose(-1, -3)?.dims(), [4, 3, 2]);
+    assert_eq!(t.transpose(D::Minus(1), D::Minus2)?.dims(), [2, 4, 3]);
+    assert_eq!(t.pad_with_zeros(-2i64, 1, 0)?.dims(), [2, 4, 4]);
+    // Dimensions are inserted after the resolved one, -1 appends a new dimension.
+    assert_eq!(t.unsqueeze(-1)?.dims(), [2, 3, 4, 1]);
+    assert_eq!(t.unsqueeze(-4)?.dims(), [1, 2, 3, 4]);
+    assert_eq!(Tensor::stack(&[&t, &t], -1)?.dims(), [2, 3, 4, 2]);
+    assert_eq!(Tensor::cat(&[&t, &t], -1)?.dims(), [2, 3, 8]);
+
+    let err = t.sum(-4).unwrap_err().to_string();
+    assert!(err.contains("dimension index -4 (resolved to -1)"), "{err}");
+    let err = t.unsqueeze(-5i64).unwrap_err().to_string();
+    assert!(err.contains("dimension index -5 (resolved to -1)"), "{err}");
+    assert!(t.narrow(D::Minus(4), 0, 1).is_err());
+    assert!(t.narrow(D::Minus(0), 0, 1).is_err());
+    assert!(t.narrow(3, 0, 1).is_err());
+    Ok(())
+}
+
+fn split_unbind(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0f32, 24., device)?.reshape((2, 3, 4))?;
+    let pieces = t.split(&[1, 3], D::Minus1)?;
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(pieces[0].dims(), [2, 3, 1]);
+    assert_eq!(pieces[1].to_vec3::<f32>()?[1][2], [21., 22., 23.]);
+    assert_eq!(
+        Tensor::cat(&pieces, 2)?.to_vec3::<f32>()?,
+        t.to_vec3::<f32>()?
+    );
+    let pieces = t.split(&[0, 2], 0)?;
+    assert_eq!(pieces[0].dims(), [0, 3, 4]);
+    assert!(t.split(&[1, 1], 1).is_err());
+    assert!(t.split(&[2, 2], 1).is_err());
+
+    let slices = t.unbind(1)?;
+    assert_eq!(slices.len(), 3);
+    assert_eq!(
+        slices[1].to_vec2::<f32>()?,
+        [[4., 5., 6., 7.], [16., 17., 18., 19.]]
+    );
+    // The slices are strided views rather than copies.
+    assert!(!slices[1].is_contiguous());
+    assert_eq!(
+        Tensor::stack(&slices, 1)?.to_vec3::<f32>()?,
+        t.to_vec3::<f32>()?
+    );
+    let slices = t.unbind(-1)?;
+    assert_eq!(
+        slices[3].to_vec2::<f32>()?,
+        [[3., 7., 11.], [15., 19., 23.]]
+    );
+    assert!(t.unbind(0)?[1].is_contiguous());
+    assert!(Tensor::zeros((0, 2), DType::F32, device)?
+        .unbind(0)?
+        .is_empty());
+    assert!(t.unbind(3).is_err());
+    Ok(())
+}
+
+fn repeat_interleave(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[1u32, 2, 3], device)?;
+    assert_eq!(
+        t.repeat_interleave(2, 0)?.to_vec1::<u32>()?,
+        [1, 1, 2, 2, 3, 3]
+    );
+    assert_eq!(t.repeat_interleave(1, 0)?.to_vec1::<u32>()?, [1, 2, 3]);
+    assert_eq!(t.repeat_interleave(0, 0)?.dims(), [0]);
+
+    // Grouped-query attention, repeating the kv heads of a (b, heads, seq, head_dim) tensor.
+    let kv = Tensor::arange(0f32, 24., device)?.reshape((2, 2, 3, 2))?;
+    let rep = kv.repeat_interleave(3, 1)?;
+    assert_eq!(rep.dims(), [2, 6, 3, 2]);
+    for h in 0..6 {
+        assert_eq!(
+            rep.i((.., h))?.to_vec3::<f32>()?,
+            kv.i((.., h / 3))?.to_vec3::<f32>()?
+        );
+    }
+    let rep = kv.repeat_interleave(2, 2)?;
+    assert_eq!(rep.dims(), [2, 2, 6, 2]);
+    assert_eq!(
+        rep.i((1, 0))?.to_vec2::<f32>()?,
+        [
+            [12., 13.],
+            [12., 13.],
+            [14., 15.],
+            [14., 15.],
+            [16., 17.],
+            [16., 17.]
+        ]
+    );
+    // Non-contiguous inputs and negative dims.
+    let rep = kv.transpose(1, 2)?.repeat_interleave(2, -3)?;
+    assert_eq!(rep.dims(), [2, 6, 2, 2]);
+    assert_eq!(rep.i((0, 5, 1))?.to_vec1::<f32>()?, [10., 11.]);
+    assert!(kv.repeat_interleave(2, 4).is_err());
+    Ok(())
+}
+
 test_device!(zeros, zeros_cpu, zeros_gpu);
 test_device!(add_mul, add_mul_cpu, add_mul_gpu);
 test_device!(tensor_2d, tensor_2d_cpu, tensor_2d_gpu);
@@ -899,6 +1949,50 @@ test_device!(index_select, index_select_cpu, index_select_gpu);
 test_device!(index_add, index_add_cpu, index_add_gpu);
 test_device!(gather, gather_cpu, gather_gpu);
 test_device!(scatter_add, scatter_add_cpu, scatter_add_gpu);
+test_device!(diff, diff_cpu, diff_gpu);
+test_device!(pad, pad_cpu, pad_gpu);
+test_device!(meshgrid, meshgrid_cpu, meshgrid_gpu);
+test_device!(outer_kron, outer_kron_cpu, outer_kron_gpu);
+test_device!(norm, norm_cpu, norm_gpu);
+test_device!(floor_ceil_round, floor_ceil_round_cpu, floor_ceil_round_gpu);
+test_device!(split_storage, split_storage_cpu, split_storage_gpu);
+test_device!(chunks_along, chunks_along_cpu, chunks_along_gpu);
+test_device!(bf16_ops, bf16_ops_cpu, bf16_ops_gpu);
+test_device!(i64_ops, i64_ops_cpu, i64_ops_gpu);
+test_device!(cmp_scalar, cmp_scalar_cpu, cmp_scalar_gpu);
+test_device!(min_max_scalar, min_max_scalar_cpu, min_max_scalar_gpu);
+test_device!(
+    floor_divide_remainder,
+    floor_divide_remainder_cpu,
+    floor_divide_remainder_gpu
+);
+test_device!(logical_ops, logical_ops_cpu, logical_ops_gpu);
+test_device!(allclose, allclose_cpu, allclose_gpu);
+test_device!(to_vec4, to_vec4_cpu, to_vec4_gpu);
+test_device!(to_from_bytes, to_from_bytes_cpu, to_from_bytes_gpu);
+test_device!(var_inplace, var_inplace_cpu, var_inplace_gpu);
+test_device!(
+    det_inverse_solve,
+    det_inverse_solve_cpu,
+    det_inverse_solve_gpu
+);
+test_device!(
+    cholesky_triangular_solve,
+    cholesky_triangular_solve_cpu,
+    cholesky_triangular_solve_gpu
+);
+test_device!(qr_svd, qr_svd_cpu, qr_svd_gpu);
+test_device!(trace_mt, trace_mt_cpu, trace_mt_gpu);
+test_device!(movedim, movedim_cpu, movedim_gpu);
+test_device!(as_strided, as_strided_cpu, as_strided_gpu);
+test_device!(unfold, unfold_cpu, unfold_gpu);
+test_device!(negative_dims, negative_dims_cpu, negative_dims_gpu);
+test_device!(split_unbind, split_unbind_cpu, split_unbind_gpu);
+test_device!(
+    repeat_interleave,
+    repeat_interleave_cpu,
+    repeat_interleave_gpu
+);
 
 // There was originally a bug on the CPU implementation for randn
 // https://github.com/huggingface/candle/issues/381
@@ -910,3 +2004,253 @@ fn randn_hasneg() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn from_vec_no_copy() -> Result<()> {
+    let data: Vec<f32> = (0..12).map(|v| v as f32 * 0.5).collect();
+    let ptr = data.as_ptr();
+    let t = Tensor::from_vec(data, (3, 4), &Device::Cpu)?;
+    let (storage, layout) = t.storage_and_layout();
+    match &*storage {
+        candle_core::Storage::Cpu(cpu) => {
+            assert_eq!(
+                cpu.as_slice::<f32>()?[layout.start_offset()..].as_ptr(),
+                ptr
+            )
+        }
+        _ => candle_core::bail!("unexpected device"),
+    }
+    drop(storage);
+    assert_eq!(t.i((2, 3))?.to_scalar::<f32>()?, 5.5);
+    assert!(Tensor::from_vec(vec![0f32; 5], (2, 3), &Device::Cpu).is_err());
+    Ok(())
+}
+
+#[test]
+fn from_storage_owned() -> Result<()> {
+    use candle_core::{CpuBuffer, CpuStorage};
+    use std::sync::Arc;
+
+    let data_ptr = |t: &Tensor| -> Result<*const f32> {
+        let (storage, layout) = t.storage_and_layout();
+        match &*storage {
+            candle_core::Storage::Cpu(cpu) => {
+                Ok(cpu.as_slice::<f32>()?[layout.start_offset()..].as_ptr())
+            }
+            _ => candle_core::bail!("unexpected device"),
+        }
+    };
+
+    // A pre-filled shared buffer, the tensor keeps it alive.
+    let data: Arc<[f32]> = (0..12).map(|v| v as f32 * 0.5).collect();
+    let storage = CpuStorage::F32(CpuBuffer::from(data.clone()));
+    let t = Tensor::from_storage_owned(storage, (3, 4))?;
+    assert_eq!(data_ptr(&t)?, data.as_ptr());
+    assert_eq!(Arc::strong_count(&data), 2);
+    assert_eq!(t.i((2, 3))?.to_scalar::<f32>()?, 5.5);
+    assert_eq!(t.sum_all()?.to_scalar::<f32>()?, 33.);
+    drop(t);
+    assert_eq!(Arc::strong_count(&data), 1);
+    let storage = CpuStorage::F32(CpuBuffer::from(data.clone()));
+    assert!(Tensor::from_storage_owned(storage, (2, 3)).is_err());
+
+    // A memory mapped file.
+    let path = std::env::temp_dir().join(format!("candle-mmap-{}", std::process::id()));
+    let bytes: Vec<u8> = (0..6).flat_map(|v| (v as f32).to_le_bytes()).collect();
+    std::fs::write(&path, bytes)?;
+    let file = std::fs::File::open(&path)?;
+    let mmap = Arc::new(unsafe { memmap2::MmapOptions::new().map(&file)? });
+    std::fs::remove_file(&path)?;
+    let ptr = mmap.as_ptr() as *const f32;
+    let buffer = unsafe { CpuBuffer::from_raw_parts(ptr, 6, mmap) };
+    assert!(!buffer.is_owned());
+    let t = Tensor::from_storage_owned(CpuStorage::F32(buffer), (2, 3))?;
+    assert_eq!(data_ptr(&t)?, ptr);
+    assert_eq!(t.to_vec2::<f32>()?, [[0., 1., 2.], [3., 4., 5.]]);
+
+    // Mutating a shared buffer copies it first.
+    let mut buffer = CpuBuffer::from(data.clone());
+    buffer[0] = 42.;
+    assert!(buffer.is_owned());
+    assert_eq!(buffer[..2], [42., 0.5]);
+    assert_eq!(data[0], 0.);
+    Ok(())
+}
+
+#[cfg(feature = "cuda")]
+#[test]
+fn to_device_cuda() -> Result<()> {
+    let t = Tensor::arange(0f32, 12., &Device::Cpu)?.reshape((3, 4))?;
+    let dev0 = Device::new_cuda(0)?;
+    let t0 = t.to_device(&dev0)?;
+    // Same device: shallow copy.
+    assert_eq!(t0.to_device(&dev0)?.id(), t0.id());
+    // Same gpu through another device handle.
+    let t0b = t0.t()?.to_device(&Device::new_cuda(0)?)?;
+    assert_eq!(t0b.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    // The peer copy requires a second gpu.
+    let dev1 = match Device::new_cuda(1) {
+        Ok(dev1) => dev1,
+        Err(_) => return Ok(()),
+    };
+    let t1 = t0.to_device(&dev1)?;
+    assert!(t1.device().same_device(&dev1));
+    assert_eq!(t1.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+    let back = (t1 * 2.)?.to_device(&dev0)?;
+    assert_eq!(back.to_vec2::<f32>()?, (t * 2.)?.to_vec2::<f32>()?);
+    Ok(())
+}
+
+#[cfg(feature = "cuda")]
+#[test]
+fn cuda_memory_pool() -> Result<()> {
+    let device = Device::new_cuda(0)?;
+    let shape = (256, 1024);
+    let bytes = 256 * 1024 * 4;
+    drop(Tensor::zeros(shape, DType::F32, &device)?);
+    let stats = device.memory_pool_stats()?;
+    if stats.peak_reserved_bytes == 0 {
+        // The device does not support memory pools so nothing is cached.
+        return Ok(());
+    }
+    assert!(stats.peak_reserved_bytes >= bytes);
+    let alloc_and_drop = || -> Result<()> {
+        let t = Tensor::ones(shape, DType::F32, &device)?;
+        drop(t.affine(2., 1.)?);
+        Ok(())
+    };
+    alloc_and_drop()?;
+    // Dropped buffers are reused rather than being allocated from the driver every time.
+    let stats = device.memory_pool_stats()?;
+    for _ in 0..100 {
+        alloc_and_drop()?
+    }
+    let new_stats = device.memory_pool_stats()?;
+    assert!(new_stats.driver_allocations - stats.driver_allocations < 10);
+    assert!(new_stats.peak_reserved_bytes <= stats.peak_reserved_bytes + bytes);
+    device.reset_memory_pool()?;
+    let reset_stats = device.memory_pool_stats()?;
+    assert!(reset_stats.cached_bytes <= new_stats.cached_bytes);
+    assert!(reset_stats.peak_reserved_bytes <= new_stats.peak_reserved_bytes);
+    assert_eq!(Device::Cpu.memory_pool_stats()?, Default::default());
+    Ok(())
+}
+
+#[test]
+fn unpin() -> Result<()> {
+    use candle_core::{CpuBuffer, CpuStorage};
+    use std::sync::Arc;
+
+    let data: Arc<[f32]> = (0..12).map(|v| v as f32).collect();
+    let storage = CpuStorage::F32(CpuBuffer::from(data.clone()));
+    let t = Tensor::from_storage_owned(storage, (3, 4))?;
+    let u = t.t()?.unpin()?;
+    assert_eq!(u.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    match &*u.storage_and_layout().0 {
+        candle_core::Storage::Cpu(CpuStorage::F32(buffer)) => assert!(buffer.is_owned()),
+        _ => candle_core::bail!("unexpected storage"),
+    }
+    drop(t);
+    assert_eq!(Arc::strong_count(&data), 1);
+    #[cfg(not(feature = "cuda"))]
+    assert!(u.pin().is_err());
+    Ok(())
+}
+
+#[cfg(feature = "cuda")]
+#[test]
+fn pin() -> Result<()> {
+    let device = Device::new_cuda(0)?;
+    let t = Tensor::arange(0f32, 12., &Device::Cpu)?.reshape((3, 4))?;
+    let pinned = t.t()?.pin()?;
+    assert!(pinned.device().is_cpu());
+    assert_eq!(pinned.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    let uploaded = pinned.to_device(&device)?;
+    assert_eq!(uploaded.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    assert_eq!(uploaded.pin()?.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    assert_eq!(pinned.unpin()?.to_vec2::<f32>()?, t.t()?.to_vec2::<f32>()?);
+    assert_eq!(
+        Tensor::zeros(0, DType::F32, &Device::Cpu)?.pin()?.dims(),
+        [0]
+    );
+
+    // Compare the upload times from pageable and pinned memory, this is only reported as the
+    // speedup depends on the machine.
+    let t = Tensor::ones((64, 1024, 1024), DType::F32, &Device::Cpu)?;
+    let pinned = t.pin()?;
+    let upload = |t: &Tensor| -> Result<std::time::Duration> {
+        // The uploads are synchronous.
+        let start = std::time::Instant::now();
+        drop(t.to_device(&device)?);
+        Ok(start.elapsed())
+    };
+    upload(&t)?;
+    let (pageable, pinned) = (upload(&t)?, upload(&pinned)?);
+    println!("256MB upload, pageable: {pageable:?}, pinned: {pinned:?}");
+    Ok(())
+}
+
+#[cfg(feature = "metal")]
+#[test]
+fn metal_mlp() -> Result<()> {
+    // The test only runs on machines with a metal gpu.
+    let device = match Device::new_metal(0) {
+        Ok(device) => device,
+        Err(_) => return Ok(()),
+    };
+    let mlp = |xs: &Tensor, ws: &[Tensor]| -> Result<Tensor> {
+        let (w1, b1, w2, b2) = (&ws[0], &ws[1], &ws[2], &ws[3]);
+        let xs = xs.matmul(&w1.t()?)?.broadcast_add(b1)?.relu()?;
+        let xs = xs.matmul(&w2.t()?)?.broadcast_add(b2)?;
+        let max = xs.max_keepdim(D::Minus1)?;
+        let xs = xs.broadcast_sub(&max)?.exp()?;
+        xs.broadcast_div(&xs.sum_keepdim(D::Minus1)?)
+    };
+    let xs = Tensor::randn(0f32, 1., (5, 8), &Device::Cpu)?;
+    let ws = [
+        Tensor::randn(0f32, 0.3, (16, 8), &Device::Cpu)?,
+        Tensor::randn(0f32, 0.3, 16, &Device::Cpu)?,
+        Tensor::randn(0f32, 0.3, (3, 16), &Device::Cpu)?,
+        Tensor::randn(0f32, 0.3, 3, &Device::Cpu)?,
+    ];
+    let expected = mlp(&xs, &ws)?;
+    let metal_ws = ws
+        .iter()
+        .map(|w| w.to_device(&device))
+        .collect::<Result<Vec<_>>>()?;
+    let ys = mlp(&xs.to_device(&device)?, &metal_ws)?;
+    assert!(ys.device().is_metal());
+    let ys = ys.to_device(&Device::Cpu)?;
+    test_utils::assert_close!(ys, expected, 1e-5, 1e-6);
+    assert_eq!(
+        ys.argmax(D::Minus1)?.to_vec1::<u32>()?,
+        expected.argmax(D::Minus1)?.to_vec1::<u32>()?
+    );
+    let halfs = xs
+        .to_device(&device)?
+        .to_dtype(DType::F16)?
+        .to_dtype(DType::F32)?;
+    test_utils::assert_close!(halfs.to_device(&Device::Cpu)?, xs, 1e-3, 1e-4);
+    Ok(())
+}
+
+#[test]
+fn default_device() -> Result<()> {
+    assert!(candle_core::default_device().is_cpu());
+    let previous = candle_core::set_default_device(Device::Cpu);
+    assert!(previous.is_cpu());
+    let t = Tensor::zeros_default((2, 3), DType::F32)?;
+    assert_eq!(t.device().location(), candle_core::DeviceLocation::Cpu);
+    let t = Tensor::new_default(&[1u32, 2, 3])?;
+    assert_eq!(t.device().location(), candle_core::DeviceLocation::Cpu);
+    assert_eq!(t.to_vec1::<u32>()?, [1, 2, 3]);
+    {
+        let _guard = candle_core::scoped_default_device(Device::cuda_if_available(0)?);
+        let t = Tensor::from_vec_default(vec![1f32, 2.], 2)?;
+        assert!(t.device().same_device(&candle_core::default_device()));
+        let t = Tensor::ones_default(2, DType::F32)?;
+        assert_eq!(t.to_vec1::<f32>()?, [1., 1.]);
+    }
+    assert!(candle_core::default_device().is_cpu());
+    Ok(())
+}
@@ -187,9 +187,9 @@ impl MaskDecoder {
         let tokens = Tensor::cat(&[&output_tokens, sparse_prompt_embeddings], 1)?;
 
         // Expand per-image data in batch direction to be per mask
-        let src = repeat_interleave(image_embeddings, tokens.dim(0)?, 0)?;
+        let src = image_embeddings.repeat_interleave(tokens.dim(0)?, 0)?;
         let src = (src + dense_prompt_embeddings)?;
-        let pos_src = repeat_interleave(image_pe, tokens.dim(0)?, 0)?;
+        let pos_src = image_pe.repeat_interleave(tokens.dim(0)?, 0)?;
         let (b, c, h, w) = src.dims4()?;
 
         // Run the transformer
@@ -221,11 +221,3 @@ impl MaskDecoder {
         Ok((masks, iou_pred))
     }
 }
-
-// Equivalent to torch.repeat_interleave
-fn repeat_interleave(img: &Tensor, repeats: usize, dim: usize) -> Result<Tensor> {
-    let img = img.unsqueeze(dim + 1)?;
-    let mut dims = img.dims().to_vec();
-    dims[dim + 1] = repeats;
-    img.broadcast_as(dims)?.flatten(dim, dim + 1)
-}
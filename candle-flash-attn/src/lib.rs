@@ -233,7 +233,9 @@ impl FlashAttnVarLen {
 
         let (seqlens_q, seqlens_q_layout) = self.seqlens_q.storage_and_layout();
         let seqlens_q = match &*seqlens_q {
-            candle::Storage::Cpu(_) => candle::bail!("seqlens_q must be a cuda tensor"),
+            candle::Storage::Cpu(_) | candle::Storage::Metal(_) => {
+                candle::bail!("seqlens_q must be a cuda tensor")
+            }
             candle::Storage::Cuda(c) => c.as_cuda_slice::<u32>()?, // Should be i32!
         };
         let seqlens_q = match seqlens_q_layout.contiguous_offsets() {
@@ -243,7 +245,9 @@ impl FlashAttnVarLen {
 
         let (seqlens_k, seqlens_k_layout) = self.seqlens_k.storage_and_layout();
         let seqlens_k = match &*seqlens_k {
-            candle::Storage::Cpu(_) => candle::bail!("seqlens_k must be a cuda tensor"),
+            candle::Storage::Cpu(_) | candle::Storage::Metal(_) => {
+                candle::bail!("seqlens_k must be a cuda tensor")
+            }
             candle::Storage::Cuda(c) => c.as_cuda_slice::<u32>()?, // Should be i32!
         };
         let seqlens_k = match seqlens_k_layout.contiguous_offsets() {
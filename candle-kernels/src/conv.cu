@@ -37,7 +37,7 @@ __device__ void conv1d(
   const size_t src_idx0 = b_idx * src_s[0];
   A d = 0;
   for (size_t offset = 0; offset < k_size; ++offset) {
-    size_t src_l = (stride * dst_l + offset) * dilation;
+    size_t src_l = stride * dst_l + offset * dilation;
     if (src_l < padding || src_l >= padding + l_in) {
       continue;
     }
@@ -113,6 +113,68 @@ __device__ void conv2d(
   dst[dst_i] = static_cast<T>(d);
 }
 
+// Depthwise conv2d, each output channel only reads from the input channel
+// dst_c_idx / multiplier so there is no loop over the input channels.
+template <typename T, typename A>
+__device__ void conv2d_depthwise(
+    const size_t src_numel,
+    const size_t w_out,
+    const size_t h_out,
+    const size_t stride,
+    const size_t padding,
+    const size_t dilation,
+    const size_t *info,
+    const T *src,
+    const T *kernel,
+    T *dst
+) {
+  const size_t dst_i = blockIdx.x * blockDim.x + threadIdx.x;
+  // src: (b_size, c_in, h_in, w_in)
+  // k: (c_out, 1, h_k, w_k)
+  const size_t *src_dims = info;
+  const size_t *src_s = info + 4;
+  const size_t *k_dims = info + 8;
+  const size_t *k_s = info + 12;
+  const size_t h_k = k_dims[2];
+  const size_t w_k = k_dims[3];
+  const size_t c_out = k_dims[0];
+  const size_t c_in = src_dims[1];
+  const size_t h_in = src_dims[2];
+  const size_t w_in = src_dims[3];
+  if (dst_i >= src_dims[0] * c_out * w_out * h_out) {
+    return;
+  }
+
+  const size_t b_idx = dst_i / (w_out * h_out * c_out);
+  const size_t dst_c_idx = (dst_i / (w_out * h_out)) % c_out;
+  const size_t src_c_idx = dst_c_idx / (c_out / c_in);
+  // NCHW layout.
+  const size_t dst_h = (dst_i / w_out) % h_out;
+  const size_t dst_w = dst_i % w_out;
+
+  const size_t src_idx0 = b_idx * src_s[0] + src_c_idx * src_s[1];
+  A d = 0;
+  // Same loop order as conv2d so that the results are bit-identical.
+  for (size_t w_offset = 0; w_offset < w_k; ++w_offset) {
+    size_t src_w = stride * dst_w + w_offset * dilation;
+    if (src_w < padding || src_w >= w_in + padding) {
+      continue;
+    }
+    src_w -= padding;
+    for (size_t h_offset = 0; h_offset < h_k; ++h_offset) {
+      size_t src_h = stride * dst_h + h_offset * dilation;
+      if (src_h < padding || src_h >= h_in + padding) {
+        continue;
+      }
+      src_h -= padding;
+      const size_t src_idx = src_idx0 + src_h * src_s[2] + src_w * src_s[3];
+      const size_t k_idx = dst_c_idx * k_s[0] + h_offset * k_s[2] + w_offset * k_s[3];
+      d += static_cast<A>(src[src_idx]) * static_cast<A>(kernel[k_idx]);
+    }
+  }
+  dst[dst_i] = static_cast<T>(d);
+}
+
 // Naive implementation of conv_transpose2d.
 template <typename T, typename A>
 __device__ void conv_transpose2d(
@@ -332,6 +394,222 @@ __device__ void upsample_nearest2d(
 }
 
 
+// The info array for the grid sample kernels starts with
+// (b_size, c_in, h_in, w_in, h_out, w_out, mode, padding, align_corners)
+// where mode is 0 for bilinear and 1 for nearest, padding is 0 for zeros and 1 for border. This
+// is followed by the input strides, the grid strides and for the backward pass the output
+// gradient strides.
+
+// Maps a normalized grid coordinate to a pixel position, also returning the derivative of the
+// position with respect to the coordinate.
+template <typename T>
+__device__ T grid_sample_unnormalize(
+    const T coord,
+    const size_t size,
+    const bool align_corners,
+    const bool border,
+    T *dpos
+) {
+  const T scale = align_corners ? static_cast<T>(size - 1) / 2 : static_cast<T>(size) / 2;
+  T pos = coord * scale + static_cast<T>(size - 1) / 2;
+  *dpos = scale;
+  if (border) {
+    if (pos < 0) {
+      pos = 0;
+      *dpos = 0;
+    }
+    else if (pos > static_cast<T>(size - 1)) {
+      pos = static_cast<T>(size - 1);
+      *dpos = 0;
+    }
+  }
+  return pos;
+}
+
+// Computes the in-bound input pixels used for the grid location (gx, gy), returning the number
+// of such pixels. For each pixel, offsets contains its offset in the (h_in, w_in) plane,
+// weights its interpolation weight and dw_dx, dw_dy the derivatives of the weight with respect
+// to the pixel positions.
+template <typename T>
+__device__ int grid_sample_taps(
+    const size_t *info,
+    const T gx,
+    const T gy,
+    size_t *offsets,
+    T *weights,
+    T *dw_dx,
+    T *dw_dy,
+    T *dix,
+    T *diy
+) {
+  const size_t h_in = info[2];
+  const size_t w_in = info[3];
+  const bool nearest = info[6] == 1;
+  const bool border = info[7] == 1;
+  const bool align_corners = info[8] == 1;
+  const T ix = grid_sample_unnormalize<T>(gx, w_in, align_corners, border, dix);
+  const T iy = grid_sample_unnormalize<T>(gy, h_in, align_corners, border, diy);
+
+  T xs[4], ys[4], ws[4], dxs[4], dys[4];
+  int n;
+  if (nearest) {
+    // round rounds halfway cases away from zero.
+    xs[0] = round(ix);
+    ys[0] = round(iy);
+    ws[0] = 1;
+    dxs[0] = 0;
+    dys[0] = 0;
+    n = 1;
+  }
+  else {
+    const T x0 = floor(ix);
+    const T y0 = floor(iy);
+    const T wx1 = ix - x0;
+    const T wy1 = iy - y0;
+    const T wx0 = 1 - wx1;
+    const T wy0 = 1 - wy1;
+    xs[0] = x0; ys[0] = y0; ws[0] = wx0 * wy0; dxs[0] = -wy0; dys[0] = -wx0;
+    xs[1] = x0 + 1; ys[1] = y0; ws[1] = wx1 * wy0; dxs[1] = wy0; dys[1] = -wx1;
+    xs[2] = x0; ys[2] = y0 + 1; ws[2] = wx0 * wy1; dxs[2] = -wy1; dys[2] = wx0;
+    xs[3] = x0 + 1; ys[3] = y0 + 1; ws[3] = wx1 * wy1; dxs[3] = wy1; dys[3] = wx1;
+    n = 4;
+  }
+
+  int n_taps = 0;
+  for (int i = 0; i < n; ++i) {
+    if (xs[i] < 0 || ys[i] < 0 || xs[i] > static_cast<T>(w_in - 1) || ys[i] > static_cast<T>(h_in - 1)) {
+      continue;
+    }
+    offsets[n_taps] = static_cast<size_t>(ys[i]) * w_in + static_cast<size_t>(xs[i]);
+    weights[n_taps] = ws[i];
+    dw_dx[n_taps] = dxs[i];
+    dw_dy[n_taps] = dys[i];
+    n_taps += 1;
+  }
+  return n_taps;
+}
+
+template <typename T>
+__device__ void grid_sample(
+    const size_t *info,
+    const T *src,
+    const T *grid,
+    T *dst
+) {
+  const size_t dst_i = blockIdx.x * blockDim.x + threadIdx.x;
+  const size_t c_in = info[1];
+  const size_t w_in = info[3];
+  const size_t h_out = info[4];
+  const size_t w_out = info[5];
+  const size_t *src_s = info + 9;
+  const size_t *grid_s = info + 13;
+  if (dst_i >= info[0] * c_in * h_out * w_out) {
+    return;
+  }
+  const size_t b_idx = dst_i / (c_in * h_out * w_out);
+  const size_t c_idx = (dst_i / (h_out * w_out)) % c_in;
+  const size_t dst_h = (dst_i / w_out) % h_out;
+  const size_t dst_w = dst_i % w_out;
+
+  const size_t grid_idx = b_idx * grid_s[0] + dst_h * grid_s[1] + dst_w * grid_s[2];
+  size_t offsets[4];
+  T weights[4], dw_dx[4], dw_dy[4], dix, diy;
+  const int n_taps = grid_sample_taps<T>(
+      info, grid[grid_idx], grid[grid_idx + grid_s[3]], offsets, weights, dw_dx, dw_dy, &dix, &diy
+  );
+  const size_t src_idx0 = b_idx * src_s[0] + c_idx * src_s[1];
+  T d = 0;
+  for (int i = 0; i < n_taps; ++i) {
+    const size_t src_idx = src_idx0 + (offsets[i] / w_in) * src_s[2] + (offsets[i] % w_in) * src_s[3];
+    d += weights[i] * src[src_idx];
+  }
+  dst[dst_i] = d;
+}
+
+// One thread per (b, c) plane of the input gradient, which has to be zero initialized.
+template <typename T>
+__device__ void grid_sample_backward_input(
+    const size_t *info,
+    const T *grid,
+    const T *grad,
+    T *grad_src
+) {
+  const size_t plane_i = blockIdx.x * blockDim.x + threadIdx.x;
+  const size_t c_in = info[1];
+  const size_t h_in = info[2];
+  const size_t w_in = info[3];
+  const size_t h_out = info[4];
+  const size_t w_out = info[5];
+  const size_t *grid_s = info + 13;
+  const size_t *grad_s = info + 17;
+  if (plane_i >= info[0] * c_in) {
+    return;
+  }
+  const size_t b_idx = plane_i / c_in;
+  const size_t c_idx = plane_i % c_in;
+  T *grad_src_plane = grad_src + plane_i * h_in * w_in;
+  for (size_t dst_h = 0; dst_h < h_out; ++dst_h) {
+    for (size_t dst_w = 0; dst_w < w_out; ++dst_w) {
+      const size_t grid_idx = b_idx * grid_s[0] + dst_h * grid_s[1] + dst_w * grid_s[2];
+      size_t offsets[4];
+      T weights[4], dw_dx[4], dw_dy[4], dix, diy;
+      const int n_taps = grid_sample_taps<T>(
+          info, grid[grid_idx], grid[grid_idx + grid_s[3]], offsets, weights, dw_dx, dw_dy, &dix, &diy
+      );
+      const T g = grad[b_idx * grad_s[0] + c_idx * grad_s[1] + dst_h * grad_s[2] + dst_w * grad_s[3]];
+      for (int i = 0; i < n_taps; ++i) {
+        grad_src_plane[offsets[i]] += g * weights[i];
+      }
+    }
+  }
+}
+
+// One thread per grid location, the gradient of the grid is contiguous.
+template <typename T>
+__device__ void grid_sample_backward_grid(
+    const size_t *info,
+    const T *src,
+    const T *grid,
+    const T *grad,
+    T *grad_grid
+) {
+  const size_t loc_i = blockIdx.x * blockDim.x + threadIdx.x;
+  const size_t c_in = info[1];
+  const size_t w_in = info[3];
+  const size_t h_out = info[4];
+  const size_t w_out = info[5];
+  const size_t *src_s = info + 9;
+  const size_t *grid_s = info + 13;
+  const size_t *grad_s = info + 17;
+  if (loc_i >= info[0] * h_out * w_out) {
+    return;
+  }
+  const size_t b_idx = loc_i / (h_out * w_out);
+  const size_t dst_h = (loc_i / w_out) % h_out;
+  const size_t dst_w = loc_i % w_out;
+
+  const size_t grid_idx = b_idx * grid_s[0] + dst_h * grid_s[1] + dst_w * grid_s[2];
+  size_t offsets[4];
+  T weights[4], dw_dx[4], dw_dy[4], dix, diy;
+  const int n_taps = grid_sample_taps<T>(
+      info, grid[grid_idx], grid[grid_idx + grid_s[3]], offsets, weights, dw_dx, dw_dy, &dix, &diy
+  );
+  T gx = 0;
+  T gy = 0;
+  for (size_t c_idx = 0; c_idx < c_in; ++c_idx) {
+    const T g = grad[b_idx * grad_s[0] + c_idx * grad_s[1] + dst_h * grad_s[2] + dst_w * grad_s[3]];
+    const size_t src_idx0 = b_idx * src_s[0] + c_idx * src_s[1];
+    for (int i = 0; i < n_taps; ++i) {
+      const size_t src_idx = src_idx0 + (offsets[i] / w_in) * src_s[2] + (offsets[i] % w_in) * src_s[3];
+      const T v = g * src[src_idx];
+      gx += v * dw_dx[i];
+      gy += v * dw_dy[i];
+    }
+  }
+  grad_grid[2 * loc_i] = gx * dix;
+  grad_grid[2 * loc_i + 1] = gy * diy;
+}
+
 #define CONV1D_OP(TYPENAME, TYPEACC, FN_NAME) \
 extern "C" __global__ void FN_NAME(  \
     const size_t src_numel, \
@@ -363,6 +641,22 @@ extern "C" __global__ void FN_NAME(  \
   conv2d<TYPENAME, TYPEACC>(src_numel, w_out, h_out, stride, padding, dilation, info, src, kernel, dst); \
 } \
 
+#define CONV2D_DEPTHWISE_OP(TYPENAME, TYPEACC, FN_NAME) \
+extern "C" __global__ void FN_NAME(  \
+    const size_t src_numel, \
+    const size_t w_out, \
+    const size_t h_out, \
+    const size_t stride, \
+    const size_t padding, \
+    const size_t dilation, \
+    const size_t *info, \
+    const TYPENAME *src, \
+    const TYPENAME *kernel, \
+    TYPENAME *dst \
+) {  \
+  conv2d_depthwise<TYPENAME, TYPEACC>(src_numel, w_out, h_out, stride, padding, dilation, info, src, kernel, dst); \
+} \
+
 #define CONVT2D_OP(TYPENAME, TYPEACC, FN_NAME) \
 extern "C" __global__ void FN_NAME(  \
     const size_t src_numel, \
@@ -421,9 +715,41 @@ extern "C" __global__ void FN_NAME(  \
   upsample_nearest2d<TYPENAME>(w_out, h_out, w_scale, h_scale, info, src, dst); \
 } \
 
+#define GRID_SAMPLE_OP(TYPENAME, FN_NAME) \
+extern "C" __global__ void FN_NAME(  \
+    const size_t *info, \
+    const TYPENAME *src, \
+    const TYPENAME *grid, \
+    TYPENAME *dst \
+) {  \
+  grid_sample<TYPENAME>(info, src, grid, dst); \
+} \
+
+#define GRID_SAMPLE_BACKWARD_INPUT_OP(TYPENAME, FN_NAME) \
+extern "C" __global__ void FN_NAME(  \
+    const size_t *info, \
+    const TYPENAME *grid, \
+    const TYPENAME *grad, \
+    TYPENAME *grad_src \
+) {  \
+  grid_sample_backward_input<TYPENAME>(info, grid, grad, grad_src); \
+} \
+
+#define GRID_SAMPLE_BACKWARD_GRID_OP(TYPENAME, FN_NAME) \
+extern "C" __global__ void FN_NAME(  \
+    const size_t *info, \
+    const TYPENAME *src, \
+    const TYPENAME *grid, \
+    const TYPENAME *grad, \
+    TYPENAME *grad_grid \
+) {  \
+  grid_sample_backward_grid<TYPENAME>(info, src, grid, grad, grad_grid); \
+} \
+
 #if __CUDA_ARCH__ >= 800
 CONV1D_OP(__nv_bfloat16, float, conv1d_bf16)
 CONV2D_OP(__nv_bfloat16, float, conv2d_bf16)
+CONV2D_DEPTHWISE_OP(__nv_bfloat16, float, conv2d_depthwise_bf16)
 CONVT2D_OP(__nv_bfloat16, float, conv_transpose2d_bf16)
 AVG_POOL2D_OP(__nv_bfloat16, float, avg_pool2d_bf16)
 MAX_POOL2D_OP(__nv_bfloat16, max_pool2d_bf16)
@@ -433,6 +759,7 @@ UPSAMPLE_NEAREST2D_OP(__nv_bfloat16, upsample_nearest2d_bf16)
 #if __CUDA_ARCH__ >= 530
 CONV1D_OP(__half, float, conv1d_f16)
 CONV2D_OP(__half, float, conv2d_f16)
+CONV2D_DEPTHWISE_OP(__half, float, conv2d_depthwise_f16)
 CONVT2D_OP(__half, float, conv_transpose2d_f16)
 AVG_POOL2D_OP(__half, float, avg_pool2d_f16)
 MAX_POOL2D_OP(__half, max_pool2d_f16)
@@ -449,6 +776,11 @@ CONV2D_OP(double, double, conv2d_f64)
 CONV2D_OP(uint8_t, uint8_t, conv2d_u8)
 CONV2D_OP(uint32_t, uint32_t, conv2d_u32)
 
+CONV2D_DEPTHWISE_OP(float, float, conv2d_depthwise_f32)
+CONV2D_DEPTHWISE_OP(double, double, conv2d_depthwise_f64)
+CONV2D_DEPTHWISE_OP(uint8_t, uint8_t, conv2d_depthwise_u8)
+CONV2D_DEPTHWISE_OP(uint32_t, uint32_t, conv2d_depthwise_u32)
+
 CONVT2D_OP(float, float, conv_transpose2d_f32)
 CONVT2D_OP(double, double, conv_transpose2d_f64)
 CONVT2D_OP(uint8_t, uint8_t, conv_transpose2d_u8)
@@ -468,3 +800,12 @@ UPSAMPLE_NEAREST2D_OP(float, upsample_nearest2d_f32)
 UPSAMPLE_NEAREST2D_OP(double, upsample_nearest2d_f64)
 UPSAMPLE_NEAREST2D_OP(uint8_t, upsample_nearest2d_u8)
 UPSAMPLE_NEAREST2D_OP(uint32_t, upsample_nearest2d_u32)
+
+GRID_SAMPLE_OP(float, grid_sample_f32)
+GRID_SAMPLE_OP(double, grid_sample_f64)
+
+GRID_SAMPLE_BACKWARD_INPUT_OP(float, grid_sample_backward_input_f32)
+GRID_SAMPLE_BACKWARD_INPUT_OP(double, grid_sample_backward_input_f64)
+
+GRID_SAMPLE_BACKWARD_GRID_OP(float, grid_sample_backward_grid_f32)
+GRID_SAMPLE_BACKWARD_GRID_OP(double, grid_sample_backward_grid_f64)
@@ -0,0 +1,29 @@
+//! Compares the per-token latency of a 4096x4096 linear layer with and without a pre-transposed
+//! weight.
+//!
+//! Run with `cargo bench -p candle-nn --bench linear`.
+use candle::{Device, Tensor};
+use candle_nn::{Linear, Module};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let device = Device::Cpu;
+    let mut group = c.benchmark_group("linear_4096x4096");
+    let w = Tensor::randn(0f32, 0.02, (4096, 4096), &device).unwrap();
+    let b = Tensor::randn(0f32, 0.02, 4096, &device).unwrap();
+    let layer = Linear::new(w.clone(), Some(b.clone()));
+    let layer_t = Linear::with_options(w, Some(b), true).unwrap();
+    // A single token as in incremental decoding, and a short prompt.
+    for seq_len in [1, 16] {
+        let xs = Tensor::randn(0f32, 1., (1, seq_len, 4096), &device).unwrap();
+        for (name, layer) in [("transposed_view", &layer), ("pre_transposed", &layer_t)] {
+            group.bench_function(BenchmarkId::new(name, seq_len), |bench| {
+                bench.iter(|| black_box(layer.forward(&xs).unwrap()))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
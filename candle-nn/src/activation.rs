@@ -2,17 +2,83 @@ use candle::Tensor;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Activation {
+    /// The gelu activation using the tanh approximation.
     Gelu,
+    /// The exact gelu activation based on the error function.
+    GeluErf,
+    /// The `x * sigmoid(1.702 x)` approximation of gelu.
+    QuickGelu,
     Relu,
+    Relu6,
     Elu(f64),
+    LeakyRelu(f64),
+    Sigmoid,
+    Tanh,
+    Silu,
+    /// Same as `Silu`.
+    Swish,
+    Mish,
+    Softplus,
+    HardSigmoid,
+    HardSwish,
 }
 
 impl super::Module for Activation {
     fn forward(&self, xs: &Tensor) -> candle::Result<Tensor> {
         match self {
             Self::Gelu => xs.gelu(),
+            Self::GeluErf => crate::ops::gelu_erf(xs),
+            Self::QuickGelu => xs * crate::ops::sigmoid(&(xs * 1.702f64)?)?,
             Self::Relu => xs.relu(),
+            Self::Relu6 => crate::ops::relu6(xs),
             &Self::Elu(alpha) => xs.elu(alpha),
+            &Self::LeakyRelu(negative_slope) => crate::ops::leaky_relu(xs, negative_slope),
+            Self::Sigmoid => crate::ops::sigmoid(xs),
+            Self::Tanh => xs.tanh(),
+            Self::Silu | Self::Swish => crate::ops::silu(xs),
+            Self::Mish => crate::ops::mish(xs),
+            Self::Softplus => crate::ops::softplus(xs),
+            Self::HardSigmoid => crate::ops::hard_sigmoid(xs),
+            Self::HardSwish => crate::ops::hard_swish(xs),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ActivationParseError(pub String);
+
+impl std::fmt::Display for ActivationParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "unknown activation {}", self.0)
+    }
+}
+
+impl std::error::Error for ActivationParseError {}
+
+/// Parses the activation names used in model configs, e.g. the `hidden_act` field of the
+/// Hugging Face transformers configs. As in these configs, `gelu` is the exact version and
+/// `gelu_new` or `gelu_pytorch_tanh` the tanh approximation. `elu` and `leaky_relu` use the
+/// default parameters of 1.0 and 0.01.
+impl std::str::FromStr for Activation {
+    type Err = ActivationParseError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gelu" | "gelu_erf" => Ok(Self::GeluErf),
+            "gelu_new" | "gelu_fast" | "gelu_pytorch_tanh" => Ok(Self::Gelu),
+            "quick_gelu" => Ok(Self::QuickGelu),
+            "relu" => Ok(Self::Relu),
+            "relu6" => Ok(Self::Relu6),
+            "elu" => Ok(Self::Elu(1.0)),
+            "leaky_relu" | "leakyrelu" => Ok(Self::LeakyRelu(0.01)),
+            "sigmoid" => Ok(Self::Sigmoid),
+            "tanh" => Ok(Self::Tanh),
+            "silu" => Ok(Self::Silu),
+            "swish" => Ok(Self::Swish),
+            "mish" => Ok(Self::Mish),
+            "softplus" => Ok(Self::Softplus),
+            "hard_sigmoid" | "hardsigmoid" => Ok(Self::HardSigmoid),
+            "hard_swish" | "hardswish" => Ok(Self::HardSwish),
+            _ => Err(ActivationParseError(s.to_string())),
         }
     }
 }
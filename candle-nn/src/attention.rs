@@ -0,0 +1,354 @@
+//! Multi-Head Attention.
+//!
+//! The inputs have shape `(b_sz, seq_len, embed_dim)`, the queries, keys and values are projected
+//! and split into `num_heads` heads of size `embed_dim / num_heads`, and scaled dot-product
+//! attention is applied on each head before the final output projection.
+//!
+//! The optional mask is broadcast to `(b_sz, num_heads, q_len, kv_len)`, it can have shape
+//! `(q_len, kv_len)`, `(b_sz, q_len, kv_len)` or `(b_sz, num_heads, q_len, kv_len)` where the batch
+//! and head dimensions can also be 1. A `u8` mask is a boolean mask where non-zero values mark the
+//! positions that cannot be attended to, any other dtype is added to the attention scores before
+//! the softmax. Query positions that cannot attend to anything result in NaN values.
+use candle::{DType, Result, Tensor, D};
+
+use crate::{Linear, Module, VarBuilder};
+
+/// The keys and values computed so far, used for incremental decoding.
+///
+/// The cached tensors have shape `(b_sz, num_heads, seq_len, head_dim)` and grow along the
+/// sequence dimension on each call to [`MultiHeadAttention::forward`]. The cache should be reset
+/// when starting a new sequence.
+#[derive(Debug, Clone, Default)]
+pub struct KvCache {
+    kv: Option<(Tensor, Tensor)>,
+}
+
+impl KvCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.kv = None
+    }
+
+    /// The number of cached positions.
+    pub fn seq_len(&self) -> usize {
+        match &self.kv {
+            None => 0,
+            Some((k, _)) => k.dims()[2],
+        }
+    }
+
+    pub fn k(&self) -> Option<&Tensor> {
+        self.kv.as_ref().map(|kv| &kv.0)
+    }
+
+    pub fn v(&self) -> Option<&Tensor> {
+        self.kv.as_ref().map(|kv| &kv.1)
+    }
+
+    /// Appends the new keys and values to the cache and returns the full keys and values.
+    pub fn append(&mut self, k: &Tensor, v: &Tensor) -> Result<(Tensor, Tensor)> {
+        let (k, v) = match &self.kv {
+            None => (k.clone(), v.clone()),
+            Some((prev_k, prev_v)) => {
+                let k = Tensor::cat(&[prev_k, k], 2)?.contiguous()?;
+                let v = Tensor::cat(&[prev_v, v], 2)?.contiguous()?;
+                (k, v)
+            }
+        };
+        self.kv = Some((k.clone(), v.clone()));
+        Ok((k, v))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiHeadAttentionConfig {
+    /// Use a single projection of shape `(3 * embed_dim, embed_dim)` for the queries, keys and
+    /// values, stored as `qkv_proj`, rather than `q_proj`, `k_proj` and `v_proj`.
+    pub fused_qkv: bool,
+    /// Whether the projections have a bias.
+    pub bias: bool,
+}
+
+impl Default for MultiHeadAttentionConfig {
+    fn default() -> Self {
+        Self {
+            fused_qkv: false,
+            bias: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum QkvProj {
+    Separate { q: Linear, k: Linear, v: Linear },
+    Fused(Linear),
+}
+
+#[derive(Debug)]
+pub struct MultiHeadAttention {
+    qkv_proj: QkvProj,
+    out_proj: Linear,
+    embed_dim: usize,
+    num_heads: usize,
+    head_dim: usize,
+}
+
+fn check_dims(embed_dim: usize, num_heads: usize) -> Result<()> {
+    if num_heads == 0 || embed_dim % num_heads != 0 {
+        candle::bail!(
+            "MultiHeadAttention: embed_dim ({embed_dim}) must be divisible by num_heads ({num_heads})"
+        )
+    }
+    Ok(())
+}
+
+impl MultiHeadAttention {
+    /// Creates the layer with separate query, key and value projections, all with a bias.
+    pub fn new(embed_dim: usize, num_heads: usize, vs: VarBuilder) -> Result<Self> {
+        Self::new_with_config(embed_dim, num_heads, Default::default(), vs)
+    }
+
+    pub fn new_with_config(
+        embed_dim: usize,
+        num_heads: usize,
+        config: MultiHeadAttentionConfig,
+        vs: VarBuilder,
+    ) -> Result<Self> {
+        check_dims(embed_dim, num_heads)?;
+        let linear = |in_dim, out_dim, vs| {
+            if config.bias {
+                crate::linear(in_dim, out_dim, vs)
+            } else {
+                crate::linear_no_bias(in_dim, out_dim, vs)
+            }
+        };
+        let qkv_proj = if config.fused_qkv {
+            QkvProj::Fused(linear(embed_dim, 3 * embed_dim, vs.pp("qkv_proj"))?)
+        } else {
+            QkvProj::Separate {
+                q: linear(embed_dim, embed_dim, vs.pp("q_proj"))?,
+                k: linear(embed_dim, embed_dim, vs.pp("k_proj"))?,
+                v: linear(embed_dim, embed_dim, vs.pp("v_proj"))?,
+            }
+        };
+        let out_proj = linear(embed_dim, embed_dim, vs.pp("out_proj"))?;
+        Ok(Self {
+            qkv_proj,
+            out_proj,
+            embed_dim,
+            num_heads,
+            head_dim: embed_dim / num_heads,
+        })
+    }
+
+    /// Creates the layer from existing separate projections.
+    pub fn from_projections(
+        q_proj: Linear,
+        k_proj: Linear,
+        v_proj: Linear,
+        out_proj: Linear,
+        num_heads: usize,
+    ) -> Result<Self> {
+        let (embed_dim, _) = out_proj.weight().dims2()?;
+        check_dims(embed_dim, num_heads)?;
+        for (name, proj) in [
+            ("q", &q_proj),
+            ("k", &k_proj),
+            ("v", &v_proj),
+            ("out", &out_proj),
+        ] {
+            if proj.weight().dims() != [embed_dim, embed_dim] {
+                candle::bail!(
+                    "MultiHeadAttention: expected a ({embed_dim}, {embed_dim}) weight for the {name} projection, got {:?}",
+                    proj.weight().shape()
+                )
+            }
+        }
+        Ok(Self {
+            qkv_proj: QkvProj::Separate {
+                q: q_proj,
+                k: k_proj,
+                v: v_proj,
+            },
+            out_proj,
+            embed_dim,
+            num_heads,
+            head_dim: embed_dim / num_heads,
+        })
+    }
+
+    pub fn embed_dim(&self) -> usize {
+        self.embed_dim
+    }
+
+    pub fn num_heads(&self) -> usize {
+        self.num_heads
+    }
+
+    fn project(&self, query: &Tensor, key: &Tensor, value: &Tensor) -> Result<[Tensor; 3]> {
+        match &self.qkv_proj {
+            QkvProj::Separate { q, k, v } => {
+                Ok([q.forward(query)?, k.forward(key)?, v.forward(value)?])
+            }
+            QkvProj::Fused(qkv) if query.id() == key.id() && key.id() == value.id() => {
+                let qkv = qkv.forward(query)?;
+                let e = self.embed_dim;
+                Ok([
+                    qkv.narrow(D::Minus1, 0, e)?,
+                    qkv.narrow(D::Minus1, e, e)?,
+                    qkv.narrow(D::Minus1, 2 * e, e)?,
+                ])
+            }
+            QkvProj::Fused(qkv) => {
+                // Distinct inputs, e.g. for cross-attention, use the matching slice of the
+                // fused projection for each of them.
+                let e = self.embed_dim;
+                let proj = |xs: &Tensor, i: usize| {
+                    let weight = qkv.weight().narrow(0, i * e, e)?;
+                    let bias = match qkv.bias() {
+                        None => None,
+                        Some(b) => Some(b.narrow(0, i * e, e)?),
+                    };
+                    Linear::new(weight, bias).forward(xs)
+                };
+                Ok([proj(query, 0)?, proj(key, 1)?, proj(value, 2)?])
+            }
+        }
+    }
+
+    fn split_heads(&self, xs: &Tensor) -> Result<Tensor> {
+        let (b_sz, seq_len, _) = xs.dims3()?;
+        xs.reshape((b_sz, seq_len, self.num_heads, self.head_dim))?
+            .transpose(1, 2)?
+            .contiguous()
+    }
+
+    fn check_input(&self, xs: &Tensor, name: &str) -> Result<(usize, usize)> {
+        match *xs.dims() {
+            [b_sz, seq_len, embed_dim] if embed_dim == self.embed_dim => Ok((b_sz, seq_len)),
+            _ => candle::bail!(
+                "MultiHeadAttention: expected {name} of shape (b_sz, seq_len, {}), got {:?}",
+                self.embed_dim,
+                xs.shape()
+            ),
+        }
+    }
+
+    fn broadcast_mask(&self, mask: &Tensor, att_dims: &[usize]) -> Result<Tensor> {
+        let (b_sz, q_len, kv_len) = (att_dims[0], att_dims[2], att_dims[3]);
+        let mask_dims = mask.dims();
+        let compatible = match *mask_dims {
+            [q, kv] => q == q_len && kv == kv_len,
+            [b, q, kv] => (b == 1 || b == b_sz) && q == q_len && kv == kv_len,
+            [b, h, q, kv] => {
+                (b == 1 || b == b_sz)
+                    && (h == 1 || h == self.num_heads)
+                    && q == q_len
+                    && kv == kv_len
+            }
+            _ => false,
+        };
+        if !compatible {
+            candle::bail!(
+                "MultiHeadAttention: mask of shape {:?} is incompatible with attention scores of shape {att_dims:?}, expected (q_len, kv_len), (b_sz, q_len, kv_len) or (b_sz, num_heads, q_len, kv_len)",
+                mask.shape()
+            )
+        }
+        let mask = match *mask_dims {
+            [b, q, kv] => mask.reshape((b, 1, q, kv))?,
+            _ => mask.clone(),
+        };
+        mask.broadcast_as(att_dims)
+    }
+
+    /// Applies attention and returns both the output, of shape `(b_sz, q_len, embed_dim)`, and
+    /// the attention weights, of shape `(b_sz, num_heads, q_len, kv_len)`.
+    ///
+    /// When a cache is provided, the new keys and values are appended to it and the queries
+    /// attend to all the cached positions, `kv_len` is then the total number of positions.
+    pub fn forward_with_weights(
+        &self,
+        query: &Tensor,
+        key: &Tensor,
+        value: &Tensor,
+        mask: Option<&Tensor>,
+        cache: Option<&mut KvCache>,
+    ) -> Result<(Tensor, Tensor)> {
+        let (b_sz, q_len) = self.check_input(query, "query")?;
+        let (k_b_sz, k_len) = self.check_input(key, "key")?;
+        let (v_b_sz, v_len) = self.check_input(value, "value")?;
+        if k_b_sz != b_sz || v_b_sz != b_sz || k_len != v_len {
+            candle::bail!(
+                "MultiHeadAttention: incompatible shapes for query {:?}, key {:?} and value {:?}",
+                query.shape(),
+                key.shape(),
+                value.shape()
+            )
+        }
+        let [q, k, v] = self.project(query, key, value)?;
+        let q = self.split_heads(&q)?;
+        let k = self.split_heads(&k)?;
+        let v = self.split_heads(&v)?;
+        let (k, v) = match cache {
+            None => (k, v),
+            Some(cache) => {
+                if let Some(prev_k) = cache.k() {
+                    if prev_k.dims()[0] != b_sz {
+                        candle::bail!(
+                            "MultiHeadAttention: the cache has a batch size of {} but the input has {b_sz}",
+                            prev_k.dims()[0]
+                        )
+                    }
+                }
+                cache.append(&k, &v)?
+            }
+        };
+
+        let scale = 1. / (self.head_dim as f64).sqrt();
+        let att = (q.broadcast_matmul(&k.t()?)? * scale)?;
+        let att = match mask {
+            None => att,
+            Some(mask) => {
+                let mask = self.broadcast_mask(mask, att.dims())?;
+                if mask.dtype() == DType::U8 {
+                    let neg_inf = Tensor::new(f32::NEG_INFINITY, att.device())?
+                        .to_dtype(att.dtype())?
+                        .broadcast_as(att.shape())?;
+                    mask.where_cond(&neg_inf, &att)?
+                } else {
+                    (att + mask.to_dtype(q.dtype())?)?
+                }
+            }
+        };
+        let att = crate::ops::softmax(&att, D::Minus1)?;
+        let ys =
+            att.broadcast_matmul(&v)?
+                .transpose(1, 2)?
+                .reshape((b_sz, q_len, self.embed_dim))?;
+        let ys = self.out_proj.forward(&ys)?;
+        Ok((ys, att))
+    }
+
+    /// Applies attention, see [`Self::forward_with_weights`].
+    pub fn forward(
+        &self,
+        query: &Tensor,
+        key: &Tensor,
+        value: &Tensor,
+        mask: Option<&Tensor>,
+        cache: Option<&mut KvCache>,
+    ) -> Result<Tensor> {
+        self.forward_with_weights(query, key, value, mask, cache)
+            .map(|(ys, _)| ys)
+    }
+}
+
+/// Unmasked self-attention.
+impl Module for MultiHeadAttention {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        MultiHeadAttention::forward(self, xs, xs, xs, None, None)
+    }
+}
@@ -3,11 +3,12 @@
 //! This layer applies Batch Normalization over a mini-batch of inputs as described in [`Batch
 //! Normalization`]. The input is expected to have at least three dimensions.
 //!
-//! Note that this implementation is for inference only, there is no possibility to track the
-//! running stats.
+//! In training mode, see [`crate::Module::set_training`], the layer normalizes using the batch
+//! statistics and updates its running statistics. In evaluation mode, the default, the running
+//! statistics are used and the layer is a per-feature affine transform.
 //!
 //! [`Batch Normalization`]: https://arxiv.org/abs/1502.03167
-use candle::{DType, Result, Tensor};
+use candle::{DType, Result, Tensor, Var};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BatchNormConfig {
@@ -40,12 +41,26 @@ impl From<f64> for BatchNormConfig {
 
 #[derive(Debug)]
 pub struct BatchNorm {
-    running_mean: Tensor,
-    running_var: Tensor,
+    running_mean: Var,
+    running_var: Var,
     weight_and_bias: Option<(Tensor, Tensor)>,
     remove_mean: bool,
     eps: f64,
+    momentum: f64,
     num_features: usize,
+    training: bool,
+}
+
+const DEFAULT_MOMENTUM: f64 = 0.1;
+
+// The running statistics are updated in place. When they are variables, e.g. buffers from a
+// `VarMap`, the storage is shared so that the updates are visible from the map, other tensors
+// are copied.
+fn running_stat(t: Tensor) -> Result<Var> {
+    match Var::from_variable(&t) {
+        Some(var) => Ok(var),
+        None => Var::from_tensor(&t),
+    }
 }
 
 impl BatchNorm {
@@ -73,12 +88,14 @@ impl BatchNorm {
             )
         }
         Ok(Self {
-            running_mean,
-            running_var,
+            running_mean: running_stat(running_mean)?,
+            running_var: running_stat(running_var)?,
             weight_and_bias: Some((weight, bias)),
             remove_mean: true,
             eps,
+            momentum: DEFAULT_MOMENTUM,
             num_features,
+            training: false,
         })
     }
 
@@ -92,17 +109,63 @@ impl BatchNorm {
             candle::bail!("batch-norm eps cannot be negative {eps}")
         }
         Ok(Self {
-            running_mean,
-            running_var,
+            running_mean: running_stat(running_mean)?,
+            running_var: running_stat(running_var)?,
             weight_and_bias: None,
             remove_mean: true,
             eps,
+            momentum: DEFAULT_MOMENTUM,
             num_features,
+            training: false,
         })
     }
+
+    /// Sets the momentum used to update the running statistics in training mode, i.e.
+    /// `running = (1 - momentum) * running + momentum * batch`. The default is 0.1.
+    pub fn with_momentum(mut self, momentum: f64) -> Result<Self> {
+        if !(0. ..=1.).contains(&momentum) {
+            candle::bail!("batch-norm momentum should be between 0 and 1, got {momentum}")
+        }
+        self.momentum = momentum;
+        Ok(self)
+    }
+
+    pub fn running_mean(&self) -> &Tensor {
+        self.running_mean.as_tensor()
+    }
+
+    pub fn running_var(&self) -> &Tensor {
+        self.running_var.as_tensor()
+    }
+
+    /// Returns the per-feature `(scale, shift)` used in evaluation mode where the output is
+    /// `x * scale + shift`, e.g. to fold the layer into a preceding convolution.
+    ///
+    /// When the mean is not removed, the input is divided by the root of the running second
+    /// moment `running_var + running_mean^2` as in training mode.
+    pub fn eval_scale_shift(&self) -> Result<(Tensor, Tensor)> {
+        let running_mean = self.running_mean.as_tensor();
+        let second_moment = if self.remove_mean {
+            self.running_var.as_tensor().clone()
+        } else {
+            (self.running_var.as_tensor() + running_mean.sqr()?)?
+        };
+        let inv_std = (second_moment + self.eps)?.sqrt()?.recip()?;
+        let (scale, shift) = match &self.weight_and_bias {
+            None => (inv_std, Tensor::zeros_like(running_mean)?),
+            Some((weight, bias)) => ((inv_std * weight)?, bias.clone()),
+        };
+        let shift = if self.remove_mean {
+            (shift - (running_mean * &scale)?)?
+        } else {
+            shift
+        };
+        Ok((scale, shift))
+    }
 }
 
 impl BatchNorm {
+    /// Normalizes `x` using the statistics of the batch and updates the running statistics.
     pub fn forward_learning(&self, x: &Tensor) -> Result<Tensor> {
         let x_dtype = x.dtype();
         let internal_dtype = match x_dtype {
@@ -126,13 +189,17 @@ impl BatchNorm {
         let x = x.transpose(0, 1)?;
         let x_dims_post_transpose = x.dims();
         let x = x.flatten_from(1)?.contiguous()?;
-        let x = if self.remove_mean {
-            let mean_x = x.mean_keepdim(1)?;
-            x.broadcast_sub(&mean_x)?
+        let mean_x = x.mean_keepdim(1)?;
+        let centered = x.broadcast_sub(&mean_x)?;
+        let var_x = centered.sqr()?.mean_keepdim(1)?;
+        // The running statistics track the batch mean and variance in both cases.
+        self.update_running_stats(&mean_x, &var_x, x.dim(1)?)?;
+        let (x, norm_x) = if self.remove_mean {
+            (centered, var_x)
         } else {
-            x
+            let norm_x = x.sqr()?.mean_keepdim(1)?;
+            (x, norm_x)
         };
-        let norm_x = x.sqr()?.mean_keepdim(1)?;
         let x_normed = x.broadcast_div(&(norm_x + self.eps)?.sqrt()?)?;
         let x = x_normed.to_dtype(x_dtype)?;
         let x = match &self.weight_and_bias {
@@ -145,10 +212,26 @@ impl BatchNorm {
         };
         x.reshape(x_dims_post_transpose)?.transpose(0, 1)
     }
+
+    fn update_running_stats(&self, mean_x: &Tensor, var_x: &Tensor, n: usize) -> Result<()> {
+        let dtype = self.running_mean.dtype();
+        let update = |running: &Var, batch: &Tensor| -> Result<()> {
+            let batch = batch.detach()?.flatten_all()?.to_dtype(dtype)?;
+            let v = ((running.as_tensor() * (1. - self.momentum))? + (batch * self.momentum)?)?;
+            running.set(&v)
+        };
+        update(&self.running_mean, mean_x)?;
+        // The running variance uses the unbiased estimate of the batch variance.
+        let unbiased = if n > 1 { n as f64 / (n - 1) as f64 } else { 1. };
+        update(&self.running_var, &(var_x * unbiased)?)
+    }
 }
 
 impl crate::Module for BatchNorm {
     fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        if self.training {
+            return self.forward_learning(x);
+        }
         let target_shape: Vec<usize> = x
             .dims()
             .iter()
@@ -156,17 +239,13 @@ impl crate::Module for BatchNorm {
             .map(|(idx, v)| if idx == 1 { *v } else { 1 })
             .collect();
         let target_shape = target_shape.as_slice();
-        let x = x
-            .broadcast_sub(&self.running_mean.reshape(target_shape)?)?
-            .broadcast_div(&(self.running_var.reshape(target_shape)? + self.eps)?.sqrt()?)?;
-        match &self.weight_and_bias {
-            None => Ok(x),
-            Some((weight, bias)) => {
-                let weight = weight.reshape(target_shape)?;
-                let bias = bias.reshape(target_shape)?;
-                x.broadcast_mul(&weight)?.broadcast_add(&bias)
-            }
-        }
+        let (scale, shift) = self.eval_scale_shift()?;
+        x.broadcast_mul(&scale.reshape(target_shape)?)?
+            .broadcast_add(&shift.reshape(target_shape)?)
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training
     }
 }
 
@@ -179,8 +258,10 @@ pub fn batch_norm<C: Into<BatchNormConfig>>(
     if config.eps < 0. {
         candle::bail!("batch-norm eps cannot be negative {}", config.eps)
     }
-    let running_mean = vb.get_with_hints(num_features, "running_mean", crate::Init::Const(0.))?;
-    let running_var = vb.get_with_hints(num_features, "running_var", crate::Init::Const(1.))?;
+    let running_mean =
+        vb.get_buffer_with_hints(num_features, "running_mean", crate::Init::Const(0.))?;
+    let running_var =
+        vb.get_buffer_with_hints(num_features, "running_var", crate::Init::Const(1.))?;
     let weight_and_bias = if config.affine {
         let weight = vb.get_with_hints(num_features, "weight", crate::Init::Const(1.))?;
         let bias = vb.get_with_hints(num_features, "bias", crate::Init::Const(0.))?;
@@ -189,11 +270,13 @@ pub fn batch_norm<C: Into<BatchNormConfig>>(
         None
     };
     Ok(BatchNorm {
-        running_mean,
-        running_var,
+        running_mean: running_stat(running_mean)?,
+        running_var: running_stat(running_var)?,
         weight_and_bias,
         remove_mean: config.remove_mean,
         eps: config.eps,
+        momentum: DEFAULT_MOMENTUM,
         num_features,
+        training: false,
     })
 }
@@ -5,6 +5,7 @@ use candle::{Result, Tensor};
 pub struct Embedding {
     embeddings: Tensor,
     hidden_size: usize,
+    padding_idx: Option<usize>,
 }
 
 impl Embedding {
@@ -12,12 +13,28 @@ impl Embedding {
         Self {
             embeddings,
             hidden_size,
+            padding_idx: None,
         }
     }
 
+    /// Sets the padding index, the lookups for this index return the stored row as is but do not
+    /// propagate any gradient to it.
+    pub fn with_padding_idx(mut self, padding_idx: usize) -> Result<Self> {
+        let vocab_size = self.embeddings.dim(0)?;
+        if padding_idx >= vocab_size {
+            candle::bail!("embedding: padding_idx {padding_idx} is out of range for {vocab_size}")
+        }
+        self.padding_idx = Some(padding_idx);
+        Ok(self)
+    }
+
     pub fn embeddings(&self) -> &Tensor {
         &self.embeddings
     }
+
+    pub fn padding_idx(&self) -> Option<usize> {
+        self.padding_idx
+    }
 }
 
 impl crate::Module for Embedding {
@@ -26,6 +43,21 @@ impl crate::Module for Embedding {
         final_dims.push(self.hidden_size);
         let indexes = indexes.flatten_all()?;
         let values = self.embeddings.index_select(&indexes, 0)?;
+        let values = match self.padding_idx {
+            None => values,
+            Some(padding_idx) => {
+                // The padded positions use a detached copy so that no gradient flows back to the
+                // padding row.
+                let padding_idx = Tensor::new(padding_idx as u32, indexes.device())?
+                    .to_dtype(indexes.dtype())?
+                    .broadcast_as(indexes.shape())?;
+                let mask = indexes
+                    .eq(&padding_idx)?
+                    .unsqueeze(1)?
+                    .broadcast_as(values.shape())?;
+                mask.where_cond(&values.detach()?, &values)?
+            }
+        };
         let values = values.reshape(final_dims)?;
         Ok(values)
     }
@@ -1,6 +1,7 @@
 //! Group Normalization.
 //!
-//! This layer applies Group Normalization over a mini-batch of inputs.
+//! This layer applies Group Normalization over a mini-batch of inputs. Instance Normalization is
+//! the special case where each channel is its own group.
 use candle::{DType, Result, Tensor};
 
 // This group norm version handles both weight and bias so removes the mean.
@@ -83,3 +84,41 @@ pub fn group_norm(
     let bias = vb.get_with_hints(num_channels, "bias", crate::Init::Const(0.))?;
     GroupNorm::new(weight, bias, num_channels, num_groups, eps)
 }
+
+/// Instance Normalization over `(n, c, h, w)` inputs, each channel of each sample is normalized
+/// on its own before applying the per-channel weight and bias.
+///
+/// This is a [`GroupNorm`] using one group per channel.
+#[derive(Debug)]
+pub struct InstanceNorm2d {
+    inner: GroupNorm,
+}
+
+impl InstanceNorm2d {
+    pub fn new(weight: Tensor, bias: Tensor, num_channels: usize, eps: f64) -> Result<Self> {
+        let inner = GroupNorm::new(weight, bias, num_channels, num_channels, eps)?;
+        Ok(Self { inner })
+    }
+}
+
+impl crate::Module for InstanceNorm2d {
+    fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        if x.rank() != 4 {
+            candle::bail!(
+                "InstanceNorm2d expects an input of shape (n, c, h, w), got {:?}",
+                x.shape()
+            )
+        }
+        self.inner.forward(x)
+    }
+}
+
+pub fn instance_norm2d(
+    num_channels: usize,
+    eps: f64,
+    vb: crate::VarBuilder,
+) -> Result<InstanceNorm2d> {
+    let weight = vb.get_with_hints(num_channels, "weight", crate::Init::Const(1.))?;
+    let bias = vb.get_with_hints(num_channels, "bias", crate::Init::Const(0.))?;
+    InstanceNorm2d::new(weight, bias, num_channels, eps)
+}
@@ -143,6 +143,9 @@ pub fn layer_norm<C: Into<LayerNormConfig>>(
 }
 
 /// RmsNorm is a specialized version of the LayerNorm module.
+///
+/// This computes `x / sqrt(mean(x^2) + eps) * weight` over the last dimension, unlike LayerNorm
+/// the mean is not removed and there is no bias.
 #[derive(Debug)]
 pub struct RmsNorm(LayerNorm);
 
@@ -162,6 +165,8 @@ impl crate::Module for RmsNorm {
     }
 }
 
+/// Creates a [`RmsNorm`] layer over a last dimension of size `size`, the weight is initialized
+/// to ones.
 pub fn rms_norm(size: usize, eps: f64, vb: crate::VarBuilder) -> Result<RmsNorm> {
     let config = LayerNormConfig {
         eps,
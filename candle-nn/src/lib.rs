@@ -1,4 +1,5 @@
 pub mod activation;
+pub mod attention;
 pub mod batch_norm;
 pub mod conv;
 pub mod embedding;
@@ -11,24 +12,32 @@ pub mod loss;
 pub mod ops;
 pub mod optim;
 pub mod rnn;
+pub mod rotary;
+pub mod sequential;
+pub mod upsample;
+pub mod utils;
 pub mod var_builder;
 pub mod var_map;
 
 pub use activation::Activation;
+pub use attention::{KvCache, MultiHeadAttention, MultiHeadAttentionConfig};
 pub use batch_norm::{batch_norm, BatchNorm, BatchNormConfig};
 pub use conv::{
-    conv1d, conv2d, conv2d_no_bias, conv_transpose2d, conv_transpose2d_no_bias, Conv1d,
-    Conv1dConfig, Conv2d, Conv2dConfig, ConvTranspose2d, ConvTranspose2dConfig,
+    conv1d, conv2d, conv2d_no_bias, conv3d, conv3d_no_bias, conv_transpose2d,
+    conv_transpose2d_no_bias, Conv1d, Conv1dConfig, Conv2d, Conv2dConfig, Conv3d, Conv3dConfig,
+    ConvTranspose2d, ConvTranspose2dConfig,
 };
 pub use embedding::{embedding, Embedding};
 pub use func::{func, Func};
-pub use group_norm::{group_norm, GroupNorm};
+pub use group_norm::{group_norm, instance_norm2d, GroupNorm, InstanceNorm2d};
 pub use init::Init;
 pub use layer_norm::{layer_norm, rms_norm, LayerNorm, LayerNormConfig, RmsNorm};
-pub use linear::{linear, linear_no_bias, Linear};
-pub use ops::Dropout;
-pub use optim::{AdamW, Optimizer, ParamsAdamW, SGD};
+pub use linear::{linear, linear_no_bias, Linear, QLinear};
+pub use ops::{Dropout, Dropout2d};
+pub use optim::{AdamW, Optimizer, ParamsAdamW, ParamsSGD, SGD};
 pub use rnn::{gru, lstm, GRUConfig, LSTMConfig, GRU, LSTM, RNN};
+pub use sequential::{seq, ModuleList, Sequential};
+pub use upsample::{Upsample, UpsampleMode};
 pub use var_builder::VarBuilder;
 pub use var_map::VarMap;
 
@@ -3,7 +3,8 @@
 //! This layer applies a linear transformation to the incoming data, `y = x@w.t() + b`.
 //! The bias is optional. The `forward` method can be used to apply the layer, it supports input
 //! with a batch dimension (so of shape `(b_sz, in_c)`) or without (of shape `(in_c,)`), the
-//! output has shape `(b_sz, out_c)` and `(out_c,)` respectively.
+//! output has shape `(b_sz, out_c)` and `(out_c,)` respectively. Inputs with more leading
+//! dimensions, e.g. `(b_sz, seq_len, in_c)`, are also supported.
 //!
 //! ```rust
 //! use candle::{Tensor, Device::Cpu};
@@ -17,17 +18,59 @@
 //! assert_eq!(ys.to_vec2::<f32>()?, &[[210.0, 430.0, 650.0]]);
 //! # Ok(()) }
 //! ```
+use candle::quantized::{GgmlDType, QMatMul, QTensor};
 use candle::{Result, Tensor};
 
 #[derive(Debug)]
 pub struct Linear {
     weight: Tensor,
     bias: Option<Tensor>,
+    /// A contiguous copy of the transposed weight, of shape `(in_c, out_c)`, when the layer has
+    /// been created with `pre_transpose` set.
+    weight_t: Option<Tensor>,
 }
 
 impl Linear {
     pub fn new(weight: Tensor, bias: Option<Tensor>) -> Self {
-        Self { weight, bias }
+        Self {
+            weight,
+            bias,
+            weight_t: None,
+        }
+    }
+
+    /// Creates a linear layer from externally loaded weights, checking that the weight has
+    /// shape `(out_c, in_c)` and that the bias, if any, has shape `(out_c,)`.
+    pub fn from_weights(weight: Tensor, bias: Option<Tensor>) -> Result<Self> {
+        Self::with_options(weight, bias, false)
+    }
+
+    /// Creates a linear layer, when `pre_transpose` is true a contiguous transposed copy of the
+    /// weight is stored so that `forward` uses a single matmul on a contiguous rhs rather than a
+    /// transposed view of the weight. This uses more memory as both versions of the weight are
+    /// kept, and whether it is faster depends on the backend and on the input size, see the
+    /// `linear` benchmark.
+    pub fn with_options(weight: Tensor, bias: Option<Tensor>, pre_transpose: bool) -> Result<Self> {
+        let (out_c, _in_c) = weight.dims2()?;
+        if let Some(bias) = &bias {
+            if bias.dims() != [out_c] {
+                candle::bail!(
+                    "linear: bias shape {:?} does not match the weight shape {:?}",
+                    bias.shape(),
+                    weight.shape()
+                )
+            }
+        }
+        let weight_t = if pre_transpose {
+            Some(weight.t()?.contiguous()?)
+        } else {
+            None
+        };
+        Ok(Self {
+            weight,
+            bias,
+            weight_t,
+        })
     }
 
     pub fn weight(&self) -> &Tensor {
@@ -41,12 +84,33 @@ impl Linear {
 
 impl super::Module for Linear {
     fn forward(&self, x: &Tensor) -> candle::Result<Tensor> {
-        let w = match *x.dims() {
-            [b1, b2, _, _] => self.weight.broadcast_left((b1, b2))?.t()?,
-            [bsize, _, _] => self.weight.broadcast_left(bsize)?.t()?,
-            _ => self.weight.t()?,
+        let x = match &self.weight_t {
+            Some(weight_t) => match *x.dims() {
+                [] | [_] | [_, _] => x.matmul(weight_t)?,
+                _ => {
+                    // Merge all the leading dimensions so that a single matmul is used.
+                    let mut dims = x.dims()[..x.rank() - 1].to_vec();
+                    let ys = x.flatten_to(x.rank() - 2)?.matmul(weight_t)?;
+                    dims.push(ys.dim(1)?);
+                    ys.reshape(dims)?
+                }
+            },
+            None => {
+                let w = match *x.dims() {
+                    [b1, b2, _, _] => self.weight.broadcast_left((b1, b2))?.t()?,
+                    [bsize, _, _] => self.weight.broadcast_left(bsize)?.t()?,
+                    [] | [_] | [_, _] => self.weight.t()?,
+                    _ => {
+                        // Merge all the leading dimensions into a single batch dimension.
+                        let mut dims = x.dims()[..x.rank() - 1].to_vec();
+                        let ys = self.forward(&x.flatten_to(x.rank() - 2)?)?;
+                        dims.push(ys.dim(1)?);
+                        return ys.reshape(dims);
+                    }
+                };
+                x.matmul(&w)?
+            }
         };
-        let x = x.matmul(&w)?;
         match &self.bias {
             None => Ok(x),
             Some(bias) => x.broadcast_add(bias),
@@ -54,6 +118,67 @@ impl super::Module for Linear {
     }
 }
 
+/// A linear layer using a block-quantized weight, the matmul is performed by the cpu kernels of
+/// [`candle::quantized::QMatMul`] on f32 activations, the output has the same dtype as the input.
+/// This can be used in place of a [`Linear`]
+/// layer, e.g. by quantizing the weights of an existing model with [`QLinear::from_linear`].
+#[derive(Debug)]
+pub struct QLinear {
+    weight: QMatMul,
+    bias: Option<Tensor>,
+}
+
+impl QLinear {
+    /// Creates a layer from a quantized weight of shape `(out_c, in_c)`.
+    pub fn new(weight: QTensor, bias: Option<Tensor>) -> Result<Self> {
+        let (out_c, _in_c) = weight.shape().dims2()?;
+        if let Some(bias) = &bias {
+            if bias.dims() != [out_c] {
+                candle::bail!(
+                    "qlinear: bias shape {:?} does not match the weight shape {:?}",
+                    bias.shape(),
+                    weight.shape()
+                )
+            }
+        }
+        Ok(Self {
+            weight: QMatMul::from_qtensor(weight),
+            bias,
+        })
+    }
+
+    /// Quantizes the weight of a linear layer, the bias is kept as is. The input dimension of
+    /// the layer must be a multiple of the block size of `dtype`.
+    pub fn from_linear(linear: &Linear, dtype: GgmlDType) -> Result<Self> {
+        let weight = QTensor::quantize_as(linear.weight(), dtype)?;
+        Self::new(weight, linear.bias().cloned())
+    }
+
+    pub fn weight(&self) -> &QTensor {
+        self.weight.inner()
+    }
+
+    pub fn bias(&self) -> Option<&Tensor> {
+        self.bias.as_ref()
+    }
+}
+
+impl super::Module for QLinear {
+    fn forward(&self, x: &Tensor) -> candle::Result<Tensor> {
+        let dtype = x.dtype();
+        let x = x.to_dtype(candle::DType::F32)?.contiguous()?;
+        let x = match x.rank() {
+            1 => self.weight.forward(&x.unsqueeze(0)?)?.squeeze(0)?,
+            _ => self.weight.forward(&x)?,
+        };
+        let x = match &self.bias {
+            None => x,
+            Some(bias) => x.broadcast_add(&bias.to_dtype(candle::DType::F32)?)?,
+        };
+        x.to_dtype(dtype)
+    }
+}
+
 /// Create or initialize a new linear layer.
 ///
 /// This uses some default names for weight and biases, namely `"weight"` and `"bias"`.
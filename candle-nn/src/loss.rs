@@ -1,30 +1,46 @@
-use candle::{Result, Tensor};
+use candle::{DType, Result, Tensor, D};
+
+/// Specifies how the per-element values of a loss get reduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reduction {
+    /// No reduction, the per-element losses are returned.
+    None,
+    /// The average over all the elements.
+    #[default]
+    Mean,
+    /// The sum over all the elements.
+    Sum,
+    /// The sum over all the elements divided by the size of the first dimension, the batch size.
+    /// This is the reduction that matches the mathematical definition of `kl_div`.
+    BatchMean,
+}
+
+/// Reduces a tensor of per-element losses according to `reduction`.
+///
+/// `Reduction::Mean`, `Reduction::Sum` and `Reduction::BatchMean` return a scalar tensor computed
+/// over all the elements of `loss`, `Reduction::None` returns `loss` unchanged.
+pub fn apply_reduction(loss: &Tensor, reduction: Reduction) -> Result<Tensor> {
+    match reduction {
+        Reduction::None => Ok(loss.clone()),
+        Reduction::Mean => loss.mean_all(),
+        Reduction::Sum => loss.sum_all(),
+        Reduction::BatchMean => loss.sum_all()? / loss.dim(0)? as f64,
+    }
+}
 
 /// The negative log likelihood loss.
 ///
 /// Arguments
 ///
 /// * [inp]: The input tensor of dimensions `N, C` where `N` is the batch size and `C` the number
-///          of categories. This is expected to contain log probabilities.
-/// * [target]: The ground truth labels as a tensor of u32 of dimension `N`.
+///          of categories. This is expected to contain log probabilities, e.g. the output of
+///          `log_softmax`, use `cross_entropy` for raw logits. Inputs of dimensions `N, S, C` are
+///          also supported.
+/// * [target]: The ground truth labels as a tensor of u32 or i64 of dimension `N` (resp. `N, S`).
 ///
 /// The resulting tensor is a scalar containing the average value over the batch.
 pub fn nll(inp: &Tensor, target: &Tensor) -> Result<Tensor> {
-    let b_sz = match target.dims() {
-        &[b_sz] => b_sz,
-        dims => candle::bail!("the target tensor should have a single dimension ({dims:?})"),
-    };
-    match inp.dims() {
-        &[inp_b_sz, _] => {
-            if inp_b_sz != b_sz {
-                candle::bail!("batch size mismatch between inp ({inp_b_sz}) and target ({b_sz})")
-            }
-        }
-        dims => candle::bail!("the target tensor should have two dimensions ({dims:?})"),
-    }
-    inp.gather(&target.unsqueeze(1)?, 1)?
-        .sum_all()?
-        .affine(-1f64 / b_sz as f64, 0.)
+    nll_with_config(inp, target, &CrossEntropyConfig::default())
 }
 
 /// The cross-entropy loss.
@@ -32,19 +48,287 @@ pub fn nll(inp: &Tensor, target: &Tensor) -> Result<Tensor> {
 /// Arguments
 ///
 /// * [inp]: The input tensor of dimensions `N, C` where `N` is the batch size and `C` the number
-///          of categories. This is expected to raw logits.
-/// * [target]: The ground truth labels as a tensor of u32 of dimension `N`.
+///          of categories. This is expected to raw logits. Inputs of dimensions `N, S, C` are also
+///          supported, e.g. with `S` being the sequence length.
+/// * [target]: The ground truth labels as a tensor of u32 or i64 of dimension `N` (resp. `N, S`).
 ///
 /// The resulting tensor is a scalar containing the average value over the batch.
 pub fn cross_entropy(inp: &Tensor, target: &Tensor) -> Result<Tensor> {
-    if inp.rank() != 2 {
-        candle::bail!("cross_entropy expects an input tensor of rank 2")
+    cross_entropy_with_config(inp, target, &CrossEntropyConfig::default())
+}
+
+/// The cross-entropy loss where the positions with a target equal to `ignore_index` do not
+/// contribute to the loss.
+///
+/// Arguments
+///
+/// * [inp]: The input tensor of dimensions `N, C` where `N` is the batch size and `C` the number
+///          of categories. This is expected to raw logits.
+/// * [target]: The ground truth labels as a tensor of u32 or i64 of dimension `N`.
+/// * [ignore_index]: The target value to ignore, e.g. `-100`. When `None` this is the same as
+///                   `cross_entropy`.
+///
+/// The resulting tensor is a scalar containing the average value over the non-ignored positions
+/// of the batch, or 0 when all the positions are ignored.
+pub fn cross_entropy_with_ignore_index(
+    inp: &Tensor,
+    target: &Tensor,
+    ignore_index: Option<i64>,
+) -> Result<Tensor> {
+    let config = CrossEntropyConfig {
+        ignore_index,
+        ..Default::default()
+    };
+    cross_entropy_with_config(inp, target, &config)
+}
+
+/// Options for `cross_entropy_with_config` and `nll_with_config`, the default values result in
+/// the plain loss averaged over the batch.
+#[derive(Debug, Clone, Default)]
+pub struct CrossEntropyConfig {
+    /// Positions with this target value do not contribute to the loss, e.g. `-100` for padding.
+    pub ignore_index: Option<i64>,
+    /// The amount of smoothing between 0 and 1, the target distribution becomes a mix of the
+    /// one-hot target with weight `1 - label_smoothing` and of the uniform distribution.
+    pub label_smoothing: f64,
+    /// Per-class weights, a tensor of dimension `C`.
+    pub weight: Option<Tensor>,
+    /// With `Reduction::Mean`, the sum of the losses is divided by the sum of the weights of the
+    /// targets of the non-ignored positions (their number when there are no weights). The loss is
+    /// 0 rather than NaN when this sum is 0, e.g. when all the positions are ignored.
+    /// `Reduction::None` returns a tensor with the same shape as the target.
+    pub reduction: Reduction,
+}
+
+/// The cross-entropy loss with the options from `config`, see `cross_entropy` for the expected
+/// input and target shapes.
+///
+/// The log-probabilities are computed with a numerically stable log-softmax and the targets are
+/// selected with a gather, so no one-hot encoding of the targets is materialized in the forward
+/// or the backward pass.
+pub fn cross_entropy_with_config(
+    inp: &Tensor,
+    target: &Tensor,
+    config: &CrossEntropyConfig,
+) -> Result<Tensor> {
+    if !matches!(inp.rank(), 2 | 3) {
+        candle::bail!(
+            "cross_entropy expects an input tensor of rank 2 or 3 ({:?})",
+            inp.dims()
+        )
+    }
+    let log_probs = crate::ops::log_softmax(inp, D::Minus1)?;
+    nll_with_config(&log_probs, target, config)
+}
+
+/// The negative log likelihood loss with the options from `config`, the input contains log
+/// probabilities, see `nll` for the expected input and target shapes. This is the same as
+/// `cross_entropy_with_config` applied to the logits.
+pub fn nll_with_config(
+    inp: &Tensor,
+    target: &Tensor,
+    config: &CrossEntropyConfig,
+) -> Result<Tensor> {
+    let (target_dims, n_classes) = match inp.dims() {
+        [b_sz, n_classes] => (vec![*b_sz], *n_classes),
+        [b_sz, seq_len, n_classes] => (vec![*b_sz, *seq_len], *n_classes),
+        dims => candle::bail!("nll expects an input tensor of rank 2 or 3 ({dims:?})"),
+    };
+    if target.dims() != target_dims {
+        candle::bail!(
+            "nll expects a target of shape {target_dims:?} for an input of shape {:?}, got {:?}",
+            inp.shape(),
+            target.shape()
+        )
+    }
+    if !(0. ..=1.).contains(&config.label_smoothing) {
+        candle::bail!(
+            "nll: label_smoothing should be between 0 and 1, got {}",
+            config.label_smoothing
+        )
+    }
+    let weight = match &config.weight {
+        None => None,
+        Some(weight) => {
+            if weight.dims() != [n_classes] {
+                candle::bail!(
+                    "nll: expected a weight of shape [{n_classes}], got {:?}",
+                    weight.shape()
+                )
+            }
+            Some(weight.to_dtype(inp.dtype())?)
+        }
+    };
+    let inp = inp.reshape((target_dims.iter().product::<usize>(), n_classes))?;
+    let target = target.flatten_all()?.to_dtype(DType::I64)?;
+    let keep = match config.ignore_index {
+        None => target.ones_like()?.to_dtype(DType::U8)?,
+        Some(ignore_index) => target.ne_scalar(ignore_index as f64)?,
+    };
+    // Ignored positions get mapped to class 0 so that the gather stays in bounds, their loss is
+    // masked out afterwards.
+    let target = keep
+        .where_cond(&target, &target.zeros_like()?)?
+        .to_dtype(DType::U32)?;
+    // The weight of each position, zero for the ignored ones.
+    let keep = keep.to_dtype(inp.dtype())?;
+    let position_weight = match &weight {
+        None => keep.clone(),
+        Some(weight) => (weight.index_select(&target, 0)? * &keep)?,
+    };
+    let log_probs = inp;
+    let target_log_probs = log_probs.gather(&target.unsqueeze(1)?, 1)?.squeeze(1)?;
+    let loss = (target_log_probs.neg()? * &position_weight)?;
+    let loss = if config.label_smoothing > 0. {
+        let smooth_loss = match &weight {
+            None => log_probs.sum(1)?,
+            Some(weight) => log_probs.broadcast_mul(&weight.unsqueeze(0)?)?.sum(1)?,
+        };
+        let smooth_loss = (smooth_loss.neg()? * keep)?;
+        let eps = config.label_smoothing;
+        ((loss * (1. - eps))? + (smooth_loss * (eps / n_classes as f64))?)?
+    } else {
+        loss
+    };
+    match config.reduction {
+        Reduction::None => loss.reshape(target_dims),
+        Reduction::Sum => loss.sum_all(),
+        Reduction::Mean => {
+            // Avoid 0 / 0 when all the positions are ignored, the loss sum is 0 in this case.
+            let total = position_weight.sum_all()?;
+            let total = total
+                .eq_scalar(0.)?
+                .where_cond(&total.ones_like()?, &total)?;
+            loss.sum_all()?.div(&total)
+        }
+        Reduction::BatchMean => loss.sum_all()? / target_dims[0] as f64,
     }
-    let inp = crate::ops::log_softmax(inp, 1)?;
-    nll(&inp, target)
 }
 
 /// The mean squared error loss.
 pub fn mse(inp: &Tensor, target: &Tensor) -> Result<Tensor> {
-    (inp - target)?.sqr()?.mean_all()
+    let loss = (inp - target)?.sqr()?;
+    apply_reduction(&loss, Reduction::Mean)
+}
+
+// Broadcasts the prediction and the target to a common shape.
+fn broadcast_pair(inp: &Tensor, target: &Tensor, op: &str) -> Result<(Tensor, Tensor)> {
+    let (inp_dims, target_dims) = (inp.dims(), target.dims());
+    let rank = usize::max(inp_dims.len(), target_dims.len());
+    let mut dims = Vec::with_capacity(rank);
+    for idx in 0..rank {
+        let dim = |ds: &[usize]| (idx + ds.len()).checked_sub(rank).map_or(1, |i| ds[i]);
+        let (d1, d2) = (dim(inp_dims), dim(target_dims));
+        if d1 != d2 && d1 != 1 && d2 != 1 {
+            candle::bail!(
+                "{op}: the prediction of shape {:?} and the target of shape {:?} cannot be broadcasted together",
+                inp.shape(),
+                target.shape()
+            )
+        }
+        dims.push(usize::max(d1, d2))
+    }
+    Ok((
+        inp.broadcast_as(dims.as_slice())?,
+        target.broadcast_as(dims)?,
+    ))
+}
+
+/// The squared error loss `(inp - target)^2`, the prediction and target shapes are broadcasted
+/// together.
+pub fn mse_with_reduction(inp: &Tensor, target: &Tensor, reduction: Reduction) -> Result<Tensor> {
+    let (inp, target) = broadcast_pair(inp, target, "mse")?;
+    apply_reduction(&(inp - target)?.sqr()?, reduction)
+}
+
+/// The absolute error loss `|inp - target|`, the prediction and target shapes are broadcasted
+/// together.
+pub fn l1(inp: &Tensor, target: &Tensor, reduction: Reduction) -> Result<Tensor> {
+    let (inp, target) = broadcast_pair(inp, target, "l1")?;
+    apply_reduction(&(inp - target)?.abs()?, reduction)
+}
+
+/// The Huber loss, with `d = |inp - target|` this is `0.5 * d^2` when `d < delta` and
+/// `delta * (d - 0.5 * delta)` otherwise.
+pub fn huber(inp: &Tensor, target: &Tensor, delta: f64, reduction: Reduction) -> Result<Tensor> {
+    if delta <= 0. {
+        candle::bail!("huber: delta should be positive, got {delta}")
+    }
+    let (inp, target) = broadcast_pair(inp, target, "huber")?;
+    let diff = (inp - target)?.abs()?;
+    // With q = min(d, delta), 0.5 * q^2 + delta * (d - q) gives both branches.
+    let quadratic = diff.minimum_scalar(delta)?;
+    let linear = (diff - &quadratic)?;
+    let loss = ((quadratic.sqr()? * 0.5)? + (linear * delta)?)?;
+    apply_reduction(&loss, reduction)
+}
+
+/// The smooth L1 loss, this is `huber(inp, target, beta) / beta` and the L1 loss when `beta`
+/// is 0.
+pub fn smooth_l1(inp: &Tensor, target: &Tensor, beta: f64, reduction: Reduction) -> Result<Tensor> {
+    if beta == 0. {
+        return l1(inp, target, reduction);
+    }
+    huber(inp, target, beta, reduction)? / beta
+}
+
+/// The binary cross-entropy loss `-(target * log(inp) + (1 - target) * log(1 - inp))`.
+///
+/// The prediction is expected to contain probabilities, the logarithms are clamped to be at
+/// least -100 so that predictions of exactly 0 or 1 give a finite loss. When the prediction is
+/// the output of a sigmoid, `binary_cross_entropy_with_logits` is more stable.
+pub fn binary_cross_entropy(inp: &Tensor, target: &Tensor, reduction: Reduction) -> Result<Tensor> {
+    let (inp, target) = broadcast_pair(inp, target, "binary_cross_entropy")?;
+    let log_p = inp.log()?.maximum_scalar(-100.)?;
+    let log_1mp = inp.affine(-1., 1.)?.log()?.maximum_scalar(-100.)?;
+    let loss = ((&target * log_p)? + (target.affine(-1., 1.)? * log_1mp)?)?.neg()?;
+    apply_reduction(&loss, reduction)
+}
+
+/// The binary cross-entropy loss applied to `sigmoid(inp)`.
+///
+/// This is computed as `max(x, 0) - x * target + log(1 + exp(-|x|))` rather than applying a
+/// sigmoid followed by a log, so that large logits do not overflow, including in f16.
+pub fn binary_cross_entropy_with_logits(
+    inp: &Tensor,
+    target: &Tensor,
+    reduction: Reduction,
+) -> Result<Tensor> {
+    let (inp, target) = broadcast_pair(inp, target, "binary_cross_entropy_with_logits")?;
+    let loss = (crate::ops::softplus(&inp)? - (&inp * target)?)?;
+    apply_reduction(&loss, reduction)
+}
+
+/// The Kullback-Leibler divergence loss, following the PyTorch conventions.
+///
+/// Arguments
+///
+/// * [inp]: The log probabilities of the predicted distribution, e.g. the output of
+///          `log_softmax`. Unlike the `target`, this is never a probability.
+/// * [target]: The target distribution, as probabilities when `log_target` is false and as log
+///             probabilities otherwise. The shapes of `inp` and `target` are broadcasted together.
+/// * [log_target]: Whether `target` contains log probabilities.
+/// * [reduction]: `Reduction::BatchMean` matches the mathematical definition of the divergence
+///                for inputs of shape `N, C` (or `N, S, C` summed over the sequence),
+///                `Reduction::Mean` averages over all the elements and so also divides by `C`.
+///
+/// The per-element loss is `target * (log(target) - inp)`, with 0 where `target` is 0, or
+/// `exp(target) * (target - inp)` when `log_target` is true.
+pub fn kl_div(
+    inp: &Tensor,
+    target: &Tensor,
+    log_target: bool,
+    reduction: Reduction,
+) -> Result<Tensor> {
+    let (inp, target) = broadcast_pair(inp, target, "kl_div")?;
+    let loss = if log_target {
+        (target.exp()? * (&target - inp)?)?
+    } else {
+        // Use target * log(target) = 0 when target is 0, the log is computed on 1 instead to
+        // avoid NaN values.
+        let positive = target.gt_scalar(0.)?;
+        let log_target = positive.where_cond(&target, &target.ones_like()?)?.log()?;
+        (&target * (log_target - inp)?)?
+    };
+    apply_reduction(&loss, reduction)
 }
@@ -1,4 +1,4 @@
-use candle::{CpuStorage, Layout, Result, Shape, Tensor};
+use candle::{CpuStorage, DType, Device, Layout, Result, Shape, Tensor, D};
 use rayon::prelude::*;
 
 /// Applies the softmax function to the input tensor, rescaling the element so that elements on
@@ -44,6 +44,238 @@ pub fn sigmoid(xs: &Tensor) -> Result<Tensor> {
     (xs.neg()?.exp()? + 1.0)?.recip()
 }
 
+/// Computes `log(1 + exp(x))`, this is evaluated as `max(x, 0) + log(1 + exp(-|x|))` so that large
+/// inputs do not overflow.
+pub fn softplus(xs: &Tensor) -> Result<Tensor> {
+    let log1p_exp = (xs.abs()?.neg()?.exp()? + 1.0)?.log()?;
+    xs.relu()? + log1p_exp
+}
+
+/// Computes `x * tanh(softplus(x))`.
+pub fn mish(xs: &Tensor) -> Result<Tensor> {
+    xs * softplus(xs)?.tanh()?
+}
+
+/// Computes `min(max(x, 0), 6)`.
+pub fn relu6(xs: &Tensor) -> Result<Tensor> {
+    xs.relu()? - (xs - 6.)?.relu()?
+}
+
+/// Computes `relu6(x + 3) / 6`.
+pub fn hard_sigmoid(xs: &Tensor) -> Result<Tensor> {
+    relu6(&(xs + 3.)?)? / 6.
+}
+
+/// Computes `x * relu6(x + 3) / 6`.
+pub fn hard_swish(xs: &Tensor) -> Result<Tensor> {
+    xs * hard_sigmoid(xs)?
+}
+
+/// Computes `x` for positive values and `negative_slope * x` otherwise.
+pub fn leaky_relu(xs: &Tensor, negative_slope: f64) -> Result<Tensor> {
+    xs.relu()? - (xs.neg()?.relu()? * negative_slope)?
+}
+
+// The error function using the approximation 7.1.26 from Abramowitz and Stegun, the absolute
+// error is below 1.5e-7.
+fn erf(xs: &Tensor) -> Result<Tensor> {
+    const P: f64 = 0.3275911;
+    const A: [f64; 5] = [
+        0.254829592,
+        -0.284496736,
+        1.421413741,
+        -1.453152027,
+        1.061405429,
+    ];
+    let abs = xs.abs()?;
+    let t = ((&abs * P)? + 1.)?.recip()?;
+    let mut poly = (&t * A[4])?;
+    for a in A[..4].iter().rev() {
+        poly = ((poly + *a)? * &t)?;
+    }
+    let erf_abs = ((poly * abs.sqr()?.neg()?.exp()?)?.neg()? + 1.)?;
+    // erf is odd, the sign does not depend on the input for the backward pass.
+    let ones = xs.ones_like()?;
+    let sign = xs.ge(&xs.zeros_like()?)?.where_cond(&ones, &ones.neg()?)?;
+    erf_abs * sign
+}
+
+/// The exact gelu activation `0.5 x (1 + erf(x / sqrt(2)))`, rather than the tanh approximation
+/// used by `Tensor::gelu`.
+pub fn gelu_erf(xs: &Tensor) -> Result<Tensor> {
+    let cdf = ((erf(&(xs / std::f64::consts::SQRT_2)?)? + 1.)? * 0.5)?;
+    xs * cdf
+}
+
+/// Computes the cosine similarity `(a . b) / (|a| |b|)` between `a` and `b` along dimension
+/// `dim`, the denominator being floored to `eps` so that zero vectors do not result in NaN.
+///
+/// The two tensors should have shapes that can be broadcasted together, the returned tensor has
+/// the broadcasted shape with dimension `dim` removed.
+///
+/// ```rust
+/// use candle::{Tensor, Device, test_utils::to_vec1_round};
+/// let a = Tensor::new(&[[1f32, 0.], [1., 1.]], &Device::Cpu)?;
+/// let b = Tensor::new(&[[2f32, 0.], [-1., 0.]], &Device::Cpu)?;
+/// let s = candle_nn::ops::cosine_similarity(&a, &b, 1, 1e-8)?;
+/// assert_eq!(to_vec1_round(&s, 4)?, &[1., -0.7071]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn cosine_similarity<D: candle::shape::Dim>(
+    a: &Tensor,
+    b: &Tensor,
+    dim: D,
+    eps: f64,
+) -> Result<Tensor> {
+    let dim = dim.to_index(a.shape(), "cosine-similarity")?;
+    let dot = a.broadcast_mul(b)?.sum(dim)?;
+    let den = a
+        .norm(2., dim, false)?
+        .broadcast_mul(&b.norm(2., dim, false)?)?;
+    let eps = Tensor::new(eps, den.device())?
+        .to_dtype(den.dtype())?
+        .broadcast_as(den.shape())?;
+    dot.broadcast_div(&den.maximum(&eps)?)
+}
+
+/// Computes the `p`-norm distance between each pair of rows of `a` and `b`. If `a` has shape
+/// `(n, d)` and `b` has shape `(m, d)`, the result has shape `(n, m)`.
+///
+/// ```rust
+/// use candle::{Tensor, Device};
+/// let a = Tensor::new(&[[0f32, 0.], [3., 4.]], &Device::Cpu)?;
+/// let d = candle_nn::ops::cdist(&a, &a, 2.)?;
+/// assert_eq!(d.to_vec2::<f32>()?, &[[0., 5.], [5., 0.]]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn cdist(a: &Tensor, b: &Tensor, p: f64) -> Result<Tensor> {
+    let (_n, d_a) = a.dims2()?;
+    let (_m, d_b) = b.dims2()?;
+    if d_a != d_b {
+        candle::bail!("cdist: feature size mismatch between a ({d_a}) and b ({d_b})")
+    }
+    a.unsqueeze(1)?
+        .broadcast_sub(&b.unsqueeze(0)?)?
+        .norm(p, 2, false)
+}
+
+/// Computes the mean of `x` along `dim` only taking into account the positions where `mask` is
+/// set, i.e. `sum(x * mask, dim) / sum(mask, dim)`.
+///
+/// The mask should be broadcastable to the shape of `x`, e.g. a `(batch, seq_len, 1)` mask for
+/// an input of shape `(batch, seq_len, hidden)`. Lanes where the mask is entirely zero result in
+/// zeros rather than NaN.
+///
+/// ```rust
+/// use candle::{Tensor, Device};
+/// let x = Tensor::new(&[[1f32, 2., 3., 4.], [5., 6., 7., 8.]], &Device::Cpu)?;
+/// let mask = Tensor::new(&[[1u8, 1, 0, 0], [0, 0, 0, 0]], &Device::Cpu)?;
+/// let m = candle_nn::ops::masked_mean(&x, &mask, 1)?;
+/// assert_eq!(m.to_vec1::<f32>()?, &[1.5, 0.]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+pub fn masked_mean<D: candle::shape::Dim>(x: &Tensor, mask: &Tensor, dim: D) -> Result<Tensor> {
+    let dim = dim.to_index(x.shape(), "masked-mean")?;
+    let mask = mask.to_dtype(x.dtype())?.broadcast_as(x.shape())?;
+    let sum = (x * &mask)?.sum(dim)?;
+    let count = mask.sum(dim)?;
+    // Use a count of one for the fully masked lanes, the sum is zero for these.
+    let empty = count.eq(&count.zeros_like()?)?.to_dtype(x.dtype())?;
+    sum / (count + empty)?
+}
+
+/// Computes the coefficient of determination `1 - SS_res / SS_tot` between `pred` and `target`
+/// which must have the same shape, all the elements are used. The result is a scalar tensor.
+///
+/// When the target is constant, `SS_tot` is zero and the result is `1` if the prediction is
+/// perfect and `0` otherwise, similar to scikit-learn.
+pub fn r2_score(pred: &Tensor, target: &Tensor) -> Result<Tensor> {
+    if pred.dims() != target.dims() {
+        candle::bail!(
+            "r2_score: pred shape {:?} does not match target shape {:?}",
+            pred.shape(),
+            target.shape()
+        )
+    }
+    let ss_res = (target - pred)?.sqr()?.sum_all()?;
+    let ss_tot = target
+        .broadcast_sub(&target.mean_all()?)?
+        .sqr()?
+        .sum_all()?;
+    if ss_tot.to_dtype(DType::F64)?.to_scalar::<f64>()? == 0. {
+        let perfect = ss_res.to_dtype(DType::F64)?.to_scalar::<f64>()? == 0.;
+        let r2 = if perfect { 1. } else { 0. };
+        return Tensor::new(r2, target.device())?.to_dtype(target.dtype());
+    }
+    1. - (ss_res / ss_tot)?
+}
+
+/// Precomputes the cosine and sine tables used by rotary position embeddings (RoPE).
+///
+/// This is [`crate::rotary::cos_sin`] with the tables converted to `dtype`, both returned
+/// tensors have shape `(max_seq_len, head_dim)` and can be used with [`apply_rope`].
+pub fn build_rope_cache(
+    max_seq_len: usize,
+    head_dim: usize,
+    base: f64,
+    dtype: DType,
+    device: &Device,
+) -> Result<(Tensor, Tensor)> {
+    let (cos, sin) = crate::rotary::cos_sin(max_seq_len, head_dim, base, device)?;
+    Ok((cos.to_dtype(dtype)?, sin.to_dtype(dtype)?))
+}
+
+/// Applies rotary position embeddings to `xs` of shape `(batch, heads, seq_len, head_dim)`
+/// using tables built by [`build_rope_cache`] and the given `layout`, see
+/// [`crate::rotary::apply_rotary_emb`].
+///
+/// The element at sequence position `i` uses the row `offset + i` of the tables, so that the
+/// tokens generated in a streaming decode can use the same cache. The rotation is computed in
+/// the dtype of the tables and the result is converted back to the dtype of `xs`.
+pub fn apply_rope(
+    xs: &Tensor,
+    cos: &Tensor,
+    sin: &Tensor,
+    offset: usize,
+    layout: crate::rotary::RopeLayout,
+) -> Result<Tensor> {
+    let seq_len = xs.dim(2)?;
+    let (max_seq_len, _) = cos.dims2()?;
+    if offset + seq_len > max_seq_len {
+        candle::bail!(
+            "apply_rope: positions up to {} are out of the cache of length {max_seq_len}",
+            offset + seq_len
+        )
+    }
+    let cos = cos.narrow(0, offset, seq_len)?;
+    let sin = sin.narrow(0, offset, seq_len)?;
+    let x = xs.to_dtype(cos.dtype())?;
+    crate::rotary::apply_rotary_emb(&x, &cos, &sin, layout)?.to_dtype(xs.dtype())
+}
+
+/// Scaled dot-product attention, computes `softmax(q @ k^T * scale + mask) @ v`.
+///
+/// The inputs have shape `(batch, heads, seq_len, head_dim)`, the key and value sequence lengths
+/// can differ from the query one. The optional additive `mask` should be broadcastable to
+/// `(batch, heads, q_seq_len, kv_seq_len)`, e.g. a `(q_seq_len, kv_seq_len)` tensor using
+/// `-inf` for the masked positions. `scale` defaults to `1 / sqrt(head_dim)`.
+pub fn sdpa(
+    q: &Tensor,
+    k: &Tensor,
+    v: &Tensor,
+    mask: Option<&Tensor>,
+    scale: Option<f64>,
+) -> Result<Tensor> {
+    let (_b_sz, _n_heads, _q_len, head_dim) = q.dims4()?;
+    let scale = scale.unwrap_or(1. / (head_dim as f64).sqrt());
+    let att = (q.matmul(&k.t()?)? * scale)?;
+    let att = match mask {
+        None => att,
+        Some(mask) => att.broadcast_add(mask)?,
+    };
+    softmax(&att, D::Minus1)?.matmul(v)
+}
+
 pub fn dropout(xs: &Tensor, drop_p: f32) -> Result<Tensor> {
     // This implementation is inefficient as it stores the full mask for the backward pass.
     // Instead we could just store the seed and have a specialized kernel that would both
@@ -53,6 +285,9 @@ pub fn dropout(xs: &Tensor, drop_p: f32) -> Result<Tensor> {
     if !(0. ..1.).contains(&drop_p) {
         candle::bail!("dropout probability has to be in [0, 1), got {drop_p}")
     }
+    if drop_p == 0. {
+        return Ok(xs.clone());
+    }
     let rand = Tensor::rand(0f32, 1f32, xs.shape(), xs.device())?;
     let scale = 1.0 / (1.0 - drop_p as f64);
     let drop_p = Tensor::new(drop_p, xs.device())?.broadcast_as(xs.shape())?;
@@ -60,6 +295,109 @@ pub fn dropout(xs: &Tensor, drop_p: f32) -> Result<Tensor> {
     xs * mask
 }
 
+/// Channel-wise dropout, each channel of each sample of `xs`, of shape `(b, c, ...)`, is zeroed
+/// with probability `drop_p` and the remaining channels are scaled by `1 / (1 - drop_p)`.
+pub fn dropout2d(xs: &Tensor, drop_p: f32) -> Result<Tensor> {
+    if !(0. ..1.).contains(&drop_p) {
+        candle::bail!("dropout2d probability has to be in [0, 1), got {drop_p}")
+    }
+    if xs.rank() < 2 {
+        candle::bail!(
+            "dropout2d expects at least two dimensions, got {:?}",
+            xs.shape()
+        )
+    }
+    if drop_p == 0. {
+        return Ok(xs.clone());
+    }
+    let mut mask_dims = vec![1; xs.rank()];
+    mask_dims[0] = xs.dim(0)?;
+    mask_dims[1] = xs.dim(1)?;
+    let rand = Tensor::rand(0f32, 1f32, mask_dims, xs.device())?;
+    let scale = 1.0 / (1.0 - drop_p as f64);
+    let drop_p = Tensor::new(drop_p, xs.device())?.broadcast_as(rand.shape())?;
+    let mask = (rand.ge(&drop_p)? * scale)?.to_dtype(xs.dtype())?;
+    xs.broadcast_mul(&mask)
+}
+
+/// Stochastic depth, also known as drop path, randomly drops whole samples of a residual branch.
+///
+/// In training mode each sample along the first (batch) dimension is zeroed with probability
+/// `drop_prob` and the remaining samples are scaled by `1 / (1 - drop_prob)`. The random choices
+/// are derived from `seed`. In eval mode this returns the input unchanged.
+pub fn drop_path(xs: &Tensor, drop_prob: f64, training: bool, seed: u64) -> Result<Tensor> {
+    use rand::{Rng, SeedableRng};
+
+    if !(0. ..1.).contains(&drop_prob) {
+        candle::bail!("drop_path probability has to be in [0, 1), got {drop_prob}")
+    }
+    if !training || drop_prob == 0. {
+        return Ok(xs.clone());
+    }
+    let b_sz = xs.dim(0)?;
+    let scale = 1. / (1. - drop_prob);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mask: Vec<f64> = (0..b_sz)
+        .map(|_| {
+            if rng.gen::<f64>() < drop_prob {
+                0.
+            } else {
+                scale
+            }
+        })
+        .collect();
+    let mut mask_dims = vec![1; xs.rank()];
+    mask_dims[0] = b_sz;
+    let mask = Tensor::from_vec(mask, mask_dims, xs.device())?.to_dtype(xs.dtype())?;
+    xs.broadcast_mul(&mask)
+}
+
+/// Samples `k` indices without replacement along `dim` using the Gumbel-top-k trick.
+///
+/// Gumbel noise is added to `logits` and the indices of the `k` largest perturbed values are
+/// returned, ordered by decreasing perturbed value. This is equivalent to drawing `k` items one
+/// after the other without replacement, each with probability proportional to the softmax of the
+/// remaining logits. The noise is derived from `seed`. The returned tensor has dtype `u32` and the
+/// same shape as `logits` except for `dim` which has size `k`.
+pub fn gumbel_top_k<D: candle::shape::Dim>(
+    logits: &Tensor,
+    k: usize,
+    dim: D,
+    seed: u64,
+) -> Result<Tensor> {
+    use rand::{Rng, SeedableRng};
+
+    let dim = dim.to_index(logits.shape(), "gumbel_top_k")?;
+    let n = logits.dim(dim)?;
+    if k > n {
+        candle::bail!("gumbel_top_k: k ({k}) is larger than the dimension size ({n})")
+    }
+    let last_dim = logits.rank() - 1;
+    let logits = logits.transpose(dim, last_dim)?;
+    let mut dims = logits.dims().to_vec();
+    let values = logits
+        .to_dtype(DType::F32)?
+        .flatten_all()?
+        .to_vec1::<f32>()?;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut indexes = Vec::with_capacity(values.len() / n.max(1) * k);
+    let mut perturbed = Vec::with_capacity(n);
+    for row in values.chunks(n.max(1)) {
+        perturbed.clear();
+        for (i, &v) in row.iter().enumerate() {
+            // gen::<f32>() is in [0, 1), use 1 - u to avoid taking the log of 0.
+            let u = 1. - rng.gen::<f32>();
+            perturbed.push((v - (-u.ln()).ln(), i as u32))
+        }
+        perturbed.sort_by(|a, b| b.0.total_cmp(&a.0));
+        indexes.extend(perturbed.iter().take(k).map(|&(_, i)| i))
+    }
+    dims[last_dim] = k;
+    Tensor::from_vec(indexes, dims, logits.device())?
+        .transpose(dim, last_dim)?
+        .contiguous()
+}
+
 #[derive(Debug)]
 pub struct Dropout {
     drop_p: f32,
@@ -79,6 +417,26 @@ impl Dropout {
     }
 }
 
+/// Drops whole channels in training mode, see [`dropout2d`].
+#[derive(Debug)]
+pub struct Dropout2d {
+    drop_p: f32,
+}
+
+impl Dropout2d {
+    pub fn new(drop_p: f32) -> Dropout2d {
+        Self { drop_p }
+    }
+
+    pub fn forward(&self, xs: &Tensor, train: bool) -> Result<Tensor> {
+        if train {
+            dropout2d(xs, self.drop_p)
+        } else {
+            Ok(xs.clone())
+        }
+    }
+}
+
 struct SoftmaxLastDim;
 
 impl candle::CustomOp1 for SoftmaxLastDim {
@@ -1,5 +1,6 @@
 //! Various optimization algorithms.
-use candle::{Result, Tensor, Var};
+use candle::{Result, Tensor, TensorId, Var};
+use std::collections::HashMap;
 
 /// The interface optimizers should implement.
 pub trait Optimizer: Sized {
@@ -28,44 +29,129 @@ pub trait Optimizer: Sized {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct ParamsSGD {
+    pub lr: f64,
+    pub momentum: f64,
+    pub dampening: f64,
+    pub nesterov: bool,
+    pub weight_decay: f64,
+    /// When set, the weight decay is applied directly to the parameters as in AdamW rather than
+    /// being added to the gradients as in the PyTorch implementation.
+    pub decoupled_weight_decay: bool,
+}
+
+impl Default for ParamsSGD {
+    fn default() -> Self {
+        Self {
+            lr: 0.01,
+            momentum: 0.,
+            dampening: 0.,
+            nesterov: false,
+            weight_decay: 0.,
+            decoupled_weight_decay: false,
+        }
+    }
+}
+
 /// Optimizer for Stochastic Gradient Descent.
 ///
-/// Contrary to the PyTorch implementation of SGD, this version does not support momentum.
+/// The momentum, dampening and nesterov parameters follow the PyTorch implementation of SGD.
+/// The momentum buffers are created from the gradients on the first step where a variable gets
+/// a gradient, so they live on the same device as the variable.
 #[derive(Debug)]
 pub struct SGD {
     vars: Vec<Var>,
-    learning_rate: f64,
+    params: ParamsSGD,
+    momentum_buffers: HashMap<TensorId, Tensor>,
 }
 
 impl Optimizer for SGD {
     type Config = f64;
 
     fn new(vars: Vec<Var>, learning_rate: f64) -> Result<Self> {
-        Ok(Self {
-            vars,
-            learning_rate,
-        })
+        let params = ParamsSGD {
+            lr: learning_rate,
+            ..ParamsSGD::default()
+        };
+        Self::new_with_params(vars, params)
     }
 
     fn learning_rate(&self) -> f64 {
-        self.learning_rate
+        self.params.lr
     }
 
     fn step(&mut self, grads: &candle::backprop::GradStore) -> Result<()> {
+        let ParamsSGD {
+            lr,
+            momentum,
+            dampening,
+            nesterov,
+            weight_decay,
+            decoupled_weight_decay,
+        } = self.params;
         for var in self.vars.iter() {
-            if let Some(grad) = grads.get(var) {
-                var.set(&var.sub(&(grad * self.learning_rate)?)?)?;
+            let grad = match grads.get(var) {
+                Some(grad) => grad,
+                None => continue,
+            };
+            // The gradients carry the graph of the forward pass, the update and the momentum
+            // buffers are detached from it so that the buffers do not keep all the previous
+            // graphs alive.
+            let theta = var.as_tensor().detach()?;
+            let mut grad = grad.detach()?;
+            if weight_decay != 0. && !decoupled_weight_decay {
+                grad = (grad + (&theta * weight_decay)?)?
             }
+            if momentum != 0. {
+                let buf = match self.momentum_buffers.get(&var.id()) {
+                    None => grad.clone(),
+                    Some(buf) => ((buf * momentum)? + (&grad * (1. - dampening))?)?,
+                };
+                grad = if nesterov {
+                    (grad + (&buf * momentum)?)?
+                } else {
+                    buf.clone()
+                };
+                self.momentum_buffers.insert(var.id(), buf);
+            }
+            let theta = if weight_decay != 0. && decoupled_weight_decay {
+                (theta * (1. - lr * weight_decay))?
+            } else {
+                theta
+            };
+            var.set(&theta.sub(&(grad * lr)?)?)?;
         }
         Ok(())
     }
 
     fn set_learning_rate(&mut self, lr: f64) {
-        self.learning_rate = lr
+        self.params.lr = lr
     }
 }
 
 impl SGD {
+    pub fn new_with_params(vars: Vec<Var>, params: ParamsSGD) -> Result<Self> {
+        if params.nesterov && (params.momentum <= 0. || params.dampening != 0.) {
+            candle::bail!("nesterov momentum requires a positive momentum and zero dampening")
+        }
+        Ok(Self {
+            vars,
+            params,
+            momentum_buffers: HashMap::new(),
+        })
+    }
+
+    pub fn params(&self) -> &ParamsSGD {
+        &self.params
+    }
+
+    /// The momentum buffer for a variable, this is `None` until the first step where the
+    /// variable has a gradient or when momentum is not used.
+    pub fn momentum_buffer(&self, var: &Var) -> Option<&Tensor> {
+        self.momentum_buffers.get(&var.id())
+    }
+
     pub fn into_inner(self) -> Vec<Var> {
         self.vars
     }
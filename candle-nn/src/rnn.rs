@@ -18,6 +18,8 @@ pub trait RNN {
     ///
     /// The input should have dimensions [batch_size, seq_len, features].
     /// The initial state is the result of applying zero_state.
+    /// The output has dimensions [batch_size, seq_len, hidden_dim] and contains the hidden state
+    /// after each step.
     fn seq(&self, input: &Tensor) -> Result<(Tensor, Self::State)> {
         let batch_dim = input.dim(0)?;
         let state = self.zero_state(batch_dim)?;
@@ -134,6 +136,40 @@ pub fn lstm(
     })
 }
 
+impl LSTM {
+    /// Applies a single LSTM step to `input` of shape `[batch_size, features]` using the hidden
+    /// and cell states `(h, c)`, both of shape `[batch_size, hidden_dim]`, returns the next
+    /// hidden and cell states.
+    ///
+    /// This is the same as [`RNN::step`] but operates on tensors rather than [`LSTMState`].
+    pub fn forward(&self, input: &Tensor, (h, c): (&Tensor, &Tensor)) -> Result<(Tensor, Tensor)> {
+        let state = LSTMState {
+            h: h.clone(),
+            c: c.clone(),
+        };
+        let state = self.step(input, &state)?;
+        Ok((state.h, state.c))
+    }
+
+    /// Applies the LSTM over the time dimension of `input` of shape
+    /// `[batch_size, seq_len, features]` starting from the hidden and cell states `(h, c)`.
+    ///
+    /// Returns the hidden states after each step, of shape `[batch_size, seq_len, hidden_dim]`,
+    /// together with the final hidden and cell states.
+    pub fn seq_forward(
+        &self,
+        input: &Tensor,
+        (h, c): (&Tensor, &Tensor),
+    ) -> Result<(Tensor, (Tensor, Tensor))> {
+        let state = LSTMState {
+            h: h.clone(),
+            c: c.clone(),
+        };
+        let (output, state) = self.seq_init(input, &state)?;
+        Ok((output, (state.h, state.c)))
+    }
+}
+
 impl RNN for LSTM {
     type State = LSTMState;
 
@@ -181,7 +217,7 @@ impl RNN for LSTM {
             state = self.step(&input, &state)?;
             output.push(state.h.clone());
         }
-        let output = Tensor::cat(&output, 1)?;
+        let output = Tensor::stack(&output, 1)?;
         Ok((output, state))
     }
 }
@@ -284,6 +320,27 @@ pub fn gru(
     })
 }
 
+impl GRU {
+    /// Applies a single GRU step to `input` of shape `[batch_size, features]` using the hidden
+    /// state `h` of shape `[batch_size, hidden_dim]`, returns the next hidden state.
+    ///
+    /// This is the same as [`RNN::step`] but operates on tensors rather than [`GRUState`].
+    pub fn forward(&self, input: &Tensor, h: &Tensor) -> Result<Tensor> {
+        let state = self.step(input, &GRUState { h: h.clone() })?;
+        Ok(state.h)
+    }
+
+    /// Applies the GRU over the time dimension of `input` of shape
+    /// `[batch_size, seq_len, features]` starting from the hidden state `h`.
+    ///
+    /// Returns the hidden states after each step, of shape `[batch_size, seq_len, hidden_dim]`,
+    /// together with the final hidden state.
+    pub fn seq_forward(&self, input: &Tensor, h: &Tensor) -> Result<(Tensor, Tensor)> {
+        let (output, state) = self.seq_init(input, &GRUState { h: h.clone() })?;
+        Ok((output, state.h))
+    }
+}
+
 impl RNN for GRU {
     type State = GRUState;
 
@@ -324,7 +381,7 @@ impl RNN for GRU {
             state = self.step(&input, &state)?;
             output.push(state.h.clone());
         }
-        let output = Tensor::cat(&output, 1)?;
+        let output = Tensor::stack(&output, 1)?;
         Ok((output, state))
     }
 }
@@ -0,0 +1,89 @@
+//! Rotary position embeddings (RoPE).
+//!
+//! Each pair of elements of the last dimension is rotated by the angle `pos * freq_i`, see
+//! [`RoFormer`]. Checkpoints differ in how the pairs are laid out, see [`RopeLayout`].
+//!
+//! [`RoFormer`]: https://arxiv.org/abs/2104.09864
+use candle::{DType, Device, Result, Tensor, D};
+
+/// How the elements rotated together are laid out in the last dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RopeLayout {
+    /// The "rotate half" layout used by GPT-NeoX and Llama: the last dimension is split in two
+    /// halves and the elements `i` and `i + head_dim / 2` are rotated together.
+    #[default]
+    NeoX,
+    /// The interleaved layout used by GPT-J: the adjacent elements `2i` and `2i + 1` are rotated
+    /// together.
+    GptJ,
+}
+
+/// Builds the f32 cosine and sine tables of shape `(seq_len, head_dim)` for the positions
+/// `0..seq_len`, the frequency for dimension `i < head_dim / 2` being `base^(-2i / head_dim)`.
+/// The second half of the last dimension repeats the first half.
+///
+/// See [`crate::ops::build_rope_cache`] to build the tables with a different dtype.
+pub fn cos_sin(
+    seq_len: usize,
+    head_dim: usize,
+    base: f64,
+    device: &Device,
+) -> Result<(Tensor, Tensor)> {
+    if head_dim % 2 != 0 {
+        candle::bail!("cos_sin: head_dim has to be even, got {head_dim}")
+    }
+    let inv_freq: Vec<_> = (0..head_dim / 2)
+        .map(|i| 1f32 / base.powf(2. * i as f64 / head_dim as f64) as f32)
+        .collect();
+    let inv_freq = Tensor::from_vec(inv_freq, (1, head_dim / 2), device)?;
+    let freqs = Tensor::arange(0u32, seq_len as u32, device)?
+        .to_dtype(DType::F32)?
+        .reshape((seq_len, 1))?
+        .matmul(&inv_freq)?;
+    let freqs = Tensor::cat(&[&freqs, &freqs], D::Minus1)?;
+    Ok((freqs.cos()?, freqs.sin()?))
+}
+
+/// Applies rotary embeddings to `x` of shape `(batch, heads, seq_len, head_dim)`.
+///
+/// `cos` and `sin` have shape `(seq_len, head_dim)` and contain the tables for the positions of
+/// the elements of `x`, e.g. as returned by [`cos_sin`] or a slice of them. The same tables are
+/// used for both layouts, the pair using frequency `i` is rotated by the values at column `i`.
+pub fn apply_rotary_emb(
+    x: &Tensor,
+    cos: &Tensor,
+    sin: &Tensor,
+    layout: RopeLayout,
+) -> Result<Tensor> {
+    let (b_sz, n_heads, seq_len, head_dim) = x.dims4()?;
+    if head_dim % 2 != 0 {
+        candle::bail!("apply_rotary_emb: the last dim has to be even, got {head_dim}")
+    }
+    for t in [cos, sin] {
+        if t.dims() != [seq_len, head_dim] {
+            candle::bail!(
+                "apply_rotary_emb: expected cos/sin of shape ({seq_len}, {head_dim}), got {:?}",
+                t.shape()
+            )
+        }
+    }
+    match layout {
+        RopeLayout::NeoX => {
+            let x1 = x.narrow(D::Minus1, 0, head_dim / 2)?;
+            let x2 = x.narrow(D::Minus1, head_dim / 2, head_dim / 2)?;
+            let rotate_x = Tensor::cat(&[&x2.neg()?, &x1], D::Minus1)?;
+            x.broadcast_mul(cos)? + rotate_x.broadcast_mul(sin)?
+        }
+        RopeLayout::GptJ => {
+            let half = head_dim / 2;
+            let cos = cos.narrow(D::Minus1, 0, half)?;
+            let sin = sin.narrow(D::Minus1, 0, half)?;
+            let x = x.reshape((b_sz, n_heads, seq_len, half, 2))?;
+            let x1 = x.narrow(D::Minus1, 0, 1)?.squeeze(D::Minus1)?;
+            let x2 = x.narrow(D::Minus1, 1, 1)?.squeeze(D::Minus1)?;
+            let y1 = (x1.broadcast_mul(&cos)? - x2.broadcast_mul(&sin)?)?;
+            let y2 = (x2.broadcast_mul(&cos)? + x1.broadcast_mul(&sin)?)?;
+            Tensor::stack(&[&y1, &y2], D::Minus1)?.reshape((b_sz, n_heads, seq_len, head_dim))
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Containers for layers.
+//!
+//! A [`Sequential`] applies its layers one after the other, e.g. for a small MLP:
+//!
+//! ```rust
+//! use candle::{DType, Device, Tensor};
+//! use candle_nn::{Module, VarBuilder};
+//! # fn main() -> candle::Result<()> {
+//! let vb = VarBuilder::zeros(DType::F32, &Device::Cpu);
+//! let mlp = candle_nn::seq()
+//!     .add(candle_nn::linear(4, 8, vb.pp("fc1"))?)
+//!     .add_fn(|xs| xs.relu())
+//!     .add(candle_nn::linear(8, 2, vb.pp("fc2"))?);
+//! let ys = mlp.forward(&Tensor::zeros((3, 4), DType::F32, &Device::Cpu)?)?;
+//! assert_eq!(ys.dims(), &[3, 2]);
+//! # Ok(()) }
+//! ```
+//!
+//! A [`ModuleList`] only stores the layers and gives indexed access to them, the caller is in
+//! charge of wiring them, e.g. with residual connections.
+use candle::{Module, Result, Tensor};
+
+/// A sequence of layers, the output of each layer is the input of the next one.
+#[derive(Debug, Default)]
+pub struct Sequential {
+    layers: Vec<Box<dyn Module>>,
+}
+
+/// Creates an empty [`Sequential`].
+pub fn seq() -> Sequential {
+    Sequential::default()
+}
+
+impl Sequential {
+    /// Appends a layer.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<M: Module + 'static>(mut self, layer: M) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Appends a layer defined by a closure, e.g. an activation.
+    pub fn add_fn<F>(self, f: F) -> Self
+    where
+        F: 'static + Fn(&Tensor) -> Result<Tensor> + Send,
+    {
+        self.add(crate::func(f))
+    }
+
+    /// The number of layers.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Applies the layers and returns the output of each of them.
+    pub fn forward_all(&self, xs: &Tensor) -> Result<Vec<Tensor>> {
+        let mut outputs = Vec::with_capacity(self.layers.len());
+        let mut xs = xs.clone();
+        for layer in self.layers.iter() {
+            xs = layer.forward(&xs)?;
+            outputs.push(xs.clone())
+        }
+        Ok(outputs)
+    }
+}
+
+impl Module for Sequential {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let mut xs = xs.clone();
+        for layer in self.layers.iter() {
+            xs = layer.forward(&xs)?
+        }
+        Ok(xs)
+    }
+
+    fn set_training(&mut self, training: bool) {
+        for layer in self.layers.iter_mut() {
+            layer.set_training(training)
+        }
+    }
+}
+
+/// A list of layers that can be accessed by index.
+#[derive(Debug, Default)]
+pub struct ModuleList {
+    modules: Vec<Box<dyn Module>>,
+}
+
+impl ModuleList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<M: Module + 'static>(&mut self, module: M) {
+        self.modules.push(Box::new(module))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&dyn Module> {
+        self.modules.get(index).map(|m| m.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Module> {
+        self.modules.iter().map(|m| m.as_ref())
+    }
+
+    /// Changes all the modules to use training mode vs eval mode.
+    pub fn set_training(&mut self, training: bool) {
+        for module in self.modules.iter_mut() {
+            module.set_training(training)
+        }
+    }
+}
+
+impl std::ops::Index<usize> for ModuleList {
+    type Output = dyn Module;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.modules[index].as_ref()
+    }
+}
@@ -0,0 +1,49 @@
+//! Upsampling Layer.
+use candle::{Result, Tensor};
+
+/// The interpolation modes supported by the [`Upsample`] layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsampleMode {
+    Nearest,
+    Bilinear { align_corners: bool },
+    Bicubic { align_corners: bool },
+}
+
+/// Resizes `(batch, channels, h, w)` inputs to a fixed `(target_h, target_w)` spatial size.
+///
+/// ```rust
+/// use candle::{Tensor, Device, Module};
+/// use candle_nn::{Upsample, UpsampleMode};
+/// let up = Upsample::new(4, 4, UpsampleMode::Bilinear { align_corners: false });
+/// let xs = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], &Device::Cpu)?;
+/// let ys = up.forward(&xs)?;
+/// assert_eq!(ys.dims(), &[1, 1, 4, 4]);
+/// # Ok::<(), candle::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Upsample {
+    target_h: usize,
+    target_w: usize,
+    mode: UpsampleMode,
+}
+
+impl Upsample {
+    pub fn new(target_h: usize, target_w: usize, mode: UpsampleMode) -> Self {
+        Self {
+            target_h,
+            target_w,
+            mode,
+        }
+    }
+}
+
+impl crate::Module for Upsample {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let (h, w) = (self.target_h, self.target_w);
+        match self.mode {
+            UpsampleMode::Nearest => xs.upsample_nearest2d(h, w),
+            UpsampleMode::Bilinear { align_corners } => xs.upsample_bilinear2d(h, w, align_corners),
+            UpsampleMode::Bicubic { align_corners } => xs.upsample_bicubic2d(h, w, align_corners),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Training utilities.
+use crate::VarMap;
+use candle::backprop::GradStore;
+use candle::{DType, Device, Result, Tensor, Var};
+use std::collections::HashMap;
+
+/// Rescales the gradients of `vars` so that their global L2 norm, computed as if all the
+/// gradients were concatenated in a single vector, is at most `max_norm`. This is similar to
+/// PyTorch `clip_grad_norm_`.
+///
+/// The norm is accumulated on the gradients device using `f32` values whatever the dtype of
+/// the gradients. The returned value is the norm before clipping. Variables without a gradient
+/// in `grads` are skipped.
+pub fn clip_grad_norm(grads: &mut GradStore, vars: &[Var], max_norm: f64) -> Result<f64> {
+    if max_norm.is_nan() || max_norm < 0. {
+        candle::bail!("clip_grad_norm: max_norm should be non-negative, got {max_norm}")
+    }
+    let mut sum_sq: Option<Tensor> = None;
+    for var in vars.iter() {
+        if let Some(grad) = grads.get(var) {
+            let sq = grad.to_dtype(DType::F32)?.sqr()?.sum_all()?;
+            sum_sq = Some(match sum_sq {
+                None => sq,
+                Some(sum_sq) => (sum_sq + sq)?,
+            })
+        }
+    }
+    let total_norm = match sum_sq {
+        None => return Ok(0.),
+        Some(sum_sq) => sum_sq.sqrt()?.to_scalar::<f32>()? as f64,
+    };
+    let clip_coef = max_norm / (total_norm + 1e-6);
+    if clip_coef < 1. {
+        for var in vars.iter() {
+            if let Some(grad) = grads.get_mut(var) {
+                *grad = (&*grad * clip_coef)?
+            }
+        }
+    }
+    Ok(total_norm)
+}
+
+/// Clamps each element of the gradients of `vars` to the `[-clip_value, clip_value]` range.
+/// This is similar to PyTorch `clip_grad_value_`.
+pub fn clip_grad_value(grads: &mut GradStore, vars: &[Var], clip_value: f64) -> Result<()> {
+    if clip_value.is_nan() || clip_value < 0. {
+        candle::bail!("clip_grad_value: clip_value should be non-negative, got {clip_value}")
+    }
+    for var in vars.iter() {
+        if let Some(grad) = grads.get_mut(var) {
+            *grad = grad
+                .maximum_scalar(-clip_value)?
+                .minimum_scalar(clip_value)?
+        }
+    }
+    Ok(())
+}
+
+/// Exponential moving average of some variables, this is typically used when training diffusion
+/// models where the averaged weights are used for evaluation.
+///
+/// The shadow weights are stored on the same devices as the variables and are updated without
+/// being tracked in the computation graph.
+///
+/// ```rust
+/// use candle::{DType, Device, Module, Tensor};
+/// use candle_nn::{utils::Ema, VarBuilder, VarMap};
+/// # fn main() -> candle::Result<()> {
+/// let varmap = VarMap::new();
+/// let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+/// let model = candle_nn::linear(4, 2, vb.pp("lin"))?;
+/// let mut ema = Ema::new(&varmap)?;
+/// // After each optimizer step.
+/// ema.step(0.999)?;
+/// // Evaluate the model using the averaged weights.
+/// ema.copy_to(&varmap)?;
+/// let _ys = model.forward(&Tensor::zeros((1, 4), DType::F32, &Device::Cpu)?)?;
+/// ema.restore()?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Ema {
+    vars: Vec<(String, Var)>,
+    shadows: Vec<Var>,
+    backup: Vec<(Var, Tensor)>,
+}
+
+impl Ema {
+    /// Tracks the trainable variables of a `VarMap`, buffers are excluded. The shadow weights
+    /// are initialized with the current values of the variables.
+    pub fn new(varmap: &VarMap) -> Result<Self> {
+        let trainable: std::collections::HashSet<_> =
+            varmap.all_vars().iter().map(|v| v.id()).collect();
+        let mut vars: Vec<_> = varmap
+            .data()
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, var)| trainable.contains(&var.id()))
+            .map(|(name, var)| (name.clone(), var.clone()))
+            .collect();
+        vars.sort_by(|(n1, _), (n2, _)| n1.cmp(n2));
+        Self::from_named_vars(vars)
+    }
+
+    /// Tracks a list of variables, the variables are named by their index in the list when
+    /// saving the shadow weights.
+    pub fn from_vars(vars: &[Var]) -> Result<Self> {
+        let vars = vars
+            .iter()
+            .enumerate()
+            .map(|(i, var)| (i.to_string(), var.clone()))
+            .collect();
+        Self::from_named_vars(vars)
+    }
+
+    fn from_named_vars(vars: Vec<(String, Var)>) -> Result<Self> {
+        let shadows = vars
+            .iter()
+            .map(|(_, var)| Var::from_tensor(&var.as_tensor().copy()?))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            vars,
+            shadows,
+            backup: vec![],
+        })
+    }
+
+    /// Updates the shadow weights with `shadow = decay * shadow + (1 - decay) * var`.
+    pub fn step(&mut self, decay: f64) -> Result<()> {
+        if !(0. ..=1.).contains(&decay) {
+            candle::bail!("ema decay should be between 0 and 1, got {decay}")
+        }
+        for ((_, var), shadow) in self.vars.iter().zip(self.shadows.iter()) {
+            let var = var.as_tensor().detach()?;
+            let next = ((shadow.as_tensor() * decay)? + (var * (1. - decay))?)?;
+            shadow.set(&next)?
+        }
+        Ok(())
+    }
+
+    /// The shadow weight for the variable with the given name.
+    pub fn shadow(&self, name: &str) -> Option<&Tensor> {
+        self.vars
+            .iter()
+            .position(|(n, _)| n == name)
+            .map(|i| self.shadows[i].as_tensor())
+    }
+
+    /// Writes the shadow weights into the variables of `varmap` that have the same names, the
+    /// previous values are kept so that they can be put back with [`Ema::restore`].
+    pub fn copy_to(&mut self, varmap: &VarMap) -> Result<()> {
+        let data = varmap.data().lock().unwrap();
+        let dsts = self
+            .vars
+            .iter()
+            .map(|(name, _)| match data.get(name) {
+                Some(var) => Ok(var.clone()),
+                None => candle::bail!("ema: cannot find {name} in the varmap"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.copy_to_(dsts)
+    }
+
+    /// Writes the shadow weights into the tracked variables, the previous values are kept so
+    /// that they can be put back with [`Ema::restore`].
+    pub fn copy_to_vars(&mut self) -> Result<()> {
+        let dsts = self.vars.iter().map(|(_, var)| var.clone()).collect();
+        self.copy_to_(dsts)
+    }
+
+    fn copy_to_(&mut self, dsts: Vec<Var>) -> Result<()> {
+        if !self.backup.is_empty() {
+            candle::bail!("ema: the shadow weights have already been copied, call restore first")
+        }
+        let mut backup = Vec::with_capacity(dsts.len());
+        for (dst, shadow) in dsts.into_iter().zip(self.shadows.iter()) {
+            let previous = dst.as_tensor().copy()?.detach()?;
+            dst.set(shadow.as_tensor())?;
+            backup.push((dst, previous))
+        }
+        self.backup = backup;
+        Ok(())
+    }
+
+    /// Puts back the values that were replaced by the last call to [`Ema::copy_to`] or
+    /// [`Ema::copy_to_vars`], this does nothing if there is no such call.
+    pub fn restore(&mut self) -> Result<()> {
+        for (dst, previous) in self.backup.drain(..) {
+            dst.set(&previous)?
+        }
+        Ok(())
+    }
+
+    /// Saves the shadow weights in the safetensors format.
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
+        let data: HashMap<_, _> = self
+            .vars
+            .iter()
+            .zip(self.shadows.iter())
+            .map(|((name, _), shadow)| (name.clone(), shadow.as_tensor().clone()))
+            .collect();
+        candle::safetensors::save(&data, path)
+    }
+
+    /// Loads the shadow weights from a safetensors file written by [`Ema::save`].
+    pub fn load<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut data = candle::safetensors::load(path, &Device::Cpu)?;
+        for ((name, _), shadow) in self.vars.iter().zip(self.shadows.iter()) {
+            match data.remove(name) {
+                Some(tensor) => shadow.set(&tensor.to_device(shadow.device())?)?,
+                None => candle::bail!("cannot find tensor for {name} in {path:?}"),
+            }
+        }
+        Ok(())
+    }
+}
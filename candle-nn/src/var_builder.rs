@@ -56,6 +56,20 @@ pub trait Backend: Send + Sync {
         dev: &Device,
     ) -> Result<Tensor>;
 
+    /// Retrieve a non-trainable buffer, e.g. the running statistics of a batch norm layer.
+    ///
+    /// By default buffers are retrieved in the same way as other tensors.
+    fn get_buffer(
+        &self,
+        s: Shape,
+        name: &str,
+        h: Self::Hints,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        self.get(s, name, h, dtype, dev)
+    }
+
     fn contains_tensor(&self, name: &str) -> bool;
 }
 
@@ -70,6 +84,18 @@ pub trait SimpleBackend: Send + Sync {
         dev: &Device,
     ) -> Result<Tensor>;
 
+    /// Retrieve a non-trainable buffer, by default this is the same as `get`.
+    fn get_buffer(
+        &self,
+        s: Shape,
+        name: &str,
+        h: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        self.get(s, name, h, dtype, dev)
+    }
+
     fn contains_tensor(&self, name: &str) -> bool;
 }
 
@@ -86,6 +112,17 @@ impl<'a> Backend for Box<dyn SimpleBackend + 'a> {
         self.as_ref().get(s, name, h, dtype, dev)
     }
 
+    fn get_buffer(
+        &self,
+        s: Shape,
+        name: &str,
+        h: Self::Hints,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        self.as_ref().get_buffer(s, name, h, dtype, dev)
+    }
+
     fn contains_tensor(&self, name: &str) -> bool {
         self.as_ref().contains_tensor(name)
     }
@@ -188,6 +225,22 @@ impl<'a, B: Backend> VarBuilderArgs<'a, B> {
     pub fn get<S: Into<Shape>>(&self, s: S, name: &str) -> Result<Tensor> {
         self.get_with_hints(s, name, Default::default())
     }
+
+    /// Retrieve the non-trainable buffer associated with the given name at the current path.
+    ///
+    /// Buffers are stored alongside the other tensors, e.g. they are part of the checkpoints, but
+    /// a `VarMap` does not return them in `all_vars` so that optimizers leave them untouched.
+    pub fn get_buffer_with_hints<S: Into<Shape>>(
+        &self,
+        s: S,
+        name: &str,
+        hints: B::Hints,
+    ) -> Result<Tensor> {
+        let path = self.path(name);
+        self.data
+            .backend
+            .get_buffer(s.into(), &path, hints, self.data.dtype, &self.data.device)
+    }
 }
 
 struct Zeros;
@@ -247,6 +300,17 @@ impl SimpleBackend for VarMap {
         VarMap::get(self, s, name, h, dtype, dev)
     }
 
+    fn get_buffer(
+        &self,
+        s: Shape,
+        name: &str,
+        h: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        VarMap::get_buffer(self, s, name, h, dtype, dev)
+    }
+
     fn contains_tensor(&self, name: &str) -> bool {
         self.data().lock().unwrap().contains_key(name)
     }
@@ -292,6 +356,32 @@ impl<'a> SimpleBackend for SafeTensorWithRouting<'a> {
     }
 }
 
+impl SimpleBackend for candle::safetensors::MmapedSafetensors {
+    fn get(
+        &self,
+        s: Shape,
+        path: &str,
+        _: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        let tensor = self.load(path, dev)?.to_dtype(dtype)?;
+        if tensor.shape() != &s {
+            Err(candle::Error::UnexpectedShape {
+                msg: format!("shape mismatch for {path}"),
+                expected: s,
+                got: tensor.shape().clone(),
+            }
+            .bt())?
+        }
+        Ok(tensor)
+    }
+
+    fn contains_tensor(&self, name: &str) -> bool {
+        self.contains_tensor(name)
+    }
+}
+
 impl SimpleBackend for candle::npy::NpzTensors {
     fn get(
         &self,
@@ -325,6 +415,39 @@ impl SimpleBackend for candle::npy::NpzTensors {
     }
 }
 
+impl SimpleBackend for candle::pickle::PthTensors {
+    fn get(
+        &self,
+        s: Shape,
+        path: &str,
+        _: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        let tensor = match self.get(path)? {
+            None => Err(Error::CannotFindTensor {
+                path: path.to_string(),
+            }
+            .bt())?,
+            Some(tensor) => tensor,
+        };
+        let tensor = tensor.to_device(dev)?.to_dtype(dtype)?;
+        if tensor.shape() != &s {
+            Err(candle::Error::UnexpectedShape {
+                msg: format!("shape mismatch for {path}"),
+                expected: s,
+                got: tensor.shape().clone(),
+            }
+            .bt())?
+        }
+        Ok(tensor)
+    }
+
+    fn contains_tensor(&self, name: &str) -> bool {
+        self.tensor_infos().contains_key(name)
+    }
+}
+
 impl<'a> VarBuilder<'a> {
     fn new(backend: Box<dyn SimpleBackend + 'a>, dtype: DType, device: Device) -> Self {
         let data = TensorData {
@@ -376,11 +499,104 @@ impl<'a> VarBuilder<'a> {
         Self::new(Box::new(tensors), dtype, dev.clone())
     }
 
+    /// Initializes a `VarBuilder` that retrieves tensors stored in a collection of memory mapped
+    /// safetensors files, e.g. the shards of a large checkpoint. Contrary to `from_safetensors`,
+    /// the files do not have to be read upfront: a tensor is only read from the mapped files
+    /// when it is requested, and it is copied directly to the target device.
+    ///
+    /// # Safety
+    ///
+    /// The unsafe is inherited from [`candle::safetensors::MmapedFile::new`], the files should
+    /// not be modified while the `VarBuilder` is alive.
+    pub unsafe fn from_mmaped_safetensors<P: AsRef<std::path::Path>>(
+        paths: &[P],
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Self> {
+        let tensors = candle::safetensors::MmapedSafetensors::multi(paths)?;
+        Ok(Self::new(Box::new(tensors), dtype, dev.clone()))
+    }
+
+    /// Returns a `VarBuilder` that translates the tensor names before looking them up in the
+    /// current backend. The function is applied to the full path of the tensors, i.e. including
+    /// the prefix, so that checkpoints using a different naming scheme can be loaded without
+    /// modifying the model code. This also applies to `contains_tensor`.
+    ///
+    /// ```rust
+    /// use candle::{DType, Device, Tensor};
+    /// use candle_nn::VarBuilder;
+    /// # fn main() -> candle::Result<()> {
+    /// let w = Tensor::ones((2, 2), DType::F32, &Device::Cpu)?;
+    /// let ts = [("transformer.h.0.attn.c_attn.weight".to_string(), w)];
+    /// let vb = VarBuilder::from_tensors(ts.into_iter().collect(), DType::F32, &Device::Cpu);
+    /// let vb = vb.rename_f(|name| {
+    ///     name.replace("layers.", "transformer.h.")
+    ///         .replace(".qkv.", ".attn.c_attn.")
+    /// });
+    /// let w = vb.pp("layers").pp(0).pp("qkv").get((2, 2), "weight")?;
+    /// # Ok(()) }
+    /// ```
+    pub fn rename_f<F: Fn(&str) -> String + Sync + Send + 'a>(self, f: F) -> Self {
+        let path = self.path.clone();
+        let dtype = self.dtype();
+        let device = self.device().clone();
+        let rename = Rename {
+            inner: self,
+            renamer: f,
+        };
+        let mut vb = Self::new(Box::new(rename), dtype, device);
+        vb.path = path;
+        vb
+    }
+
     /// Initializes a `VarBuilder` that retrieves tensors stored in a numpy npz file.
     pub fn from_npz<P: AsRef<std::path::Path>>(p: P, dtype: DType, dev: &Device) -> Result<Self> {
         let npz = candle::npy::NpzTensors::new(p)?;
         Ok(Self::new(Box::new(npz), dtype, dev.clone()))
     }
+
+    /// Initializes a `VarBuilder` that retrieves tensors stored in a PyTorch checkpoint, e.g. a
+    /// `pytorch_model.bin` file, tensors are only read when requested.
+    pub fn from_pth<P: AsRef<std::path::Path>>(p: P, dtype: DType, dev: &Device) -> Result<Self> {
+        let pth = candle::pickle::PthTensors::new(p)?;
+        Ok(Self::new(Box::new(pth), dtype, dev.clone()))
+    }
+}
+
+struct Rename<'a, F: Fn(&str) -> String> {
+    inner: VarBuilder<'a>,
+    renamer: F,
+}
+
+impl<'a, F: Fn(&str) -> String + Sync + Send> SimpleBackend for Rename<'a, F> {
+    fn get(
+        &self,
+        s: Shape,
+        name: &str,
+        h: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        let name = (self.renamer)(name);
+        self.inner.data.backend.get(s, &name, h, dtype, dev)
+    }
+
+    fn get_buffer(
+        &self,
+        s: Shape,
+        name: &str,
+        h: crate::Init,
+        dtype: DType,
+        dev: &Device,
+    ) -> Result<Tensor> {
+        let name = (self.renamer)(name);
+        self.inner.data.backend.get_buffer(s, &name, h, dtype, dev)
+    }
+
+    fn contains_tensor(&self, name: &str) -> bool {
+        let name = (self.renamer)(name);
+        self.inner.data.backend.contains_tensor(&name)
+    }
 }
 
 pub struct ShardedSafeTensors<'a>(SafeTensorWithRouting<'a>);
@@ -1,14 +1,27 @@
 use candle::{safetensors::Load, DType, Device, Result, Shape, Tensor, Var};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
+/// The keys that did not match when loading a `VarMap` with [`VarMap::load_non_strict`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Variables of the map for which the file has no tensor, these are left unchanged.
+    pub missing: Vec<String>,
+    /// Tensors from the file that do not correspond to any variable, these are ignored.
+    pub unexpected: Vec<String>,
+}
+
 /// A `VarMap` is a store that holds named variables. Variables can be retrieved from the stores
 /// and new variables can be added by providing some initialization config in case they are
 /// missing.
 /// `VarMap` structures can be serialized in the safetensors format.
+///
+/// Some variables can be registered as non-trainable buffers, these are serialized with the
+/// other variables but are not returned by `all_vars`.
 #[derive(Clone)]
 pub struct VarMap {
     data: Arc<Mutex<HashMap<String, Var>>>,
+    buffers: Arc<Mutex<HashSet<String>>>,
 }
 
 impl VarMap {
@@ -16,14 +29,30 @@ impl VarMap {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let data = Arc::new(Mutex::new(HashMap::new()));
-        Self { data }
+        let buffers = Arc::new(Mutex::new(HashSet::new()));
+        Self { data, buffers }
     }
 
-    /// Retrieve all the variables currently stored in the map.
+    /// Retrieve all the trainable variables currently stored in the map, buffers are excluded.
     pub fn all_vars(&self) -> Vec<Var> {
         let tensor_data = self.data.lock().unwrap();
-        #[allow(clippy::map_clone)]
-        tensor_data.values().map(|c| c.clone()).collect::<Vec<_>>()
+        let buffers = self.buffers.lock().unwrap();
+        tensor_data
+            .iter()
+            .filter(|(name, _)| !buffers.contains(*name))
+            .map(|(_, var)| var.clone())
+            .collect::<Vec<_>>()
+    }
+
+    /// Retrieve all the buffers currently stored in the map.
+    pub fn all_buffers(&self) -> Vec<Var> {
+        let tensor_data = self.data.lock().unwrap();
+        let buffers = self.buffers.lock().unwrap();
+        tensor_data
+            .iter()
+            .filter(|(name, _)| buffers.contains(*name))
+            .map(|(_, var)| var.clone())
+            .collect::<Vec<_>>()
     }
 
     /// Save the map in the safetensors format.
@@ -35,26 +64,66 @@ impl VarMap {
     }
 
     /// Load some values from a safetensors file and modify the existing variables to have these
-    /// values.
+    /// values. The values are converted to the dtype and device of the variables, an error is
+    /// returned if a variable is missing from the file or if the shapes do not match.
     ///
     /// Note that values for variables that are currently not in the map are not kept.
     pub fn load<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
         let path = path.as_ref();
+        let report = self.load_(path, true)?;
+        if let Some(name) = report.missing.first() {
+            candle::bail!("cannot find tensor for {name} in {path:?}")
+        }
+        Ok(())
+    }
+
+    /// Similar to [`VarMap::load`] but variables missing from the file keep their current value.
+    /// The returned report lists these variables as well as the tensors from the file that do
+    /// not match any variable. Shape mismatches still result in an error.
+    pub fn load_non_strict<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<LoadReport> {
+        self.load_(path.as_ref(), false)
+    }
+
+    fn load_(&mut self, path: &std::path::Path, stop_on_missing: bool) -> Result<LoadReport> {
         let data = unsafe { candle::safetensors::MmapedFile::new(path)? };
         let data = data.deserialize()?;
-        let mut tensor_data = self.data.lock().unwrap();
-        for (name, var) in tensor_data.iter_mut() {
+        let tensor_data = self.data.lock().unwrap();
+        let mut missing = vec![];
+        for (name, var) in tensor_data.iter() {
             match data.tensor(name) {
                 Ok(data) => {
-                    let data: Tensor = data.load(var.device())?;
+                    let data: Tensor = data.load(var.device())?.to_dtype(var.dtype())?;
+                    if data.shape() != var.shape() {
+                        candle::bail!(
+                            "shape mismatch for {name} in {path:?}: expected {:?}, got {:?}",
+                            var.shape(),
+                            data.shape()
+                        )
+                    }
                     if let Err(err) = var.set(&data) {
                         candle::bail!("error setting {name} using data from {path:?}: {err}",)
                     }
                 }
-                Err(_) => candle::bail!("cannot find tensor for {name}"),
+                Err(_) => {
+                    missing.push(name.to_string());
+                    if stop_on_missing {
+                        break;
+                    }
+                }
             }
         }
-        Ok(())
+        let mut unexpected: Vec<_> = data
+            .names()
+            .into_iter()
+            .filter(|name| !tensor_data.contains_key(*name))
+            .cloned()
+            .collect();
+        missing.sort();
+        unexpected.sort();
+        Ok(LoadReport {
+            missing,
+            unexpected,
+        })
     }
 
     /// Retrieve or add a new variable.
@@ -81,6 +150,20 @@ impl VarMap {
         Ok(tensor)
     }
 
+    /// Retrieve or add a new non-trainable buffer.
+    pub fn get_buffer<S: Into<Shape>>(
+        &self,
+        shape: S,
+        path: &str,
+        init: crate::Init,
+        dtype: DType,
+        device: &Device,
+    ) -> Result<Tensor> {
+        let tensor = self.get(shape, path, init, dtype, device)?;
+        self.buffers.lock().unwrap().insert(path.to_string());
+        Ok(tensor)
+    }
+
     pub fn data(&self) -> &Mutex<HashMap<String, Var>> {
         &self.data
     }
@@ -0,0 +1,178 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{DType, Device, IndexOp, Tensor, D};
+use candle_nn::{
+    KvCache, Linear, Module, MultiHeadAttention, MultiHeadAttentionConfig, VarBuilder, VarMap,
+};
+
+fn max_diff(a: &Tensor, b: &Tensor) -> Result<f32> {
+    let diff = (a - b)?.abs()?.flatten_all()?.max(0)?;
+    Ok(diff.to_scalar::<f32>()?)
+}
+
+// Attention computed head by head, without any masking.
+fn reference(
+    xs: &Tensor,
+    proj: &[Tensor; 4],
+    biases: &[Tensor; 4],
+    num_heads: usize,
+) -> Result<Tensor> {
+    let (_, _, embed_dim) = xs.dims3()?;
+    let head_dim = embed_dim / num_heads;
+    let lin =
+        |xs: &Tensor, i: usize| Linear::new(proj[i].clone(), Some(biases[i].clone())).forward(xs);
+    let (q, k, v) = (lin(xs, 0)?, lin(xs, 1)?, lin(xs, 2)?);
+    let mut heads = vec![];
+    for h in 0..num_heads {
+        let q = q.narrow(2, h * head_dim, head_dim)?;
+        let k = k.narrow(2, h * head_dim, head_dim)?;
+        let v = v.narrow(2, h * head_dim, head_dim)?;
+        let att = (q.matmul(&k.t()?)? / (head_dim as f64).sqrt())?;
+        let att = candle_nn::ops::softmax(&att, D::Minus1)?;
+        heads.push(att.matmul(&v)?)
+    }
+    Ok(lin(&Tensor::cat(&heads, 2)?, 3)?)
+}
+
+#[test]
+fn multi_head_attention() -> Result<()> {
+    let device = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let mha = MultiHeadAttention::new(8, 2, vb.pp("mha"))?;
+    let get = |name: &str| -> Result<Tensor> {
+        Ok(varmap.data().lock().unwrap()[&format!("mha.{name}")]
+            .as_tensor()
+            .clone())
+    };
+    let names = ["q_proj", "k_proj", "v_proj", "out_proj"];
+    let proj = names.map(|n| get(&format!("{n}.weight")).unwrap());
+    let biases = names.map(|n| get(&format!("{n}.bias")).unwrap());
+
+    let xs = Tensor::randn(0f32, 1., (2, 5, 8), device)?;
+    let (ys, weights) = mha.forward_with_weights(&xs, &xs, &xs, None, None)?;
+    assert_eq!(ys.dims(), &[2, 5, 8]);
+    assert_eq!(weights.dims(), &[2, 2, 5, 5]);
+    let sums = weights.sum(D::Minus1)?.flatten_all()?.to_vec1::<f32>()?;
+    assert!(sums.iter().all(|s| (s - 1.).abs() < 1e-5));
+    assert!(max_diff(&ys, &reference(&xs, &proj, &biases, 2)?)? < 1e-5);
+    assert!(max_diff(&ys, &Module::forward(&mha, &xs)?)? < 1e-6);
+
+    // Boolean and additive causal masks give the same result.
+    let bool_mask: Vec<u8> = (0..5)
+        .flat_map(|i| (0..5).map(move |j| u8::from(j > i)))
+        .collect();
+    let bool_mask = Tensor::from_vec(bool_mask, (5, 5), device)?;
+    let add_mask = bool_mask
+        .to_dtype(DType::F32)?
+        .affine(-1e9, 0.)?
+        .unsqueeze(0)?;
+    let (ys_bool, weights) = mha.forward_with_weights(&xs, &xs, &xs, Some(&bool_mask), None)?;
+    let ys_add = mha.forward(&xs, &xs, &xs, Some(&add_mask), None)?;
+    assert!(max_diff(&ys_bool, &ys_add)? < 1e-5);
+    assert_eq!(
+        weights.i((0, 1, 0))?.to_vec1::<f32>()?[1..],
+        [0f32, 0., 0., 0.]
+    );
+
+    // Incremental decoding with a cache matches the masked forward on the full sequence.
+    let mut cache = KvCache::new();
+    let mut steps = vec![];
+    let prefix = xs.narrow(1, 0, 3)?;
+    steps.push(mha.forward(
+        &prefix,
+        &prefix,
+        &prefix,
+        Some(&bool_mask.i((..3, ..3))?),
+        Some(&mut cache),
+    )?);
+    for pos in 3..5 {
+        let x = xs.narrow(1, pos, 1)?;
+        steps.push(mha.forward(&x, &x, &x, None, Some(&mut cache))?);
+        assert_eq!(cache.seq_len(), pos + 1);
+    }
+    assert_eq!(cache.k().unwrap().dims(), &[2, 2, 5, 4]);
+    let incremental = Tensor::cat(&steps, 1)?;
+    assert!(max_diff(&incremental, &ys_bool)? < 1e-5);
+    cache.reset();
+    assert_eq!(cache.seq_len(), 0);
+    Ok(())
+}
+
+#[test]
+fn multi_head_attention_fused() -> Result<()> {
+    let device = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let config = MultiHeadAttentionConfig {
+        fused_qkv: true,
+        bias: false,
+    };
+    let mha = MultiHeadAttention::new_with_config(6, 3, config, vb.pp("mha"))?;
+    let qkv = varmap.data().lock().unwrap()["mha.qkv_proj.weight"]
+        .as_tensor()
+        .clone();
+    assert_eq!(qkv.dims(), &[18, 6]);
+    assert!(!varmap
+        .data()
+        .lock()
+        .unwrap()
+        .contains_key("mha.out_proj.bias"));
+
+    // The fused projection is applied once for self-attention and sliced otherwise.
+    let xs = Tensor::randn(0f32, 1., (1, 4, 6), device)?;
+    let ys = mha.forward(&xs, &xs, &xs, None, None)?;
+    let xs2 = xs.copy()?;
+    assert!(max_diff(&ys, &mha.forward(&xs, &xs2, &xs2, None, None)?)? < 1e-5);
+
+    // Cross-attention with a different number of keys.
+    let memory = Tensor::randn(0f32, 1., (1, 7, 6), device)?;
+    let (ys, weights) = mha.forward_with_weights(&xs, &memory, &memory, None, None)?;
+    assert_eq!(ys.dims(), &[1, 4, 6]);
+    assert_eq!(weights.dims(), &[1, 3, 4, 7]);
+    Ok(())
+}
+
+#[test]
+fn multi_head_attention_errors() -> Result<()> {
+    let device = &Device::Cpu;
+    let vb = VarBuilder::zeros(DType::F32, device);
+    let err = MultiHeadAttention::new(10, 3, vb.clone()).unwrap_err();
+    assert!(err.to_string().contains("divisible"), "{err}");
+
+    let mha = MultiHeadAttention::new(8, 2, vb)?;
+    let xs = Tensor::zeros((2, 5, 8), DType::F32, device)?;
+    let bad = Tensor::zeros((2, 5, 6), DType::F32, device)?;
+    let err = mha.forward(&bad, &bad, &bad, None, None).unwrap_err();
+    assert!(err.to_string().contains("query"), "{err}");
+    let other = Tensor::zeros((2, 4, 8), DType::F32, device)?;
+    assert!(mha.forward(&xs, &xs, &other, None, None).is_err());
+
+    for dims in [vec![5, 4], vec![3, 5, 5], vec![2, 3, 5, 5], vec![5]] {
+        let mask = Tensor::zeros(dims, DType::U8, device)?;
+        let err = mha.forward(&xs, &xs, &xs, Some(&mask), None).unwrap_err();
+        assert!(err.to_string().contains("mask"), "{err}");
+    }
+    for dims in [
+        vec![5, 5],
+        vec![1, 5, 5],
+        vec![2, 1, 5, 5],
+        vec![1, 2, 5, 5],
+    ] {
+        let mask = Tensor::zeros(dims, DType::U8, device)?;
+        mha.forward(&xs, &xs, &xs, Some(&mask), None)?;
+    }
+
+    let mut cache = KvCache::new();
+    mha.forward(&xs, &xs, &xs, None, Some(&mut cache))?;
+    let single = xs.narrow(0, 0, 1)?;
+    assert!(mha
+        .forward(&single, &single, &single, None, Some(&mut cache))
+        .is_err());
+    Ok(())
+}
@@ -6,7 +6,7 @@ extern crate accelerate_src;
 
 use anyhow::Result;
 use candle::{test_utils, DType, Device, Tensor};
-use candle_nn::BatchNorm;
+use candle_nn::{BatchNorm, Module, VarBuilder, VarMap};
 
 /* The test below has been generated using the following PyTorch code:
 import torch
@@ -73,3 +73,120 @@ fn batch_norm() -> Result<()> {
     assert_eq!(test_utils::to_vec1_round(&sum_diff2, 4)?, &[0f32]);
     Ok(())
 }
+
+#[test]
+fn batch_norm_running_stats() -> Result<()> {
+    let device = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let mut bn = candle_nn::batch_norm(2, 1e-5, vb.pp("bn"))?;
+    // The running stats are buffers, only the weight and bias are trainable.
+    assert_eq!(varmap.all_vars().len(), 2);
+    assert_eq!(varmap.all_buffers().len(), 2);
+
+    // Channel 0 has values 0, 1, 2, 3 and channel 1 has values 2, 4, 6, 8.
+    let xs = Tensor::new(&[[[0f32, 1.], [2., 4.]], [[2., 3.], [6., 8.]]], device)?;
+    bn.set_training(true);
+    let ys = bn.forward(&xs)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&ys, 4)?,
+        [
+            [[-1.3416, -0.4472], [-1.3416, -0.4472]],
+            [[0.4472, 1.3416], [0.4472, 1.3416]]
+        ]
+    );
+    // The batch means are 1.5 and 5, the unbiased variances are 5/3 and 20/3.
+    assert_eq!(
+        test_utils::to_vec1_round(bn.running_mean(), 4)?,
+        [0.15, 0.5]
+    );
+    assert_eq!(
+        test_utils::to_vec1_round(bn.running_var(), 4)?,
+        [1.0667, 1.5667]
+    );
+    // The updates are visible from the var map.
+    let running_mean = varmap.data().lock().unwrap()["bn.running_mean"].clone();
+    assert_eq!(test_utils::to_vec1_round(&running_mean, 4)?, [0.15, 0.5]);
+
+    // In evaluation mode the running stats are used and not updated.
+    bn.set_training(false);
+    let ys = bn.forward(&xs)?;
+    let expected = xs
+        .broadcast_sub(&bn.running_mean().reshape((1, 2, 1))?)?
+        .broadcast_div(&(bn.running_var() + 1e-5)?.sqrt()?.reshape((1, 2, 1))?)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&ys, 4)?,
+        test_utils::to_vec3_round(&expected, 4)?
+    );
+    let (scale, shift) = bn.eval_scale_shift()?;
+    let folded = xs
+        .broadcast_mul(&scale.reshape((1, 2, 1))?)?
+        .broadcast_add(&shift.reshape((1, 2, 1))?)?;
+    assert_eq!(ys.to_vec3::<f32>()?, folded.to_vec3::<f32>()?);
+    assert_eq!(
+        test_utils::to_vec1_round(bn.running_mean(), 4)?,
+        [0.15, 0.5]
+    );
+    Ok(())
+}
+
+#[test]
+fn batch_norm_no_mean_removal() -> Result<()> {
+    let device = &Device::Cpu;
+    let config = candle_nn::BatchNormConfig {
+        remove_mean: false,
+        affine: false,
+        ..Default::default()
+    };
+    let mut bn = candle_nn::batch_norm(2, config, VarBuilder::zeros(DType::F32, device))?
+        .with_momentum(1.)?;
+    let xs = Tensor::new(&[[[0f32, 1.], [2., 4.]], [[2., 3.], [6., 8.]]], device)?;
+    bn.set_training(true);
+    // The input is only scaled by the root of the second moments, 3.5 and 30.
+    let ys = bn.forward(&xs)?;
+    let expected = xs.broadcast_div(&Tensor::new(&[[[3.5f32], [30.]]], device)?.sqrt()?)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&ys, 4)?,
+        test_utils::to_vec3_round(&expected, 4)?
+    );
+    // The running stats are still the batch means and unbiased variances.
+    assert_eq!(test_utils::to_vec1_round(bn.running_mean(), 4)?, [1.5, 5.]);
+    assert_eq!(
+        test_utils::to_vec1_round(bn.running_var(), 4)?,
+        [1.6667, 6.6667]
+    );
+
+    // In evaluation mode the mean is not removed either and the second moments are
+    // `running_var + running_mean^2`.
+    bn.set_training(false);
+    let ys = bn.forward(&xs)?;
+    let second_moment = Tensor::new(&[[[5f32 / 3. + 2.25], [20. / 3. + 25.]]], device)?;
+    let expected = xs.broadcast_div(&(second_moment + 1e-5)?.sqrt()?)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&ys, 4)?,
+        test_utils::to_vec3_round(&expected, 4)?
+    );
+    assert!(bn.with_momentum(1.5).is_err());
+    Ok(())
+}
+
+#[test]
+fn batch_norm_from_checkpoint() -> Result<()> {
+    let device = &Device::Cpu;
+    let tensors: std::collections::HashMap<_, _> = [
+        ("bn.running_mean", Tensor::new(&[1f32, -1.], device)?),
+        ("bn.running_var", Tensor::new(&[4f32, 0.25], device)?),
+        ("bn.weight", Tensor::new(&[2f32, 1.], device)?),
+        ("bn.bias", Tensor::new(&[0f32, 0.5], device)?),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+    let vb = VarBuilder::from_tensors(tensors, DType::F32, device);
+    let bn = candle_nn::batch_norm(2, 0., vb.pp("bn"))?;
+    assert_eq!(bn.running_mean().to_vec1::<f32>()?, [1., -1.]);
+    assert_eq!(bn.running_var().to_vec1::<f32>()?, [4., 0.25]);
+    let xs = Tensor::new(&[[3f32, 0.]], device)?;
+    assert_eq!(bn.forward(&xs)?.to_vec2::<f32>()?, [[2., 2.5]]);
+    Ok(())
+}
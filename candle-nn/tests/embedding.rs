@@ -0,0 +1,55 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{DType, Device, Tensor, Var};
+use candle_nn::{Embedding, Module, VarBuilder, VarMap};
+
+#[test]
+fn embedding() -> Result<()> {
+    let device = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let emb = candle_nn::embedding(5, 3, vb.pp("emb"))?;
+    // The indexes can have any rank.
+    let ids = Tensor::new(&[[0u32, 4, 2], [2, 2, 1]], device)?;
+    let ys = emb.forward(&ids)?;
+    assert_eq!(ys.dims(), &[2, 3, 3]);
+    let row2 = emb.embeddings().get(2)?.to_vec1::<f32>()?;
+    assert_eq!(ys.get(1)?.get(0)?.to_vec1::<f32>()?, row2);
+
+    // The gradient of each row counts the number of lookups.
+    let grads = ys.sum_all()?.backward()?;
+    let weight = varmap.all_vars().remove(0);
+    let grad = grads.get(&weight).expect("no grad for the embeddings");
+    assert_eq!(grad.sum(1)?.to_vec1::<f32>()?, [3., 3., 9., 0., 3.]);
+    Ok(())
+}
+
+#[test]
+fn embedding_padding_idx() -> Result<()> {
+    let device = &Device::Cpu;
+    let weight = Var::new(&[[0f32, 0.], [1., 2.], [3., 4.]], device)?;
+    let emb = Embedding::new(weight.as_tensor().clone(), 2).with_padding_idx(0)?;
+    assert_eq!(emb.padding_idx(), Some(0));
+    let ids = Tensor::new(&[[1i64, 0, 2], [0, 0, 1]], device)?;
+    let ys = emb.forward(&ids)?;
+    assert_eq!(
+        ys.to_vec3::<f32>()?,
+        [
+            [[1., 2.], [0., 0.], [3., 4.]],
+            [[0., 0.], [0., 0.], [1., 2.]]
+        ]
+    );
+    let grads = (ys * 2.)?.sum_all()?.backward()?;
+    let grad = grads.get(&weight).expect("no grad for the embeddings");
+    assert_eq!(grad.to_vec2::<f32>()?, [[0., 0.], [4., 4.], [2., 2.]]);
+
+    assert!(Embedding::new(weight.as_tensor().clone(), 2)
+        .with_padding_idx(3)
+        .is_err());
+    Ok(())
+}
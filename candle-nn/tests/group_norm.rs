@@ -26,8 +26,8 @@ extern crate accelerate_src;
 
 use anyhow::Result;
 use candle::test_utils::to_vec3_round;
-use candle::{Device, Tensor};
-use candle_nn::{GroupNorm, Module};
+use candle::{DType, Device, Tensor, Var};
+use candle_nn::{GroupNorm, InstanceNorm2d, Module, VarBuilder, VarMap};
 
 #[test]
 fn group_norm() -> Result<()> {
@@ -103,3 +103,85 @@ fn group_norm() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn instance_norm() -> Result<()> {
+    let device = &Device::Cpu;
+    let xs = Tensor::arange(0f32, 48., device)?
+        .sqr()?
+        .affine(0.01, -3.)?
+        .reshape((2, 3, 2, 4))?;
+    let w = Tensor::new(&[1f32, 2., -0.5], device)?;
+    let b = Tensor::new(&[0f32, 0.5, 1.], device)?;
+    let inorm = InstanceNorm2d::new(w.clone(), b.clone(), 3, 1e-5)?;
+    let gnorm = GroupNorm::new(w.clone(), b.clone(), 3, 3, 1e-5)?;
+    let ys = inorm.forward(&xs)?;
+    assert_eq!(
+        ys.flatten_all()?.to_vec1::<f32>()?,
+        gnorm.forward(&xs)?.flatten_all()?.to_vec1::<f32>()?
+    );
+    // Each (sample, channel) plane has mean `b` and standard deviation `|w|`.
+    let ys = ys.flatten_from(2)?;
+    let mean = ys.mean_keepdim(2)?;
+    let std = ys.broadcast_sub(&mean)?.sqr()?.mean(2)?.sqrt()?;
+    assert_eq!(
+        to_vec3_round(&mean, 4)?,
+        [[[0.0], [0.5], [1.0]], [[0.0], [0.5], [1.0]]]
+    );
+    assert_eq!(
+        candle::test_utils::to_vec2_round(&std, 3)?,
+        [[1.0, 2.0, 0.5], [1.0, 2.0, 0.5]]
+    );
+
+    assert!(GroupNorm::new(w.clone(), b.clone(), 3, 2, 1e-5).is_err());
+    assert!(inorm.forward(&xs.flatten_from(2)?).is_err());
+    Ok(())
+}
+
+#[test]
+fn group_norm_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F64, device);
+    let gn = candle_nn::group_norm(2, 4, 1e-5, vb.pp("gn"))?;
+    let inorm = candle_nn::instance_norm2d(4, 1e-5, vb.pp("in"))?;
+    let xs = Var::from_tensor(
+        &Tensor::arange(0f64, 16., device)?
+            .sqr()?
+            .affine(0.1, -1.)?
+            .reshape((1, 4, 2, 2))?,
+    )?;
+    let r = Tensor::arange(0f64, 16., device)?
+        .cos()?
+        .reshape((1, 4, 2, 2))?;
+    for norm in [&gn as &dyn Module, &inorm] {
+        let loss = |xs: &Tensor| -> candle::Result<f64> {
+            norm.forward(xs)?.mul(&r)?.sum_all()?.to_scalar::<f64>()
+        };
+        let grads = norm.forward(&xs)?.mul(&r)?.sum_all()?.backward()?;
+        let grad_x = grads.get(&xs).expect("no grad for xs");
+        // Compare with finite differences, which requires the gradient to flow through the
+        // mean and variance.
+        let grad_x = grad_x.flatten_all()?.to_vec1::<f64>()?;
+        for idx in [0, 5, 10, 15] {
+            let mut delta = vec![0f64; 16];
+            delta[idx] = 1e-5;
+            let delta = Tensor::from_vec(delta, (1, 4, 2, 2), device)?;
+            let fd =
+                (loss(&(xs.as_tensor() + &delta)?)? - loss(&(xs.as_tensor() - &delta)?)?) / 2e-5;
+            assert!(
+                (fd - grad_x[idx]).abs() < 1e-4,
+                "{idx} {fd} {}",
+                grad_x[idx]
+            );
+        }
+    }
+    // The affine parameters of both layers get a gradient.
+    let loss = (gn.forward(&xs)? + inorm.forward(&xs)?)?;
+    let grads = loss.mul(&r)?.sum_all()?.backward()?;
+    assert_eq!(varmap.all_vars().len(), 4);
+    for var in varmap.all_vars() {
+        assert!(grads.get(&var).is_some());
+    }
+    Ok(())
+}
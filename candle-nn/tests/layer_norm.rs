@@ -5,8 +5,8 @@ extern crate intel_mkl_src;
 extern crate accelerate_src;
 
 use anyhow::Result;
-use candle::{test_utils, Device, Tensor};
-use candle_nn::{LayerNorm, Module};
+use candle::{test_utils, DType, Device, Tensor, Var};
+use candle_nn::{LayerNorm, Module, RmsNorm, VarBuilder, VarMap};
 
 #[test]
 fn layer_norm() -> Result<()> {
@@ -44,3 +44,42 @@ fn layer_norm() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn rms_norm() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Tensor::new(&[1f32, 2., 0.5], device)?;
+    let rms = RmsNorm::new(w, 1e-8);
+    let inp = Tensor::new(&[[[1f32, 2., 2.], [3., 0., -4.]]], device)?;
+    let res = rms.forward(&inp)?;
+    // The root mean squares are sqrt(3) and sqrt(25/3), no mean is removed.
+    assert_eq!(
+        test_utils::to_vec3_round(&res, 4)?,
+        [[[0.5774, 2.3094, 0.5774], [1.0392, 0.0, -0.6928]]]
+    );
+
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let rms = candle_nn::rms_norm(3, 1e-8, vb.pp("rms"))?;
+    let xs = Var::new(&[[1f32, 2., 2.]], device)?;
+    let ys = rms.forward(&xs)?;
+    // The weight is initialized to ones.
+    assert_eq!(
+        test_utils::to_vec2_round(&ys, 4)?,
+        [[0.5774, 1.1547, 1.1547]]
+    );
+    let grads = ys.sum_all()?.backward()?;
+    let weight = varmap.data().lock().unwrap()["rms.weight"].clone();
+    let grad_w = grads.get(&weight).expect("no grad for weight");
+    assert_eq!(
+        test_utils::to_vec1_round(grad_w, 4)?,
+        [0.5774, 1.1547, 1.1547]
+    );
+    // d/dx_i sum_j x_j / r = 1 / r - x_i * sum_j x_j / (n * r^3)
+    let grad_x = grads.get(&xs).expect("no grad for xs");
+    assert_eq!(
+        test_utils::to_vec2_round(grad_x, 4)?,
+        [[0.2566, -0.0642, -0.0642]]
+    );
+    Ok(())
+}
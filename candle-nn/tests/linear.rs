@@ -0,0 +1,114 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use candle::{test_utils::to_vec3_round, DType, Device, IndexOp, Result, Tensor};
+use candle_nn::{Linear, Module, VarBuilder, VarMap};
+
+#[test]
+fn linear() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], device)?;
+    let b = Tensor::new(&[1f32, -1., 0.5], device)?;
+    let layer = Linear::new(w.clone(), Some(b.clone()));
+
+    let xs = Tensor::arange(0f32, 12., device)?.reshape((2, 3, 2))?;
+    let ys = layer.forward(&xs)?;
+    assert_eq!(ys.dims(), &[2, 3, 3]);
+    let expected = xs
+        .reshape((6, 2))?
+        .matmul(&w.t()?)?
+        .broadcast_add(&b)?
+        .reshape((2, 3, 3))?;
+    assert_eq!(ys.to_vec3::<f32>()?, expected.to_vec3::<f32>()?);
+
+    // Any number of leading dimensions is supported.
+    let xs = xs.reshape((2, 1, 3, 1, 2))?;
+    let ys = layer.forward(&xs)?;
+    assert_eq!(ys.dims(), &[2, 1, 3, 1, 3]);
+    assert_eq!(
+        to_vec3_round(&ys.reshape((2, 3, 3))?, 4)?,
+        to_vec3_round(&expected, 4)?
+    );
+
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let layer = candle_nn::linear_no_bias(2, 4, vb.pp("lin"))?;
+    assert_eq!(layer.weight().dims(), &[4, 2]);
+    assert!(layer.bias().is_none());
+    assert_eq!(layer.forward(&xs)?.dims(), &[2, 1, 3, 1, 4]);
+    Ok(())
+}
+
+#[test]
+fn linear_pre_transpose() -> Result<()> {
+    let device = &Device::Cpu;
+    let w = Tensor::arange(0f32, 12., device)?.reshape((4, 3))?;
+    let b = Tensor::new(&[1f32, -1., 0.5, 2.], device)?;
+    let layer = Linear::new(w.clone(), Some(b.clone()));
+    let layer_t = Linear::with_options(w.clone(), Some(b.clone()), true)?;
+    assert_eq!(layer_t.weight().dims(), &[4, 3]);
+
+    let xs = Tensor::arange(0f32, 30., device)?.reshape((5, 2, 3))?;
+    for xs in [xs.clone(), xs.i(0)?, xs.reshape((5, 1, 2, 1, 3))?] {
+        let ys = layer.forward(&xs)?;
+        let ys_t = layer_t.forward(&xs)?;
+        assert_eq!(ys.dims(), ys_t.dims());
+        assert_eq!(
+            ys.flatten_all()?.to_vec1::<f32>()?,
+            ys_t.flatten_all()?.to_vec1::<f32>()?
+        );
+    }
+
+    let layer = Linear::from_weights(w.clone(), None)?;
+    assert!(layer.bias().is_none());
+    assert!(Linear::from_weights(w.clone(), Some(b.narrow(0, 0, 3)?)).is_err());
+    assert!(Linear::from_weights(b, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn qlinear() -> Result<()> {
+    use candle::quantized::GgmlDType;
+    use candle_nn::QLinear;
+
+    let device = &Device::Cpu;
+    let (in_c, out_c) = (256, 8);
+    let w = Tensor::randn(0f32, 1., (out_c, in_c), device)?;
+    let b = Tensor::randn(0f32, 1., out_c, device)?;
+    let xs = Tensor::randn(0f32, 1., (2, 3, in_c), device)?;
+    let linear = Linear::new(w, Some(b));
+    for dtype in [GgmlDType::Q8_0, GgmlDType::Q4_0, GgmlDType::Q4K] {
+        let qlinear = QLinear::from_linear(&linear, dtype)?;
+        assert_eq!(qlinear.weight().dtype(), dtype);
+        let ys = qlinear.forward(&xs)?;
+        assert_eq!(ys.dims(), &[2, 3, out_c]);
+        // Compare with a f32 matmul on the dequantized weight, the remaining error comes from
+        // the activations being quantized to 8 bits in the dot-product kernels.
+        let dequantized = qlinear.weight().dequantize(device)?;
+        let expected = Linear::new(dequantized, linear.bias().cloned()).forward(&xs)?;
+        let err = (&ys - &expected)?.abs()?.flatten_all()?.max(0)?;
+        let scale = expected.abs()?.flatten_all()?.max(0)?;
+        let rel_err = err.to_scalar::<f32>()? / scale.to_scalar::<f32>()?;
+        assert!(rel_err < 0.02, "{dtype:?} {rel_err}");
+
+        let ys1 = qlinear.forward(&xs.i((0, 0))?)?;
+        assert_eq!(ys1.dims(), &[out_c]);
+        assert_eq!(ys1.to_vec1::<f32>()?, ys.i((0, 0))?.to_vec1::<f32>()?);
+    }
+    // Other activation dtypes are converted to f32 for the matmul and back for the output.
+    let qlinear = QLinear::from_linear(&linear, GgmlDType::Q8_0)?;
+    let ys_f16 = qlinear.forward(&xs.to_dtype(DType::F16)?)?;
+    assert_eq!(ys_f16.dtype(), DType::F16);
+    let xs_f16 = xs.to_dtype(DType::F16)?.to_dtype(DType::F32)?;
+    let expected = qlinear.forward(&xs_f16)?.to_dtype(DType::F16)?;
+    assert_eq!(
+        ys_f16.to_dtype(DType::F32)?.to_vec3::<f32>()?,
+        expected.to_dtype(DType::F32)?.to_vec3::<f32>()?
+    );
+    let weight = candle::quantized::QTensor::quantize_as(linear.weight(), GgmlDType::Q8_0)?;
+    assert!(QLinear::new(weight, Some(Tensor::zeros(3, DType::F32, device)?)).is_err());
+    Ok(())
+}
@@ -4,8 +4,9 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use candle::test_utils::to_vec0_round;
-use candle::{Device, Result, Tensor};
+use candle::test_utils::{to_vec0_round, to_vec1_round};
+use candle::{Device, IndexOp, Result, Tensor, Var, D};
+use candle_nn::loss::{CrossEntropyConfig, Reduction};
 
 /* Equivalent python code:
 import torch
@@ -39,3 +40,384 @@ fn nll_and_cross_entropy() -> Result<()> {
     assert_eq!(to_vec0_round(&loss, 4)?, 1.1312);
     Ok(())
 }
+
+#[test]
+fn reduction() -> Result<()> {
+    use candle_nn::loss::{apply_reduction, Reduction};
+    let loss = Tensor::new(&[[1f32, 2.], [3., 6.]], &Device::Cpu)?;
+    let none = apply_reduction(&loss, Reduction::None)?;
+    assert_eq!(none.to_vec2::<f32>()?, &[[1., 2.], [3., 6.]]);
+    let mean = apply_reduction(&loss, Reduction::Mean)?;
+    assert_eq!(mean.to_vec0::<f32>()?, 3.);
+    let sum = apply_reduction(&loss, Reduction::Sum)?;
+    assert_eq!(sum.to_vec0::<f32>()?, 12.);
+    assert_eq!(Reduction::default(), Reduction::Mean);
+    Ok(())
+}
+
+#[test]
+fn cross_entropy_ignore_index() -> Result<()> {
+    let cpu = Device::Cpu;
+    let input = Tensor::new(
+        &[
+            [1.1050f32, 0.3013, -1.5394, -2.1528, -0.8634],
+            [1.0730, -0.9419, -0.1670, -0.6582, 0.5061],
+            [0.8318, 1.1154, -0.3610, 0.5351, 1.0830],
+            [-0.3124, 0.7813, 1.4415, 0.2183, -0.4321],
+        ],
+        &cpu,
+    )?;
+    let target = Tensor::new(&[1i64, 0, -100, 4], &cpu)?;
+    let loss = candle_nn::loss::cross_entropy_with_ignore_index(&input, &target, Some(-100))?;
+
+    // The loss should only account for the non-ignored positions.
+    let kept = Tensor::new(&[0u32, 1, 3], &cpu)?;
+    let expected = candle_nn::loss::cross_entropy(
+        &input.index_select(&kept, 0)?,
+        &Tensor::new(&[1u32, 0, 4], &cpu)?,
+    )?;
+    assert_eq!(to_vec0_round(&loss, 4)?, to_vec0_round(&expected, 4)?);
+
+    // Without ignore index this is the usual cross-entropy.
+    let target = Tensor::new(&[1u32, 0, 4, 2], &cpu)?;
+    let loss = candle_nn::loss::cross_entropy_with_ignore_index(&input, &target, None)?;
+    let expected = candle_nn::loss::cross_entropy(&input, &target)?;
+    assert_eq!(to_vec0_round(&loss, 4)?, to_vec0_round(&expected, 4)?);
+
+    // When all the positions are ignored, the loss and its gradient are 0 rather than NaN.
+    let input = candle::Var::from_tensor(&input)?;
+    let target = Tensor::new(&[-100i64, -100, -100, -100], &cpu)?;
+    let loss = candle_nn::loss::cross_entropy_with_ignore_index(&input, &target, Some(-100))?;
+    assert_eq!(loss.to_vec0::<f32>()?, 0.);
+    let grads = loss.backward()?;
+    let grad = grads.get(&input).unwrap().flatten_all()?.to_vec1::<f32>()?;
+    assert!(grad.iter().all(|&g| g == 0.), "{grad:?}");
+    Ok(())
+}
+
+/* The expected values match:
+F.cross_entropy(input, target, weight=weight, label_smoothing=eps, ignore_index=-100, reduction=r)
+with the input from the test above and the following values:
+target = torch.tensor([1, 0, -100, 4])
+weight = torch.tensor([1., 2., 0.5, 1., 3.])
+*/
+#[test]
+fn cross_entropy_config() -> Result<()> {
+    let cpu = Device::Cpu;
+    let input = Tensor::new(
+        &[
+            [1.1050f32, 0.3013, -1.5394, -2.1528, -0.8634],
+            [1.0730, -0.9419, -0.1670, -0.6582, 0.5061],
+            [0.8318, 1.1154, -0.3610, 0.5351, 1.0830],
+            [-0.3124, 0.7813, 1.4415, 0.2183, -0.4321],
+        ],
+        &cpu,
+    )?;
+    let target = Tensor::new(&[1i64, 0, -100, 4], &cpu)?;
+    let weight = Tensor::new(&[1f32, 2., 0.5, 1., 3.], &cpu)?;
+    let loss = |weight: Option<&Tensor>, label_smoothing: f64, reduction: Reduction| {
+        let config = CrossEntropyConfig {
+            ignore_index: Some(-100),
+            label_smoothing,
+            weight: weight.cloned(),
+            reduction,
+        };
+        candle_nn::loss::cross_entropy_with_config(&input, &target, &config)
+    };
+
+    let l = loss(None, 0., Reduction::None)?;
+    assert_eq!(to_vec1_round(&l, 4)?, [1.3325, 0.7734, 0., 2.6333]);
+    assert_eq!(to_vec0_round(&loss(None, 0., Reduction::Mean)?, 4)?, 1.5797);
+    let l = loss(Some(&weight), 0., Reduction::None)?;
+    assert_eq!(to_vec1_round(&l, 4)?, [2.665, 0.7734, 0., 7.9]);
+    let l = loss(Some(&weight), 0., Reduction::Mean)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 1.8897);
+    let l = loss(Some(&weight), 0., Reduction::Sum)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 11.3383);
+    assert_eq!(
+        to_vec0_round(&loss(None, 0.1, Reduction::Mean)?, 4)?,
+        1.6221
+    );
+    let l = loss(Some(&weight), 0.1, Reduction::Mean)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 1.8526);
+    let l = loss(Some(&weight), 0.1, Reduction::None)?;
+    assert_eq!(to_vec1_round(&l, 4)?, [2.7196, 0.9737, 0., 7.4223]);
+
+    assert!(loss(None, 1.5, Reduction::Mean).is_err());
+    assert!(loss(Some(&weight.narrow(0, 0, 3)?), 0., Reduction::Mean).is_err());
+    let bad_target = Tensor::new(&[1i64, 0, 2], &cpu)?;
+    assert!(candle_nn::loss::cross_entropy(&input, &bad_target).is_err());
+    Ok(())
+}
+
+// Compares against the naive version using one-hot smoothed targets, on a (batch, seq, classes)
+// input.
+#[test]
+fn cross_entropy_naive() -> Result<()> {
+    let cpu = Device::Cpu;
+    let (b_sz, seq_len, n_classes) = (2, 3, 4);
+    let logits = Var::from_tensor(&Tensor::randn(0f32, 2., (b_sz, seq_len, n_classes), &cpu)?)?;
+    let target_vec = [3u32, 0, 1, 2, 2, 0];
+    let target = Tensor::new(&target_vec, &cpu)?.reshape((b_sz, seq_len))?;
+    let eps = 0.2;
+
+    let config = CrossEntropyConfig {
+        label_smoothing: eps,
+        ..Default::default()
+    };
+    let loss = candle_nn::loss::cross_entropy_with_config(&logits, &target, &config)?;
+    let grad = loss.backward()?.get(&logits).unwrap().clone();
+
+    let smoothed: Vec<f32> = target_vec
+        .iter()
+        .flat_map(|&t| {
+            (0..n_classes as u32).map(move |c| {
+                let one_hot = if c == t { 1. } else { 0. };
+                (1. - eps as f32) * one_hot + eps as f32 / n_classes as f32
+            })
+        })
+        .collect();
+    let smoothed = Tensor::from_vec(smoothed, (b_sz, seq_len, n_classes), &cpu)?;
+    let log_probs = candle_nn::ops::log_softmax(&logits, D::Minus1)?;
+    let naive = (smoothed * log_probs)?.sum(D::Minus1)?.neg()?.mean_all()?;
+    let naive_grad = naive.backward()?.get(&logits).unwrap().clone();
+
+    let diff = (&loss - &naive)?.abs()?.to_vec0::<f32>()?;
+    assert!(diff < 1e-5, "{loss} {naive}");
+    let diff = (grad - naive_grad)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_vec0::<f32>()? < 1e-6);
+
+    let per_position = candle_nn::loss::cross_entropy_with_config(
+        &logits,
+        &target,
+        &CrossEntropyConfig {
+            reduction: Reduction::None,
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(per_position.dims(), &[b_sz, seq_len]);
+    Ok(())
+}
+
+/* Equivalent python code:
+import torch
+import torch.nn.functional as F
+x = torch.tensor([[0.2, -1.5, 3.0], [2.0, 0.7, -0.4]])
+y = torch.tensor([1.0, 0.0, 0.5])
+print(F.mse_loss(x, y.expand(2, 3), reduction="none"))
+print(F.l1_loss(x, y.expand(2, 3)))
+print(F.huber_loss(x, y.expand(2, 3), delta=1.0, reduction="none"))
+print(F.smooth_l1_loss(x, y.expand(2, 3), beta=0.5))
+print(F.binary_cross_entropy(torch.sigmoid(x), y.expand(2, 3), reduction="none"))
+print(F.binary_cross_entropy_with_logits(x, y.expand(2, 3)))
+*/
+#[test]
+fn regression_and_bce_losses() -> Result<()> {
+    use candle_nn::loss;
+
+    let cpu = Device::Cpu;
+    let x = Tensor::new(&[[0.2f32, -1.5, 3.0], [2.0, 0.7, -0.4]], &cpu)?;
+    let y = Tensor::new(&[1f32, 0., 0.5], &cpu)?;
+    let to_vec2_round = candle::test_utils::to_vec2_round;
+
+    let l = loss::mse_with_reduction(&x, &y, Reduction::None)?;
+    assert_eq!(
+        to_vec2_round(&l, 4)?,
+        [[0.64, 2.25, 6.25], [1.0, 0.49, 0.81]]
+    );
+    assert_eq!(
+        to_vec0_round(&loss::l1(&x, &y, Reduction::Mean)?, 4)?,
+        1.2333
+    );
+    let l = loss::huber(&x, &y, 1.0, Reduction::None)?;
+    assert_eq!(
+        to_vec2_round(&l, 4)?,
+        [[0.32, 1.0, 2.0], [0.5, 0.245, 0.405]]
+    );
+    let l = loss::huber(&x, &y, 1.0, Reduction::Sum)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 4.47);
+    let l = loss::smooth_l1(&x, &y, 0.5, Reduction::Mean)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 0.9833);
+
+    let bce = [[0.5981, 0.2014, 1.5486], [0.1269, 1.1032, 0.713]];
+    let p = candle_nn::ops::sigmoid(&x)?;
+    let l = loss::binary_cross_entropy(&p, &y, Reduction::None)?;
+    assert_eq!(to_vec2_round(&l, 4)?, bce);
+    let l = loss::binary_cross_entropy_with_logits(&x, &y, Reduction::None)?;
+    assert_eq!(to_vec2_round(&l, 4)?, bce);
+    let l = loss::binary_cross_entropy_with_logits(&x, &y, Reduction::Mean)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 0.7152);
+
+    // Large logits stay finite, including in f16 where exp(20.) overflows.
+    let x = Tensor::new(&[-20f32, 20.], &cpu)?.to_dtype(candle::DType::F16)?;
+    let y = Tensor::new(&[1f32, 0.], &cpu)?.to_dtype(candle::DType::F16)?;
+    let l = loss::binary_cross_entropy_with_logits(&x, &y, Reduction::None)?;
+    let l = l.to_dtype(candle::DType::F32)?.to_vec1::<f32>()?;
+    assert_eq!(l, [20., 20.]);
+    // Probabilities of exactly 0 and 1 are clamped.
+    let p = Tensor::new(&[0f32, 1.], &cpu)?;
+    let y = Tensor::new(&[1f32, 0.], &cpu)?;
+    let l = loss::binary_cross_entropy(&p, &y, Reduction::None)?;
+    assert_eq!(l.to_vec1::<f32>()?, [100., 100.]);
+
+    let err = loss::l1(
+        &x,
+        &Tensor::zeros(3, candle::DType::F16, &cpu)?,
+        Reduction::Mean,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("cannot be broadcasted"), "{err}");
+    assert!(loss::huber(&p, &y, 0., Reduction::Mean).is_err());
+    Ok(())
+}
+
+#[test]
+fn regression_and_bce_losses_grad() -> Result<()> {
+    use candle_nn::loss;
+
+    let cpu = Device::Cpu;
+    let x = Tensor::new(&[[0.2f64, -1.5, 3.0], [2.1, 0.7, -0.4]], &cpu)?;
+    let y = Tensor::new(&[1f64, 0., 0.5], &cpu)?;
+    let p = Tensor::new(&[[0.2f64, 0.9, 0.45], [0.6, 0.05, 0.7]], &cpu)?;
+    type LossFn<'a> = Box<dyn Fn(&Tensor) -> Result<Tensor> + 'a>;
+    let y = &y;
+    let cases: Vec<(&str, &Tensor, LossFn)> = vec![
+        (
+            "mse",
+            &x,
+            Box::new(|x| loss::mse_with_reduction(x, y, Reduction::Mean)),
+        ),
+        ("l1", &x, Box::new(|x| loss::l1(x, y, Reduction::Sum))),
+        (
+            "huber",
+            &x,
+            Box::new(|x| loss::huber(x, y, 0.8, Reduction::Mean)),
+        ),
+        (
+            "smooth_l1",
+            &x,
+            Box::new(|x| loss::smooth_l1(x, y, 0.5, Reduction::Mean)),
+        ),
+        (
+            "bce",
+            &p,
+            Box::new(|p| loss::binary_cross_entropy(p, y, Reduction::Mean)),
+        ),
+        (
+            "bce_with_logits",
+            &x,
+            Box::new(|x| loss::binary_cross_entropy_with_logits(x, y, Reduction::Mean)),
+        ),
+    ];
+    for (name, inp, f) in cases {
+        let var = Var::from_tensor(inp)?;
+        let grad = f(&var)?.backward()?.get(&var).unwrap().clone();
+        let grad = grad.flatten_all()?.to_vec1::<f64>()?;
+        let inp_vec = inp.flatten_all()?.to_vec1::<f64>()?;
+        let eps = 1e-6;
+        for i in 0..inp_vec.len() {
+            let shifted = |delta: f64| -> Result<f64> {
+                let mut v = inp_vec.clone();
+                v[i] += delta;
+                let t = Tensor::from_vec(v, inp.shape(), &cpu)?;
+                f(&t)?.to_vec0::<f64>()
+            };
+            let expected = (shifted(eps)? - shifted(-eps)?) / (2. * eps);
+            assert!((grad[i] - expected).abs() < 1e-5, "{name} {i} {grad:?}");
+        }
+    }
+    Ok(())
+}
+
+/* Equivalent python code:
+import torch
+import torch.nn.functional as F
+log_p = F.log_softmax(torch.tensor([[0.5, -1.0, 2.0], [0.1, 0.2, -0.3]]), dim=1)
+q = torch.tensor([[0.2, 0.0, 0.8], [0.3, 0.3, 0.4]])
+print(F.kl_div(log_p, q, reduction="none"))
+print(F.kl_div(log_p, q, reduction="sum"))
+print(F.kl_div(log_p, q, reduction="mean"))
+print(F.kl_div(log_p, q, reduction="batchmean"))
+*/
+#[test]
+fn kl_div() -> Result<()> {
+    use candle_nn::loss::kl_div;
+
+    let cpu = Device::Cpu;
+    let logits = Tensor::new(&[[0.5f32, -1.0, 2.0], [0.1, 0.2, -0.3]], &cpu)?;
+    let log_p = candle_nn::ops::log_softmax(&logits, 1)?;
+    let q = Tensor::new(&[[0.2f32, 0.0, 0.8], [0.3, 0.3, 0.4]], &cpu)?;
+
+    let l = kl_div(&log_p, &q, false, Reduction::None)?;
+    assert_eq!(
+        candle::test_utils::to_vec2_round(&l, 4)?,
+        [[0.0264, 0., 0.0145], [-0.0549, -0.0849, 0.2018]]
+    );
+    assert_eq!(
+        to_vec0_round(&kl_div(&log_p, &q, false, Reduction::Sum)?, 4)?,
+        0.1028
+    );
+    assert_eq!(
+        to_vec0_round(&kl_div(&log_p, &q, false, Reduction::Mean)?, 4)?,
+        0.0171
+    );
+    let l = kl_div(&log_p, &q, false, Reduction::BatchMean)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 0.0514);
+
+    // Passing the target as log probabilities gives the same values, except for the zero
+    // probability which becomes -inf.
+    let q = Tensor::new(&[[0.2f32, 1e-30, 0.8], [0.3, 0.3, 0.4]], &cpu)?;
+    let l_log = kl_div(&log_p, &q.log()?, true, Reduction::BatchMean)?;
+    assert_eq!(to_vec0_round(&l_log, 4)?, 0.0514);
+
+    // The gradient with respect to the input is -q / batch_size.
+    let var = Var::from_tensor(&log_p)?;
+    let l = kl_div(&var, &q, false, Reduction::BatchMean)?;
+    let grad = l.backward()?.get(&var).unwrap().clone();
+    let expected = (q.neg()? / 2.)?;
+    let diff = (grad - expected)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_vec0::<f32>()? < 1e-6);
+
+    // (batch, seq, classes) inputs with a broadcasted target.
+    let log_p = log_p.unsqueeze(0)?.repeat((4, 1, 1))?;
+    let l = kl_div(&log_p, &q, false, Reduction::BatchMean)?;
+    assert_eq!(to_vec0_round(&l, 4)?, 0.1028);
+    assert!(kl_div(&log_p, &q.narrow(1, 0, 2)?, false, Reduction::Sum).is_err());
+    Ok(())
+}
+
+#[test]
+fn nll_with_config() -> Result<()> {
+    let cpu = Device::Cpu;
+    let logits = Tensor::randn(0f32, 1., (2, 3, 5), &cpu)?;
+    let target = Tensor::new(&[[1i64, 4, -100], [0, 2, 2]], &cpu)?;
+    let log_probs = candle_nn::ops::log_softmax(&logits, 2)?;
+    let config = CrossEntropyConfig {
+        ignore_index: Some(-100),
+        reduction: Reduction::None,
+        ..Default::default()
+    };
+    let nll = candle_nn::loss::nll_with_config(&log_probs, &target, &config)?;
+    let ce = candle_nn::loss::cross_entropy_with_config(&logits, &target, &config)?;
+    assert_eq!(nll.dims(), &[2, 3]);
+    assert_eq!(
+        candle::test_utils::to_vec2_round(&nll, 4)?,
+        candle::test_utils::to_vec2_round(&ce, 4)?
+    );
+    let expected = log_probs.i((1, 2, 2))?.neg()?;
+    assert_eq!(
+        to_vec0_round(&nll.i((1, 2))?, 4)?,
+        to_vec0_round(&expected, 4)?
+    );
+    assert_eq!(nll.i((0, 2))?.to_vec0::<f32>()?, 0.);
+
+    let config = CrossEntropyConfig {
+        ignore_index: Some(-100),
+        reduction: Reduction::BatchMean,
+        ..Default::default()
+    };
+    let batch_mean = candle_nn::loss::nll_with_config(&log_probs, &target, &config)?;
+    let expected = (nll.sum_all()? / 2.)?;
+    assert_eq!(to_vec0_round(&batch_mean, 4)?, to_vec0_round(&expected, 4)?);
+    Ok(())
+}
@@ -4,7 +4,10 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use candle::{test_utils::to_vec3_round, Device, Result, Tensor};
+use candle::{
+    test_utils::{to_vec1_round, to_vec2_round, to_vec3_round},
+    Device, IndexOp, Result, Tensor,
+};
 
 #[test]
 fn softmax() -> Result<()> {
@@ -62,3 +65,396 @@ fn softmax_numerical_stability() -> Result<()> {
     assert_eq!(softmax.to_vec1::<f32>()?, &[1f32, 0.]);
     Ok(())
 }
+
+#[test]
+fn cosine_similarity() -> Result<()> {
+    let device = &Device::Cpu;
+    let a = Tensor::new(&[[1f32, 2., 3.], [0., 0., 0.], [1., 0., 0.]], device)?;
+    let b = Tensor::new(&[[1f32, 2., 3.], [1., 2., 3.], [0., 1., 0.]], device)?;
+    let s = candle_nn::ops::cosine_similarity(&a, &b, 1, 1e-8)?;
+    // Identical vectors have a similarity of 1, the zero vector does not produce a NaN.
+    assert_eq!(to_vec1_round(&s, 4)?, &[1., 0., 0.]);
+    let s = candle_nn::ops::cosine_similarity(&a, &(&a * -2.)?, 1, 1e-8)?;
+    assert_eq!(to_vec1_round(&s, 4)?, &[-1., 0., -1.]);
+    Ok(())
+}
+
+#[test]
+fn cdist() -> Result<()> {
+    let device = &Device::Cpu;
+    let a = Tensor::new(&[[0f32, 0.], [1., 2.], [3., 4.]], device)?;
+    let b = Tensor::new(&[[0f32, 0.], [1., 0.]], device)?;
+    let d = candle_nn::ops::cdist(&a, &b, 2.)?;
+    assert_eq!(
+        to_vec2_round(&d, 4)?,
+        &[[0., 1.], [2.2361, 2.], [5., 4.4721]]
+    );
+    let d = candle_nn::ops::cdist(&a, &b, 1.)?;
+    assert_eq!(d.to_vec2::<f32>()?, &[[0., 1.], [3., 2.], [7., 6.]]);
+    assert!(candle_nn::ops::cdist(&a, &a.t()?, 2.).is_err());
+
+    // The zero distances on the diagonal do not result in NaN gradients.
+    let a = candle::Var::new(&[[0f32, 0.], [3., 4.]], device)?;
+    let grads = candle_nn::ops::cdist(&a, &a, 2.)?.sum_all()?.backward()?;
+    let grad = grads.get(&a).unwrap();
+    assert_eq!(to_vec2_round(grad, 4)?, &[[-1.2, -1.6], [1.2, 1.6]]);
+    Ok(())
+}
+
+#[test]
+fn drop_path() -> Result<()> {
+    let device = &Device::Cpu;
+    let xs = Tensor::ones((1000, 2, 3), candle::DType::F32, device)?;
+    let ys = candle_nn::ops::drop_path(&xs, 0.3, false, 42)?;
+    assert_eq!(ys.to_vec3::<f32>()?, xs.to_vec3::<f32>()?);
+
+    let ys = candle_nn::ops::drop_path(&xs, 0.3, true, 42)?;
+    let mut dropped = 0;
+    for sample in ys.to_vec3::<f32>()? {
+        let values: Vec<f32> = sample.into_iter().flatten().collect();
+        // Each sample is either entirely dropped or entirely scaled.
+        if values.iter().all(|&v| v == 0.) {
+            dropped += 1
+        } else {
+            assert!(values.iter().all(|&v| (v - 1. / 0.7).abs() < 1e-6))
+        }
+    }
+    assert!((250..350).contains(&dropped), "{dropped}");
+
+    // The same seed gives the same mask.
+    let ys2 = candle_nn::ops::drop_path(&xs, 0.3, true, 42)?;
+    assert_eq!(ys.to_vec3::<f32>()?, ys2.to_vec3::<f32>()?);
+    Ok(())
+}
+
+#[test]
+fn rope_cache() -> Result<()> {
+    use candle::DType;
+    use candle_nn::rotary::RopeLayout::{GptJ, NeoX};
+    let device = &Device::Cpu;
+    let (cos, sin) = candle_nn::ops::build_rope_cache(16, 4, 100., DType::F32, device)?;
+    assert_eq!(cos.dims(), [16, 4]);
+    assert_eq!(sin.dims(), [16, 4]);
+
+    // A single token at position 5, the frequencies are 1 and 1 / 10.
+    let xs = Tensor::new(&[[[[1f32, 2., 3., 4.]]]], device)?;
+    let ys = candle_nn::ops::apply_rope(&xs, &cos, &sin, 5, NeoX)?;
+    let (c0, s0) = (5f32.cos(), 5f32.sin());
+    let (c1, s1) = (0.5f32.cos(), 0.5f32.sin());
+    let expected = Tensor::new(
+        &[
+            c0 - 3. * s0,
+            2. * c1 - 4. * s1,
+            3. * c0 + s0,
+            4. * c1 + 2. * s1,
+        ],
+        device,
+    )?;
+    assert_eq!(
+        to_vec1_round(&ys.flatten_all()?, 4)?,
+        to_vec1_round(&expected, 4)?
+    );
+
+    // Decoding at an offset matches the corresponding positions of the full sequence.
+    let xs = Tensor::arange(0f32, 64., device)?
+        .affine(0.1, -3.)?
+        .reshape((1, 2, 8, 4))?;
+    let full = candle_nn::ops::apply_rope(&xs, &cos, &sin, 0, NeoX)?;
+    let step = candle_nn::ops::apply_rope(&xs.i((.., .., 5..7))?, &cos, &sin, 5, NeoX)?;
+    assert_eq!(
+        to_vec3_round(&step.i(0)?, 4)?,
+        to_vec3_round(&full.i((0, .., 5..7))?, 4)?
+    );
+
+    // The cache can use a different dtype from the model.
+    let ys = candle_nn::ops::apply_rope(&xs.to_dtype(DType::F16)?, &cos, &sin, 0, NeoX)?;
+    assert_eq!(ys.dtype(), DType::F16);
+    assert!(candle_nn::ops::apply_rope(&xs, &cos, &sin, 10, NeoX).is_err());
+
+    // The layout is forwarded to the rotary module.
+    let (c, s) = candle_nn::rotary::cos_sin(8, 4, 100., device)?;
+    for layout in [NeoX, GptJ] {
+        let ys = candle_nn::ops::apply_rope(&xs, &cos, &sin, 0, layout)?;
+        let expected = candle_nn::rotary::apply_rotary_emb(&xs, &c, &s, layout)?;
+        assert_eq!(
+            to_vec3_round(&ys.i(0)?, 4)?,
+            to_vec3_round(&expected.i(0)?, 4)?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn sdpa() -> Result<()> {
+    let device = &Device::Cpu;
+    let q = Tensor::new(&[[[[1f32, 0.]]]], device)?;
+    let k = Tensor::new(&[[[[1f32, 0.], [0., 1.]]]], device)?;
+    let v = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], device)?;
+    // The scores are (log 3, 0) so the attention weights are (0.75, 0.25).
+    let ys = candle_nn::ops::sdpa(&q, &k, &v, None, Some(3f64.ln()))?;
+    assert_eq!(to_vec1_round(&ys.flatten_all()?, 4)?, [1.5, 2.5]);
+    // The default scale is 1 / sqrt(2), giving weights of 0.6698 and 0.3302.
+    let ys = candle_nn::ops::sdpa(&q, &k, &v, None, None)?;
+    assert_eq!(to_vec1_round(&ys.flatten_all()?, 4)?, [1.6605, 2.6605]);
+
+    // With a causal mask and uniform scores, each position averages the values up to itself.
+    let q = Tensor::zeros((2, 3, 4, 2), candle::DType::F32, device)?;
+    let v = Tensor::arange(0f32, 4., device)?
+        .reshape((1, 1, 4, 1))?
+        .broadcast_as((2, 3, 4, 1))?
+        .contiguous()?;
+    let mask: Vec<f32> = (0..4)
+        .flat_map(|i| (0..4).map(move |j| if j > i { f32::NEG_INFINITY } else { 0. }))
+        .collect();
+    let mask = Tensor::from_vec(mask, (4, 4), device)?;
+    let ys = candle_nn::ops::sdpa(&q, &q, &v, Some(&mask), None)?;
+    assert_eq!(ys.dims(), [2, 3, 4, 1]);
+    for row in ys.reshape((6, 4))?.to_vec2::<f32>()? {
+        assert_eq!(row, [0., 0.5, 1., 1.5]);
+    }
+    Ok(())
+}
+
+#[test]
+fn masked_mean() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Tensor::arange(0f32, 24., device)?.reshape((2, 4, 3))?;
+    // The first sequence has 3 valid positions, the second one only 1.
+    let mask = Tensor::new(&[[1f32, 1., 1., 0.], [1., 0., 0., 0.]], device)?;
+    let m = candle_nn::ops::masked_mean(&x, &mask.unsqueeze(2)?, 1)?;
+    let manual = Tensor::stack(&[x.narrow(1, 0, 3)?.i(0)?.mean(0)?, x.i((1, 0))?], 0)?;
+    assert_eq!(m.to_vec2::<f32>()?, manual.to_vec2::<f32>()?);
+    assert_eq!(m.to_vec2::<f32>()?, [[3., 4., 5.], [12., 13., 14.]]);
+
+    // A fully masked sequence gives zeros.
+    let mask = Tensor::new(&[[1u8, 1, 1, 1], [0, 0, 0, 0]], device)?;
+    let m = candle_nn::ops::masked_mean(&x, &mask.unsqueeze(2)?, 1)?;
+    assert_eq!(m.to_vec2::<f32>()?, [[4.5, 5.5, 6.5], [0., 0., 0.]]);
+    Ok(())
+}
+
+#[test]
+fn gumbel_top_k() -> Result<()> {
+    let device = &Device::Cpu;
+    let probs = [0.1f32, 0.2, 0.3, 0.4];
+    let n_draws = 20000;
+    let logits = Tensor::new(&probs, device)?
+        .log()?
+        .unsqueeze(0)?
+        .broadcast_as((n_draws, probs.len()))?;
+
+    // With k = 1 the selection frequencies match the softmax of the logits.
+    let samples = candle_nn::ops::gumbel_top_k(&logits, 1, 1, 42)?;
+    assert_eq!(samples.dims(), &[n_draws, 1]);
+    let mut counts = [0usize; 4];
+    for i in samples.flatten_all()?.to_vec1::<u32>()? {
+        counts[i as usize] += 1
+    }
+    for (c, p) in counts.iter().zip(probs.iter()) {
+        assert!((*c as f32 / n_draws as f32 - p).abs() < 0.02, "{counts:?}")
+    }
+
+    // With k = 2 item i is included with probability p_i + sum_{j != i} p_j p_i / (1 - p_j).
+    let samples = candle_nn::ops::gumbel_top_k(&logits, 2, 1, 1337)?;
+    let mut counts = [0usize; 4];
+    for row in samples.to_vec2::<u32>()? {
+        assert_ne!(row[0], row[1]);
+        for i in row {
+            counts[i as usize] += 1
+        }
+    }
+    for (i, &p_i) in probs.iter().enumerate() {
+        let second: f32 = probs
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, &p_j)| p_j * p_i / (1. - p_j))
+            .sum();
+        let freq = counts[i] as f32 / n_draws as f32;
+        assert!((freq - p_i - second).abs() < 0.02, "{counts:?}")
+    }
+
+    // Sampling along the first dimension, the output is deterministic for a given seed.
+    let t = logits.t()?.narrow(1, 0, 3)?;
+    let samples = candle_nn::ops::gumbel_top_k(&t, 4, 0, 7)?;
+    assert_eq!(samples.dims(), &[4, 3]);
+    let mut col = samples.t()?.to_vec2::<u32>()?[0].clone();
+    col.sort();
+    assert_eq!(col, [0, 1, 2, 3]);
+    assert_eq!(
+        samples.to_vec2::<u32>()?,
+        candle_nn::ops::gumbel_top_k(&t, 4, 0, 7)?.to_vec2::<u32>()?
+    );
+    assert!(candle_nn::ops::gumbel_top_k(&t, 5, 0, 7).is_err());
+    Ok(())
+}
+
+#[test]
+fn r2_score() -> Result<()> {
+    let device = &Device::Cpu;
+    let target = Tensor::new(&[[1f32, 2.], [4., 5.]], device)?;
+    let r2 = |pred: &Tensor, target: &Tensor| -> Result<f32> {
+        candle_nn::ops::r2_score(pred, target)?.to_scalar::<f32>()
+    };
+    assert_eq!(r2(&target, &target)?, 1.);
+    // Predicting the mean of the target gives 0.
+    let mean = Tensor::new(3f32, device)?.broadcast_as((2, 2))?;
+    assert_eq!(r2(&mean, &target)?, 0.);
+    // SS_res = 1 + 1 + 0 + 0, SS_tot = 4 + 1 + 1 + 4.
+    let pred = Tensor::new(&[[2f32, 1.], [4., 5.]], device)?;
+    assert_eq!(r2(&pred, &target)?, 0.8);
+
+    // A constant target gives 1 for a perfect prediction and 0 otherwise.
+    let constant = Tensor::new(&[2f32, 2., 2.], device)?;
+    assert_eq!(r2(&constant, &constant)?, 1.);
+    assert_eq!(r2(&Tensor::new(&[2f32, 2., 3.], device)?, &constant)?, 0.);
+
+    assert!(candle_nn::ops::r2_score(&constant, &target).is_err());
+    Ok(())
+}
+
+#[test]
+fn dropout() -> Result<()> {
+    let device = &Device::Cpu;
+    let xs = candle::Var::new(&[[1f32, 2., 3., 4.], [5., 6., 7., 8.]], device)?;
+    let dropout = candle_nn::Dropout::new(0.5);
+    assert_eq!(
+        dropout.forward(&xs, false)?.to_vec2::<f32>()?,
+        xs.to_vec2::<f32>()?
+    );
+    assert_eq!(
+        candle_nn::ops::dropout(&xs, 0.)?.to_vec2::<f32>()?,
+        xs.to_vec2::<f32>()?
+    );
+
+    // The kept values are scaled by 2 and the gradient uses the same mask.
+    let ys = dropout.forward(&xs, true)?;
+    let grads = ys.sum_all()?.backward()?;
+    let grad = grads.get(&xs).expect("no grad for xs").flatten_all()?;
+    let ys = ys.flatten_all()?.to_vec1::<f32>()?;
+    let xs_flat = xs.flatten_all()?.to_vec1::<f32>()?;
+    for ((y, x), g) in ys.iter().zip(xs_flat.iter()).zip(grad.to_vec1::<f32>()?) {
+        if *y == 0. {
+            assert_eq!(g, 0.)
+        } else {
+            assert_eq!((*y, g), (x * 2., 2.))
+        }
+    }
+    assert!(candle_nn::ops::dropout(&xs, 1.).is_err());
+    Ok(())
+}
+
+#[test]
+fn dropout2d() -> Result<()> {
+    let device = &Device::Cpu;
+    let xs = Tensor::ones((4, 16, 3, 3), candle::DType::F32, device)?;
+    let dropout = candle_nn::Dropout2d::new(0.5);
+    assert_eq!(
+        dropout.forward(&xs, false)?.sum_all()?.to_scalar::<f32>()?,
+        576.
+    );
+    // Each channel is either fully dropped or fully kept and scaled.
+    let ys = dropout.forward(&xs, true)?.flatten_from(2)?;
+    let min = ys.min(2)?.flatten_all()?.to_vec1::<f32>()?;
+    let max = ys.max(2)?.flatten_all()?.to_vec1::<f32>()?;
+    assert_eq!(min, max);
+    assert!(min.iter().all(|v| *v == 0. || *v == 2.));
+    assert!(min.contains(&0.) && min.contains(&2.));
+    Ok(())
+}
+
+#[test]
+fn activations() -> Result<()> {
+    use candle_nn::{Activation, Module};
+
+    let device = &Device::Cpu;
+    let xs = Tensor::new(&[-3f32, -1., -0.5, 0., 0.5, 1., 3.], device)?;
+    // Reference values computed with python's math module.
+    #[allow(clippy::approx_constant)]
+    let cases = [
+        (
+            "gelu",
+            [-0.004, -0.1587, -0.1543, 0.0, 0.3457, 0.8413, 2.996],
+        ),
+        (
+            "gelu_new",
+            [-0.0036, -0.1588, -0.1543, 0.0, 0.3457, 0.8412, 2.9964],
+        ),
+        (
+            "quick_gelu",
+            [-0.0181, -0.1542, -0.1496, 0.0, 0.3504, 0.8458, 2.9819],
+        ),
+        (
+            "silu",
+            [-0.1423, -0.2689, -0.1888, 0.0, 0.3112, 0.7311, 2.8577],
+        ),
+        (
+            "swish",
+            [-0.1423, -0.2689, -0.1888, 0.0, 0.3112, 0.7311, 2.8577],
+        ),
+        (
+            "mish",
+            [-0.1456, -0.3034, -0.2207, 0.0, 0.3752, 0.8651, 2.9865],
+        ),
+        (
+            "softplus",
+            [0.0486, 0.3133, 0.4741, 0.6931, 0.9741, 1.3133, 3.0486],
+        ),
+        (
+            "hard_sigmoid",
+            [0.0, 0.3333, 0.4167, 0.5, 0.5833, 0.6667, 1.0],
+        ),
+        (
+            "hardswish",
+            [0.0, -0.3333, -0.2083, 0.0, 0.2917, 0.6667, 3.0],
+        ),
+        ("elu", [-0.9502, -0.6321, -0.3935, 0.0, 0.5, 1.0, 3.0]),
+        ("leaky_relu", [-0.03, -0.01, -0.005, 0.0, 0.5, 1.0, 3.0]),
+        ("relu6", [0.0, 0.0, 0.0, 0.0, 0.5, 1.0, 3.0]),
+    ];
+    for (name, expected) in cases {
+        let act: Activation = name.parse().unwrap();
+        let ys = act.forward(&xs)?;
+        assert_eq!(to_vec1_round(&ys, 4)?, expected, "{name}");
+    }
+    assert!("foo".parse::<Activation>().is_err());
+    assert_eq!("GELU".parse::<Activation>(), Ok(Activation::GeluErf));
+
+    // Large inputs do not overflow.
+    let large = Tensor::new(&[-100f32, 100.], device)?;
+    let ys = candle_nn::ops::softplus(&large)?;
+    assert_eq!(ys.to_vec1::<f32>()?, [0., 100.]);
+    let ys = candle_nn::ops::mish(&large)?;
+    assert_eq!(to_vec1_round(&ys, 4)?, [0., 100.]);
+
+    // The gradients match finite differences, away from the non-differentiable points.
+    let xs = Tensor::new(&[-2.5f64, -1.2, -0.3, 0.4, 1.1, 2.7], device)?;
+    let acts = [
+        Activation::Gelu,
+        Activation::GeluErf,
+        Activation::QuickGelu,
+        Activation::Relu6,
+        Activation::Elu(0.7),
+        Activation::LeakyRelu(0.1),
+        Activation::Sigmoid,
+        Activation::Tanh,
+        Activation::Silu,
+        Activation::Mish,
+        Activation::Softplus,
+        Activation::HardSigmoid,
+        Activation::HardSwish,
+    ];
+    for act in acts {
+        let var = candle::Var::from_tensor(&xs)?;
+        let grads = act.forward(&var)?.sum_all()?.backward()?;
+        let grad = grads.get(&var).unwrap().to_vec1::<f64>()?;
+        let eps = 1e-5;
+        let plus = act.forward(&(&xs + eps)?)?.to_vec1::<f64>()?;
+        let minus = act.forward(&(&xs - eps)?)?.to_vec1::<f64>()?;
+        for i in 0..grad.len() {
+            let expected = (plus[i] - minus[i]) / (2. * eps);
+            assert!((grad[i] - expected).abs() < 1e-4, "{act:?} {grad:?}");
+        }
+    }
+    Ok(())
+}
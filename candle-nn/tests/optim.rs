@@ -8,7 +8,7 @@ use candle::test_utils::{to_vec0_round, to_vec2_round};
 
 use anyhow::Result;
 use candle::{Device, Tensor, Var};
-use candle_nn::{AdamW, Linear, Module, Optimizer, ParamsAdamW, SGD};
+use candle_nn::{AdamW, Linear, Module, Optimizer, ParamsAdamW, ParamsSGD, SGD};
 
 #[test]
 fn sgd_optim() -> Result<()> {
@@ -121,3 +121,264 @@ fn adamw_linear_regression() -> Result<()> {
     assert_eq!(to_vec0_round(b.as_tensor(), 4)?, 0.7873);
     Ok(())
 }
+
+#[test]
+fn accumulate_grads() -> Result<()> {
+    let w = Var::new(&[[0.5f32, -1.]], &Device::Cpu)?;
+    let b = Var::new(&[0.25f32], &Device::Cpu)?;
+    let lin = Linear::new(w.as_tensor().clone(), Some(b.as_tensor().clone()));
+    let xs = Tensor::new(&[[2f32, 1.], [7., 4.], [-4., 12.], [5., 8.]], &Device::Cpu)?;
+    let ys = Tensor::new(&[[3f32], [15.], [-10.], [21.]], &Device::Cpu)?;
+    let loss_fn = |xs: &Tensor, ys: &Tensor| lin.forward(xs)?.sub(ys)?.sqr()?.sum_all();
+
+    // Gradients for the whole batch in a single backward pass.
+    let full_grads = loss_fn(&xs, &ys)?.backward()?;
+
+    // Gradients accumulated over two micro-batches.
+    let mut grads = loss_fn(&xs.narrow(0, 0, 2)?, &ys.narrow(0, 0, 2)?)?.backward()?;
+    let grads2 = loss_fn(&xs.narrow(0, 2, 2)?, &ys.narrow(0, 2, 2)?)?.backward()?;
+    grads.add_in_place(grads2)?;
+
+    for var in [&w, &b] {
+        let full = full_grads.get(var).unwrap().flatten_all()?;
+        let acc = grads.get(var).unwrap().flatten_all()?;
+        assert_eq!(full.to_vec1::<f32>()?, acc.to_vec1::<f32>()?);
+    }
+
+    let mut sgd = SGD::new(vec![w.clone(), b.clone()], 0.001)?;
+    sgd.step(&grads)?;
+    assert_eq!(to_vec2_round(&w, 4)?, &[[0.957, -0.3625]]);
+    Ok(())
+}
+
+/* The expected values follow the PyTorch SGD update rule, e.g. for the first case:
+    x = torch.tensor(0., requires_grad=True)
+    sgd = optim.SGD([x], lr=0.1, momentum=0.9, dampening=0.1, weight_decay=0.01)
+    for _step in range(3):
+        loss = (x - 4.2) ** 2
+        sgd.zero_grad()
+        loss.backward()
+        sgd.step()
+*/
+#[test]
+fn sgd_momentum() -> Result<()> {
+    let run = |params: ParamsSGD| -> Result<(f32, f32)> {
+        let x = Var::new(0f32, &Device::Cpu)?;
+        let mut sgd = SGD::new_with_params(vec![x.clone()], params)?;
+        assert!(sgd.momentum_buffer(&x).is_none());
+        for _step in 0..3 {
+            let loss = (x.as_tensor() - 4.2)?.sqr()?;
+            sgd.backward_step(&loss)?
+        }
+        let buf = match sgd.momentum_buffer(&x) {
+            Some(buf) => {
+                assert!(buf.device().same_device(x.device()));
+                buf.to_scalar::<f32>()?
+            }
+            None => 0.,
+        };
+        Ok((x.to_scalar::<f32>()?, buf))
+    };
+    let round = |(x, buf): (f32, f32)| ((x * 1e4).round() / 1e4, (buf * 1e4).round() / 1e4);
+
+    let params = ParamsSGD {
+        lr: 0.1,
+        momentum: 0.9,
+        dampening: 0.1,
+        weight_decay: 0.01,
+        ..Default::default()
+    };
+    assert_eq!(round(run(params)?), (3.7821, -15.8205));
+    let params = ParamsSGD {
+        lr: 0.1,
+        momentum: 0.9,
+        nesterov: true,
+        weight_decay: 0.01,
+        ..Default::default()
+    };
+    assert_eq!(round(run(params)?), (4.6457, -13.3184));
+    let params = ParamsSGD {
+        lr: 0.1,
+        momentum: 0.9,
+        weight_decay: 0.1,
+        decoupled_weight_decay: true,
+        ..Default::default()
+    };
+    assert_eq!(round(run(params)?), (3.9103, -16.7328));
+
+    // Without momentum, no buffer is kept.
+    let params = ParamsSGD {
+        lr: 0.1,
+        ..Default::default()
+    };
+    assert_eq!(round(run(params)?), (2.0496, 0.));
+
+    let params = ParamsSGD {
+        nesterov: true,
+        ..Default::default()
+    };
+    assert!(SGD::new_with_params(vec![], params).is_err());
+    Ok(())
+}
+
+#[test]
+fn sgd_momentum_buffer_detached() -> Result<()> {
+    let x = Var::new(&[1f32, -2.], &Device::Cpu)?;
+    let params = ParamsSGD {
+        lr: 0.01,
+        momentum: 0.9,
+        weight_decay: 0.1,
+        ..Default::default()
+    };
+    let mut sgd = SGD::new_with_params(vec![x.clone()], params)?;
+    for _step in 0..100 {
+        let loss = (x.as_tensor() - 4.2)?.sqr()?.sum_all()?;
+        sgd.backward_step(&loss)?;
+        // The buffer does not depend on the variable, so it does not keep the forward graphs
+        // of the previous steps alive.
+        let buf = sgd.momentum_buffer(&x).unwrap();
+        assert!(buf.sum_all()?.backward()?.get(&x).is_none());
+    }
+    Ok(())
+}
+
+#[test]
+fn sgd_momentum_least_squares() -> Result<()> {
+    let xs = [
+        [1f32, 2.],
+        [2., -1.],
+        [3., 1.],
+        [-1., 0.5],
+        [0.5, 3.],
+        [2., 2.],
+    ];
+    let ys = [4.1f32, 2.9, 7.2, -1.8, 6.4, 8.1];
+
+    // Analytic solution of the normal equations `(X^T X) w = X^T y`.
+    let (mut a, mut b, mut d, mut u, mut v) = (0f32, 0f32, 0f32, 0f32, 0f32);
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        a += x[0] * x[0];
+        b += x[0] * x[1];
+        d += x[1] * x[1];
+        u += x[0] * y;
+        v += x[1] * y;
+    }
+    let det = a * d - b * b;
+    let expected = [(d * u - b * v) / det, (a * v - b * u) / det];
+
+    let xs = Tensor::new(&xs, &Device::Cpu)?;
+    let ys = Tensor::new(&ys, &Device::Cpu)?.unsqueeze(1)?;
+    for nesterov in [false, true] {
+        let w = Var::zeros((2, 1), candle::DType::F32, &Device::Cpu)?;
+        let params = ParamsSGD {
+            lr: 0.02,
+            momentum: 0.9,
+            nesterov,
+            ..Default::default()
+        };
+        let mut sgd = SGD::new_with_params(vec![w.clone()], params)?;
+        for _step in 0..300 {
+            let loss = xs.matmul(&w)?.sub(&ys)?.sqr()?.mean_all()?;
+            sgd.backward_step(&loss)?;
+        }
+        let w = w.flatten_all()?.to_vec1::<f32>()?;
+        for (w, e) in w.iter().zip(expected.iter()) {
+            assert!((w - e).abs() < 1e-3, "{w} {e}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn clip_grad() -> Result<()> {
+    use candle_nn::utils::{clip_grad_norm, clip_grad_value};
+
+    let w = Var::new(&[1f32, 2.], &Device::Cpu)?;
+    let b = Var::new(&[0f64], &Device::Cpu)?;
+    let unused = Var::new(&[0f32], &Device::Cpu)?;
+    let vars = [w.clone(), b.clone(), unused];
+    let grads = || -> Result<candle::backprop::GradStore> {
+        let lw = (w.as_tensor() * Tensor::new(&[3f32, 4.], &Device::Cpu)?)?.sum_all()?;
+        let lb = (b.as_tensor() * 12.)?.sum_all()?;
+        let loss = (lw.to_dtype(candle::DType::F64)? + lb)?;
+        Ok(loss.backward()?)
+    };
+
+    // The global norm is sqrt(3^2 + 4^2 + 12^2) = 13.
+    let mut g = grads()?;
+    let norm = clip_grad_norm(&mut g, &vars, 6.5)?;
+    assert!((norm - 13.).abs() < 1e-5);
+    assert_eq!(
+        candle::test_utils::to_vec1_round(g.get(&w).unwrap(), 4)?,
+        [1.5, 2.0]
+    );
+    let gb = g.get(&b).unwrap();
+    assert_eq!(gb.dtype(), candle::DType::F64);
+    assert!((gb.to_vec1::<f64>()?[0] - 6.).abs() < 1e-5);
+
+    // Gradients with a norm below the threshold are left unchanged.
+    let mut g = grads()?;
+    let norm = clip_grad_norm(&mut g, &vars, 20.)?;
+    assert!((norm - 13.).abs() < 1e-5);
+    assert_eq!(g.get(&w).unwrap().to_vec1::<f32>()?, [3., 4.]);
+    assert_eq!(clip_grad_norm(&mut g, &[], 1.)?, 0.);
+    assert!(clip_grad_norm(&mut g, &vars, -1.).is_err());
+
+    let mut g = grads()?;
+    clip_grad_value(&mut g, &vars, 3.5)?;
+    assert_eq!(g.get(&w).unwrap().to_vec1::<f32>()?, [3., 3.5]);
+    assert_eq!(g.get(&b).unwrap().to_vec1::<f64>()?, [3.5]);
+    Ok(())
+}
+
+#[test]
+fn ema() -> Result<()> {
+    use candle_nn::{utils::Ema, VarBuilder, VarMap};
+
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, candle::DType::F32, &Device::Cpu);
+    let lin = candle_nn::linear(2, 1, vb.pp("lin"))?;
+    let get = |name: &str| varmap.data().lock().unwrap()[name].clone();
+    let (w, b) = (get("lin.weight"), get("lin.bias"));
+    w.set(&Tensor::new(&[[1f32, 2.]], &Device::Cpu)?)?;
+    b.set(&Tensor::new(&[0f32], &Device::Cpu)?)?;
+
+    let mut ema = Ema::new(&varmap)?;
+    w.set(&Tensor::new(&[[3f32, 0.]], &Device::Cpu)?)?;
+    b.set(&Tensor::new(&[4f32], &Device::Cpu)?)?;
+    ema.step(0.5)?;
+    ema.step(0.5)?;
+    // After two steps: 0.25 * initial + 0.75 * current.
+    let shadow_w = ema.shadow("lin.weight").unwrap();
+    assert_eq!(shadow_w.to_vec2::<f32>()?, [[2.5, 0.5]]);
+    assert_eq!(ema.shadow("lin.bias").unwrap().to_vec1::<f32>()?, [3.]);
+
+    // Evaluate with the averaged weights, then restore the trained ones.
+    let xs = Tensor::new(&[[1f32, 1.]], &Device::Cpu)?;
+    ema.copy_to(&varmap)?;
+    assert_eq!(lin.forward(&xs)?.to_vec2::<f32>()?, [[6.]]);
+    assert!(ema.copy_to(&varmap).is_err());
+    ema.restore()?;
+    assert_eq!(lin.forward(&xs)?.to_vec2::<f32>()?, [[7.]]);
+    assert_eq!(w.to_vec2::<f32>()?, [[3., 0.]]);
+
+    // The shadow weights survive a save/load round trip.
+    let path = std::env::temp_dir().join(format!("candle-ema-{}.safetensors", std::process::id()));
+    ema.save(&path)?;
+    let mut ema2 = Ema::new(&varmap)?;
+    assert_eq!(ema2.shadow("lin.bias").unwrap().to_vec1::<f32>()?, [4.]);
+    ema2.load(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(ema2.shadow("lin.bias").unwrap().to_vec1::<f32>()?, [3.]);
+
+    // Tracking a list of variables.
+    let mut ema = Ema::from_vars(std::slice::from_ref(&w))?;
+    w.set(&Tensor::new(&[[1f32, 1.]], &Device::Cpu)?)?;
+    ema.step(0.9)?;
+    ema.copy_to_vars()?;
+    assert_eq!(to_vec2_round(w.as_tensor(), 4)?, [[2.8, 0.1]]);
+    ema.restore()?;
+    assert_eq!(w.to_vec2::<f32>()?, [[1., 1.]]);
+    assert!(ema.step(1.5).is_err());
+    Ok(())
+}
@@ -4,7 +4,7 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use candle::{test_utils::to_vec2_round, DType, Device, Result, Tensor};
+use candle::{test_utils::to_vec2_round, DType, Device, IndexOp, Result, Tensor};
 use candle_nn::RNN;
 
 /* The following test can be verified against PyTorch using the following snippet.
@@ -99,3 +99,127 @@ fn gru() -> Result<()> {
     assert_eq!(to_vec2_round(h, 4)?, &[[0.0579, 0.8836, -0.9991]]);
     Ok(())
 }
+
+#[test]
+fn lstm_seq() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let varmap = candle_nn::VarMap::new();
+    let vb = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, cpu);
+    let lstm = candle_nn::lstm(2, 3, Default::default(), vb)?;
+    let input = Tensor::new(&[3f32, 1.5, 1., 0.5, 4., 2., 1., 0.5], cpu)?.reshape((2, 2, 2))?;
+    let (output, state) = lstm.seq(&input)?;
+    assert_eq!(output.dims(), &[2, 2, 3]);
+    assert_eq!(state.h().dims(), &[2, 3]);
+    assert_eq!(state.c().dims(), &[2, 3]);
+
+    // The last output is the final hidden state, and it matches stepping manually.
+    let last = output.i((.., 1, ..))?;
+    assert_eq!(to_vec2_round(&last, 4)?, to_vec2_round(state.h(), 4)?);
+    let mut manual = lstm.zero_state(2)?;
+    for seq_index in 0..2 {
+        manual = lstm.step(&input.i((.., seq_index, ..))?, &manual)?;
+    }
+    assert_eq!(to_vec2_round(manual.h(), 4)?, to_vec2_round(state.h(), 4)?);
+
+    // The hidden state propagates: the second step depends on the first input.
+    let other_first = Tensor::new(&[0f32, 0., 1., 0.5, 0., 0., 1., 0.5], cpu)?;
+    let (other_output, _) = lstm.seq(&other_first.reshape((2, 2, 2))?)?;
+    let diff = (other_output.i((.., 1, ..))? - &last)?.abs()?.sum_all()?;
+    assert!(diff.to_vec0::<f32>()? > 1e-4);
+
+    // Gradients flow back through time to the input-to-hidden weights via every step.
+    let loss = output.i((.., 1, ..))?.sum_all()?;
+    let grads = loss.backward()?;
+    for var in varmap.all_vars() {
+        let grad = grads.get(&var).expect("no grad for lstm weight");
+        assert!(grad.abs()?.sum_all()?.to_vec0::<f32>()? > 0.);
+    }
+    Ok(())
+}
+
+#[test]
+fn gru_forward() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let varmap = candle_nn::VarMap::new();
+    let vb = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, cpu);
+    let gru = candle_nn::gru(2, 3, Default::default(), vb)?;
+
+    // A single step.
+    let h0 = Tensor::new(&[[0.1f32, -0.2, 0.3], [0., 0.5, -0.5]], cpu)?;
+    let input = Tensor::new(&[[3f32, 1.5], [1., 0.5]], cpu)?;
+    let h1 = gru.forward(&input, &h0)?;
+    assert_eq!(h1.dims(), &[2, 3]);
+    // The new hidden state is an interpolation between the previous one and tanh values.
+    assert!(h1.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()? < 1.);
+
+    // A rollout over a short sequence matches stepping manually.
+    let input = Tensor::arange(0f32, 8., cpu)?
+        .affine(0.5, -1.)?
+        .reshape((2, 2, 2))?;
+    let (output, h_last) = gru.seq_forward(&input, &h0)?;
+    assert_eq!(output.dims(), &[2, 2, 3]);
+    let mut h = h0.clone();
+    for seq_index in 0..2 {
+        h = gru.forward(&input.i((.., seq_index, ..))?, &h)?;
+        assert_eq!(
+            to_vec2_round(&output.i((.., seq_index, ..))?, 4)?,
+            to_vec2_round(&h, 4)?
+        );
+    }
+    assert_eq!(to_vec2_round(&h_last, 4)?, to_vec2_round(&h, 4)?);
+    Ok(())
+}
+
+/* The expected values can be checked with the following NumPy reference implementation.
+import numpy as np
+sigmoid = lambda x: 1 / (1 + np.exp(-x))
+w_ih = np.cos(0.1 * np.arange(24.).reshape(8, 3))
+w_hh = np.sin(0.1 * np.arange(16.).reshape(8, 2))
+b = 0.1 * (np.arange(8.) - 4)
+def step(x, h, c):
+    i, f, g, o = np.split(w_ih @ x + w_hh @ h + b, 4)
+    c = sigmoid(f) * c + sigmoid(i) * np.tanh(g)
+    return sigmoid(o) * np.tanh(c), c
+h1, c1 = step(np.array([1., -1., 0.5]), np.array([0.5, -0.5]), np.array([0.2, -0.1]))
+h2, c2 = step(np.array([0., 2., -1.]), h1, c1)
+print(h1, c1, h2, c2)
+# [0.0936 0.0022] [0.1827 0.0043] [0.1892 0.0776] [0.3857 0.1663]
+*/
+#[test]
+fn lstm_forward() -> Result<()> {
+    let cpu = &Device::Cpu;
+    let w_ih = (Tensor::arange(0f32, 24f32, cpu)?.reshape((8, 3))? * 0.1)?.cos()?;
+    let w_hh = (Tensor::arange(0f32, 16f32, cpu)?.reshape((8, 2))? * 0.1)?.sin()?;
+    let b_ih = Tensor::arange(0f32, 8f32, cpu)?.affine(0.1, -0.4)?;
+    let b_hh = Tensor::zeros(8, DType::F32, cpu)?;
+    let tensors: std::collections::HashMap<_, _> = [
+        ("weight_ih_l0".to_string(), w_ih),
+        ("weight_hh_l0".to_string(), w_hh),
+        ("bias_ih_l0".to_string(), b_ih),
+        ("bias_hh_l0".to_string(), b_hh),
+    ]
+    .into_iter()
+    .collect();
+    let vb = candle_nn::VarBuilder::from_tensors(tensors, DType::F32, cpu);
+    let lstm = candle_nn::lstm(3, 2, Default::default(), vb)?;
+
+    let h0 = Tensor::new(&[[0.5f32, -0.5]], cpu)?;
+    let c0 = Tensor::new(&[[0.2f32, -0.1]], cpu)?;
+    let x1 = Tensor::new(&[[1f32, -1., 0.5]], cpu)?;
+    let (h1, c1) = lstm.forward(&x1, (&h0, &c0))?;
+    assert_eq!(to_vec2_round(&h1, 4)?, &[[0.0936, 0.0022]]);
+    assert_eq!(to_vec2_round(&c1, 4)?, &[[0.1827, 0.0043]]);
+
+    // A two-step rollout.
+    let x2 = Tensor::new(&[[0f32, 2., -1.]], cpu)?;
+    let input = Tensor::stack(&[&x1, &x2], 1)?;
+    let (output, (h2, c2)) = lstm.seq_forward(&input, (&h0, &c0))?;
+    assert_eq!(output.dims(), &[1, 2, 2]);
+    assert_eq!(
+        to_vec2_round(&output.i((.., 0, ..))?, 4)?,
+        &[[0.0936, 0.0022]]
+    );
+    assert_eq!(to_vec2_round(&h2, 4)?, &[[0.1892, 0.0776]]);
+    assert_eq!(to_vec2_round(&c2, 4)?, &[[0.3857, 0.1663]]);
+    Ok(())
+}
@@ -0,0 +1,84 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{test_utils::to_vec3_round, Device, IndexOp, Tensor, D};
+use candle_nn::rotary::{self, RopeLayout};
+
+#[test]
+fn rotary_emb() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Tensor::arange(0f32, 96., device)?
+        .affine(0.25, -10.)?
+        .reshape((2, 2, 3, 8))?;
+    let (cos, sin) = rotary::cos_sin(3, 8, 10000., device)?;
+    let y = rotary::apply_rotary_emb(&x, &cos, &sin, RopeLayout::NeoX)?;
+    assert_eq!(y.dims(), x.dims());
+
+    // Position 0 is not rotated.
+    assert_eq!(
+        y.i((.., .., 0))?.to_vec3::<f32>()?,
+        x.i((.., .., 0))?.to_vec3::<f32>()?
+    );
+    // Other positions are rotated, but the magnitude of each (i, i + 4) pair is preserved.
+    assert_ne!(
+        y.i((.., .., 1))?.to_vec3::<f32>()?,
+        x.i((.., .., 1))?.to_vec3::<f32>()?
+    );
+    let pair_norms = |t: &Tensor| -> Result<Vec<Vec<Vec<f32>>>> {
+        let t1 = t.narrow(D::Minus1, 0, 4)?;
+        let t2 = t.narrow(D::Minus1, 4, 4)?;
+        let norms = (t1.sqr()? + t2.sqr()?)?.sqrt()?.flatten_to(1)?;
+        Ok(to_vec3_round(&norms, 3)?)
+    };
+    assert_eq!(pair_norms(&y)?, pair_norms(&x)?);
+
+    assert!(rotary::apply_rotary_emb(&x, &cos.i(..2)?, &sin.i(..2)?, RopeLayout::NeoX).is_err());
+    Ok(())
+}
+
+#[test]
+fn rotary_emb_layouts() -> Result<()> {
+    let device = &Device::Cpu;
+    let (seq_len, head_dim) = (3, 4);
+    let x = Tensor::arange(0f32, 12., device)?
+        .affine(0.5, -2.)?
+        .reshape((1, 1, seq_len, head_dim))?;
+    let (cos, sin) = rotary::cos_sin(seq_len, head_dim, 10000., device)?;
+    let neox = rotary::apply_rotary_emb(&x, &cos, &sin, RopeLayout::NeoX)?;
+    let gptj = rotary::apply_rotary_emb(&x, &cos, &sin, RopeLayout::GptJ)?;
+
+    // Reference implementation rotating the pairs (i0, i1) by the angle pos * freq.
+    let xs = x.flatten_all()?.to_vec1::<f32>()?;
+    let rotate = |pairs: &[(usize, usize)]| {
+        let mut ys = xs.clone();
+        for pos in 0..seq_len {
+            for (freq_idx, &(i0, i1)) in pairs.iter().enumerate() {
+                let freq = 10000f32.powf(-2. * freq_idx as f32 / head_dim as f32);
+                let (c, s) = ((pos as f32 * freq).cos(), (pos as f32 * freq).sin());
+                let (a, b) = (xs[pos * head_dim + i0], xs[pos * head_dim + i1]);
+                ys[pos * head_dim + i0] = a * c - b * s;
+                ys[pos * head_dim + i1] = b * c + a * s;
+            }
+        }
+        ys.iter()
+            .map(|v| (v * 1e4).round() / 1e4)
+            .collect::<Vec<_>>()
+    };
+    let round = |t: &Tensor| -> Result<Vec<f32>> {
+        let t = t.flatten_all()?.to_vec1::<f32>()?;
+        Ok(t.iter().map(|v| (v * 1e4).round() / 1e4).collect())
+    };
+    assert_eq!(round(&neox)?, rotate(&[(0, 2), (1, 3)]));
+    assert_eq!(round(&gptj)?, rotate(&[(0, 1), (2, 3)]));
+    // Using the wrong layout gives different values past the first position.
+    assert_ne!(round(&neox)?, round(&gptj)?);
+    assert_eq!(
+        neox.i((.., .., 0))?.to_vec3::<f32>()?,
+        gptj.i((.., .., 0))?.to_vec3::<f32>()?
+    );
+    Ok(())
+}
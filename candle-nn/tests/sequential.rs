@@ -0,0 +1,90 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{Device, Tensor};
+use candle_nn::{Linear, Module, ModuleList};
+
+// Doubles its input in training mode only.
+#[derive(Debug)]
+struct Double {
+    training: bool,
+}
+
+impl Module for Double {
+    fn forward(&self, xs: &Tensor) -> candle::Result<Tensor> {
+        if self.training {
+            xs * 2.
+        } else {
+            Ok(xs.clone())
+        }
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training
+    }
+}
+
+#[test]
+fn sequential() -> Result<()> {
+    let device = &Device::Cpu;
+    let w1 = Tensor::new(&[[1f32, -1.], [2., 0.5], [-1., -1.]], device)?;
+    let w2 = Tensor::new(&[[1f32, 1., 1.]], device)?;
+    let mlp = candle_nn::seq()
+        .add(Linear::new(w1.clone(), None))
+        .add_fn(|xs| xs.relu())
+        .add(Linear::new(
+            w2.clone(),
+            Some(Tensor::new(&[0.5f32], device)?),
+        ));
+    assert_eq!(mlp.len(), 3);
+
+    let xs = Tensor::new(&[[1f32, 2.], [-3., 1.]], device)?;
+    let ys = mlp.forward(&xs)?;
+    let expected = (xs.matmul(&w1.t()?)?.relu()?.matmul(&w2.t()?)? + 0.5)?;
+    assert_eq!(ys.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+    assert_eq!(ys.to_vec2::<f32>()?, [[3.5], [2.5]]);
+
+    let outputs = mlp.forward_all(&xs)?;
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(outputs[1].to_vec2::<f32>()?, [[0., 3., 0.], [0., 0., 2.]]);
+
+    // An empty sequence is the identity.
+    let empty = candle_nn::seq();
+    assert!(empty.is_empty());
+    assert_eq!(empty.forward(&xs)?.to_vec2::<f32>()?, xs.to_vec2::<f32>()?);
+
+    // Layers can be nested and the training mode is propagated.
+    let mut nested = candle_nn::seq()
+        .add(mlp)
+        .add(candle_nn::seq().add(Double { training: false }));
+    assert_eq!(nested.forward(&xs)?.to_vec2::<f32>()?, [[3.5], [2.5]]);
+    nested.set_training(true);
+    assert_eq!(nested.forward(&xs)?.to_vec2::<f32>()?, [[7.], [5.]]);
+    Ok(())
+}
+
+#[test]
+fn module_list() -> Result<()> {
+    let device = &Device::Cpu;
+    let mut blocks = ModuleList::new();
+    for i in 1..4 {
+        let w = Tensor::new(&[[i as f32, 0.], [0., i as f32]], device)?;
+        blocks.push(Linear::new(w, None));
+    }
+    assert_eq!(blocks.len(), 3);
+    assert!(blocks.get(3).is_none());
+
+    // Residual connections around each block.
+    let mut xs = Tensor::new(&[[1f32, -2.]], device)?;
+    for block in blocks.iter() {
+        xs = (&xs + block.forward(&xs)?)?;
+    }
+    assert_eq!(xs.to_vec2::<f32>()?, [[24., -48.]]);
+    let ys = blocks[1].forward(&xs)?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[48., -96.]]);
+    Ok(())
+}
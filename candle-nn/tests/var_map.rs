@@ -0,0 +1,192 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{DType, Device, Tensor};
+use candle_nn::{Linear, Module, Optimizer, VarBuilder, VarMap, SGD};
+
+fn mlp(vb: VarBuilder) -> Result<(Linear, Linear)> {
+    let l1 = candle_nn::linear(3, 4, vb.pp("l1"))?;
+    let l2 = candle_nn::linear(4, 2, vb.pp("l2"))?;
+    Ok((l1, l2))
+}
+
+fn forward(mlp: &(Linear, Linear), xs: &Tensor) -> Result<Tensor> {
+    Ok(mlp.1.forward(&mlp.0.forward(xs)?.relu()?)?)
+}
+
+fn tmp_file(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("candle-{name}-{}.safetensors", std::process::id()))
+}
+
+#[test]
+fn varmap_save_load() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let model = mlp(VarBuilder::from_varmap(&varmap, DType::F32, dev))?;
+    let xs = Tensor::randn(0f32, 1., (5, 3), dev)?;
+
+    // Train a step so that the weights differ from their initialization.
+    let mut sgd = SGD::new(varmap.all_vars(), 0.1)?;
+    sgd.backward_step(&forward(&model, &xs)?.sqr()?.mean_all()?)?;
+    let ys = forward(&model, &xs)?;
+
+    let path = tmp_file("varmap");
+    varmap.save(&path)?;
+    let mut varmap2 = VarMap::new();
+    let model2 = mlp(VarBuilder::from_varmap(&varmap2, DType::F32, dev))?;
+    varmap2.load(&path)?;
+    assert_eq!(
+        forward(&model2, &xs)?.to_vec2::<f32>()?,
+        ys.to_vec2::<f32>()?
+    );
+
+    // Values are converted to the dtype of the variables.
+    let mut varmap3 = VarMap::new();
+    let model3 = mlp(VarBuilder::from_varmap(&varmap3, DType::F64, dev))?;
+    varmap3.load(&path)?;
+    let ys3 = forward(&model3, &xs.to_dtype(DType::F64)?)?.to_dtype(DType::F32)?;
+    let diff = (ys3 - &ys)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_vec0::<f32>()? < 1e-5);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn varmap_load_mismatch() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let _l1 = candle_nn::linear(3, 4, vb.pp("l1"))?;
+    let _extra = candle_nn::linear(2, 2, vb.pp("extra"))?;
+    let path = tmp_file("varmap-mismatch");
+    varmap.save(&path)?;
+
+    // Missing and unexpected keys.
+    let mut varmap2 = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap2, DType::F32, dev);
+    let l1 = candle_nn::linear(3, 4, vb.pp("l1"))?;
+    let _l2 = candle_nn::linear(4, 2, vb.pp("l2"))?;
+    let err = varmap2.load(&path).unwrap_err();
+    assert!(
+        err.to_string().contains("cannot find tensor for l2."),
+        "{err}"
+    );
+    let report = varmap2.load_non_strict(&path)?;
+    assert_eq!(report.missing, ["l2.bias", "l2.weight"]);
+    assert_eq!(report.unexpected, ["extra.bias", "extra.weight"]);
+    let expected = varmap.data().lock().unwrap()["l1.weight"].to_vec2::<f32>()?;
+    assert_eq!(l1.weight().to_vec2::<f32>()?, expected);
+
+    // Shape mismatches are always an error.
+    let mut varmap3 = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap3, DType::F32, dev);
+    let _l1 = candle_nn::linear(4, 4, vb.pp("l1"))?;
+    let err = varmap3.load_non_strict(&path).unwrap_err();
+    assert!(
+        err.to_string().contains("shape mismatch for l1.weight"),
+        "{err}"
+    );
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn var_builder_mmaped_shards() -> Result<()> {
+    let dev = &Device::Cpu;
+    let varmap = VarMap::new();
+    let model = mlp(VarBuilder::from_varmap(&varmap, DType::F32, dev))?;
+    let xs = Tensor::randn(0f32, 1., (5, 3), dev)?;
+    let ys = forward(&model, &xs)?;
+
+    // Split the weights in two shards.
+    let data = varmap.data().lock().unwrap();
+    let shard = |prefix: &str| -> std::collections::HashMap<String, Tensor> {
+        data.iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, var)| (name.clone(), var.as_tensor().clone()))
+            .collect()
+    };
+    let paths = [
+        tmp_file("model-00001-of-00002"),
+        tmp_file("model-00002-of-00002"),
+    ];
+    candle::safetensors::save(&shard("l1."), &paths[0])?;
+    candle::safetensors::save(&shard("l2."), &paths[1])?;
+
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&paths, DType::F32, dev)? };
+    assert!(vb.contains_tensor("l1.weight"));
+    assert!(vb.contains_tensor("l2.bias"));
+    assert!(!vb.contains_tensor("l3.bias"));
+    let model2 = mlp(vb.clone())?;
+    assert_eq!(
+        forward(&model2, &xs)?.to_vec2::<f32>()?,
+        ys.to_vec2::<f32>()?
+    );
+
+    // Tensors are converted to the requested dtype and shapes are checked.
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&paths, DType::F64, dev)? };
+    let w = vb.get((2, 4), "l2.weight")?;
+    assert_eq!(w.dtype(), DType::F64);
+    assert!(vb.get((4, 2), "l2.weight").is_err());
+    assert!(vb.get(2, "l3.bias").is_err());
+    for path in paths.iter() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn var_builder_rename() -> Result<()> {
+    let dev = &Device::Cpu;
+    let ts: std::collections::HashMap<String, Tensor> = [
+        (
+            "transformer.h.0.attn.c_attn.weight",
+            Tensor::ones((4, 3), DType::F32, dev)?,
+        ),
+        (
+            "transformer.h.0.attn.c_attn.bias",
+            Tensor::zeros(4, DType::F32, dev)?,
+        ),
+        (
+            "transformer.h.1.attn.c_attn.weight",
+            Tensor::ones((4, 3), DType::F32, dev)?,
+        ),
+    ]
+    .into_iter()
+    .map(|(name, t)| (name.to_string(), t))
+    .collect();
+    let vb = VarBuilder::from_tensors(ts, DType::F32, dev);
+    assert!(!vb.contains_tensor("layers.0.qkv.weight"));
+    let vb = vb.rename_f(|name| {
+        name.replace("layers.", "transformer.h.")
+            .replace(".qkv.", ".attn.c_attn.")
+    });
+
+    // The renaming applies to the full path, prefixes included.
+    let layer0 = vb.pp("layers").pp(0);
+    assert!(layer0.contains_tensor("qkv.weight"));
+    let qkv = candle_nn::linear(3, 4, layer0.pp("qkv"))?;
+    let ys = qkv.forward(&Tensor::ones((1, 3), DType::F32, dev)?)?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[3., 3., 3., 3.]]);
+
+    // Optional weights: the second layer has no bias.
+    let layer1 = vb.pp("layers").pp(1).pp("qkv");
+    assert!(layer1.contains_tensor("weight"));
+    assert!(!layer1.contains_tensor("bias"));
+    assert!(layer1.get(4, "bias").is_err());
+
+    // Renames compose.
+    let vb = vb.rename_f(|name| name.replace("block", "layers"));
+    assert!(vb.contains_tensor("block.1.qkv.weight"));
+
+    // The zeros backend fabricates tensors of any shape.
+    let vb = VarBuilder::zeros(DType::F64, dev);
+    let t = vb.pp("anything").get((2, 5), "weight")?;
+    assert_eq!(t.dtype(), DType::F64);
+    assert_eq!(t.sum_all()?.to_vec0::<f64>()?, 0.);
+    Ok(())
+}
@@ -0,0 +1,426 @@
+use crate::onnx;
+use crate::onnx::attribute_proto::AttributeType;
+use crate::onnx::tensor_proto::DataType;
+use candle::{bail, DType, Device, Result, Tensor};
+use std::collections::{hash_map::Entry, HashMap};
+
+pub type Value = Tensor;
+
+/// Returns the candle dtype corresponding to an ONNX data type, if any.
+pub fn dtype(dt: DataType) -> Option<DType> {
+    match dt {
+        DataType::Uint8 | DataType::Bool => Some(DType::U8),
+        DataType::Uint32 => Some(DType::U32),
+        DataType::Int64 => Some(DType::I64),
+        DataType::Float16 => Some(DType::F16),
+        DataType::Float => Some(DType::F32),
+        DataType::Double => Some(DType::F64),
+        DataType::Bfloat16 => Some(DType::BF16),
+        _ => None,
+    }
+}
+
+fn get_attr_<'a>(
+    node: &'a onnx::NodeProto,
+    name: &str,
+    attr_type: AttributeType,
+) -> Result<Option<&'a onnx::AttributeProto>> {
+    match node.attribute.iter().find(|attr| attr.name == name) {
+        None => Ok(None),
+        Some(attr) if attr.r#type == attr_type as i32 => Ok(Some(attr)),
+        Some(attr) => bail!(
+            "unexpected type {:?} for attribute {name} of {}, expected {attr_type:?}",
+            AttributeType::try_from(attr.r#type),
+            node.name
+        ),
+    }
+}
+
+fn get_attr_i(node: &onnx::NodeProto, name: &str) -> Result<Option<i64>> {
+    Ok(get_attr_(node, name, AttributeType::Int)?.map(|attr| attr.i))
+}
+
+fn get_attr_f(node: &onnx::NodeProto, name: &str) -> Result<Option<f32>> {
+    Ok(get_attr_(node, name, AttributeType::Float)?.map(|attr| attr.f))
+}
+
+fn get_attr_s<'a>(node: &'a onnx::NodeProto, name: &str) -> Result<Option<&'a [u8]>> {
+    Ok(get_attr_(node, name, AttributeType::String)?.map(|attr| attr.s.as_slice()))
+}
+
+fn get_attr_ints<'a>(node: &'a onnx::NodeProto, name: &str) -> Result<Option<&'a [i64]>> {
+    Ok(get_attr_(node, name, AttributeType::Ints)?.map(|attr| attr.ints.as_slice()))
+}
+
+fn get_attr_t<'a>(node: &'a onnx::NodeProto, name: &str) -> Result<Option<&'a onnx::TensorProto>> {
+    Ok(get_attr_(node, name, AttributeType::Tensor)?.and_then(|attr| attr.t.as_ref()))
+}
+
+// Converts a possibly negative axis to an index in `0..rank`.
+fn normalize_axis(axis: i64, rank: usize) -> Result<usize> {
+    let r = rank as i64;
+    if axis < -r || axis >= r {
+        bail!("axis {axis} out of range for rank {rank}")
+    }
+    Ok(if axis < 0 { axis + r } else { axis } as usize)
+}
+
+fn le_chunks<const N: usize>(raw: &[u8]) -> impl Iterator<Item = [u8; N]> + '_ {
+    raw.chunks_exact(N).map(|c| c.try_into().unwrap())
+}
+
+/// Converts an ONNX tensor, e.g. an initializer or the value of a `Constant` node, to a cpu
+/// tensor. Int32 values are converted to i64 and bool values to u8.
+pub fn get_tensor(t: &onnx::TensorProto, name: &str) -> Result<Tensor> {
+    let dims: Vec<usize> = t.dims.iter().map(|&d| d as usize).collect();
+    let dev = &Device::Cpu;
+    let raw = t.raw_data.as_slice();
+    let data_type = match DataType::try_from(t.data_type) {
+        Ok(data_type) => data_type,
+        Err(_) => bail!("unknown data type {} for {name}", t.data_type),
+    };
+    match data_type {
+        DataType::Int32 => {
+            let data: Vec<i64> = if raw.is_empty() {
+                t.int32_data.iter().map(|&v| v as i64).collect()
+            } else {
+                le_chunks::<4>(raw)
+                    .map(|b| i32::from_le_bytes(b) as i64)
+                    .collect()
+            };
+            Tensor::from_vec(data, dims, dev)
+        }
+        DataType::Float16 | DataType::Bfloat16 if raw.is_empty() => {
+            // Half precision values are stored as their bits in `int32_data`.
+            let bits = t.int32_data.iter().map(|&v| v as u16);
+            if data_type == DataType::Float16 {
+                let data = bits.map(half::f16::from_bits).collect::<Vec<_>>();
+                Tensor::from_vec(data, dims, dev)
+            } else {
+                let data = bits.map(half::bf16::from_bits).collect::<Vec<_>>();
+                Tensor::from_vec(data, dims, dev)
+            }
+        }
+        DataType::Float if raw.is_empty() => Tensor::from_vec(t.float_data.clone(), dims, dev),
+        DataType::Double if raw.is_empty() => Tensor::from_vec(t.double_data.clone(), dims, dev),
+        DataType::Int64 if raw.is_empty() => Tensor::from_vec(t.int64_data.clone(), dims, dev),
+        DataType::Uint8 | DataType::Bool if raw.is_empty() => {
+            let data = t.int32_data.iter().map(|&v| v as u8).collect::<Vec<_>>();
+            Tensor::from_vec(data, dims, dev)
+        }
+        DataType::Uint32 if raw.is_empty() => {
+            let data = t.uint64_data.iter().map(|&v| v as u32).collect::<Vec<_>>();
+            Tensor::from_vec(data, dims, dev)
+        }
+        data_type => match dtype(data_type) {
+            Some(dtype) => Tensor::from_raw_buffer(raw, dtype, &dims, dev),
+            None => bail!("unsupported data type {data_type:?} for {name}"),
+        },
+    }
+}
+
+fn to_i64s(t: &Tensor) -> Result<Vec<i64>> {
+    t.to_dtype(DType::I64)?.flatten_all()?.to_vec1::<i64>()
+}
+
+fn matmul(a: &Tensor, b: &Tensor) -> Result<Tensor> {
+    // Rank 1 operands are promoted to matrices and the added dimension removed afterwards.
+    match (a.rank(), b.rank()) {
+        (1, 1) => a.unsqueeze(0)?.matmul(&b.unsqueeze(1)?)?.reshape(()),
+        (1, _) => a.unsqueeze(0)?.broadcast_matmul(b)?.squeeze(b.rank() - 2),
+        (_, 1) => a.broadcast_matmul(&b.unsqueeze(1)?)?.squeeze(a.rank() - 1),
+        _ => a.broadcast_matmul(b),
+    }
+}
+
+fn conv(node: &onnx::NodeProto, xs: &Tensor, ws: &Tensor, bs: Option<&Tensor>) -> Result<Tensor> {
+    if let Some(auto_pad) = get_attr_s(node, "auto_pad")? {
+        if auto_pad != b"NOTSET" {
+            bail!(
+                "unsupported auto_pad {} for {}",
+                String::from_utf8_lossy(auto_pad),
+                node.name
+            )
+        }
+    }
+    let spatial = xs.rank().saturating_sub(2);
+    let all_same = |vs: &[i64]| vs.windows(2).all(|w| w[0] == w[1]);
+    let single = |name: &str, default: usize| -> Result<usize> {
+        match get_attr_ints(node, name)? {
+            None => Ok(default),
+            Some(vs) if vs.len() == spatial && all_same(vs) => Ok(vs[0] as usize),
+            Some(vs) => bail!("unsupported {name} {vs:?} for {}", node.name),
+        }
+    };
+    let strides = single("strides", 1)?;
+    let dilations = single("dilations", 1)?;
+    let groups = get_attr_i(node, "group")?.unwrap_or(1) as usize;
+    // Pads are given as the begin values for all the spatial dimensions followed by the end
+    // values, uneven pads are applied beforehand.
+    let pads = get_attr_ints(node, "pads")?.unwrap_or(&[]);
+    let (xs, padding) = if pads.is_empty() || all_same(pads) {
+        (xs.clone(), pads.first().map_or(0, |&p| p as usize))
+    } else if pads.len() == 2 * spatial {
+        let mut xs = xs.clone();
+        for d in 0..spatial {
+            let (begin, end) = (pads[d] as usize, pads[d + spatial] as usize);
+            xs = xs.pad_with_zeros(d + 2, begin, end)?
+        }
+        (xs, 0)
+    } else {
+        bail!("unsupported pads {pads:?} for {}", node.name)
+    };
+    let ys = match spatial {
+        1 => xs.conv1d(ws, padding, strides, dilations, groups)?,
+        2 => xs.conv2d(ws, padding, strides, dilations, groups)?,
+        3 => xs.conv3d(ws, padding, strides, dilations, groups)?,
+        _ => bail!("unsupported input shape {:?} for {}", xs.shape(), node.name),
+    };
+    match bs {
+        None => Ok(ys),
+        Some(bs) => {
+            let mut dims = vec![1; ys.rank()];
+            dims[1] = bs.elem_count();
+            ys.broadcast_add(&bs.reshape(dims)?)
+        }
+    }
+}
+
+fn reshape(xs: &Tensor, shape: &[i64], allow_zero: bool) -> Result<Tensor> {
+    let mut dims = Vec::with_capacity(shape.len());
+    let mut infer = None;
+    for (i, &d) in shape.iter().enumerate() {
+        match d {
+            -1 if infer.is_none() => {
+                infer = Some(i);
+                dims.push(1)
+            }
+            0 if !allow_zero => dims.push(xs.dim(i)?),
+            d if d >= 0 => dims.push(d as usize),
+            _ => bail!("invalid reshape target {shape:?} for {:?}", xs.shape()),
+        }
+    }
+    if let Some(i) = infer {
+        let known: usize = dims.iter().product();
+        if known == 0 || xs.elem_count() % known != 0 {
+            bail!("invalid reshape target {shape:?} for {:?}", xs.shape())
+        }
+        dims[i] = xs.elem_count() / known
+    }
+    xs.reshape(dims)
+}
+
+fn slice(xs: &Tensor, starts: &[i64], ends: &[i64], axes: &[i64], steps: &[i64]) -> Result<Tensor> {
+    let mut xs = xs.clone();
+    for (i, (&start, &end)) in starts.iter().zip(ends.iter()).enumerate() {
+        let axis = match axes.get(i) {
+            Some(&axis) => normalize_axis(axis, xs.rank())?,
+            None => i,
+        };
+        let step = steps.get(i).copied().unwrap_or(1);
+        if step <= 0 {
+            bail!("unsupported slice step {step}")
+        }
+        let dim = xs.dim(axis)? as i64;
+        let clamp = |v: i64| if v < 0 { v + dim } else { v }.clamp(0, dim) as usize;
+        let (start, end) = (clamp(start), clamp(end));
+        let len = end.saturating_sub(start);
+        xs = if step == 1 {
+            xs.narrow(axis, start, len)?
+        } else {
+            let ids: Vec<u32> = (start..end)
+                .step_by(step as usize)
+                .map(|i| i as u32)
+                .collect();
+            let ids = Tensor::new(ids, xs.device())?;
+            xs.index_select(&ids, axis)?
+        }
+    }
+    Ok(xs)
+}
+
+fn gather(xs: &Tensor, indices: &Tensor, axis: usize) -> Result<Tensor> {
+    let dim = xs.dim(axis)? as i64;
+    let ids = to_i64s(indices)?
+        .into_iter()
+        .map(|i| if i < 0 { i + dim } else { i })
+        .collect::<Vec<_>>();
+    let n = ids.len();
+    let ids = Tensor::from_vec(ids, n, xs.device())?;
+    let ys = xs.index_select(&ids, axis)?;
+    let mut dims = xs.dims()[..axis].to_vec();
+    dims.extend_from_slice(indices.dims());
+    dims.extend_from_slice(&xs.dims()[axis + 1..]);
+    ys.reshape(dims)
+}
+
+/// Evaluates a model on the given inputs and returns the values of the graph outputs.
+///
+/// The graph is interpreted node by node using the tensor operations, the initializers are
+/// loaded on the cpu. An error naming the op type and node is returned for unsupported ops.
+pub fn simple_eval(
+    model: &onnx::ModelProto,
+    inputs: HashMap<String, Value>,
+) -> Result<HashMap<String, Value>> {
+    let graph = match &model.graph {
+        None => bail!("no graph defined in proto"),
+        Some(graph) => graph,
+    };
+    // The version of the default operator set, the semantics of some ops depend on it.
+    let opset_version = model
+        .opset_import
+        .iter()
+        .find(|opset| opset.domain.is_empty() || opset.domain == "ai.onnx")
+        .map(|opset| opset.version);
+    let mut values = inputs;
+    // Initializers only provide default values, inputs given by the caller take precedence.
+    for t in graph.initializer.iter() {
+        if let Entry::Vacant(entry) = values.entry(t.name.to_string()) {
+            entry.insert(get_tensor(t, t.name.as_str())?);
+        }
+    }
+    for input in graph.input.iter() {
+        if !values.contains_key(&input.name) {
+            bail!("missing input {}", input.name)
+        }
+    }
+    for node in graph.node.iter() {
+        let get = |name: &str| match values.get(name) {
+            Some(value) => Ok(value),
+            None => bail!("cannot find {name} for op {}", node.name),
+        };
+        let input = |i: usize| match node.input.get(i) {
+            Some(name) => get(name),
+            None => bail!("missing input {i} for op {}", node.name),
+        };
+        // Optional inputs can be omitted or given an empty name.
+        let opt_input = |i: usize| match node.input.get(i) {
+            Some(name) if !name.is_empty() => get(name).map(Some),
+            _ => Ok(None),
+        };
+        let output = match node.op_type.as_str() {
+            "Add" => input(0)?.broadcast_add(input(1)?)?,
+            "Sub" => input(0)?.broadcast_sub(input(1)?)?,
+            "Mul" => input(0)?.broadcast_mul(input(1)?)?,
+            "Div" => input(0)?.broadcast_div(input(1)?)?,
+            "MatMul" => matmul(input(0)?, input(1)?)?,
+            "Gemm" => {
+                let alpha = get_attr_f(node, "alpha")?.unwrap_or(1.) as f64;
+                let beta = get_attr_f(node, "beta")?.unwrap_or(1.) as f64;
+                let a = input(0)?;
+                let b = input(1)?;
+                let a = if get_attr_i(node, "transA")?.unwrap_or(0) != 0 {
+                    a.t()?
+                } else {
+                    a.clone()
+                };
+                let b = if get_attr_i(node, "transB")?.unwrap_or(0) != 0 {
+                    b.t()?
+                } else {
+                    b.clone()
+                };
+                let ys = (a.matmul(&b)? * alpha)?;
+                match opt_input(2)? {
+                    None => ys,
+                    Some(c) => ys.broadcast_add(&(c * beta)?)?,
+                }
+            }
+            "Conv" => conv(node, input(0)?, input(1)?, opt_input(2)?)?,
+            "Relu" => input(0)?.relu()?,
+            "Gelu" => match get_attr_s(node, "approximate")? {
+                Some(b"tanh") => input(0)?.gelu()?,
+                _ => candle_nn::ops::gelu_erf(input(0)?)?,
+            },
+            "Sigmoid" => candle_nn::ops::sigmoid(input(0)?)?,
+            "Softmax" => match opset_version {
+                // Before opset 13 the input is coerced to 2d, flattening the dimensions before
+                // and after the axis which defaults to 1.
+                Some(v) if v < 13 => {
+                    let xs = input(0)?;
+                    let axis = get_attr_i(node, "axis")?.unwrap_or(1);
+                    let axis = normalize_axis(axis, xs.rank())?;
+                    let d0 = xs.dims()[..axis].iter().product::<usize>();
+                    let xs2d = xs.reshape((d0, xs.elem_count() / d0.max(1)))?;
+                    candle_nn::ops::softmax(&xs2d, 1)?.reshape(xs.shape())?
+                }
+                _ => {
+                    let xs = input(0)?;
+                    let axis = get_attr_i(node, "axis")?.unwrap_or(-1);
+                    candle_nn::ops::softmax(xs, normalize_axis(axis, xs.rank())?)?
+                }
+            },
+            "Reshape" => {
+                let allow_zero = get_attr_i(node, "allowzero")?.unwrap_or(0) != 0;
+                reshape(input(0)?, &to_i64s(input(1)?)?, allow_zero)?
+            }
+            "Transpose" => {
+                let xs = input(0)?;
+                let perm = match get_attr_ints(node, "perm")? {
+                    Some(perm) => perm.iter().map(|&p| p as usize).collect(),
+                    None => (0..xs.rank()).rev().collect::<Vec<_>>(),
+                };
+                xs.permute(perm)?
+            }
+            "Concat" => {
+                let xs = (0..node.input.len())
+                    .map(input)
+                    .collect::<Result<Vec<_>>>()?;
+                let axis = match get_attr_i(node, "axis")? {
+                    Some(axis) => normalize_axis(axis, input(0)?.rank())?,
+                    None => bail!("missing axis for {}", node.name),
+                };
+                Tensor::cat(&xs, axis)?
+            }
+            "Slice" => {
+                let to_vec = |v: Option<&Tensor>| v.map_or(Ok(vec![]), to_i64s);
+                slice(
+                    input(0)?,
+                    &to_i64s(input(1)?)?,
+                    &to_i64s(input(2)?)?,
+                    &to_vec(opt_input(3)?)?,
+                    &to_vec(opt_input(4)?)?,
+                )?
+            }
+            "Gather" => {
+                let xs = input(0)?;
+                let axis = get_attr_i(node, "axis")?.unwrap_or(0);
+                gather(xs, input(1)?, normalize_axis(axis, xs.rank())?)?
+            }
+            "Cast" => {
+                let to = match get_attr_i(node, "to")? {
+                    Some(to) => to as i32,
+                    None => bail!("missing to for {}", node.name),
+                };
+                match DataType::try_from(to).ok().and_then(dtype) {
+                    Some(dtype) => input(0)?.to_dtype(dtype)?,
+                    None => bail!("unsupported cast to {to} for {}", node.name),
+                }
+            }
+            "Constant" => match get_attr_t(node, "value")? {
+                Some(t) => get_tensor(t, node.name.as_str())?,
+                None => match (
+                    get_attr_f(node, "value_float")?,
+                    get_attr_i(node, "value_int")?,
+                ) {
+                    (Some(v), _) => Tensor::new(v, &Device::Cpu)?,
+                    (None, Some(v)) => Tensor::new(v, &Device::Cpu)?,
+                    (None, None) => bail!("unsupported value for constant {}", node.name),
+                },
+            },
+            "Identity" => input(0)?.clone(),
+            op_type => bail!("unsupported op_type {op_type} for op {}", node.name),
+        };
+        match node.output.first() {
+            Some(name) => values.insert(name.to_string(), output),
+            None => bail!("no output for op {}", node.name),
+        };
+    }
+    graph
+        .output
+        .iter()
+        .map(|output| match values.remove(&output.name) {
+            None => bail!("cannot find output {}", output.name),
+            Some(value) => Ok((output.name.to_string(), value)),
+        })
+        .collect()
+}
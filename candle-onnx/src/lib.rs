@@ -0,0 +1,27 @@
+//! ONNX model import.
+//!
+//! This reads models in the ONNX format and evaluates them with the candle tensor operations,
+//! which is enough to run exported classifiers or encoders without writing model code.
+//!
+//! ```no_run
+//! use std::collections::HashMap;
+//! # fn main() -> candle::Result<()> {
+//! let model = candle_onnx::read_file("model.onnx")?;
+//! let xs = candle::Tensor::zeros((1, 3, 224, 224), candle::DType::F32, &candle::Device::Cpu)?;
+//! let inputs = HashMap::from([("input".to_string(), xs)]);
+//! let outputs = candle_onnx::simple_eval(&model, inputs)?;
+//! # Ok(()) }
+//! ```
+use candle::Result;
+use prost::Message;
+
+pub mod eval;
+pub mod onnx;
+
+pub use eval::{dtype, simple_eval};
+
+/// Reads and decodes an ONNX model file.
+pub fn read_file<P: AsRef<std::path::Path>>(p: P) -> Result<onnx::ModelProto> {
+    let buf = std::fs::read(p)?;
+    onnx::ModelProto::decode(buf.as_slice()).map_err(candle::Error::wrap)
+}
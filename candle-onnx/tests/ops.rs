@@ -0,0 +1,360 @@
+use anyhow::Result;
+use candle::{Device, Tensor};
+use candle_onnx::onnx::attribute_proto::AttributeType;
+use candle_onnx::onnx::tensor_proto::DataType;
+use candle_onnx::onnx::{
+    AttributeProto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto, TensorProto,
+    ValueInfoProto,
+};
+use prost::Message;
+use std::collections::HashMap;
+
+fn node(op_type: &str, inputs: &[&str], output: &str, attribute: Vec<AttributeProto>) -> NodeProto {
+    NodeProto {
+        op_type: op_type.to_string(),
+        name: format!("{op_type}_0"),
+        input: inputs.iter().map(|s| s.to_string()).collect(),
+        output: vec![output.to_string()],
+        attribute,
+        ..Default::default()
+    }
+}
+
+fn value_info(name: &str) -> ValueInfoProto {
+    ValueInfoProto {
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+fn attr_i(name: &str, i: i64) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: AttributeType::Int.into(),
+        i,
+        ..Default::default()
+    }
+}
+
+fn attr_f(name: &str, f: f32) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: AttributeType::Float.into(),
+        f,
+        ..Default::default()
+    }
+}
+
+fn attr_ints(name: &str, ints: &[i64]) -> AttributeProto {
+    AttributeProto {
+        name: name.to_string(),
+        r#type: AttributeType::Ints.into(),
+        ints: ints.to_vec(),
+        ..Default::default()
+    }
+}
+
+fn f32_initializer(name: &str, dims: &[i64], data: &[f32]) -> TensorProto {
+    TensorProto {
+        name: name.to_string(),
+        dims: dims.to_vec(),
+        data_type: DataType::Float.into(),
+        raw_data: data.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        ..Default::default()
+    }
+}
+
+fn i64_initializer(name: &str, data: &[i64]) -> TensorProto {
+    TensorProto {
+        name: name.to_string(),
+        dims: vec![data.len() as i64],
+        data_type: DataType::Int64.into(),
+        int64_data: data.to_vec(),
+        ..Default::default()
+    }
+}
+
+fn model(nodes: Vec<NodeProto>, initializer: Vec<TensorProto>, inputs: &[&str]) -> ModelProto {
+    let output = nodes.last().unwrap().output[0].clone();
+    ModelProto {
+        ir_version: 8,
+        graph: Some(GraphProto {
+            node: nodes,
+            initializer,
+            input: inputs.iter().map(|s| value_info(s)).collect(),
+            output: vec![value_info(&output)],
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn eval(model: &ModelProto, inputs: &[(&str, Tensor)]) -> candle::Result<Tensor> {
+    let inputs = inputs
+        .iter()
+        .map(|(name, t)| (name.to_string(), t.clone()))
+        .collect::<HashMap<_, _>>();
+    let output = &model.graph.as_ref().unwrap().output[0].name;
+    let mut outputs = candle_onnx::simple_eval(model, inputs)?;
+    Ok(outputs.remove(output).unwrap())
+}
+
+#[test]
+fn read_file() -> Result<()> {
+    let model = model(
+        vec![node("Add", &["x", "w"], "y", vec![])],
+        vec![f32_initializer("w", &[2], &[1., 2.])],
+        &["x"],
+    );
+    let path = std::env::temp_dir().join(format!("candle-onnx-{}.onnx", std::process::id()));
+    std::fs::write(&path, model.encode_to_vec())?;
+    let read = candle_onnx::read_file(&path);
+    std::fs::remove_file(&path)?;
+    let read = read?;
+    assert_eq!(read, model);
+    let x = Tensor::new(&[[1f32, 1.], [2., 2.]], &Device::Cpu)?;
+    let y = eval(&read, &[("x", x.clone())])?;
+    assert_eq!(y.to_vec2::<f32>()?, [[2., 3.], [3., 4.]]);
+    // Initializers are only defaults, values given for them as inputs take precedence.
+    let w = Tensor::new(&[10f32, 20.], &Device::Cpu)?;
+    let y = eval(&read, &[("x", x), ("w", w)])?;
+    assert_eq!(y.to_vec2::<f32>()?, [[11., 21.], [12., 22.]]);
+    Ok(())
+}
+
+#[test]
+fn binary_and_activations() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::new(&[-1f32, 0., 2.], dev)?;
+    let m = model(
+        vec![
+            node("Mul", &["x", "x"], "x2", vec![]),
+            node("Sub", &["x2", "x"], "x3", vec![]),
+            node("Relu", &["x3"], "y", vec![]),
+        ],
+        vec![],
+        &["x"],
+    );
+    assert_eq!(
+        eval(&m, &[("x", x.clone())])?.to_vec1::<f32>()?,
+        [2., 0., 2.]
+    );
+    let m = model(vec![node("Sigmoid", &["x"], "y", vec![])], vec![], &["x"]);
+    let y = eval(&m, &[("x", x.clone())])?.to_vec1::<f32>()?;
+    assert!((y[1] - 0.5).abs() < 1e-6);
+    let m = model(
+        vec![node("Softmax", &["x"], "y", vec![attr_i("axis", -1)])],
+        vec![],
+        &["x"],
+    );
+    let y = eval(&m, &[("x", x)])?.sum_all()?.to_scalar::<f32>()?;
+    assert!((y - 1.).abs() < 1e-6);
+
+    // Before opset 13, softmax flattens the input to 2d around the axis which defaults to 1.
+    let x = Tensor::arange(0f32, 12., dev)?.reshape((2, 3, 2))?;
+    let mut m = model(vec![node("Softmax", &["x"], "y", vec![])], vec![], &["x"]);
+    m.opset_import = vec![OperatorSetIdProto {
+        domain: "".to_string(),
+        version: 11,
+    }];
+    let y = eval(&m, &[("x", x.clone())])?;
+    assert_eq!(y.dims(), &[2, 3, 2]);
+    let sums = y.sum((1, 2))?.to_vec1::<f32>()?;
+    assert!(sums.iter().all(|s| (s - 1.).abs() < 1e-6), "{sums:?}");
+    let expected = candle_nn::ops::softmax(&x.reshape((2, 6))?, 1)?.reshape((2, 3, 2))?;
+    let diff = (y - expected)?
+        .abs()?
+        .flatten_all()?
+        .max(0)?
+        .to_scalar::<f32>()?;
+    assert!(diff < 1e-6);
+    m.opset_import[0].version = 13;
+    let sums = eval(&m, &[("x", x)])?
+        .sum(2)?
+        .flatten_all()?
+        .to_vec1::<f32>()?;
+    assert!(sums.iter().all(|s| (s - 1.).abs() < 1e-6), "{sums:?}");
+    Ok(())
+}
+
+#[test]
+fn gemm_and_matmul() -> Result<()> {
+    let dev = &Device::Cpu;
+    let a = Tensor::new(&[[1f32, 2.], [3., 4.]], dev)?;
+    let m = model(
+        vec![node(
+            "Gemm",
+            &["a", "b", "c"],
+            "y",
+            vec![
+                attr_f("alpha", 2.),
+                attr_f("beta", 0.5),
+                attr_i("transB", 1),
+            ],
+        )],
+        vec![
+            f32_initializer("b", &[2, 2], &[1., 0., 1., 1.]),
+            f32_initializer("c", &[2], &[2., 4.]),
+        ],
+        &["a"],
+    );
+    // a @ b^t = [[1, 3], [3, 7]]
+    assert_eq!(
+        eval(&m, &[("a", a.clone())])?.to_vec2::<f32>()?,
+        [[3., 8.], [7., 16.]]
+    );
+    let m = model(
+        vec![node("MatMul", &["a", "v"], "y", vec![])],
+        vec![f32_initializer("v", &[2], &[1., -1.])],
+        &["a"],
+    );
+    assert_eq!(eval(&m, &[("a", a)])?.to_vec1::<f32>()?, [-1., -1.]);
+    Ok(())
+}
+
+#[test]
+fn conv() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::arange(0f32, 9., dev)?.reshape((1, 1, 3, 3))?;
+    let m = model(
+        vec![node(
+            "Conv",
+            &["x", "w", "b"],
+            "y",
+            vec![attr_ints("pads", &[1, 1, 1, 1])],
+        )],
+        vec![
+            f32_initializer("w", &[1, 1, 1, 1], &[2.]),
+            f32_initializer("b", &[1], &[1.]),
+        ],
+        &["x"],
+    );
+    let y = eval(&m, &[("x", x.clone())])?;
+    assert_eq!(y.dims(), [1, 1, 5, 5]);
+    assert_eq!(
+        y.squeeze(0)?.squeeze(0)?.to_vec2::<f32>()?[1],
+        [1., 1., 3., 5., 1.]
+    );
+    // Uneven pads only add a column on the right.
+    let m = model(
+        vec![node(
+            "Conv",
+            &["x", "w"],
+            "y",
+            vec![attr_ints("pads", &[0, 0, 0, 1])],
+        )],
+        vec![f32_initializer("w", &[1, 1, 2, 2], &[1., 1., 1., 1.])],
+        &["x"],
+    );
+    let y = eval(&m, &[("x", x)])?;
+    assert_eq!(
+        y.squeeze(0)?.squeeze(0)?.to_vec2::<f32>()?,
+        [[8., 12., 7.], [20., 24., 13.]]
+    );
+    Ok(())
+}
+
+#[test]
+fn shape_ops() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::arange(0f32, 6., dev)?.reshape((2, 3))?;
+    let m = model(
+        vec![
+            node("Reshape", &["x", "shape"], "r", vec![]),
+            node("Transpose", &["r"], "y", vec![]),
+        ],
+        vec![i64_initializer("shape", &[0, -1, 1])],
+        &["x"],
+    );
+    let y = eval(&m, &[("x", x.clone())])?;
+    assert_eq!(y.dims(), [1, 3, 2]);
+    assert_eq!(
+        y.squeeze(0)?.to_vec2::<f32>()?,
+        [[0., 3.], [1., 4.], [2., 5.]]
+    );
+    let m = model(
+        vec![node("Concat", &["x", "x"], "y", vec![attr_i("axis", -1)])],
+        vec![],
+        &["x"],
+    );
+    assert_eq!(eval(&m, &[("x", x.clone())])?.dims(), [2, 6]);
+    let m = model(
+        vec![node(
+            "Slice",
+            &["x", "starts", "ends", "axes", "steps"],
+            "y",
+            vec![],
+        )],
+        vec![
+            i64_initializer("starts", &[0]),
+            i64_initializer("ends", &[i64::MAX]),
+            i64_initializer("axes", &[-1]),
+            i64_initializer("steps", &[2]),
+        ],
+        &["x"],
+    );
+    assert_eq!(
+        eval(&m, &[("x", x.clone())])?.to_vec2::<f32>()?,
+        [[0., 2.], [3., 5.]]
+    );
+    let m = model(
+        vec![node("Gather", &["x", "ids"], "y", vec![attr_i("axis", 1)])],
+        vec![i64_initializer("ids", &[-1, 0])],
+        &["x"],
+    );
+    assert_eq!(
+        eval(&m, &[("x", x.clone())])?.to_vec2::<f32>()?,
+        [[2., 0.], [5., 3.]]
+    );
+    let m = model(
+        vec![
+            node(
+                "Cast",
+                &["x"],
+                "c",
+                vec![attr_i("to", DataType::Int64 as i64)],
+            ),
+            node("Identity", &["c"], "y", vec![]),
+        ],
+        vec![],
+        &["x"],
+    );
+    assert_eq!(
+        eval(&m, &[("x", x)])?.to_vec2::<i64>()?,
+        [[0, 1, 2], [3, 4, 5]]
+    );
+    Ok(())
+}
+
+#[test]
+fn constant() -> Result<()> {
+    let value = AttributeProto {
+        name: "value".to_string(),
+        r#type: AttributeType::Tensor.into(),
+        t: Some(TensorProto {
+            dims: vec![2],
+            data_type: DataType::Int32.into(),
+            int32_data: vec![3, -4],
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let m = model(vec![node("Constant", &[], "y", vec![value])], vec![], &[]);
+    assert_eq!(eval(&m, &[])?.to_vec1::<i64>()?, [3, -4]);
+    Ok(())
+}
+
+#[test]
+fn errors() -> Result<()> {
+    let x = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+    let m = model(vec![node("Foo", &["x"], "y", vec![])], vec![], &["x"]);
+    let err = eval(&m, &[("x", x)]).unwrap_err().to_string();
+    assert!(
+        err.contains("unsupported op_type Foo for op Foo_0"),
+        "{err}"
+    );
+    let m = model(vec![node("Relu", &["x"], "y", vec![])], vec![], &["x"]);
+    let err = eval(&m, &[]).unwrap_err().to_string();
+    assert!(err.contains("missing input x"), "{err}");
+    Ok(())
+}